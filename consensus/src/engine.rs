@@ -17,6 +17,7 @@
 //! 7. After quorum votes, the ConsensusFrame is finalized
 //! 8. Next round begins with the finalized frame as anchor
 
+use setu_keys::{KeyRotation, Signer};
 use setu_types::{ConsensusConfig, ConsensusFrame, Event, EventId, SetuResult, Vote};
 use setu_vlc::VLCSnapshot;
 use std::sync::Arc;
@@ -57,6 +58,9 @@ pub struct ConsensusEngine {
     consensus_manager: Arc<RwLock<ConsensusManager>>,
     /// This validator's ID
     local_validator_id: String,
+    /// Signer for this validator's votes; without one, votes are neither
+    /// signed nor verified
+    signer: Option<Arc<dyn Signer>>,
     /// Channel for sending consensus messages
     message_tx: mpsc::Sender<ConsensusMessage>,
     /// Channel for receiving consensus messages (reserved for future use)
@@ -83,11 +87,19 @@ impl ConsensusEngine {
                 validator_id.clone(),
             ))),
             local_validator_id: validator_id,
+            signer: None,
             message_tx: tx,
             message_rx: Arc::new(RwLock::new(rx)),
         }
     }
 
+    /// Sign this validator's votes with `signer` and verify received votes
+    /// against the keys in the validator set
+    pub fn with_signer(mut self, signer: Arc<dyn Signer>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
     /// Add an event to the DAG and try to create a CF if conditions are met
     pub async fn add_event(&self, event: Event) -> SetuResult<EventId> {
         // Update local VLC by merging with the event's VLC
@@ -97,6 +109,14 @@ impl ConsensusEngine {
             vlc.tick();
         }
 
+        // Key rotations must be signed by both keys, but only take effect in
+        // the validator set once their event is finalized
+        if let Some(rotation) = &event.key_rotation {
+            rotation
+                .verify()
+                .map_err(|e| setu_types::SetuError::InvalidData(e.to_string()))?;
+        }
+
         // Add event to DAG
         let event_id = {
             let mut dag = self.dag.write().await;
@@ -217,28 +237,58 @@ impl ConsensusEngine {
         manager.receive_cf(cf.clone());
 
         // Vote for the CF (in MVP, we always approve valid CFs)
-        let vote = manager.vote_for_cf(&cf.id, true);
-        if let Some(v) = vote {
-            let _ = self.message_tx.send(ConsensusMessage::Vote(v)).await;
+        if let Some(vote) = manager.prepare_vote(&cf.id, true) {
+            let vote = self.sign_vote(vote).await?;
+            manager.record_vote(vote.clone());
+            let _ = self.message_tx.send(ConsensusMessage::Vote(vote)).await;
         }
 
         Ok(())
     }
 
+    /// Signer of this validator's votes, if votes are signed
+    pub fn signer(&self) -> Option<&Arc<dyn Signer>> {
+        self.signer.as_ref()
+    }
+
+    /// Sign a vote of this validator for the current round
+    pub async fn sign_vote(&self, vote: Vote) -> SetuResult<Vote> {
+        let Some(signer) = &self.signer else {
+            return Ok(vote);
+        };
+        let round = self.validator_set.read().await.current_round();
+        let vote = vote.with_round(round);
+        let signature = signer
+            .sign_vote(round, &vote.signing_bytes())
+            .await
+            .map_err(|e| setu_types::SetuError::InvalidData(e.to_string()))?;
+        let signature = [vec![signature.scheme().flag()], signature.as_bytes()].concat();
+        Ok(vote.with_signature(signature))
+    }
+
     /// Receive a vote from another validator
     pub async fn receive_vote(&self, vote: Vote) -> SetuResult<bool> {
+        if self.signer.is_some() {
+            self.validator_set
+                .read()
+                .await
+                .verify_vote(&vote)
+                .map_err(|e| setu_types::SetuError::InvalidData(e.to_string()))?;
+        }
+
         let mut manager = self.consensus_manager.write().await;
         let finalized = manager.receive_vote(vote);
 
         if finalized {
-            if let Some(cf) = manager.last_finalized_cf() {
+            if let Some(cf) = manager.last_finalized_cf().cloned() {
                 let _ = self
                     .message_tx
                     .send(ConsensusMessage::FrameFinalized(cf.clone()))
                     .await;
 
-                // Advance to the next round after finalization
+                // Apply rotations, then advance to the next round after finalization
                 drop(manager);
+                self.apply_finalized_rotations(&cf).await;
                 self.advance_round().await;
             }
         }
@@ -246,6 +296,19 @@ impl ConsensusEngine {
         Ok(finalized)
     }
 
+    /// Apply the key rotations recorded by the events of a finalized frame
+    async fn apply_finalized_rotations(&self, cf: &ConsensusFrame) {
+        let dag = self.dag.read().await;
+        let mut validator_set = self.validator_set.write().await;
+        for event_id in &cf.anchor.event_ids {
+            if let Some(rotation) = dag.get_event(event_id).and_then(|e| e.key_rotation.as_ref()) {
+                // A rotation that is no longer valid, e.g. because its epoch
+                // passed while it waited for finality, has no effect
+                let _ = validator_set.apply_key_rotation(KeyRotation::clone(rotation));
+            }
+        }
+    }
+
     /// Compute the state root from the DAG
     fn compute_state_root_internal(&self, dag: &Dag) -> String {
         use sha2::{Digest, Sha256};
@@ -355,6 +418,90 @@ mod tests {
         assert_eq!(stats.node_count, 1);
     }
 
+    #[tokio::test]
+    async fn test_key_rotation_applied_on_finalization() {
+        use setu_keys::{SetuKeyPair, SignatureScheme};
+        use setu_types::Anchor;
+
+        let old = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let new = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let mut validator_set = create_validator_set();
+        let mut info = validator_set.get_validator("v1").unwrap().clone();
+        let pk = old.public();
+        info.node.public_key = [vec![pk.scheme().flag()], pk.as_bytes()].concat();
+        validator_set.add_validator(info);
+        let engine = ConsensusEngine::new(ConsensusConfig::default(), "v1".to_string(), validator_set);
+
+        let vlc = VLCSnapshot {
+            vector_clock: VectorClock::new(),
+            logical_time: 0,
+            physical_time: 0,
+        };
+        let rotation = KeyRotation::new("v1", &old, &new, 5);
+
+        // Rejected by the DAG (missing parent): no effect
+        let orphan = Event::key_rotation(rotation.clone(), vec!["missing".to_string()], vlc.clone(), "v1".to_string());
+        assert!(engine.add_event(orphan).await.is_err());
+        assert_eq!(engine.validator_set.read().await.public_key_at("v1", 5), Some(old.public()));
+
+        // Accepted but pending: still no effect
+        let event = Event::key_rotation(rotation, vec![], vlc.clone(), "v1".to_string());
+        let event_id = engine.add_event(event).await.unwrap();
+        assert_eq!(engine.validator_set.read().await.public_key_at("v1", 5), Some(old.public()));
+
+        // Finalized: the new key is used from the rotation's epoch
+        let anchor = Anchor::new(vec![event_id], vlc, String::new(), None, String::new(), 0);
+        engine.apply_finalized_rotations(&ConsensusFrame::new(anchor, "v1".to_string())).await;
+        let validator_set = engine.validator_set.read().await;
+        assert_eq!(validator_set.public_key_at("v1", 4), Some(old.public()));
+        assert_eq!(validator_set.public_key_at("v1", 5), Some(new.public()));
+    }
+
+    #[tokio::test]
+    async fn test_votes_signed_and_verified() {
+        use setu_keys::{LocalSigner, SetuKeyPair, SignatureScheme};
+        use setu_types::Anchor;
+
+        let keys: Vec<_> = (0..3).map(|_| SetuKeyPair::generate(SignatureScheme::ED25519)).collect();
+        let mut validator_set = create_validator_set();
+        for (i, key) in keys.iter().enumerate() {
+            let mut info = validator_set.get_validator(&format!("v{}", i + 1)).unwrap().clone();
+            let pk = key.public();
+            info.node.public_key = [vec![pk.scheme().flag()], pk.as_bytes()].concat();
+            validator_set.add_validator(info);
+        }
+        let engine = |i: usize| {
+            ConsensusEngine::new(ConsensusConfig::default(), format!("v{}", i + 1), validator_set.clone())
+                .with_signer(Arc::new(LocalSigner::new(keys[i].clone())))
+        };
+        let (v1, v2) = (engine(0), engine(1));
+
+        let vlc = VLCSnapshot {
+            vector_clock: VectorClock::new(),
+            logical_time: 0,
+            physical_time: 0,
+        };
+        let cf = ConsensusFrame::new(Anchor::new(vec![], vlc, String::new(), None, String::new(), 0), "v1".to_string());
+
+        // Own vote is signed with the node key before it is sent
+        v1.receive_cf(cf.clone()).await.unwrap();
+        let sent = match v1.message_rx.write().await.try_recv().unwrap() {
+            ConsensusMessage::Vote(vote) => vote,
+            other => panic!("expected a vote, got {:?}", other),
+        };
+        assert!(validator_set.verify_vote(&sent).is_ok());
+
+        // Unsigned, moved to another round, or claimed by another validator: rejected
+        let vote = v2.sign_vote(Vote::new("v2".to_string(), cf.id.clone(), true)).await.unwrap();
+        assert!(v1.receive_vote(Vote::new("v2".to_string(), cf.id.clone(), true)).await.is_err());
+        assert!(v1.receive_vote(vote.clone().with_round(1)).await.is_err());
+        let mut forged = vote.clone();
+        forged.validator_id = "v3".to_string();
+        assert!(v1.receive_vote(forged).await.is_err());
+
+        assert!(v1.receive_vote(vote).await.is_ok());
+    }
+
     #[tokio::test]
     async fn test_engine_leader_check() {
         let config = ConsensusConfig::default();
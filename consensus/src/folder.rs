@@ -1,8 +1,9 @@
 use setu_types::{
-    Anchor, ConsensusConfig, ConsensusFrame, EventId, Vote,
+    Anchor, ConsensusConfig, ConsensusFrame, EventId, FinalityReceipt, Vote,
 };
 use crate::dag::Dag;
 use crate::vlc::VLC;
+use setu_merkle::MerkleMountainRange;
 use std::collections::HashMap;
 
 #[derive(Debug)]
@@ -11,6 +12,8 @@ pub struct DagFolder {
     last_anchor: Option<Anchor>,
     anchor_depth: u64,
     last_fold_vlc: u64,
+    /// Accumulates the IDs of all folded anchors, in order
+    anchor_mmr: MerkleMountainRange,
 }
 
 impl DagFolder {
@@ -20,6 +23,7 @@ impl DagFolder {
             last_anchor: None,
             anchor_depth: 0,
             last_fold_vlc: 0,
+            anchor_mmr: MerkleMountainRange::new(),
         }
     }
 
@@ -53,9 +57,11 @@ impl DagFolder {
             vlc.snapshot(),
             state_root,
             self.last_anchor.as_ref().map(|a| a.id.clone()),
+            hex::encode(self.anchor_mmr.root().as_bytes()),
             to_depth,
         );
 
+        self.anchor_mmr.append(anchor.id.as_bytes());
         self.last_anchor = Some(anchor.clone());
         self.anchor_depth = to_depth + 1;
         self.last_fold_vlc = vlc.logical_time();
@@ -70,6 +76,11 @@ impl DagFolder {
     pub fn anchor_depth(&self) -> u64 {
         self.anchor_depth
     }
+
+    /// MMR over all folded anchor IDs; anchor `i` is leaf `i`.
+    pub fn anchor_mmr(&self) -> &MerkleMountainRange {
+        &self.anchor_mmr
+    }
 }
 
 #[derive(Debug)]
@@ -111,16 +122,26 @@ impl ConsensusManager {
     }
 
     pub fn vote_for_cf(&mut self, cf_id: &str, approve: bool) -> Option<Vote> {
-        let cf = self.pending_cfs.get_mut(cf_id)?;
-        
+        let vote = self.prepare_vote(cf_id, approve)?;
+        self.record_vote(vote.clone());
+        Some(vote)
+    }
+
+    /// This validator's vote on a pending CF it has not voted on yet,
+    /// without recording it
+    pub fn prepare_vote(&self, cf_id: &str, approve: bool) -> Option<Vote> {
+        let cf = self.pending_cfs.get(cf_id)?;
         if cf.votes.contains_key(&self.local_validator_id) {
             return None;
         }
+        Some(Vote::new(self.local_validator_id.clone(), cf_id.to_string(), approve))
+    }
 
-        let vote = Vote::new(self.local_validator_id.clone(), cf_id.to_string(), approve);
-        cf.add_vote(vote.clone());
-        
-        Some(vote)
+    /// Record this validator's own vote on a pending CF
+    pub fn record_vote(&mut self, vote: Vote) {
+        if let Some(cf) = self.pending_cfs.get_mut(&vote.cf_id) {
+            cf.add_vote(vote);
+        }
     }
 
     pub fn receive_vote(&mut self, vote: Vote) -> bool {
@@ -165,14 +186,27 @@ impl ConsensusManager {
         self.finalized_cfs.last()
     }
 
+    /// Receipt proving that an event was folded into a finalized CF
+    pub fn finality_receipt(&self, event_id: &EventId) -> Option<FinalityReceipt> {
+        self.finalized_cfs
+            .iter()
+            .rev()
+            .find_map(|cf| cf.finality_receipt(event_id))
+    }
+
     pub fn should_fold(&self, vlc: &VLC) -> bool {
         self.folder.should_fold(vlc)
     }
+
+    pub fn anchor_mmr(&self) -> &MerkleMountainRange {
+        self.folder.anchor_mmr()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use setu_merkle::HashValue;
     use setu_types::{Event, EventType};
 
     fn create_vlc(node_id: &str, time: u64) -> VLC {
@@ -232,4 +266,75 @@ mod tests {
         let cf = manager.try_create_cf(&dag, &vlc, "state_root".to_string());
         assert!(cf.is_some());
     }
+
+    #[test]
+    fn test_finality_receipt() {
+        let config = ConsensusConfig {
+            vlc_delta_threshold: 5,
+            min_events_per_cf: 1,
+            validator_count: 3,
+            ..Default::default()
+        };
+        let mut manager = ConsensusManager::new(config, "validator1".to_string());
+        let (dag, vlc) = setup_dag_with_events(10);
+
+        let cf = manager.try_create_cf(&dag, &vlc, "state_root".to_string()).unwrap();
+        let event_id = cf.anchor.event_ids[4].clone();
+        assert!(manager.finality_receipt(&event_id).is_none());
+
+        for validator in ["validator1", "validator2", "validator3"] {
+            manager.receive_vote(Vote::new(validator.to_string(), cf.id.clone(), true));
+        }
+        assert_eq!(manager.finalized_count(), 1);
+
+        let receipt = manager.finality_receipt(&event_id).unwrap();
+        assert_eq!(receipt.cf_id, cf.id);
+        assert_eq!(receipt.event_index, 4);
+        assert!(receipt.verify(&cf.anchor).is_ok());
+    }
+
+    #[test]
+    fn test_anchor_chain_root() {
+        let config = ConsensusConfig {
+            vlc_delta_threshold: 5,
+            min_events_per_cf: 1,
+            ..Default::default()
+        };
+        let mut folder = DagFolder::new(config);
+        let (mut dag, mut vlc) = setup_dag_with_events(10);
+
+        let mut anchors = Vec::new();
+        for _ in 0..4 {
+            anchors.push(folder.fold(&dag, &vlc, "state_root".to_string()).unwrap());
+
+            // Extend the DAG past the folded depth before folding again
+            for _ in 0..5 {
+                vlc.tick();
+                let event = Event::new(
+                    EventType::Transfer,
+                    dag.get_tips(),
+                    vlc.snapshot(),
+                    "node1".to_string(),
+                );
+                dag.add_event(event).unwrap();
+            }
+        }
+
+        // Each anchor commits to the MMR of the anchors before it
+        let mmr = folder.anchor_mmr();
+        assert_eq!(mmr.leaf_count(), 4);
+        for (i, anchor) in anchors.iter().enumerate() {
+            let root = mmr.root_at(i as u64).unwrap();
+            assert_eq!(anchor.anchor_chain_root, hex::encode(root.as_bytes()));
+        }
+
+        // The first anchor is provable from the latest anchor alone
+        let latest_root = HashValue::from_hex(&anchors[3].anchor_chain_root).unwrap();
+        let proof = mmr.get_proof_at(0, 3).unwrap();
+        assert!(proof.verify(&latest_root, anchors[0].id.as_bytes(), 0).is_ok());
+
+        let earlier_root = HashValue::from_hex(&anchors[1].anchor_chain_root).unwrap();
+        let consistency = mmr.get_consistency_proof(1).unwrap();
+        assert!(consistency.verify(&earlier_root, &mmr.root()).is_ok());
+    }
 }
@@ -6,7 +6,8 @@
 //! This module manages the set of validators participating in consensus.
 //! It integrates with the liveness module for leader election.
 
-use setu_types::ValidatorInfo;
+use setu_keys::{vote_signing_bytes, KeyError, KeyHistory, KeyRotation, PublicKey, Signature, SignatureScheme};
+use setu_types::{ValidatorInfo, Vote};
 #[cfg(test)]
 use setu_types::NodeInfo;
 use std::collections::HashMap;
@@ -55,6 +56,9 @@ pub struct ValidatorSet {
     
     /// Cached proposer election instance
     election: Option<RotatingProposer>,
+
+    /// Key histories of validators that have rotated their key
+    key_histories: HashMap<ValidatorId, KeyHistory>,
 }
 
 impl ValidatorSet {
@@ -66,6 +70,7 @@ impl ValidatorSet {
             current_round: 0,
             strategy: ElectionStrategy::default(),
             election: None,
+            key_histories: HashMap::new(),
         }
     }
 
@@ -77,6 +82,7 @@ impl ValidatorSet {
             current_round: 0,
             strategy,
             election: None,
+            key_histories: HashMap::new(),
         }
     }
 
@@ -99,6 +105,7 @@ impl ValidatorSet {
     /// Remove a validator from the set.
     pub fn remove_validator(&mut self, validator_id: &str) -> Option<ValidatorInfo> {
         let removed = self.validators.remove(validator_id);
+        self.key_histories.remove(validator_id);
         
         // Rebuild election when validators change (must be done before electing new leader)
         self.rebuild_election();
@@ -116,6 +123,67 @@ impl ValidatorSet {
         self.validators.get(validator_id)
     }
 
+    /// Apply an on-ledger key rotation for a registered validator.
+    ///
+    /// Epochs are consensus rounds: the rotation must take effect after the
+    /// current round, so keys of rounds already finalized cannot change.
+    pub fn apply_key_rotation(&mut self, rotation: KeyRotation) -> Result<(), KeyError> {
+        if !self.key_histories.contains_key(&rotation.node_id) {
+            let initial_key = self
+                .registered_key(&rotation.node_id)
+                .ok_or_else(|| {
+                    KeyError::InvalidRotation(format!("Unknown validator {}", rotation.node_id))
+                })??;
+            self.key_histories.insert(
+                rotation.node_id.clone(),
+                KeyHistory::new(rotation.node_id.clone(), initial_key),
+            );
+        }
+        self.key_histories
+            .get_mut(&rotation.node_id)
+            .unwrap()
+            .apply(rotation, self.current_round)
+    }
+
+    /// Public key a validator signs with at `epoch`, honouring rotations.
+    pub fn public_key_at(&self, validator_id: &str, epoch: u64) -> Option<PublicKey> {
+        match self.key_histories.get(validator_id) {
+            Some(history) => Some(history.key_at(epoch).clone()),
+            None => self.registered_key(validator_id)?.ok(),
+        }
+    }
+
+    /// Verify a signature by a validator at `epoch`.
+    pub fn verify_validator_signature(
+        &self,
+        validator_id: &str,
+        epoch: u64,
+        msg: &[u8],
+        sig: &Signature,
+    ) -> Result<(), KeyError> {
+        let public_key = self.public_key_at(validator_id, epoch).ok_or_else(|| {
+            KeyError::KeyNotFound(validator_id.to_string())
+        })?;
+        public_key.verify(msg, sig)
+    }
+
+    /// Verify a vote's signature by the voter's key in the vote's round.
+    pub fn verify_vote(&self, vote: &Vote) -> Result<(), KeyError> {
+        let (flag, bytes) = vote.signature.split_first().ok_or_else(|| {
+            KeyError::SignatureVerification(format!("Vote by {} is not signed", vote.validator_id))
+        })?;
+        let sig = Signature::from_bytes(SignatureScheme::from_flag(*flag)?, bytes)?;
+        let msg = vote_signing_bytes(vote.round, &vote.signing_bytes());
+        self.verify_validator_signature(&vote.validator_id, vote.round, &msg, &sig)
+    }
+
+    /// Key a validator registered with, stored as `flag || bytes`.
+    fn registered_key(&self, validator_id: &str) -> Option<Result<PublicKey, KeyError>> {
+        let bytes = &self.validators.get(validator_id)?.node.public_key;
+        let (flag, key) = bytes.split_first()?;
+        Some(SignatureScheme::from_flag(*flag).and_then(|scheme| PublicKey::from_bytes(scheme, key)))
+    }
+
     /// Get the current leader.
     pub fn get_leader(&self) -> Option<&ValidatorInfo> {
         self.leader_id
@@ -459,4 +527,37 @@ mod tests {
         assert_eq!(new_round, 1);
         assert_eq!(set.current_round(), 1);
     }
+
+    #[test]
+    fn test_key_rotation() {
+        use setu_keys::SetuKeyPair;
+
+        let old = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let new = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let mut info = create_validator("v1");
+        let pk = old.public();
+        info.node.public_key = [vec![pk.scheme().flag()], pk.as_bytes()].concat();
+
+        let mut set = ValidatorSet::new();
+        set.add_validator(info);
+        set.apply_key_rotation(KeyRotation::new("v1", &old, &new, 5)).unwrap();
+
+        assert_eq!(set.public_key_at("v1", 4), Some(old.public()));
+        assert_eq!(set.public_key_at("v1", 5), Some(new.public()));
+        assert!(set.verify_validator_signature("v1", 4, b"vote", &old.sign(b"vote")).is_ok());
+        assert!(set.verify_validator_signature("v1", 5, b"vote", &old.sign(b"vote")).is_err());
+        assert!(set.verify_validator_signature("v1", 5, b"vote", &new.sign(b"vote")).is_ok());
+
+        // Rounds that already started cannot be rewritten
+        let newer = SetuKeyPair::generate(SignatureScheme::ED25519);
+        set.set_round(7);
+        assert!(set.apply_key_rotation(KeyRotation::new("v1", &new, &newer, 6)).is_err());
+        assert!(set.apply_key_rotation(KeyRotation::new("v1", &new, &newer, 7)).is_err());
+        assert_eq!(set.public_key_at("v1", 7), Some(new.public()));
+
+        // Unknown validators and validators without a registered key cannot rotate
+        assert!(set.apply_key_rotation(KeyRotation::new("v2", &old, &new, 6)).is_err());
+        set.add_validator(create_validator("v3"));
+        assert!(set.apply_key_rotation(KeyRotation::new("v3", &old, &new, 6)).is_err());
+    }
 }
@@ -0,0 +1,148 @@
+// Copyright (c) Hetu Project
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reference signing daemon.
+//!
+//! Holds one key and serves the [`setu_keys::remote_signer`] protocol on a
+//! Unix socket, refusing to sign conflicting votes for the same round.
+//!
+//! Usage:
+//!   setu-signer --key <keyfile> --socket <path> [--guard <path>]
+//!
+//! Encrypted key files are unlocked with the passphrase in
+//! `SETU_SIGNER_PASSPHRASE`. The double-sign record defaults to the key file
+//! path with a `.guard` extension. Only one daemon may use a guard at a time:
+//! it holds an exclusive lock on `<guard>.lock` while running.
+
+use setu_keys::remote_signer::serve;
+use setu_keys::signer::{DoubleSignGuard, LocalSigner};
+use setu_keys::{read_encrypted_keypair_from_file, read_keypair_from_file, KeyError, SetuKeyPair};
+use std::fs::File;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::net::UnixListener;
+
+const PASSPHRASE_ENV: &str = "SETU_SIGNER_PASSPHRASE";
+
+struct Args {
+    key: PathBuf,
+    socket: PathBuf,
+    guard: PathBuf,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut key = None;
+    let mut socket = None;
+    let mut guard = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let slot = match arg.as_str() {
+            "--key" => &mut key,
+            "--socket" => &mut socket,
+            "--guard" => &mut guard,
+            other => return Err(format!("Unknown argument: {}", other)),
+        };
+        *slot = Some(PathBuf::from(args.next().ok_or(format!("Missing value for {}", arg))?));
+    }
+    let key = key.ok_or("--key is required")?;
+    let socket = socket.ok_or("--socket is required")?;
+    let guard = guard.unwrap_or_else(|| key.with_extension("guard"));
+    Ok(Args { key, socket, guard })
+}
+
+fn load_key(path: &Path) -> Result<SetuKeyPair, KeyError> {
+    match read_keypair_from_file(path) {
+        Err(KeyError::PassphraseRequired(_)) => {
+            let passphrase = std::env::var(PASSPHRASE_ENV).map_err(|_| {
+                KeyError::PassphraseRequired(format!("set {} to unlock {:?}", PASSPHRASE_ENV, path))
+            })?;
+            read_encrypted_keypair_from_file(path, &passphrase)
+        }
+        result => result,
+    }
+}
+
+/// Take an exclusive lock next to the guard file, held until the process exits.
+///
+/// The guard itself is replaced on every write, so the lock lives in a file
+/// of its own.
+fn lock_guard(guard: &Path) -> Result<File, KeyError> {
+    let mut name = guard.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    let path = guard.with_file_name(name);
+    let file = File::options().write(true).create(true).truncate(false).open(&path)?;
+    // SAFETY: `file` owns a valid descriptor for the duration of the call
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        return Err(KeyError::Io(std::io::Error::new(
+            std::io::ErrorKind::WouldBlock,
+            format!("{:?} is locked by another signer", path),
+        )));
+    }
+    Ok(file)
+}
+
+/// Remove a socket left behind by a previous run, refusing to touch anything
+/// that is not a socket or still accepts connections.
+fn remove_stale_socket(path: &Path) -> Result<(), KeyError> {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(KeyError::Io(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{:?} exists and is not a socket", path),
+        )));
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(KeyError::Io(std::io::Error::new(
+            std::io::ErrorKind::AddrInUse,
+            format!("{:?} is in use by a running signer", path),
+        )));
+    }
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Bind the socket so it is created owner-only, rather than restricting it
+/// after another user had a chance to connect.
+fn bind_private(path: &Path) -> std::io::Result<UnixListener> {
+    // SAFETY: umask only swaps the process file mode mask; nothing else
+    // creates files while the daemon is starting up
+    let previous = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(path);
+    // SAFETY: as above
+    unsafe { libc::umask(previous) };
+    listener
+}
+
+#[tokio::main]
+async fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: setu-signer --key <keyfile> --socket <path> [--guard <path>]");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(args).await {
+        eprintln!("setu-signer: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: Args) -> Result<(), KeyError> {
+    let keypair = load_key(&args.key)?;
+    let _lock = lock_guard(&args.guard)?;
+    let guard = DoubleSignGuard::open(&args.guard)?;
+    println!("Signing as {} ({})", keypair.address(), keypair.scheme());
+
+    remove_stale_socket(&args.socket)?;
+    let listener = bind_private(&args.socket)?;
+    println!("Listening on {:?}", args.socket);
+
+    serve(listener, Arc::new(LocalSigner::with_guard(keypair, guard))).await
+}
@@ -0,0 +1,195 @@
+// Copyright (c) Hetu Project
+// SPDX-License-Identifier: Apache-2.0
+
+//! BLS12-381 signature aggregation.
+//!
+//! BLS keys use the "minimal public key" variant (48-byte public keys in G1,
+//! 96-byte signatures in G2) with the proof-of-possession ciphersuite from the
+//! IRTF BLS draft, the same one Ethereum consensus uses. Signatures from many
+//! validators over the same message, such as votes on a ConsensusFrame, can be
+//! compressed into a single aggregate signature checked against all of their
+//! public keys at once.
+//!
+//! Aggregating public keys is only safe for keys whose proof of possession has
+//! been checked; otherwise a rogue key can forge an aggregate. Validators
+//! should publish [`SetuKeyPair::proof_of_possession`] when they register, and
+//! the registry should check it with [`PublicKey::verify_proof_of_possession`].
+//!
+//! # Example
+//!
+//! ```
+//! use setu_keys::{PublicKey, SetuKeyPair, Signature, SignatureScheme};
+//!
+//! let validators: Vec<_> = (0..4)
+//!     .map(|_| SetuKeyPair::generate(SignatureScheme::BLS12381))
+//!     .collect();
+//! let msg = b"cf-id";
+//!
+//! let votes: Vec<Signature> = validators.iter().map(|kp| kp.sign(msg)).collect();
+//! let public_keys: Vec<PublicKey> = validators.iter().map(|kp| kp.public()).collect();
+//!
+//! let aggregate = Signature::aggregate(&votes).unwrap();
+//! assert!(aggregate.verify_aggregate(msg, &public_keys).is_ok());
+//! ```
+
+use crate::crypto::{PublicKey, SetuKeyPair, Signature};
+use crate::error::KeyError;
+use blst::min_pk::{AggregatePublicKey, AggregateSignature};
+use blst::BLST_ERROR;
+
+/// Domain separation tag for message signatures.
+pub const BLS_SIG_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Domain separation tag for proofs of possession.
+pub const BLS_POP_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Convert a blst status code into a verification result.
+pub(crate) fn bls_error(err: BLST_ERROR) -> Result<(), KeyError> {
+    match err {
+        BLST_ERROR::BLST_SUCCESS => Ok(()),
+        err => Err(KeyError::SignatureVerification(format!("BLS: {:?}", err))),
+    }
+}
+
+fn bls_public_keys(pks: &[PublicKey]) -> Result<Vec<&blst::min_pk::PublicKey>, KeyError> {
+    pks.iter()
+        .map(|pk| match pk {
+            PublicKey::Bls12381(pk) => Ok(pk),
+            other => Err(KeyError::UnsupportedScheme(format!(
+                "Cannot aggregate {} public key",
+                other.scheme()
+            ))),
+        })
+        .collect()
+}
+
+fn bls_signature(sig: &Signature) -> Result<&blst::min_pk::Signature, KeyError> {
+    match sig {
+        Signature::Bls12381(sig) => Ok(sig),
+        other => Err(KeyError::UnsupportedScheme(format!(
+            "Cannot aggregate {} signature",
+            other.scheme()
+        ))),
+    }
+}
+
+impl SetuKeyPair {
+    /// Sign this keypair's own public key, proving knowledge of the secret key.
+    ///
+    /// Only available for BLS12-381 keypairs.
+    pub fn proof_of_possession(&self) -> Result<Signature, KeyError> {
+        self.sign_bls_with_dst(&self.public().as_bytes(), BLS_POP_DST)
+    }
+}
+
+impl PublicKey {
+    /// Verify a proof of possession produced by [`SetuKeyPair::proof_of_possession`].
+    pub fn verify_proof_of_possession(&self, pop: &Signature) -> Result<(), KeyError> {
+        let pk = bls_public_keys(std::slice::from_ref(self))?[0];
+        bls_error(bls_signature(pop)?.verify(true, &pk.compress(), BLS_POP_DST, &[], pk, false))
+    }
+
+    /// Aggregate BLS12-381 public keys into one.
+    ///
+    /// Every key must have a verified proof of possession.
+    pub fn aggregate(pks: &[PublicKey]) -> Result<PublicKey, KeyError> {
+        let pks = bls_public_keys(pks)?;
+        let aggregate = AggregatePublicKey::aggregate(&pks, false)
+            .map_err(|e| KeyError::InvalidKeyFormat(format!("BLS: {:?}", e)))?;
+        Ok(PublicKey::Bls12381(aggregate.to_public_key()))
+    }
+}
+
+impl Signature {
+    /// Aggregate BLS12-381 signatures into one.
+    pub fn aggregate(sigs: &[Signature]) -> Result<Signature, KeyError> {
+        let sigs = sigs.iter().map(bls_signature).collect::<Result<Vec<_>, _>>()?;
+        let aggregate = AggregateSignature::aggregate(&sigs, true)
+            .map_err(|e| KeyError::InvalidKeyFormat(format!("BLS: {:?}", e)))?;
+        Ok(Signature::Bls12381(aggregate.to_signature()))
+    }
+
+    /// Verify an aggregate of signatures by `pks` over the same `msg`.
+    ///
+    /// Every key must have a verified proof of possession.
+    pub fn verify_aggregate(&self, msg: &[u8], pks: &[PublicKey]) -> Result<(), KeyError> {
+        let pks = bls_public_keys(pks)?;
+        bls_error(bls_signature(self)?.fast_aggregate_verify(true, msg, BLS_SIG_DST, &pks))
+    }
+
+    /// Verify an aggregate of signatures where `pks[i]` signed `msgs[i]`.
+    pub fn verify_aggregate_distinct(
+        &self,
+        msgs: &[&[u8]],
+        pks: &[PublicKey],
+    ) -> Result<(), KeyError> {
+        let pks = bls_public_keys(pks)?;
+        bls_error(bls_signature(self)?.aggregate_verify(true, msgs, BLS_SIG_DST, &pks, false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::SignatureScheme;
+
+    fn bls_keys(n: usize) -> Vec<SetuKeyPair> {
+        (0..n)
+            .map(|_| SetuKeyPair::generate(SignatureScheme::BLS12381))
+            .collect()
+    }
+
+    #[test]
+    fn test_proof_of_possession() {
+        let kp = SetuKeyPair::generate(SignatureScheme::BLS12381);
+        let other = SetuKeyPair::generate(SignatureScheme::BLS12381);
+        let pop = kp.proof_of_possession().unwrap();
+
+        assert!(kp.public().verify_proof_of_possession(&pop).is_ok());
+        assert!(other.public().verify_proof_of_possession(&pop).is_err());
+        // A proof of possession is not a valid signature over the public key
+        assert!(kp.public().verify(&kp.public().as_bytes(), &pop).is_err());
+
+        let ed = SetuKeyPair::generate(SignatureScheme::ED25519);
+        assert!(ed.proof_of_possession().is_err());
+    }
+
+    #[test]
+    fn test_aggregate_same_message() {
+        let keys = bls_keys(5);
+        let pks: Vec<_> = keys.iter().map(|kp| kp.public()).collect();
+        let sigs: Vec<_> = keys.iter().map(|kp| kp.sign(b"vote")).collect();
+
+        let aggregate = Signature::aggregate(&sigs).unwrap();
+        assert!(aggregate.verify_aggregate(b"vote", &pks).is_ok());
+        assert!(aggregate.verify_aggregate(b"other", &pks).is_err());
+        assert!(aggregate.verify_aggregate(b"vote", &pks[..4]).is_err());
+
+        // Same as verifying against the aggregate public key
+        let aggregate_pk = PublicKey::aggregate(&pks).unwrap();
+        assert!(aggregate_pk.verify(b"vote", &aggregate).is_ok());
+    }
+
+    #[test]
+    fn test_aggregate_distinct_messages() {
+        let keys = bls_keys(3);
+        let msgs: [&[u8]; 3] = [b"a", b"b", b"c"];
+        let pks: Vec<_> = keys.iter().map(|kp| kp.public()).collect();
+        let sigs: Vec<_> = keys.iter().zip(msgs).map(|(kp, m)| kp.sign(m)).collect();
+
+        let aggregate = Signature::aggregate(&sigs).unwrap();
+        assert!(aggregate.verify_aggregate_distinct(&msgs, &pks).is_ok());
+        assert!(aggregate
+            .verify_aggregate_distinct(&[b"a", b"b", b"x"], &pks)
+            .is_err());
+    }
+
+    #[test]
+    fn test_aggregate_rejects_other_schemes() {
+        let ed = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let bls = SetuKeyPair::generate(SignatureScheme::BLS12381);
+        assert!(Signature::aggregate(&[bls.sign(b"m"), ed.sign(b"m")]).is_err());
+        assert!(PublicKey::aggregate(&[bls.public(), ed.public()]).is_err());
+        assert!(Signature::aggregate(&[]).is_err());
+    }
+}
@@ -9,7 +9,11 @@
 //! - `PublicKey`: Public key representation
 //! - `Signature`: Digital signature
 //! - `SetuAddress`: Account address derived from public key
+//!
+//! BLS12-381 keys sign with the proof-of-possession ciphersuite; see
+//! [`crate::bls`] for aggregation and proofs of possession.
 
+use crate::bls::{bls_error, BLS_SIG_DST};
 use crate::error::KeyError;
 use blake2::{Blake2b, Digest};
 use blake2::digest::consts::U32;
@@ -23,18 +27,25 @@ use p256::ecdsa::{
     SigningKey as Secp256r1SigningKey, VerifyingKey as Secp256r1VerifyingKey,
     Signature as Secp256r1Signature,
 };
+use blst::min_pk::{
+    PublicKey as Bls12381PublicKey, SecretKey as Bls12381SecretKey,
+    Signature as Bls12381Signature,
+};
+use rand::RngCore;
 
 /// Type alias for Blake2b-256
 type Blake2b256 = Blake2b<U32>;
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display};
 use std::str::FromStr;
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 
 /// Flag bytes for different signature schemes (used in serialization).
 pub const ED25519_FLAG: u8 = 0x00;
 pub const SECP256K1_FLAG: u8 = 0x01;
 pub const SECP256R1_FLAG: u8 = 0x02;
+pub const BLS12381_FLAG: u8 = 0x03;
+// 0x04 is taken by `multisig::MULTISIG_FLAG`
 
 /// Supported signature schemes in Setu.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -42,6 +53,7 @@ pub enum SignatureScheme {
     ED25519,
     Secp256k1,
     Secp256r1,
+    BLS12381,
 }
 
 impl SignatureScheme {
@@ -51,6 +63,7 @@ impl SignatureScheme {
             SignatureScheme::ED25519 => ED25519_FLAG,
             SignatureScheme::Secp256k1 => SECP256K1_FLAG,
             SignatureScheme::Secp256r1 => SECP256R1_FLAG,
+            SignatureScheme::BLS12381 => BLS12381_FLAG,
         }
     }
 
@@ -60,6 +73,7 @@ impl SignatureScheme {
             ED25519_FLAG => Ok(SignatureScheme::ED25519),
             SECP256K1_FLAG => Ok(SignatureScheme::Secp256k1),
             SECP256R1_FLAG => Ok(SignatureScheme::Secp256r1),
+            BLS12381_FLAG => Ok(SignatureScheme::BLS12381),
             _ => Err(KeyError::UnsupportedScheme(format!(
                 "Unknown flag: {}",
                 flag
@@ -74,6 +88,7 @@ impl Display for SignatureScheme {
             SignatureScheme::ED25519 => write!(f, "ed25519"),
             SignatureScheme::Secp256k1 => write!(f, "secp256k1"),
             SignatureScheme::Secp256r1 => write!(f, "secp256r1"),
+            SignatureScheme::BLS12381 => write!(f, "bls12381"),
         }
     }
 }
@@ -86,6 +101,7 @@ impl FromStr for SignatureScheme {
             "ed25519" => Ok(SignatureScheme::ED25519),
             "secp256k1" => Ok(SignatureScheme::Secp256k1),
             "secp256r1" | "p256" => Ok(SignatureScheme::Secp256r1),
+            "bls12381" | "bls12-381" | "bls" => Ok(SignatureScheme::BLS12381),
             _ => Err(KeyError::UnsupportedScheme(s.to_string())),
         }
     }
@@ -167,6 +183,7 @@ pub enum PublicKey {
     Ed25519(Ed25519VerifyingKey),
     Secp256k1(Secp256k1VerifyingKey),
     Secp256r1(Secp256r1VerifyingKey),
+    Bls12381(Bls12381PublicKey),
 }
 
 impl PublicKey {
@@ -176,6 +193,7 @@ impl PublicKey {
             PublicKey::Ed25519(_) => SignatureScheme::ED25519,
             PublicKey::Secp256k1(_) => SignatureScheme::Secp256k1,
             PublicKey::Secp256r1(_) => SignatureScheme::Secp256r1,
+            PublicKey::Bls12381(_) => SignatureScheme::BLS12381,
         }
     }
 
@@ -185,6 +203,7 @@ impl PublicKey {
             PublicKey::Ed25519(pk) => pk.as_bytes().to_vec(),
             PublicKey::Secp256k1(pk) => pk.to_sec1_bytes().to_vec(),
             PublicKey::Secp256r1(pk) => pk.to_sec1_bytes().to_vec(),
+            PublicKey::Bls12381(pk) => pk.compress().to_vec(),
         }
     }
 
@@ -232,6 +251,13 @@ impl PublicKey {
                     .map_err(|e| KeyError::InvalidKeyFormat(e.to_string()))?;
                 Ok(PublicKey::Secp256r1(pk))
             }
+            SignatureScheme::BLS12381 => {
+                // Rejects points off the curve, outside the subgroup, or at infinity
+                let pk = Bls12381PublicKey::key_validate(bytes).map_err(|e| {
+                    KeyError::InvalidKeyFormat(format!("Invalid BLS12-381 public key: {:?}", e))
+                })?;
+                Ok(PublicKey::Bls12381(pk))
+            }
         }
     }
 
@@ -249,6 +275,9 @@ impl PublicKey {
             (PublicKey::Secp256r1(pk), Signature::Secp256r1(sig)) => pk
                 .verify(msg, sig)
                 .map_err(|e| KeyError::SignatureVerification(e.to_string())),
+            (PublicKey::Bls12381(pk), Signature::Bls12381(sig)) => {
+                bls_error(sig.verify(true, msg, BLS_SIG_DST, &[], pk, false))
+            }
             _ => Err(KeyError::SignatureVerification(
                 "Signature scheme mismatch".to_string(),
             )),
@@ -281,6 +310,7 @@ pub enum Signature {
     Ed25519(ed25519_dalek::Signature),
     Secp256k1(Secp256k1Signature),
     Secp256r1(Secp256r1Signature),
+    Bls12381(Bls12381Signature),
 }
 
 impl Signature {
@@ -290,6 +320,7 @@ impl Signature {
             Signature::Ed25519(_) => SignatureScheme::ED25519,
             Signature::Secp256k1(_) => SignatureScheme::Secp256k1,
             Signature::Secp256r1(_) => SignatureScheme::Secp256r1,
+            Signature::Bls12381(_) => SignatureScheme::BLS12381,
         }
     }
 
@@ -299,6 +330,7 @@ impl Signature {
             Signature::Ed25519(sig) => sig.to_bytes().to_vec(),
             Signature::Secp256k1(sig) => sig.to_bytes().to_vec(),
             Signature::Secp256r1(sig) => sig.to_bytes().to_vec(),
+            Signature::Bls12381(sig) => sig.compress().to_vec(),
         }
     }
 
@@ -345,6 +377,12 @@ impl Signature {
                     .map_err(|e| KeyError::InvalidKeyFormat(e.to_string()))?;
                 Ok(Signature::Secp256r1(sig))
             }
+            SignatureScheme::BLS12381 => {
+                let sig = Bls12381Signature::from_bytes(bytes).map_err(|e| {
+                    KeyError::InvalidKeyFormat(format!("Invalid BLS12-381 signature: {:?}", e))
+                })?;
+                Ok(Signature::Bls12381(sig))
+            }
         }
     }
 }
@@ -404,6 +442,14 @@ impl SetuKeyPair {
         }
     }
 
+    /// Create a new BLS12-381 keypair.
+    pub fn bls12381(secret_key: Bls12381SecretKey) -> Self {
+        Self {
+            scheme: SignatureScheme::BLS12381,
+            secret_bytes: secret_key.to_bytes().to_vec(),
+        }
+    }
+
     /// Get the signature scheme.
     pub fn scheme(&self) -> SignatureScheme {
         self.scheme
@@ -426,6 +472,10 @@ impl SetuKeyPair {
                 let sk = Secp256r1SigningKey::from_slice(&self.secret_bytes).unwrap();
                 PublicKey::Secp256r1(*sk.verifying_key())
             }
+            SignatureScheme::BLS12381 => {
+                let sk = Bls12381SecretKey::from_bytes(&self.secret_bytes).unwrap();
+                PublicKey::Bls12381(sk.sk_to_pk())
+            }
         }
     }
 
@@ -434,6 +484,21 @@ impl SetuKeyPair {
         (&self.public()).into()
     }
 
+    /// Raw Ed25519 private key, used as the node's network (TLS) identity.
+    ///
+    /// The copy is zeroized when dropped.
+    pub fn ed25519_private_key(&self) -> Result<Zeroizing<[u8; 32]>, KeyError> {
+        if self.scheme != SignatureScheme::ED25519 {
+            return Err(KeyError::UnsupportedScheme(format!(
+                "Expected an ed25519 key, got {}",
+                self.scheme
+            )));
+        }
+        let mut key = Zeroizing::new([0u8; 32]);
+        key.copy_from_slice(&self.secret_bytes);
+        Ok(key)
+    }
+
     /// Sign a message.
     pub fn sign(&self, msg: &[u8]) -> Signature {
         match self.scheme {
@@ -455,7 +520,21 @@ impl SetuKeyPair {
                 let sig: Secp256r1Signature = sk.sign(msg);
                 Signature::Secp256r1(sig)
             }
+            SignatureScheme::BLS12381 => self.sign_bls_with_dst(msg, BLS_SIG_DST).unwrap(),
+        }
+    }
+
+    /// Sign a message with a BLS12-381 key under the given domain separation tag.
+    pub(crate) fn sign_bls_with_dst(&self, msg: &[u8], dst: &[u8]) -> Result<Signature, KeyError> {
+        if self.scheme != SignatureScheme::BLS12381 {
+            return Err(KeyError::UnsupportedScheme(format!(
+                "Expected a bls12381 keypair, got {}",
+                self.scheme
+            )));
         }
+        let sk = Bls12381SecretKey::from_bytes(&self.secret_bytes)
+            .map_err(|e| KeyError::InvalidKeyFormat(format!("{:?}", e)))?;
+        Ok(Signature::Bls12381(sk.sign(msg, dst, &[])))
     }
 
     /// Encode as Base64 with flag prefix.
@@ -499,6 +578,11 @@ impl SetuKeyPair {
                 let _ = Secp256r1SigningKey::from_slice(bytes)
                     .map_err(|e| KeyError::InvalidKeyFormat(e.to_string()))?;
             }
+            SignatureScheme::BLS12381 => {
+                let _ = Bls12381SecretKey::from_bytes(bytes).map_err(|e| {
+                    KeyError::InvalidKeyFormat(format!("Invalid BLS12-381 secret key: {:?}", e))
+                })?;
+            }
         }
 
         Ok(Self {
@@ -523,6 +607,12 @@ impl SetuKeyPair {
                 let sk = Secp256r1SigningKey::random(&mut rng);
                 Self::secp256r1(sk)
             }
+            SignatureScheme::BLS12381 => {
+                let mut ikm = Zeroizing::new([0u8; 32]);
+                rng.fill_bytes(ikm.as_mut());
+                let sk = Bls12381SecretKey::key_gen(ikm.as_ref(), &[]).unwrap();
+                Self::bls12381(sk)
+            }
         }
     }
 }
@@ -555,14 +645,34 @@ mod tests {
             SignatureScheme::ED25519,
             SignatureScheme::Secp256k1,
             SignatureScheme::Secp256r1,
+            SignatureScheme::BLS12381,
         ] {
             let kp = SetuKeyPair::generate(scheme);
             let msg = b"hello setu";
             let sig = kp.sign(msg);
             assert!(kp.public().verify(msg, &sig).is_ok());
+            assert!(kp.public().verify(b"other", &sig).is_err());
         }
     }
 
+    #[test]
+    fn test_bls_encoding() {
+        let kp = SetuKeyPair::generate(SignatureScheme::BLS12381);
+        let decoded = SetuKeyPair::decode_base64(&kp.encode_base64()).unwrap();
+        assert_eq!(decoded.public(), kp.public());
+
+        let pk = PublicKey::decode_base64(&kp.public().encode_base64()).unwrap();
+        assert_eq!(pk.as_bytes().len(), 48);
+        let sig = Signature::decode_base64(&kp.sign(b"msg").encode_base64()).unwrap();
+        assert_eq!(sig.as_bytes().len(), 96);
+        assert!(pk.verify(b"msg", &sig).is_ok());
+
+        // The point at infinity is not a valid public key
+        let mut infinity = [0u8; 48];
+        infinity[0] = 0xc0;
+        assert!(PublicKey::from_bytes(SignatureScheme::BLS12381, &infinity).is_err());
+    }
+
     #[test]
     fn test_keypair_encoding() {
         let kp = SetuKeyPair::generate(SignatureScheme::ED25519);
@@ -579,4 +689,14 @@ mod tests {
         assert!(addr_str.starts_with("0x"));
         assert_eq!(addr_str.len(), 66); // 0x + 64 hex chars
     }
+
+    #[test]
+    fn test_ed25519_private_key() {
+        let kp = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let sk = Ed25519SigningKey::from_bytes(&kp.ed25519_private_key().unwrap());
+        assert_eq!(PublicKey::Ed25519(sk.verifying_key()), kp.public());
+
+        let kp = SetuKeyPair::generate(SignatureScheme::Secp256k1);
+        assert!(kp.ed25519_private_key().is_err());
+    }
 }
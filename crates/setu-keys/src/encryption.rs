@@ -0,0 +1,325 @@
+// Copyright (c) Hetu Project
+// SPDX-License-Identifier: Apache-2.0
+
+//! Passphrase encryption for stored keys.
+//!
+//! Each key is encrypted on its own with AES-256-GCM under a key derived from
+//! the passphrase by scrypt or Argon2id. The KDF parameters, salt and nonce are
+//! stored next to the ciphertext, so parameters can be raised later without
+//! breaking existing files. The public key is stored in the clear and bound to
+//! the ciphertext as associated data, so entries can be listed without the
+//! passphrase but not swapped between keys.
+
+use crate::crypto::{PublicKey, SetuKeyPair};
+use crate::error::KeyError;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Current version of the encrypted keystore format.
+pub const ENCRYPTED_KEYSTORE_VERSION: u32 = 1;
+
+const SALT_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: usize = 32;
+
+/// Largest scrypt N accepted from a file, as log2
+const MAX_SCRYPT_LOG_N: u8 = 20;
+/// Largest memory a KDF may use, in KiB (1 GiB)
+const MAX_KDF_MEMORY_KIB: u64 = 1024 * 1024;
+/// Largest scrypt `p` and Argon2 pass and lane counts accepted from a file
+const MAX_KDF_PARALLELISM: u32 = 16;
+const MAX_ARGON2_PASSES: u32 = 16;
+
+/// Key derivation function and its cost parameters.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum KdfParams {
+    /// scrypt with N = 2^log_n
+    Scrypt { log_n: u8, r: u32, p: u32 },
+    /// Argon2id with memory in KiB
+    Argon2id { m_cost: u32, t_cost: u32, p_cost: u32 },
+}
+
+impl KdfParams {
+    /// scrypt with N = 2^15, r = 8, p = 1 (32 MiB)
+    pub fn scrypt() -> Self {
+        KdfParams::Scrypt { log_n: 15, r: 8, p: 1 }
+    }
+
+    /// Argon2id with 19 MiB, 2 passes, 1 lane
+    pub fn argon2id() -> Self {
+        KdfParams::Argon2id {
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+
+    /// Refuse parameters that would make unlocking use excessive memory or
+    /// time, as a corrupted or planted file could ask for.
+    fn check_limits(&self) -> Result<(), KeyError> {
+        let within_limits = match *self {
+            KdfParams::Scrypt { log_n, r, p } => {
+                // scrypt uses 128 * r * N bytes
+                log_n <= MAX_SCRYPT_LOG_N
+                    && (r as u64) << log_n <= MAX_KDF_MEMORY_KIB * 8
+                    && p <= MAX_KDF_PARALLELISM
+            }
+            KdfParams::Argon2id { m_cost, t_cost, p_cost } => {
+                m_cost as u64 <= MAX_KDF_MEMORY_KIB
+                    && t_cost <= MAX_ARGON2_PASSES
+                    && p_cost <= MAX_KDF_PARALLELISM
+            }
+        };
+        if within_limits {
+            Ok(())
+        } else {
+            Err(KeyError::Encryption(format!("KDF parameters exceed limits: {:?}", self)))
+        }
+    }
+
+    /// Derive an AES-256 key from a passphrase.
+    fn derive_key(&self, passphrase: &str, salt: &[u8]) -> Result<Zeroizing<[u8; KEY_LENGTH]>, KeyError> {
+        self.check_limits()?;
+        let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+        match *self {
+            KdfParams::Scrypt { log_n, r, p } => {
+                let params = scrypt::Params::new(log_n, r, p, KEY_LENGTH)
+                    .map_err(|e| KeyError::Encryption(format!("Invalid scrypt params: {}", e)))?;
+                scrypt::scrypt(passphrase.as_bytes(), salt, &params, key.as_mut())
+                    .map_err(|e| KeyError::Encryption(e.to_string()))?;
+            }
+            KdfParams::Argon2id { m_cost, t_cost, p_cost } => {
+                let params = argon2::Params::new(m_cost, t_cost, p_cost, Some(KEY_LENGTH))
+                    .map_err(|e| KeyError::Encryption(format!("Invalid argon2 params: {}", e)))?;
+                argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+                    .map_err(|e| KeyError::Encryption(e.to_string()))?;
+            }
+        }
+        Ok(key)
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self::scrypt()
+    }
+}
+
+/// Ciphertext plus everything needed to decrypt it, except the passphrase.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedData {
+    pub kdf: KdfParams,
+    /// Hex-encoded KDF salt
+    pub salt: String,
+    /// Hex-encoded AES-GCM nonce
+    pub nonce: String,
+    /// Hex-encoded ciphertext including the authentication tag
+    pub ciphertext: String,
+}
+
+impl EncryptedData {
+    /// Encrypt `plaintext`, authenticating `aad` alongside it.
+    pub fn encrypt(
+        plaintext: &[u8],
+        aad: &[u8],
+        passphrase: &str,
+        kdf: KdfParams,
+    ) -> Result<Self, KeyError> {
+        let mut rng = rand::thread_rng();
+        let mut salt = [0u8; SALT_LENGTH];
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rng.fill_bytes(&mut salt);
+        rng.fill_bytes(&mut nonce);
+
+        let key = kdf.derive_key(passphrase, &salt)?;
+        let cipher = Aes256Gcm::new_from_slice(key.as_ref())
+            .map_err(|e| KeyError::Encryption(e.to_string()))?;
+        let ciphertext = cipher
+            .encrypt(&Nonce::from(nonce), Payload { msg: plaintext, aad })
+            .map_err(|e| KeyError::Encryption(e.to_string()))?;
+
+        Ok(Self {
+            kdf,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    /// Decrypt, failing with [`KeyError::WrongPassphrase`] if authentication fails.
+    pub fn decrypt(&self, aad: &[u8], passphrase: &str) -> Result<Zeroizing<Vec<u8>>, KeyError> {
+        let salt = decode_hex("salt", &self.salt)?;
+        let nonce = decode_hex("nonce", &self.nonce)?;
+        let ciphertext = decode_hex("ciphertext", &self.ciphertext)?;
+        let nonce: [u8; NONCE_LENGTH] = nonce.as_slice().try_into().map_err(|_| {
+            KeyError::InvalidKeyFormat(format!(
+                "Nonce must be {} bytes, got {}",
+                NONCE_LENGTH,
+                nonce.len()
+            ))
+        })?;
+
+        let key = self.kdf.derive_key(passphrase, &salt)?;
+        let cipher = Aes256Gcm::new_from_slice(key.as_ref())
+            .map_err(|e| KeyError::Encryption(e.to_string()))?;
+        // AES-GCM cannot tell a wrong key from a tampered file
+        cipher
+            .decrypt(&Nonce::from(nonce), Payload { msg: &ciphertext, aad })
+            .map(Zeroizing::new)
+            .map_err(|_| KeyError::WrongPassphrase)
+    }
+}
+
+/// A keypair encrypted under a passphrase.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedKey {
+    /// Base64 `flag || pubkey`, also the associated data of the ciphertext
+    pub public_key: String,
+    /// Encrypted Base64 `flag || privkey`
+    pub crypto: EncryptedData,
+}
+
+impl EncryptedKey {
+    /// Encrypt a keypair.
+    pub fn encrypt(keypair: &SetuKeyPair, passphrase: &str, kdf: KdfParams) -> Result<Self, KeyError> {
+        let public_key = keypair.public().encode_base64();
+        let secret = Zeroizing::new(keypair.encode_base64());
+        let crypto = EncryptedData::encrypt(secret.as_bytes(), public_key.as_bytes(), passphrase, kdf)?;
+        Ok(Self { public_key, crypto })
+    }
+
+    /// Decrypt the keypair.
+    pub fn decrypt(&self, passphrase: &str) -> Result<SetuKeyPair, KeyError> {
+        let secret = self.crypto.decrypt(self.public_key.as_bytes(), passphrase)?;
+        let secret = std::str::from_utf8(&secret)
+            .map_err(|e| KeyError::InvalidKeyFormat(e.to_string()))?;
+        let keypair = SetuKeyPair::decode_base64(secret)?;
+        if keypair.public().encode_base64() != self.public_key {
+            return Err(KeyError::InvalidKeyFormat(
+                "Decrypted key does not match its public key".to_string(),
+            ));
+        }
+        Ok(keypair)
+    }
+
+    /// The public key, readable without the passphrase.
+    pub fn public(&self) -> Result<PublicKey, KeyError> {
+        PublicKey::decode_base64(&self.public_key)
+    }
+}
+
+/// On-disk format of an encrypted keystore.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedKeystoreFile {
+    pub version: u32,
+    pub keys: Vec<EncryptedKey>,
+}
+
+impl EncryptedKeystoreFile {
+    /// Encrypt a set of keypairs.
+    pub fn encrypt<'a>(
+        keypairs: impl IntoIterator<Item = &'a SetuKeyPair>,
+        passphrase: &str,
+        kdf: KdfParams,
+    ) -> Result<Self, KeyError> {
+        let keys = keypairs
+            .into_iter()
+            .map(|kp| EncryptedKey::encrypt(kp, passphrase, kdf))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            version: ENCRYPTED_KEYSTORE_VERSION,
+            keys,
+        })
+    }
+
+    /// Decrypt every keypair.
+    pub fn decrypt(&self, passphrase: &str) -> Result<Vec<SetuKeyPair>, KeyError> {
+        if self.version != ENCRYPTED_KEYSTORE_VERSION {
+            return Err(KeyError::InvalidKeyFormat(format!(
+                "Unsupported keystore version {}",
+                self.version
+            )));
+        }
+        self.keys.iter().map(|key| key.decrypt(passphrase)).collect()
+    }
+}
+
+fn decode_hex(field: &str, s: &str) -> Result<Vec<u8>, KeyError> {
+    hex::decode(s).map_err(|e| KeyError::Decoding(format!("Invalid {}: {}", field, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::SignatureScheme;
+
+    /// Cheap parameters so tests stay fast
+    const TEST_SCRYPT: KdfParams = KdfParams::Scrypt { log_n: 4, r: 8, p: 1 };
+    const TEST_ARGON2: KdfParams = KdfParams::Argon2id { m_cost: 64, t_cost: 1, p_cost: 1 };
+
+    #[test]
+    fn test_roundtrip_with_each_kdf() {
+        for kdf in [TEST_SCRYPT, TEST_ARGON2] {
+            let kp = SetuKeyPair::generate(SignatureScheme::Secp256k1);
+            let encrypted = EncryptedKey::encrypt(&kp, "hunter2", kdf).unwrap();
+            assert_eq!(encrypted.crypto.kdf, kdf);
+            assert_eq!(encrypted.public().unwrap(), kp.public());
+            assert_eq!(encrypted.decrypt("hunter2").unwrap().address(), kp.address());
+        }
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let kp = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let encrypted = EncryptedKey::encrypt(&kp, "correct", TEST_SCRYPT).unwrap();
+        assert!(matches!(encrypted.decrypt("incorrect"), Err(KeyError::WrongPassphrase)));
+    }
+
+    #[test]
+    fn test_public_key_is_authenticated() {
+        let kp = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let other = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let mut encrypted = EncryptedKey::encrypt(&kp, "pass", TEST_SCRYPT).unwrap();
+        encrypted.public_key = other.public().encode_base64();
+        assert!(encrypted.decrypt("pass").is_err());
+    }
+
+    #[test]
+    fn test_kdf_limits() {
+        assert!(KdfParams::scrypt().check_limits().is_ok());
+        assert!(KdfParams::argon2id().check_limits().is_ok());
+
+        let kp = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let encrypted = EncryptedKey::encrypt(&kp, "pass", TEST_SCRYPT).unwrap();
+        // Refused before deriving, which would otherwise take many GiB
+        for kdf in [
+            KdfParams::Scrypt { log_n: 21, r: 1, p: 1 },
+            KdfParams::Scrypt { log_n: 20, r: 16, p: 1 },
+            KdfParams::Scrypt { log_n: 4, r: 8, p: 1 << 20 },
+            KdfParams::Argon2id { m_cost: u32::MAX, t_cost: 1, p_cost: 1 },
+            KdfParams::Argon2id { m_cost: 64, t_cost: u32::MAX, p_cost: 1 },
+        ] {
+            let mut tampered = encrypted.clone();
+            tampered.crypto.kdf = kdf;
+            assert!(matches!(tampered.decrypt("pass"), Err(KeyError::Encryption(_))));
+        }
+    }
+
+    #[test]
+    fn test_file_format() {
+        let kp = SetuKeyPair::generate(SignatureScheme::Secp256r1);
+        let file = EncryptedKeystoreFile::encrypt([&kp], "pass", TEST_SCRYPT).unwrap();
+        let json = serde_json::to_value(&file).unwrap();
+        assert_eq!(json["version"], ENCRYPTED_KEYSTORE_VERSION);
+        assert_eq!(json["keys"][0]["crypto"]["kdf"]["name"], "scrypt");
+        assert_eq!(json["keys"][0]["crypto"]["kdf"]["log_n"], 4);
+
+        let decoded: EncryptedKeystoreFile = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.decrypt("pass").unwrap()[0].address(), kp.address());
+    }
+}
@@ -26,6 +26,12 @@ pub enum KeyError {
     #[error("Signature verification failed: {0}")]
     SignatureVerification(String),
 
+    #[error("Invalid multisig: {0}")]
+    InvalidMultiSig(String),
+
+    #[error("Invalid key rotation: {0}")]
+    InvalidRotation(String),
+
     #[error("Key not found for address: {0}")]
     KeyNotFound(String),
 
@@ -43,6 +49,21 @@ pub enum KeyError {
 
     #[error("Decoding error: {0}")]
     Decoding(String),
+
+    #[error("Wrong passphrase or corrupted key file")]
+    WrongPassphrase,
+
+    #[error("Passphrase required to open encrypted key file: {0}")]
+    PassphraseRequired(String),
+
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+
+    #[error("Refusing to sign a conflicting vote for round {0}")]
+    DoubleSign(u64),
+
+    #[error("Remote signer error: {0}")]
+    RemoteSigner(String),
 }
 
 impl From<KeyError> for signature::Error {
@@ -10,6 +10,7 @@
 //! - Ed25519: m/44'/99999'/0'/0'/{index}' (SLIP-0010, all hardened)
 //! - Secp256k1: m/54'/99999'/0'/0/{index} (BIP-32/44, first 3 hardened)
 //! - Secp256r1: m/74'/99999'/0'/0/{index} (BIP-32/44, first 3 hardened)
+//! - BLS12-381: m/12381/99999/0/0/{index} (EIP-2333/2334, no hardened markers)
 
 use crate::crypto::{SetuAddress, SetuKeyPair, SignatureScheme};
 use crate::error::KeyError;
@@ -18,6 +19,7 @@ use bip39::{Language, Mnemonic};
 use ed25519_dalek::SigningKey as Ed25519SigningKey;
 use k256::ecdsa::SigningKey as Secp256k1SigningKey;
 use p256::ecdsa::SigningKey as Secp256r1SigningKey;
+use blst::min_pk::SecretKey as Bls12381SecretKey;
 use slip10_ed25519::derive_ed25519_private_key;
 
 /// Setu coin type for BIP44 derivation paths.
@@ -32,6 +34,9 @@ pub const DERIVATION_PATH_PURPOSE_SECP256K1: u32 = 54;
 /// Purpose for Secp256r1 (Sui-style, distinguishes from other schemes).
 pub const DERIVATION_PATH_PURPOSE_SECP256R1: u32 = 74;
 
+/// Purpose for BLS12-381 (EIP-2334).
+pub const DERIVATION_PATH_PURPOSE_BLS12381: u32 = 12381;
+
 /// Mnemonic word count options.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WordCount {
@@ -91,6 +96,7 @@ pub fn generate_mnemonic(word_count: WordCount) -> String {
 /// - Ed25519: m/44'/99999'/0'/0'/0'
 /// - Secp256k1: m/54'/99999'/0'/0/0
 /// - Secp256r1: m/74'/99999'/0'/0/0
+/// - BLS12-381: m/12381/99999/0/0/0
 pub fn derive_key_pair_from_path(
     seed: &[u8],
     derivation_path: Option<DerivationPath>,
@@ -122,6 +128,16 @@ pub fn derive_key_pair_from_path(
             let kp = SetuKeyPair::secp256r1(sk);
             Ok((kp.address(), kp))
         }
+        SignatureScheme::BLS12381 => {
+            // EIP-2333 children are always hardened, so indexes are used as-is
+            let master = Bls12381SecretKey::derive_master_eip2333(seed)
+                .map_err(|e| KeyError::KeyGeneration(format!("{:?}", e)))?;
+            let sk = path
+                .into_iter()
+                .fold(master, |sk, index| sk.derive_child_eip2333(index.into()));
+            let kp = SetuKeyPair::bls12381(sk);
+            Ok((kp.address(), kp))
+        }
     }
 }
 
@@ -254,6 +270,47 @@ pub fn validate_path(
                 }
             }
         }
+        SignatureScheme::BLS12381 => {
+            match path {
+                Some(p) => {
+                    // EIP-2334 paths carry no hardened markers
+                    if let &[purpose, coin_type, account, change, address] = p.as_ref() {
+                        if Some(purpose)
+                            == ChildNumber::new(DERIVATION_PATH_PURPOSE_BLS12381, false).ok()
+                            && Some(coin_type)
+                                == ChildNumber::new(DERIVATION_PATH_COIN_TYPE, false).ok()
+                            && !account.is_hardened()
+                            && !change.is_hardened()
+                            && !address.is_hardened()
+                        {
+                            Ok(p)
+                        } else {
+                            Err(KeyError::InvalidDerivationPath(
+                                "BLS12-381 requires purpose=12381, coin_type=99999, and no hardened levels".to_string(),
+                            ))
+                        }
+                    } else {
+                        Err(KeyError::InvalidDerivationPath(
+                            "Derivation path must have exactly 5 levels".to_string(),
+                        ))
+                    }
+                }
+                None => {
+                    // Default path: m/12381/99999/0/0/0
+                    let path_str = format!(
+                        "m/{}/{}/{}/{}/{}",
+                        DERIVATION_PATH_PURPOSE_BLS12381,
+                        DERIVATION_PATH_COIN_TYPE,
+                        0,
+                        0,
+                        0
+                    );
+                    path_str
+                        .parse()
+                        .map_err(|_| KeyError::InvalidDerivationPath("Cannot parse default path".to_string()))
+                }
+            }
+        }
     }
 }
 
@@ -314,6 +371,14 @@ pub fn default_derivation_path(scheme: &SignatureScheme, index: u32) -> Result<D
             0,
             index
         ),
+        SignatureScheme::BLS12381 => format!(
+            "m/{}/{}/{}/{}/{}",
+            DERIVATION_PATH_PURPOSE_BLS12381,
+            DERIVATION_PATH_COIN_TYPE,
+            0,
+            0,
+            index
+        ),
     };
     path_str
         .parse()
@@ -358,6 +423,23 @@ mod tests {
         assert_eq!(addr, kp.address());
     }
 
+    #[test]
+    fn test_derive_bls12381() {
+        let (addr, kp) =
+            derive_key_pair_from_mnemonic(TEST_MNEMONIC, &SignatureScheme::BLS12381, None).unwrap();
+        assert_eq!(kp.scheme(), SignatureScheme::BLS12381);
+        assert_eq!(addr, kp.address());
+
+        let path1 = default_derivation_path(&SignatureScheme::BLS12381, 1).unwrap();
+        let (addr1, _) =
+            derive_key_pair_from_mnemonic(TEST_MNEMONIC, &SignatureScheme::BLS12381, Some(path1))
+                .unwrap();
+        assert_ne!(addr, addr1);
+
+        let hardened = "m/12381'/99999'/0'/0/0".parse().unwrap();
+        assert!(validate_path(&SignatureScheme::BLS12381, Some(hardened)).is_err());
+    }
+
     #[test]
     fn test_deterministic_derivation() {
         // Same mnemonic should produce same keys
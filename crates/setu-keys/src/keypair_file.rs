@@ -4,24 +4,80 @@
 //! Utilities for reading and writing keypairs to files.
 
 use crate::crypto::SetuKeyPair;
+use crate::encryption::{EncryptedKey, KdfParams};
 use crate::error::KeyError;
 use std::path::Path;
 
 /// Write a keypair to a file as Base64 encoded `flag || privkey`.
 pub fn write_keypair_to_file<P: AsRef<Path>>(keypair: &SetuKeyPair, path: P) -> Result<(), KeyError> {
     let contents = keypair.encode_base64();
-    std::fs::write(&path, contents)?;
-    
-    // Set restrictive permissions on Unix
+    write_private_file(path.as_ref(), contents.as_bytes())
+}
+
+/// Write a keypair to a file encrypted under `passphrase`.
+pub fn write_encrypted_keypair_to_file<P: AsRef<Path>>(
+    keypair: &SetuKeyPair,
+    path: P,
+    passphrase: &str,
+    kdf: KdfParams,
+) -> Result<(), KeyError> {
+    let encrypted = EncryptedKey::encrypt(keypair, passphrase, kdf)?;
+    let contents = serde_json::to_string_pretty(&encrypted)
+        .map_err(|e| KeyError::Serialization(e.to_string()))?;
+    write_private_file(path.as_ref(), contents.as_bytes())
+}
+
+/// Read a keypair written by [`write_encrypted_keypair_to_file`].
+pub fn read_encrypted_keypair_from_file<P: AsRef<Path>>(
+    path: P,
+    passphrase: &str,
+) -> Result<SetuKeyPair, KeyError> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Err(KeyError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Key file not found: {:?}", path),
+        )));
+    }
+    let contents = std::fs::read_to_string(path)?;
+    let encrypted: EncryptedKey = serde_json::from_str(&contents)
+        .map_err(|e| KeyError::InvalidKeyFormat(format!("Not an encrypted key file: {}", e)))?;
+    encrypted.decrypt(passphrase)
+}
+
+/// Replace `path` with `contents`, readable by its owner only.
+///
+/// The contents go to a temporary file in the same directory, created with
+/// mode 0600 on Unix, which is then renamed over `path`. A crash leaves either
+/// the old file or the new one, never a truncated key.
+pub(crate) fn write_private_file(path: &Path, contents: &[u8]) -> Result<(), KeyError> {
+    use std::io::Write;
+
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
     #[cfg(unix)]
     {
-        use std::os::unix::fs::PermissionsExt;
-        let path = path.as_ref();
-        if path.exists() {
-            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let result = options.open(&tmp).and_then(|mut file| {
+        // A stale temp file keeps its old mode, so tighten it explicitly
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
         }
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(e) = result.and_then(|()| std::fs::rename(&tmp, path)) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e.into());
     }
-    
     Ok(())
 }
 
@@ -64,6 +120,10 @@ pub fn read_key<P: AsRef<Path>>(path: P) -> Result<SetuKeyPair, KeyError> {
             return SetuKeyPair::from_bytes(crate::crypto::SignatureScheme::ED25519, &bytes);
         }
     }
+
+    if serde_json::from_str::<EncryptedKey>(contents).is_ok() {
+        return Err(KeyError::PassphraseRequired(path.display().to_string()));
+    }
     
     Err(KeyError::InvalidKeyFormat(
         "Could not parse key file in any supported format".to_string(),
@@ -79,18 +139,7 @@ pub fn write_keypair_hex<P: AsRef<Path>>(keypair: &SetuKeyPair, path: P) -> Resu
     
     // Skip the first byte (flag)
     let hex_str = hex::encode(&bytes[1..]);
-    std::fs::write(&path, hex_str)?;
-    
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let path = path.as_ref();
-        if path.exists() {
-            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
-        }
-    }
-    
-    Ok(())
+    write_private_file(path.as_ref(), hex_str.as_bytes())
 }
 
 #[cfg(test)]
@@ -129,6 +178,46 @@ mod tests {
         assert_eq!(kp.address(), loaded_hex.address());
     }
 
+    #[test]
+    fn test_encrypted_keypair_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("encrypted.key");
+
+        let kp = SetuKeyPair::generate(SignatureScheme::Secp256k1);
+        let kdf = KdfParams::Scrypt { log_n: 4, r: 8, p: 1 };
+        write_encrypted_keypair_to_file(&kp, &path, "passphrase", kdf).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains(&kp.encode_base64()));
+        assert!(matches!(read_key(&path), Err(KeyError::PassphraseRequired(_))));
+        assert!(matches!(
+            read_encrypted_keypair_from_file(&path, "wrong"),
+            Err(KeyError::WrongPassphrase)
+        ));
+        let loaded = read_encrypted_keypair_from_file(&path, "passphrase").unwrap();
+        assert_eq!(kp.address(), loaded.address());
+    }
+
+    #[test]
+    fn test_private_file_replaced_atomically() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("keypair.key");
+        std::fs::write(&path, "old").unwrap();
+
+        let kp = SetuKeyPair::generate(SignatureScheme::ED25519);
+        write_keypair_to_file(&kp, &path).unwrap();
+        assert_eq!(read_keypair_from_file(&path).unwrap().address(), kp.address());
+        // Only the key file is left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
     #[test]
     fn test_file_not_found() {
         let result = read_keypair_from_file("/nonexistent/path/key.file");
@@ -5,13 +5,16 @@
 //!
 //! This module provides:
 //! - `AccountKeystore` trait for key management operations
-//! - `FileBasedKeystore` for persistent file storage
+//! - `FileBasedKeystore` for persistent file storage, plaintext or
+//!   passphrase-encrypted (see [`crate::encryption`])
 //! - `InMemKeystore` for in-memory storage (testing/ephemeral use)
 
 use crate::crypto::{PublicKey, SetuAddress, SetuKeyPair, Signature, SignatureScheme};
+use crate::encryption::{EncryptedKeystoreFile, KdfParams};
 use crate::error::KeyError;
 use crate::key_derive::{derive_key_pair_from_mnemonic, generate_new_key, WordCount};
 use crate::key_identity::KeyIdentity;
+use crate::keypair_file::write_private_file;
 use async_trait::async_trait;
 use bip32::DerivationPath;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -19,6 +22,7 @@ use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
 
 pub const ALIASES_FILE_EXTENSION: &str = "aliases";
 
@@ -51,6 +55,11 @@ impl Keystore {
         Ok(Keystore::File(FileBasedKeystore::load_or_create(path)?))
     }
 
+    /// Open or create a passphrase-encrypted file-based keystore.
+    pub fn encrypted_file(path: &Path, passphrase: &str) -> Result<Self, KeyError> {
+        Ok(Keystore::File(FileBasedKeystore::load_or_create_encrypted(path, passphrase)?))
+    }
+
     /// Create a new in-memory keystore.
     pub fn in_memory() -> Self {
         Keystore::InMem(InMemKeystore::default())
@@ -260,12 +269,26 @@ pub trait AccountKeystore: Send + Sync {
     }
 }
 
+/// Contents of a keystore file: the encrypted format or the legacy list of
+/// plaintext Base64 keys.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeystoreFile {
+    Encrypted(EncryptedKeystoreFile),
+    Plaintext(Vec<String>),
+}
+
 /// File-based keystore that persists keys to disk.
+///
+/// Keys are written encrypted when the keystore has a passphrase, and as
+/// plaintext Base64 otherwise.
 #[derive(Default)]
 pub struct FileBasedKeystore {
     keys: BTreeMap<SetuAddress, SetuKeyPair>,
     aliases: BTreeMap<SetuAddress, Alias>,
     path: Option<PathBuf>,
+    passphrase: Option<Zeroizing<String>>,
+    kdf: KdfParams,
 }
 
 impl Serialize for FileBasedKeystore {
@@ -377,22 +400,54 @@ impl AccountKeystore for FileBasedKeystore {
 }
 
 impl FileBasedKeystore {
-    /// Load an existing keystore or create a new one.
+    /// Load an existing plaintext keystore or create a new one.
+    ///
+    /// Fails with [`KeyError::PassphraseRequired`] if the file is encrypted.
     pub fn load_or_create(path: &Path) -> Result<Self, KeyError> {
-        let keys = if path.exists() {
+        Self::load(path, None, KdfParams::default())
+    }
+
+    /// Load an existing keystore with a passphrase, or create a new encrypted one.
+    ///
+    /// A legacy plaintext keystore is migrated: it is re-written encrypted
+    /// under `passphrase` before this returns.
+    pub fn load_or_create_encrypted(path: &Path, passphrase: &str) -> Result<Self, KeyError> {
+        Self::load(path, Some(passphrase), KdfParams::default())
+    }
+
+    /// Like [`load_or_create_encrypted`](Self::load_or_create_encrypted), but
+    /// encrypts keys with `kdf` instead of the default parameters.
+    pub fn load_or_create_encrypted_with_kdf(
+        path: &Path,
+        passphrase: &str,
+        kdf: KdfParams,
+    ) -> Result<Self, KeyError> {
+        Self::load(path, Some(passphrase), kdf)
+    }
+
+    fn load(path: &Path, passphrase: Option<&str>, kdf: KdfParams) -> Result<Self, KeyError> {
+        let mut migrate = false;
+        let keypairs = if path.exists() {
             let reader = BufReader::new(fs::File::open(path)?);
-            let kp_strings: Vec<String> = serde_json::from_reader(reader)
+            let file: KeystoreFile = serde_json::from_reader(reader)
                 .map_err(|e| KeyError::Serialization(e.to_string()))?;
-            kp_strings
-                .iter()
-                .map(|s| {
-                    let kp = SetuKeyPair::decode_base64(s)?;
-                    Ok((kp.address(), kp))
-                })
-                .collect::<Result<BTreeMap<_, _>, KeyError>>()?
+            match (file, passphrase) {
+                (KeystoreFile::Encrypted(file), Some(passphrase)) => file.decrypt(passphrase)?,
+                (KeystoreFile::Encrypted(_), None) => {
+                    return Err(KeyError::PassphraseRequired(path.display().to_string()));
+                }
+                (KeystoreFile::Plaintext(kp_strings), _) => {
+                    migrate = passphrase.is_some();
+                    kp_strings
+                        .iter()
+                        .map(|s| SetuKeyPair::decode_base64(s))
+                        .collect::<Result<Vec<_>, KeyError>>()?
+                }
+            }
         } else {
-            BTreeMap::new()
+            Vec::new()
         };
+        let keys: BTreeMap<_, _> = keypairs.into_iter().map(|kp| (kp.address(), kp)).collect();
 
         // Load aliases
         let mut aliases_path = path.to_path_buf();
@@ -429,11 +484,45 @@ impl FileBasedKeystore {
                 .collect()
         };
 
-        Ok(Self {
+        let keystore = Self {
             keys,
             aliases,
             path: Some(path.to_path_buf()),
-        })
+            passphrase: passphrase.map(|p| Zeroizing::new(p.to_string())),
+            kdf,
+        };
+        if migrate {
+            write_private_file(path, keystore.keystore_contents()?.as_bytes())?;
+        }
+        Ok(keystore)
+    }
+
+    /// Whether keys are written encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.passphrase.is_some()
+    }
+
+    /// Encrypt the keystore under a new passphrase and save it.
+    ///
+    /// Works both to encrypt a plaintext keystore and to change the passphrase
+    /// of an encrypted one.
+    pub async fn set_passphrase(&mut self, passphrase: &str) -> Result<(), KeyError> {
+        self.passphrase = Some(Zeroizing::new(passphrase.to_string()));
+        self.save_keystore().await
+    }
+
+    /// Serialize the keys in the format matching the passphrase setting.
+    fn keystore_contents(&self) -> Result<String, KeyError> {
+        let contents = match &self.passphrase {
+            Some(passphrase) => {
+                let file = EncryptedKeystoreFile::encrypt(self.keys.values(), passphrase, self.kdf)?;
+                serde_json::to_string_pretty(&file)
+            }
+            None => serde_json::to_string_pretty(
+                &self.keys.values().map(|k| k.encode_base64()).collect::<Vec<_>>(),
+            ),
+        };
+        contents.map_err(|e| KeyError::Serialization(e.to_string()))
     }
 
     /// Save aliases to file.
@@ -451,11 +540,11 @@ impl FileBasedKeystore {
     /// Save keystore to file.
     pub async fn save_keystore(&self) -> Result<(), KeyError> {
         if let Some(path) = &self.path {
-            let store = serde_json::to_string_pretty(
-                &self.keys.values().map(|k| k.encode_base64()).collect::<Vec<_>>(),
-            )
-            .map_err(|e| KeyError::Serialization(e.to_string()))?;
-            tokio::fs::write(path, store).await?;
+            let contents = self.keystore_contents()?;
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || write_private_file(&path, contents.as_bytes()))
+                .await
+                .map_err(|e| KeyError::Io(std::io::Error::other(e)))??;
         }
         Ok(())
     }
@@ -467,6 +556,27 @@ impl FileBasedKeystore {
         Ok(())
     }
 
+    /// Write an encrypted copy of the keystore and its aliases to `path`.
+    ///
+    /// The backup is itself a keystore: restore it by opening it with
+    /// `passphrase`, or by copying it back in place of the original.
+    pub async fn backup(&self, path: &Path, passphrase: &str) -> Result<(), KeyError> {
+        if self.path.as_deref() == Some(path) {
+            return Err(KeyError::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("Backup would overwrite the keystore itself: {:?}", path),
+            )));
+        }
+        let backup = Self {
+            keys: self.keys.clone(),
+            aliases: self.aliases.clone(),
+            path: Some(path.to_path_buf()),
+            passphrase: Some(Zeroizing::new(passphrase.to_string())),
+            kdf: self.kdf,
+        };
+        backup.save().await
+    }
+
     /// Get all keypairs.
     pub fn key_pairs(&self) -> Vec<&SetuKeyPair> {
         self.keys.values().collect()
@@ -591,6 +701,8 @@ fn generate_random_alias_with_index(existing: &HashSet<String>, _index: usize) -
 mod tests {
     use super::*;
 
+    const TEST_KDF: KdfParams = KdfParams::Scrypt { log_n: 4, r: 8, p: 1 };
+
     #[tokio::test]
     async fn test_inmem_keystore() {
         let mut ks = InMemKeystore::default();
@@ -635,4 +747,90 @@ mod tests {
         assert_eq!(ks2.addresses().len(), 1);
         assert_eq!(ks2.addresses()[0], result.address);
     }
+
+    #[tokio::test]
+    async fn test_encrypted_file_keystore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keystore.json");
+
+        let mut ks =
+            FileBasedKeystore::load_or_create_encrypted_with_kdf(&path, "secret", TEST_KDF).unwrap();
+        let result = ks
+            .generate(Some("validator".to_string()), SignatureScheme::ED25519, None, None)
+            .await
+            .unwrap();
+        let secret = ks.export(&result.address).unwrap().encode_base64();
+        assert!(!fs::read_to_string(&path).unwrap().contains(&secret));
+
+        assert!(matches!(
+            FileBasedKeystore::load_or_create(&path),
+            Err(KeyError::PassphraseRequired(_))
+        ));
+        assert!(matches!(
+            FileBasedKeystore::load_or_create_encrypted(&path, "wrong"),
+            Err(KeyError::WrongPassphrase)
+        ));
+
+        let ks2 = FileBasedKeystore::load_or_create_encrypted(&path, "secret").unwrap();
+        assert!(ks2.is_encrypted());
+        assert_eq!(ks2.addresses(), vec![result.address]);
+        assert_eq!(ks2.get_alias(&result.address).unwrap(), "validator");
+    }
+
+    #[tokio::test]
+    async fn test_migrate_plaintext_keystore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keystore.json");
+
+        let mut plain = FileBasedKeystore::load_or_create(&path).unwrap();
+        let result = plain
+            .generate(None, SignatureScheme::Secp256k1, None, None)
+            .await
+            .unwrap();
+        let secret = plain.export(&result.address).unwrap().encode_base64();
+        assert!(fs::read_to_string(&path).unwrap().contains(&secret));
+
+        // Opening with a passphrase re-writes the file encrypted
+        let migrated =
+            FileBasedKeystore::load_or_create_encrypted_with_kdf(&path, "secret", TEST_KDF).unwrap();
+        assert_eq!(migrated.addresses(), vec![result.address]);
+        assert!(!fs::read_to_string(&path).unwrap().contains(&secret));
+
+        let mut reopened =
+            FileBasedKeystore::load_or_create_encrypted_with_kdf(&path, "secret", TEST_KDF).unwrap();
+        reopened.set_passphrase("rotated").await.unwrap();
+        assert!(FileBasedKeystore::load_or_create_encrypted(&path, "secret").is_err());
+        let ks = FileBasedKeystore::load_or_create_encrypted(&path, "rotated").unwrap();
+        assert_eq!(ks.addresses(), vec![result.address]);
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keystore.json");
+        let backup_path = dir.path().join("backup.keystore");
+
+        let mut ks =
+            FileBasedKeystore::load_or_create_encrypted_with_kdf(&path, "secret", TEST_KDF).unwrap();
+        let result = ks
+            .generate(Some("validator".to_string()), SignatureScheme::ED25519, None, None)
+            .await
+            .unwrap();
+        assert!(ks.backup(&path, "backup").await.is_err());
+        ks.backup(&backup_path, "backup").await.unwrap();
+
+        // The backup has its own passphrase
+        assert!(matches!(
+            FileBasedKeystore::load_or_create_encrypted(&backup_path, "secret"),
+            Err(KeyError::WrongPassphrase)
+        ));
+        let restored = FileBasedKeystore::load_or_create_encrypted_with_kdf(
+            &backup_path,
+            "backup",
+            TEST_KDF,
+        )
+        .unwrap();
+        assert_eq!(restored.addresses(), vec![result.address]);
+        assert_eq!(restored.get_alias(&result.address).unwrap(), "validator");
+    }
 }
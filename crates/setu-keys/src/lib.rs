@@ -5,23 +5,44 @@
 //!
 //! This crate provides:
 //! - Key generation and derivation from BIP39 mnemonics
-//! - Multiple signature schemes (Ed25519, Secp256k1, Secp256r1)
-//! - Key storage (file-based and in-memory)
+//! - Multiple signature schemes (Ed25519, Secp256k1, Secp256r1, BLS12-381)
+//! - BLS12-381 signature aggregation for validator votes
+//! - Weighted k-of-n multisig addresses
+//! - In-process and remote signers with double-sign protection
+//! - Key rotation for node identities
+//! - Key storage (file-based and in-memory), optionally passphrase-encrypted
 //! - Address derivation from public keys
 
 // Suppress warning from zeroize macro
 #![allow(unused_assignments)]
 
+pub mod bls;
 pub mod crypto;
+pub mod encryption;
 pub mod error;
 pub mod key_derive;
 pub mod key_identity;
 pub mod keypair_file;
 pub mod keystore;
+pub mod multisig;
+#[cfg(unix)]
+pub mod remote_signer;
+pub mod rotation;
+pub mod signer;
 
 pub use crypto::{PublicKey, SetuAddress, SetuKeyPair, Signature, SignatureScheme};
+pub use encryption::{EncryptedKey, KdfParams};
 pub use error::KeyError;
-pub use key_derive::{derive_key_pair_from_path, generate_new_key};
+pub use bip32::DerivationPath;
+pub use key_derive::{derive_key_pair_from_path, generate_new_key, WordCount};
 pub use key_identity::KeyIdentity;
-pub use keypair_file::{read_keypair_from_file, write_keypair_to_file};
+pub use keypair_file::{
+    read_encrypted_keypair_from_file, read_keypair_from_file, write_encrypted_keypair_to_file,
+    write_keypair_to_file,
+};
 pub use keystore::{AccountKeystore, FileBasedKeystore, InMemKeystore, Keystore};
+pub use multisig::{MultiSigPublicKey, MultiSignature};
+#[cfg(unix)]
+pub use remote_signer::RemoteSigner;
+pub use rotation::{KeyHistory, KeyRotation};
+pub use signer::{verify_vote, vote_signing_bytes, DoubleSignGuard, LocalSigner, Signer};
@@ -0,0 +1,378 @@
+// Copyright (c) Hetu Project
+// SPDX-License-Identifier: Apache-2.0
+
+//! Weighted k-of-n multisig.
+//!
+//! A [`MultiSigPublicKey`] is a list of up to [`MAX_SIGNER_IN_MULTISIG`]
+//! weighted public keys of any scheme plus a threshold. It has its own address,
+//! so objects can be owned by a committee rather than a single key. A
+//! [`MultiSignature`] carries the partial signatures of some members and a
+//! bitmap saying whose they are; it is valid when every partial signature
+//! verifies and the signers' weights add up to the threshold.
+//!
+//! # Example
+//!
+//! ```
+//! use setu_keys::{MultiSigPublicKey, MultiSignature, SetuKeyPair, SignatureScheme};
+//!
+//! let keys: Vec<_> = (0..3)
+//!     .map(|_| SetuKeyPair::generate(SignatureScheme::ED25519))
+//!     .collect();
+//! let multisig_pk = MultiSigPublicKey::new(
+//!     keys.iter().map(|kp| kp.public()).collect(),
+//!     vec![1, 1, 1],
+//!     2,
+//! )
+//! .unwrap();
+//!
+//! let msg = b"transfer";
+//! let sig = MultiSignature::combine(
+//!     &multisig_pk,
+//!     vec![(keys[0].public(), keys[0].sign(msg)), (keys[2].public(), keys[2].sign(msg))],
+//! )
+//! .unwrap();
+//! assert!(sig.verify(msg).is_ok());
+//! assert_eq!(sig.address(), multisig_pk.address());
+//! ```
+
+use crate::crypto::{PublicKey, SetuAddress, Signature};
+use crate::error::KeyError;
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use serde::{Deserialize, Serialize};
+
+type Blake2b256 = Blake2b<U32>;
+
+/// Flag byte for multisig addresses.
+pub const MULTISIG_FLAG: u8 = 0x04;
+
+/// Maximum number of keys in a multisig, so the signer bitmap fits a `u16`.
+pub const MAX_SIGNER_IN_MULTISIG: usize = 10;
+
+/// Weight of a single member.
+pub type WeightUnit = u8;
+
+/// Total weight required to sign.
+pub type ThresholdUnit = u16;
+
+/// Weighted public keys and the weight required to sign for them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiSigPublicKey {
+    pk_map: Vec<(PublicKey, WeightUnit)>,
+    threshold: ThresholdUnit,
+}
+
+impl MultiSigPublicKey {
+    /// Create a multisig public key.
+    ///
+    /// Fails if the keys are empty, too many, or repeated, if any weight is
+    /// zero, or if the threshold is zero or unreachable.
+    pub fn new(
+        pks: Vec<PublicKey>,
+        weights: Vec<WeightUnit>,
+        threshold: ThresholdUnit,
+    ) -> Result<Self, KeyError> {
+        if pks.len() != weights.len() {
+            return Err(KeyError::InvalidMultiSig(format!(
+                "{} public keys but {} weights",
+                pks.len(),
+                weights.len()
+            )));
+        }
+        let multisig_pk = Self {
+            pk_map: pks.into_iter().zip(weights).collect(),
+            threshold,
+        };
+        multisig_pk.validate()?;
+        Ok(multisig_pk)
+    }
+
+    /// Check the invariants enforced by [`MultiSigPublicKey::new`].
+    ///
+    /// Deserialized keys have not been checked yet; verification calls this.
+    pub fn validate(&self) -> Result<(), KeyError> {
+        if self.pk_map.is_empty() || self.pk_map.len() > MAX_SIGNER_IN_MULTISIG {
+            return Err(KeyError::InvalidMultiSig(format!(
+                "Multisig must have between 1 and {} keys, got {}",
+                MAX_SIGNER_IN_MULTISIG,
+                self.pk_map.len()
+            )));
+        }
+        if self.pk_map.iter().any(|(_, weight)| *weight == 0) {
+            return Err(KeyError::InvalidMultiSig("Weights must be non-zero".to_string()));
+        }
+        for (i, (pk, _)) in self.pk_map.iter().enumerate() {
+            if self.pk_map[..i].iter().any(|(other, _)| other == pk) {
+                return Err(KeyError::InvalidMultiSig(format!(
+                    "Duplicate public key {}",
+                    pk.encode_base64()
+                )));
+            }
+        }
+        if self.threshold == 0 || self.threshold > self.total_weight() {
+            return Err(KeyError::InvalidMultiSig(format!(
+                "Threshold {} must be between 1 and the total weight {}",
+                self.threshold,
+                self.total_weight()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Member keys and their weights, in signing order.
+    pub fn pubkeys(&self) -> &[(PublicKey, WeightUnit)] {
+        &self.pk_map
+    }
+
+    /// Weight required for a valid signature.
+    pub fn threshold(&self) -> ThresholdUnit {
+        self.threshold
+    }
+
+    /// Sum of all member weights.
+    pub fn total_weight(&self) -> ThresholdUnit {
+        self.pk_map.iter().map(|(_, w)| *w as ThresholdUnit).sum()
+    }
+
+    /// Position of a member key.
+    pub fn index_of(&self, pk: &PublicKey) -> Option<usize> {
+        self.pk_map.iter().position(|(member, _)| member == pk)
+    }
+
+    /// Address owned by this committee.
+    pub fn address(&self) -> SetuAddress {
+        self.into()
+    }
+}
+
+impl From<&MultiSigPublicKey> for SetuAddress {
+    fn from(multisig_pk: &MultiSigPublicKey) -> Self {
+        // Address = Blake2b-256(flag || threshold || (pk_flag || pk || weight)*)
+        let mut hasher = Blake2b256::new();
+        hasher.update([MULTISIG_FLAG]);
+        hasher.update(multisig_pk.threshold.to_le_bytes());
+        for (pk, weight) in &multisig_pk.pk_map {
+            hasher.update([pk.scheme().flag()]);
+            hasher.update(pk.as_bytes());
+            hasher.update([*weight]);
+        }
+        let mut addr = [0u8; 32];
+        addr.copy_from_slice(&hasher.finalize());
+        SetuAddress::from_bytes(addr)
+    }
+}
+
+/// Partial signatures from members of a [`MultiSigPublicKey`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiSignature {
+    /// Signatures ordered by signer index
+    sigs: Vec<Signature>,
+    /// Bit `i` set if member `i` signed
+    bitmap: u16,
+    multisig_pk: MultiSigPublicKey,
+}
+
+impl MultiSignature {
+    /// Combine member signatures, given with the key that made each.
+    ///
+    /// Signatures are not verified here; only their signers are checked to be
+    /// distinct members.
+    pub fn combine(
+        multisig_pk: &MultiSigPublicKey,
+        partial_sigs: Vec<(PublicKey, Signature)>,
+    ) -> Result<Self, KeyError> {
+        multisig_pk.validate()?;
+        if partial_sigs.is_empty() {
+            return Err(KeyError::InvalidMultiSig("No signatures to combine".to_string()));
+        }
+
+        let mut indexed = Vec::with_capacity(partial_sigs.len());
+        let mut bitmap = 0u16;
+        for (pk, sig) in partial_sigs {
+            let index = multisig_pk.index_of(&pk).ok_or_else(|| {
+                KeyError::InvalidMultiSig(format!("{} is not a member", pk.encode_base64()))
+            })?;
+            if bitmap & (1 << index) != 0 {
+                return Err(KeyError::InvalidMultiSig(format!(
+                    "Duplicate signature from member {}",
+                    index
+                )));
+            }
+            bitmap |= 1 << index;
+            indexed.push((index, sig));
+        }
+        indexed.sort_by_key(|(index, _)| *index);
+
+        Ok(Self {
+            sigs: indexed.into_iter().map(|(_, sig)| sig).collect(),
+            bitmap,
+            multisig_pk: multisig_pk.clone(),
+        })
+    }
+
+    /// Verify the signature over `msg`.
+    pub fn verify(&self, msg: &[u8]) -> Result<(), KeyError> {
+        self.multisig_pk.validate()?;
+        let members = self.multisig_pk.pubkeys();
+        if self.bitmap >> members.len() != 0 {
+            return Err(KeyError::SignatureVerification(
+                "Bitmap refers to unknown members".to_string(),
+            ));
+        }
+        let signers = self.signer_indexes();
+        if signers.len() != self.sigs.len() {
+            return Err(KeyError::SignatureVerification(format!(
+                "Bitmap has {} signers but {} signatures are present",
+                signers.len(),
+                self.sigs.len()
+            )));
+        }
+
+        let mut weight: ThresholdUnit = 0;
+        for (index, sig) in signers.into_iter().zip(&self.sigs) {
+            let (pk, member_weight) = &members[index];
+            pk.verify(msg, sig).map_err(|e| {
+                KeyError::SignatureVerification(format!("Member {}: {}", index, e))
+            })?;
+            weight += *member_weight as ThresholdUnit;
+        }
+
+        if weight < self.multisig_pk.threshold() {
+            return Err(KeyError::SignatureVerification(format!(
+                "Insufficient weight {}, threshold is {}",
+                weight,
+                self.multisig_pk.threshold()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Indexes of the members who signed, ascending.
+    pub fn signer_indexes(&self) -> Vec<usize> {
+        (0..MAX_SIGNER_IN_MULTISIG)
+            .filter(|i| self.bitmap & (1 << i) != 0)
+            .collect()
+    }
+
+    /// The multisig public key this signature is for.
+    pub fn multisig_pk(&self) -> &MultiSigPublicKey {
+        &self.multisig_pk
+    }
+
+    /// Address of the signing committee.
+    pub fn address(&self) -> SetuAddress {
+        self.multisig_pk.address()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{SetuKeyPair, SignatureScheme};
+
+    fn members() -> Vec<SetuKeyPair> {
+        vec![
+            SetuKeyPair::generate(SignatureScheme::ED25519),
+            SetuKeyPair::generate(SignatureScheme::Secp256k1),
+            SetuKeyPair::generate(SignatureScheme::Secp256r1),
+        ]
+    }
+
+    fn multisig_pk(keys: &[SetuKeyPair], weights: Vec<WeightUnit>, threshold: u16) -> MultiSigPublicKey {
+        MultiSigPublicKey::new(keys.iter().map(|kp| kp.public()).collect(), weights, threshold)
+            .unwrap()
+    }
+
+    fn partial(kp: &SetuKeyPair, msg: &[u8]) -> (PublicKey, Signature) {
+        (kp.public(), kp.sign(msg))
+    }
+
+    #[test]
+    fn test_threshold() {
+        let keys = members();
+        let pk = multisig_pk(&keys, vec![1, 1, 2], 3);
+        let msg = b"spend";
+
+        // Weight 1 + 2 meets the threshold, in any order
+        let sig = MultiSignature::combine(&pk, vec![partial(&keys[2], msg), partial(&keys[0], msg)])
+            .unwrap();
+        assert_eq!(sig.signer_indexes(), vec![0, 2]);
+        assert!(sig.verify(msg).is_ok());
+        assert!(sig.verify(b"other").is_err());
+
+        // Weight 1 + 1 does not
+        let sig = MultiSignature::combine(&pk, vec![partial(&keys[0], msg), partial(&keys[1], msg)])
+            .unwrap();
+        assert!(matches!(sig.verify(msg), Err(KeyError::SignatureVerification(_))));
+    }
+
+    #[test]
+    fn test_combine_rejects_bad_signers() {
+        let keys = members();
+        let pk = multisig_pk(&keys, vec![1, 1, 1], 2);
+        let outsider = SetuKeyPair::generate(SignatureScheme::ED25519);
+
+        assert!(MultiSignature::combine(&pk, vec![partial(&outsider, b"m")]).is_err());
+        assert!(MultiSignature::combine(
+            &pk,
+            vec![partial(&keys[0], b"m"), partial(&keys[0], b"m")]
+        )
+        .is_err());
+        assert!(MultiSignature::combine(&pk, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_tampered_signature() {
+        let keys = members();
+        let pk = multisig_pk(&keys, vec![1, 1, 1], 2);
+        let sig = MultiSignature::combine(&pk, vec![partial(&keys[0], b"m"), partial(&keys[1], b"m")])
+            .unwrap();
+
+        // Claiming a signature came from another member fails
+        let mut forged = sig.clone();
+        forged.bitmap = 0b110;
+        assert!(forged.verify(b"m").is_err());
+
+        let mut extra_bit = sig.clone();
+        extra_bit.bitmap = 0b1000_0011;
+        assert!(extra_bit.verify(b"m").is_err());
+
+        // A lowered threshold changes the address, so it is not a forgery
+        let mut lowered = sig;
+        lowered.multisig_pk.threshold = 1;
+        assert_ne!(lowered.address(), pk.address());
+    }
+
+    #[test]
+    fn test_invalid_multisig_pk() {
+        let keys = members();
+        let pks: Vec<_> = keys.iter().map(|kp| kp.public()).collect();
+
+        assert!(MultiSigPublicKey::new(pks.clone(), vec![1, 1], 1).is_err());
+        assert!(MultiSigPublicKey::new(pks.clone(), vec![1, 0, 1], 1).is_err());
+        assert!(MultiSigPublicKey::new(pks.clone(), vec![1, 1, 1], 0).is_err());
+        assert!(MultiSigPublicKey::new(pks.clone(), vec![1, 1, 1], 4).is_err());
+        assert!(MultiSigPublicKey::new(vec![pks[0].clone(), pks[0].clone()], vec![1, 1], 1).is_err());
+        assert!(MultiSigPublicKey::new(vec![], vec![], 1).is_err());
+
+        let many: Vec<_> = (0..=MAX_SIGNER_IN_MULTISIG)
+            .map(|_| SetuKeyPair::generate(SignatureScheme::ED25519).public())
+            .collect();
+        let weights = vec![1; many.len()];
+        assert!(MultiSigPublicKey::new(many, weights, 1).is_err());
+    }
+
+    #[test]
+    fn test_address() {
+        let keys = members();
+        let a = multisig_pk(&keys, vec![1, 1, 1], 2);
+        assert_eq!(a.address(), multisig_pk(&keys, vec![1, 1, 1], 2).address());
+        assert_ne!(a.address(), multisig_pk(&keys, vec![1, 1, 1], 3).address());
+        assert_ne!(a.address(), multisig_pk(&keys, vec![1, 2, 1], 2).address());
+        assert_ne!(a.address(), keys[0].address());
+
+        let json = serde_json::to_string(&a).unwrap();
+        let decoded: MultiSigPublicKey = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.address(), a.address());
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright (c) Hetu Project
+// SPDX-License-Identifier: Apache-2.0
+
+//! Remote signing over a local Unix socket.
+//!
+//! The signing daemon (`setu-signer`) owns the key and its double-sign record;
+//! nodes talk to it through [`RemoteSigner`]. The protocol is newline-delimited
+//! JSON: each connection carries any number of requests, and every request
+//! line is answered by exactly one response line. The daemon only signs votes,
+//! so every signature it releases has passed the double-sign guard.
+//!
+//! ```text
+//! -> {"method":"public_key"}
+//! <- {"result":"public_key","public_key":"AK..."}
+//! -> {"method":"sign_vote","round":7,"msg":"0a0b"}
+//! <- {"result":"signature","signature":"A5..."}
+//! -> {"method":"sign_vote","round":7,"msg":"0c0d"}
+//! <- {"result":"double_sign","round":7}
+//! ```
+//!
+//! Messages are hex-encoded; keys and signatures use the usual Base64
+//! `flag || bytes` encoding.
+
+use crate::crypto::{PublicKey, Signature};
+use crate::error::KeyError;
+use crate::signer::{verify_vote, Signer};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+/// Request sent to the signing daemon.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SignerRequest {
+    PublicKey,
+    SignVote { round: u64, msg: String },
+}
+
+/// Response from the signing daemon.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum SignerResponse {
+    PublicKey { public_key: PublicKey },
+    Signature { signature: Signature },
+    DoubleSign { round: u64 },
+    Error { message: String },
+}
+
+/// Client for a signing daemon listening on a Unix socket.
+pub struct RemoteSigner {
+    socket_path: PathBuf,
+    public_key: PublicKey,
+}
+
+impl RemoteSigner {
+    /// Connect to the daemon at `socket_path` and fetch its public key.
+    pub async fn connect(socket_path: &Path) -> Result<Self, KeyError> {
+        let response = send_request(socket_path, &SignerRequest::PublicKey).await?;
+        match response {
+            SignerResponse::PublicKey { public_key } => Ok(Self {
+                socket_path: socket_path.to_path_buf(),
+                public_key,
+            }),
+            other => Err(unexpected(other)),
+        }
+    }
+
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    async fn public_key(&self) -> Result<PublicKey, KeyError> {
+        Ok(self.public_key.clone())
+    }
+
+    async fn sign_vote(&self, round: u64, msg: &[u8]) -> Result<Signature, KeyError> {
+        let request = SignerRequest::SignVote {
+            round,
+            msg: hex::encode(msg),
+        };
+        let signature = match send_request(&self.socket_path, &request).await? {
+            SignerResponse::Signature { signature } => signature,
+            other => return Err(unexpected(other)),
+        };
+        // Guard against a daemon serving a different key than at connect time
+        verify_vote(&self.public_key, round, msg, &signature)?;
+        Ok(signature)
+    }
+}
+
+/// Send one request on a fresh connection and read the response.
+async fn send_request(socket_path: &Path, request: &SignerRequest) -> Result<SignerResponse, KeyError> {
+    let stream = UnixStream::connect(socket_path).await.map_err(|e| {
+        KeyError::RemoteSigner(format!("Cannot connect to {:?}: {}", socket_path, e))
+    })?;
+    let (read, mut write) = stream.into_split();
+
+    let mut line = serde_json::to_string(request).map_err(|e| KeyError::Serialization(e.to_string()))?;
+    line.push('\n');
+    write.write_all(line.as_bytes()).await?;
+
+    let mut response = String::new();
+    if BufReader::new(read).read_line(&mut response).await? == 0 {
+        return Err(KeyError::RemoteSigner("Connection closed by signer".to_string()));
+    }
+    serde_json::from_str(&response).map_err(|e| KeyError::RemoteSigner(format!("Bad response: {}", e)))
+}
+
+fn unexpected(response: SignerResponse) -> KeyError {
+    match response {
+        SignerResponse::DoubleSign { round } => KeyError::DoubleSign(round),
+        SignerResponse::Error { message } => KeyError::RemoteSigner(message),
+        other => KeyError::RemoteSigner(format!("Unexpected response: {:?}", other)),
+    }
+}
+
+fn decode_msg(msg: &str) -> Result<Vec<u8>, KeyError> {
+    hex::decode(msg).map_err(|e| KeyError::Decoding(format!("Invalid message: {}", e)))
+}
+
+/// Answer a single request with `signer`.
+pub async fn handle_request(signer: &dyn Signer, request: SignerRequest) -> SignerResponse {
+    let result = match request {
+        SignerRequest::PublicKey => signer
+            .public_key()
+            .await
+            .map(|public_key| SignerResponse::PublicKey { public_key }),
+        SignerRequest::SignVote { round, msg } => match decode_msg(&msg) {
+            Ok(msg) => signer
+                .sign_vote(round, &msg)
+                .await
+                .map(|signature| SignerResponse::Signature { signature }),
+            Err(e) => Err(e),
+        },
+    };
+    result.unwrap_or_else(|e| match e {
+        KeyError::DoubleSign(round) => SignerResponse::DoubleSign { round },
+        e => SignerResponse::Error { message: e.to_string() },
+    })
+}
+
+/// Serve requests on `listener` until it fails.
+pub async fn serve(listener: UnixListener, signer: Arc<dyn Signer>) -> Result<(), KeyError> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let signer = signer.clone();
+        tokio::spawn(async move {
+            // A broken connection only affects that client
+            let _ = serve_connection(stream, signer.as_ref()).await;
+        });
+    }
+}
+
+async fn serve_connection(stream: UnixStream, signer: &dyn Signer) -> Result<(), KeyError> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str::<SignerRequest>(&line) {
+            Ok(request) => handle_request(signer, request).await,
+            Err(e) => SignerResponse::Error {
+                message: format!("Bad request: {}", e),
+            },
+        };
+        let mut line =
+            serde_json::to_string(&response).map_err(|e| KeyError::Serialization(e.to_string()))?;
+        line.push('\n');
+        write.write_all(line.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{SetuKeyPair, SignatureScheme};
+    use crate::signer::LocalSigner;
+
+    async fn start_daemon(dir: &Path, kp: SetuKeyPair) -> PathBuf {
+        let socket = dir.join("signer.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        tokio::spawn(serve(listener, Arc::new(LocalSigner::new(kp))));
+        socket
+    }
+
+    #[tokio::test]
+    async fn test_remote_signing() {
+        let dir = tempfile::tempdir().unwrap();
+        let kp = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let socket = start_daemon(dir.path(), kp.clone()).await;
+
+        let signer = RemoteSigner::connect(&socket).await.unwrap();
+        assert_eq!(signer.public_key().await.unwrap(), kp.public());
+
+        let sig = signer.sign_vote(3, b"vote-a").await.unwrap();
+        assert!(verify_vote(&kp.public(), 3, b"vote-a", &sig).is_ok());
+        assert!(matches!(
+            signer.sign_vote(3, b"vote-b").await,
+            Err(KeyError::DoubleSign(3))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_protocol_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = start_daemon(dir.path(), SetuKeyPair::generate(SignatureScheme::ED25519)).await;
+
+        let stream = UnixStream::connect(&socket).await.unwrap();
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+
+        // Raw messages cannot be signed, only votes
+        write
+            .write_all(b"not json\n{\"method\":\"sign_vote\",\"round\":1,\"msg\":\"zz\"}\n{\"method\":\"sign\",\"msg\":\"00\"}\n")
+            .await
+            .unwrap();
+        for _ in 0..3 {
+            let response: SignerResponse =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            assert!(matches!(response, SignerResponse::Error { .. }));
+        }
+
+        assert!(RemoteSigner::connect(&dir.path().join("missing.sock")).await.is_err());
+    }
+}
@@ -0,0 +1,276 @@
+// Copyright (c) Hetu Project
+// SPDX-License-Identifier: Apache-2.0
+
+//! Key rotation for node identities.
+//!
+//! A [`KeyRotation`] moves a node from one key to another starting at an
+//! epoch. It is signed by both keys: the old key authorises the change and the
+//! new key proves possession, so nobody can rotate a node onto a key they do
+//! not hold, or claim someone else's key as its new one. It does not protect
+//! against theft of the old key: whoever holds it can rotate the node onto a
+//! key of their own. Rotations only take effect in future epochs, so a stolen
+//! key cannot rewrite which key was valid in epochs already finalized.
+//! Rotations are recorded on-ledger and replayed into a [`KeyHistory`], which
+//! answers which key a node held at a given epoch.
+//!
+//! # Example
+//!
+//! ```
+//! use setu_keys::{KeyHistory, KeyRotation, SetuKeyPair, SignatureScheme};
+//!
+//! let old = SetuKeyPair::generate(SignatureScheme::ED25519);
+//! let new = SetuKeyPair::generate(SignatureScheme::ED25519);
+//!
+//! let mut history = KeyHistory::new("validator-1".to_string(), old.public());
+//! // Applied in epoch 3, taking effect in epoch 5
+//! history.apply(KeyRotation::new("validator-1", &old, &new, 5), 3).unwrap();
+//!
+//! assert_eq!(history.key_at(4), &old.public());
+//! assert_eq!(history.key_at(5), &new.public());
+//! ```
+
+use crate::crypto::{PublicKey, SetuKeyPair, Signature};
+use crate::error::KeyError;
+use serde::{Deserialize, Serialize};
+
+/// Domain separation tag for rotation signatures.
+const KEY_ROTATION_DOMAIN: &[u8] = b"SETU_KEY_ROTATION";
+
+/// A node's switch from one key to another, effective from an epoch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyRotation {
+    pub node_id: String,
+    pub old_public_key: PublicKey,
+    pub new_public_key: PublicKey,
+    /// First epoch in which the new key is used
+    pub effective_epoch: u64,
+    /// Signature by the old key over [`KeyRotation::signing_bytes`]
+    pub old_signature: Signature,
+    /// Signature by the new key over [`KeyRotation::signing_bytes`]
+    pub new_signature: Signature,
+}
+
+impl KeyRotation {
+    /// Create a rotation signed by both keys.
+    pub fn new(node_id: &str, old: &SetuKeyPair, new: &SetuKeyPair, effective_epoch: u64) -> Self {
+        let old_public_key = old.public();
+        let new_public_key = new.public();
+        let msg = Self::signing_bytes(node_id, &old_public_key, &new_public_key, effective_epoch);
+        Self {
+            node_id: node_id.to_string(),
+            old_signature: old.sign(&msg),
+            new_signature: new.sign(&msg),
+            old_public_key,
+            new_public_key,
+            effective_epoch,
+        }
+    }
+
+    /// Bytes signed by both keys.
+    ///
+    /// `domain || len(node_id) || node_id || flag || old || flag || new || epoch`
+    pub fn signing_bytes(
+        node_id: &str,
+        old_public_key: &PublicKey,
+        new_public_key: &PublicKey,
+        effective_epoch: u64,
+    ) -> Vec<u8> {
+        let mut msg = KEY_ROTATION_DOMAIN.to_vec();
+        msg.extend((node_id.len() as u32).to_le_bytes());
+        msg.extend(node_id.as_bytes());
+        for pk in [old_public_key, new_public_key] {
+            msg.push(pk.scheme().flag());
+            msg.extend(pk.as_bytes());
+        }
+        msg.extend(effective_epoch.to_le_bytes());
+        msg
+    }
+
+    /// Check both signatures.
+    pub fn verify(&self) -> Result<(), KeyError> {
+        if self.old_public_key == self.new_public_key {
+            return Err(KeyError::InvalidRotation("New key equals old key".to_string()));
+        }
+        let msg = Self::signing_bytes(
+            &self.node_id,
+            &self.old_public_key,
+            &self.new_public_key,
+            self.effective_epoch,
+        );
+        self.old_public_key
+            .verify(&msg, &self.old_signature)
+            .map_err(|e| KeyError::InvalidRotation(format!("Old key signature: {}", e)))?;
+        self.new_public_key
+            .verify(&msg, &self.new_signature)
+            .map_err(|e| KeyError::InvalidRotation(format!("New key signature: {}", e)))
+    }
+}
+
+/// The keys a node has held, by epoch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyHistory {
+    node_id: String,
+    initial_key: PublicKey,
+    /// Applied rotations, by increasing effective epoch
+    rotations: Vec<KeyRotation>,
+}
+
+impl KeyHistory {
+    /// Start a history with the key the node registered with.
+    pub fn new(node_id: String, initial_key: PublicKey) -> Self {
+        Self {
+            node_id,
+            initial_key,
+            rotations: Vec::new(),
+        }
+    }
+
+    /// Apply a verified rotation during `current_epoch`.
+    ///
+    /// The rotation must start from the current key, take effect after both
+    /// `current_epoch` and the previous rotation, and not return to a key the
+    /// node has used before.
+    pub fn apply(&mut self, rotation: KeyRotation, current_epoch: u64) -> Result<(), KeyError> {
+        if rotation.node_id != self.node_id {
+            return Err(KeyError::InvalidRotation(format!(
+                "Rotation is for {}, not {}",
+                rotation.node_id, self.node_id
+            )));
+        }
+        rotation.verify()?;
+        if rotation.old_public_key != *self.current_key() {
+            return Err(KeyError::InvalidRotation(
+                "Old key is not the node's current key".to_string(),
+            ));
+        }
+        if rotation.effective_epoch <= current_epoch {
+            return Err(KeyError::InvalidRotation(format!(
+                "Epoch {} is not after the current epoch {}",
+                rotation.effective_epoch, current_epoch
+            )));
+        }
+        if let Some(last) = self.rotations.last() {
+            if rotation.effective_epoch <= last.effective_epoch {
+                return Err(KeyError::InvalidRotation(format!(
+                    "Epoch {} is not after the previous rotation at epoch {}",
+                    rotation.effective_epoch, last.effective_epoch
+                )));
+            }
+        }
+        if self.keys().any(|pk| *pk == rotation.new_public_key) {
+            return Err(KeyError::InvalidRotation("New key was used before".to_string()));
+        }
+        self.rotations.push(rotation);
+        Ok(())
+    }
+
+    /// Node this history belongs to.
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    /// Key the node uses at `epoch`.
+    pub fn key_at(&self, epoch: u64) -> &PublicKey {
+        self.rotations
+            .iter()
+            .rev()
+            .find(|r| r.effective_epoch <= epoch)
+            .map(|r| &r.new_public_key)
+            .unwrap_or(&self.initial_key)
+    }
+
+    /// Latest key, including rotations that take effect in future epochs.
+    pub fn current_key(&self) -> &PublicKey {
+        self.rotations
+            .last()
+            .map(|r| &r.new_public_key)
+            .unwrap_or(&self.initial_key)
+    }
+
+    /// Verify a signature by the node at `epoch`.
+    pub fn verify_at(&self, epoch: u64, msg: &[u8], sig: &Signature) -> Result<(), KeyError> {
+        self.key_at(epoch).verify(msg, sig)
+    }
+
+    /// Applied rotations, oldest first.
+    pub fn rotations(&self) -> &[KeyRotation] {
+        &self.rotations
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &PublicKey> {
+        std::iter::once(&self.initial_key).chain(self.rotations.iter().map(|r| &r.new_public_key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::SignatureScheme;
+
+    fn keypair() -> SetuKeyPair {
+        SetuKeyPair::generate(SignatureScheme::ED25519)
+    }
+
+    #[test]
+    fn test_rotation_requires_both_signatures() {
+        let (old, new, other) = (keypair(), keypair(), keypair());
+        let rotation = KeyRotation::new("v1", &old, &new, 3);
+        assert!(rotation.verify().is_ok());
+
+        let mut forged = rotation.clone();
+        forged.old_signature = other.sign(&KeyRotation::signing_bytes(
+            "v1",
+            &old.public(),
+            &new.public(),
+            3,
+        ));
+        assert!(forged.verify().is_err());
+
+        let mut retargeted = rotation.clone();
+        retargeted.effective_epoch = 1;
+        assert!(retargeted.verify().is_err());
+
+        // Works across schemes, e.g. moving to a BLS key
+        let bls = SetuKeyPair::generate(SignatureScheme::BLS12381);
+        assert!(KeyRotation::new("v1", &old, &bls, 3).verify().is_ok());
+    }
+
+    #[test]
+    fn test_history_by_epoch() {
+        let (k0, k1, k2) = (keypair(), keypair(), keypair());
+        let mut history = KeyHistory::new("v1".to_string(), k0.public());
+        history.apply(KeyRotation::new("v1", &k0, &k1, 10), 0).unwrap();
+        history.apply(KeyRotation::new("v1", &k1, &k2, 20), 12).unwrap();
+
+        assert_eq!(history.key_at(0), &k0.public());
+        assert_eq!(history.key_at(9), &k0.public());
+        assert_eq!(history.key_at(10), &k1.public());
+        assert_eq!(history.key_at(25), &k2.public());
+        assert_eq!(history.current_key(), &k2.public());
+
+        let sig = k0.sign(b"vote");
+        assert!(history.verify_at(5, b"vote", &sig).is_ok());
+        assert!(history.verify_at(15, b"vote", &sig).is_err());
+    }
+
+    #[test]
+    fn test_history_rejects_invalid_rotations() {
+        let (k0, k1, k2) = (keypair(), keypair(), keypair());
+        let mut history = KeyHistory::new("v1".to_string(), k0.public());
+
+        // Not from the current key
+        assert!(history.apply(KeyRotation::new("v1", &k1, &k2, 5), 0).is_err());
+        // Wrong node
+        assert!(history.apply(KeyRotation::new("v2", &k0, &k1, 5), 0).is_err());
+        // Rewriting the current or a past epoch
+        assert!(history.apply(KeyRotation::new("v1", &k0, &k1, 5), 5).is_err());
+        assert!(history.apply(KeyRotation::new("v1", &k0, &k1, 5), 8).is_err());
+
+        history.apply(KeyRotation::new("v1", &k0, &k1, 5), 0).unwrap();
+        // Not after the previous rotation
+        assert!(history.apply(KeyRotation::new("v1", &k1, &k2, 5), 0).is_err());
+        // Back to a retired key
+        assert!(history.apply(KeyRotation::new("v1", &k1, &k0, 6), 0).is_err());
+        assert_eq!(history.rotations().len(), 1);
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright (c) Hetu Project
+// SPDX-License-Identifier: Apache-2.0
+
+//! Signing abstraction for validator keys.
+//!
+//! A [`Signer`] holds (or reaches) one key and signs with it. [`LocalSigner`]
+//! keeps the key in-process; [`crate::remote_signer::RemoteSigner`] forwards
+//! requests to a separate signing daemon so the key never enters the node.
+//!
+//! A signer only signs consensus votes, through [`Signer::sign_vote`], which is
+//! checked against a [`DoubleSignGuard`]: once a vote has been signed for a
+//! round, a different vote for that round is refused. Re-signing the identical
+//! vote is allowed so that a node can retry after a crash. The signature covers
+//! [`vote_signing_bytes`], which binds the round, so a vote signed for one
+//! round does not verify for another; check it with [`verify_vote`].
+
+use crate::crypto::{PublicKey, SetuKeyPair, Signature};
+use crate::error::KeyError;
+use crate::keypair_file::write_private_file;
+use async_trait::async_trait;
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+type Blake2b256 = Blake2b<U32>;
+
+/// Number of most recent rounds remembered by a [`DoubleSignGuard`].
+pub const MAX_TRACKED_ROUNDS: usize = 1024;
+
+/// Domain separation tag for vote signatures.
+const VOTE_DOMAIN: &[u8] = b"SETU_VOTE";
+
+/// Bytes signed for a vote.
+///
+/// `domain || round (big-endian) || msg`
+pub fn vote_signing_bytes(round: u64, msg: &[u8]) -> Vec<u8> {
+    let mut bytes = VOTE_DOMAIN.to_vec();
+    bytes.extend(round.to_be_bytes());
+    bytes.extend(msg);
+    bytes
+}
+
+/// Verify a signature made by [`Signer::sign_vote`] for the vote `msg` in `round`.
+pub fn verify_vote(
+    public_key: &PublicKey,
+    round: u64,
+    msg: &[u8],
+    signature: &Signature,
+) -> Result<(), KeyError> {
+    public_key.verify(&vote_signing_bytes(round, msg), signature)
+}
+
+/// Something that can sign with a single key.
+#[async_trait]
+pub trait Signer: Send + Sync {
+    /// Public key of the signing key.
+    async fn public_key(&self) -> Result<PublicKey, KeyError>;
+
+    /// Sign a consensus vote for `round`, refusing to sign a different vote
+    /// for a round that already has one.
+    ///
+    /// The signature is over [`vote_signing_bytes`]`(round, msg)`.
+    async fn sign_vote(&self, round: u64, msg: &[u8]) -> Result<Signature, KeyError>;
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct GuardState {
+    /// Rounds below this have been forgotten and are refused
+    floor: u64,
+    /// Hex-encoded digest of the vote signed in each round
+    signed: BTreeMap<u64, String>,
+}
+
+/// Record of signed votes that prevents double-signing.
+///
+/// With a file, the record is written to disk before the signature is
+/// released, so protection survives restarts. Only the last
+/// [`MAX_TRACKED_ROUNDS`] rounds are kept; votes for older rounds are refused.
+pub struct DoubleSignGuard {
+    path: Option<PathBuf>,
+    state: Mutex<GuardState>,
+}
+
+impl DoubleSignGuard {
+    /// A guard that forgets everything on restart.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            state: Mutex::new(GuardState::default()),
+        }
+    }
+
+    /// Load a guard from `path`, or start an empty one there.
+    pub fn open(path: &Path) -> Result<Self, KeyError> {
+        let state = if path.exists() {
+            let contents = std::fs::read_to_string(path)?;
+            serde_json::from_str(&contents).map_err(|e| KeyError::Serialization(e.to_string()))?
+        } else {
+            GuardState::default()
+        };
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Record that the vote `msg` is about to be signed for `round`.
+    pub fn check_and_record(&self, round: u64, msg: &[u8]) -> Result<(), KeyError> {
+        let digest = hex::encode(Blake2b256::digest(msg));
+        let mut state = self.state.lock().unwrap();
+
+        if round < state.floor {
+            return Err(KeyError::DoubleSign(round));
+        }
+        match state.signed.get(&round) {
+            Some(signed) if *signed == digest => return Ok(()),
+            Some(_) => return Err(KeyError::DoubleSign(round)),
+            None => {}
+        }
+
+        state.signed.insert(round, digest);
+        while state.signed.len() > MAX_TRACKED_ROUNDS {
+            let (oldest, _) = state.signed.pop_first().unwrap();
+            state.floor = oldest + 1;
+        }
+        if let Some(path) = &self.path {
+            if let Err(e) = Self::persist(path, &state) {
+                state.signed.remove(&round);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the state atomically via a temporary file.
+    fn persist(path: &Path, state: &GuardState) -> Result<(), KeyError> {
+        let contents =
+            serde_json::to_string(state).map_err(|e| KeyError::Serialization(e.to_string()))?;
+        write_private_file(path, contents.as_bytes())
+    }
+}
+
+/// Signer holding the key in-process.
+pub struct LocalSigner {
+    keypair: SetuKeyPair,
+    guard: DoubleSignGuard,
+}
+
+impl LocalSigner {
+    /// Create a signer with an in-memory double-sign guard.
+    pub fn new(keypair: SetuKeyPair) -> Self {
+        Self::with_guard(keypair, DoubleSignGuard::in_memory())
+    }
+
+    /// Create a signer with the given double-sign guard.
+    pub fn with_guard(keypair: SetuKeyPair, guard: DoubleSignGuard) -> Self {
+        Self { keypair, guard }
+    }
+}
+
+#[async_trait]
+impl Signer for LocalSigner {
+    async fn public_key(&self) -> Result<PublicKey, KeyError> {
+        Ok(self.keypair.public())
+    }
+
+    async fn sign_vote(&self, round: u64, msg: &[u8]) -> Result<Signature, KeyError> {
+        self.guard.check_and_record(round, msg)?;
+        Ok(self.keypair.sign(&vote_signing_bytes(round, msg)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::SignatureScheme;
+
+    #[tokio::test]
+    async fn test_local_signer_refuses_double_sign() {
+        let signer = LocalSigner::new(SetuKeyPair::generate(SignatureScheme::BLS12381));
+        let pk = signer.public_key().await.unwrap();
+
+        let sig = signer.sign_vote(7, b"vote-a").await.unwrap();
+        assert!(verify_vote(&pk, 7, b"vote-a", &sig).is_ok());
+        // Same vote again is fine, a different one is not
+        assert!(signer.sign_vote(7, b"vote-a").await.is_ok());
+        assert!(matches!(
+            signer.sign_vote(7, b"vote-b").await,
+            Err(KeyError::DoubleSign(7))
+        ));
+        assert!(signer.sign_vote(8, b"vote-b").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_vote_signature_binds_round() {
+        let signer = LocalSigner::new(SetuKeyPair::generate(SignatureScheme::ED25519));
+        let pk = signer.public_key().await.unwrap();
+        signer.sign_vote(7, b"vote-a").await.unwrap();
+
+        // Claiming another round gets the conflicting vote signed, but only
+        // for that round: it is no vote for round 7
+        let sig = signer.sign_vote(8, b"vote-b").await.unwrap();
+        assert!(verify_vote(&pk, 8, b"vote-b", &sig).is_ok());
+        assert!(verify_vote(&pk, 7, b"vote-b", &sig).is_err());
+        assert!(pk.verify(b"vote-b", &sig).is_err());
+    }
+
+    #[test]
+    fn test_guard_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("votes.guard");
+
+        let guard = DoubleSignGuard::open(&path).unwrap();
+        guard.check_and_record(1, b"a").unwrap();
+        drop(guard);
+
+        let guard = DoubleSignGuard::open(&path).unwrap();
+        assert!(guard.check_and_record(1, b"a").is_ok());
+        assert!(guard.check_and_record(1, b"b").is_err());
+    }
+
+    #[test]
+    fn test_guard_forgets_old_rounds() {
+        let guard = DoubleSignGuard::in_memory();
+        for round in 0..=MAX_TRACKED_ROUNDS as u64 {
+            guard.check_and_record(round, b"v").unwrap();
+        }
+        // Round 0 was pruned and can no longer be signed at all
+        assert!(guard.check_and_record(0, b"v").is_err());
+        assert!(guard.check_and_record(1, b"v").is_ok());
+    }
+}
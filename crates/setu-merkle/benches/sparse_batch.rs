@@ -0,0 +1,135 @@
+//! Sparse Merkle tree batch update benchmarks.
+//!
+//! Compares the sorted, bottom-up `batch_insert` against per-key inserts and
+//! against the previous implementation, which rebuilt the whole tree from all
+//! leaves, for batch sizes typical of one ConsensusFrame.
+
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use sha2::{Digest, Sha256};
+use setu_merkle::{HashValue, SparseMerkleTree};
+
+const BATCH_SIZES: [usize; 2] = [10_000, 100_000];
+
+fn random_entries(n: usize, seed: u64) -> Vec<(HashValue, Vec<u8>)> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..n)
+        .map(|_| {
+            let key: [u8; 32] = rng.gen();
+            let value: [u8; 16] = rng.gen();
+            (HashValue::new(key), value.to_vec())
+        })
+        .collect()
+}
+
+fn sha256(parts: &[&[u8]]) -> HashValue {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    HashValue::new(hasher.finalize().into())
+}
+
+/// The previous implementation: `batch_insert` stored the values and then
+/// rebuilt every node from all leaves, single-threaded.
+#[derive(Clone, Default)]
+struct FullRebuildTree {
+    leaves: HashMap<HashValue, Vec<u8>>,
+    nodes: HashMap<HashValue, (HashValue, HashValue)>,
+    root: HashValue,
+}
+
+impl FullRebuildTree {
+    fn batch_insert(&mut self, entries: Vec<(HashValue, Vec<u8>)>) {
+        for (key, value) in entries {
+            self.leaves.insert(key, value);
+        }
+        self.nodes.clear();
+        let mut leaf_hashes: Vec<(HashValue, HashValue)> = self
+            .leaves
+            .iter()
+            .map(|(k, v)| (*k, sha256(&[&[0x02], k.as_bytes(), sha256(&[v]).as_bytes()])))
+            .collect();
+        leaf_hashes.sort_by_key(|(k, _)| *k);
+        self.root = Self::build(&leaf_hashes, 0, &mut self.nodes);
+    }
+
+    fn build(
+        leaves: &[(HashValue, HashValue)],
+        depth: usize,
+        nodes: &mut HashMap<HashValue, (HashValue, HashValue)>,
+    ) -> HashValue {
+        match leaves {
+            [] => sha256(&[b"SPARSE_EMPTY"]),
+            [(_, leaf_hash)] => *leaf_hash,
+            _ => {
+                let mid = leaves.partition_point(|(k, _)| !k.bit(depth));
+                let left = Self::build(&leaves[..mid], depth + 1, nodes);
+                let right = Self::build(&leaves[mid..], depth + 1, nodes);
+                let hash = sha256(&[&[0x03], left.as_bytes(), right.as_bytes()]);
+                nodes.insert(hash, (left, right));
+                hash
+            }
+        }
+    }
+}
+
+fn bench_batch_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sparse_batch_insert");
+    group.sample_size(10);
+
+    // Routines return the tree so dropping it is not measured
+
+    // Existing state the frame is applied on top of
+    let mut base = SparseMerkleTree::new();
+    base.batch_insert(random_entries(100_000, 1));
+    let mut legacy_base = FullRebuildTree::default();
+    legacy_base.batch_insert(random_entries(100_000, 1));
+    assert_eq!(legacy_base.root, base.root());
+
+    for &n in &BATCH_SIZES {
+        let entries = random_entries(n, 2);
+
+        group.bench_with_input(BenchmarkId::new("batch_insert", n), &entries, |b, entries| {
+            b.iter_batched(
+                || (base.clone(), entries.clone()),
+                |(mut tree, entries)| {
+                    tree.batch_insert(entries);
+                    tree
+                },
+                BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_with_input(BenchmarkId::new("sequential_insert", n), &entries, |b, entries| {
+            b.iter_batched(
+                || (base.clone(), entries.clone()),
+                |(mut tree, entries)| {
+                    for (key, value) in entries {
+                        tree.insert(key, value);
+                    }
+                    tree
+                },
+                BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_with_input(BenchmarkId::new("full_rebuild", n), &entries, |b, entries| {
+            b.iter_batched(
+                || (legacy_base.clone(), entries.clone()),
+                |(mut tree, entries)| {
+                    tree.batch_insert(entries);
+                    tree
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_batch_insert);
+criterion_main!(benches);
@@ -9,6 +9,7 @@
 //! - Domain separation with leaf/inner node prefixes
 //! - Support for inclusion proofs
 //! - Efficient batch construction
+//! - Pluggable hash function (see [`crate::hasher`])
 //!
 //! # Example
 //!
@@ -24,9 +25,11 @@
 //! ```
 
 use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
 
 use crate::error::{MerkleError, MerkleResult};
-use crate::hash::{hash_internal, hash_leaf, HashValue};
+use crate::hash::HashValue;
+use crate::hasher::{MerkleHasher, Sha256Hasher};
 
 /// A node in the binary Merkle tree.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -70,15 +73,21 @@ impl From<HashValue> for Node {
 
 /// A proof of inclusion for a leaf in the binary Merkle tree.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub struct BinaryMerkleProof {
+#[serde(bound = "")]
+pub struct BinaryMerkleProof<H = Sha256Hasher> {
     /// The sibling hashes on the path from leaf to root
     siblings: Vec<Node>,
+    #[serde(skip)]
+    hasher: PhantomData<H>,
 }
 
-impl BinaryMerkleProof {
+impl<H: MerkleHasher> BinaryMerkleProof<H> {
     /// Create a new proof from sibling nodes
     pub fn new(siblings: Vec<Node>) -> Self {
-        Self { siblings }
+        Self {
+            siblings,
+            hasher: PhantomData,
+        }
     }
 
     /// Get the depth of this proof (number of levels)
@@ -129,17 +138,17 @@ impl BinaryMerkleProof {
             )));
         }
 
-        let mut current = hash_leaf(leaf);
+        let mut current = H::hash_leaf(leaf);
         let mut index = leaf_index;
 
         for sibling in &self.siblings {
             let sibling_hash = sibling.hash();
             current = if index % 2 == 0 {
                 // Current node is left child
-                hash_internal(&current, &sibling_hash)
+                H::hash_internal(&current, &sibling_hash)
             } else {
                 // Current node is right child
-                hash_internal(&sibling_hash, &current)
+                H::hash_internal(&sibling_hash, &current)
             };
             index /= 2;
         }
@@ -171,11 +180,14 @@ impl BinaryMerkleProof {
 ///
 /// Empty subtrees are represented by zero hashes.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct BinaryMerkleTree {
+#[serde(bound = "")]
+pub struct BinaryMerkleTree<H = Sha256Hasher> {
     /// All nodes in the tree, stored level by level starting with leaves
     nodes: Vec<Node>,
     /// Number of leaves in the tree
     n_leaves: usize,
+    #[serde(skip)]
+    hasher: PhantomData<H>,
 }
 
 impl BinaryMerkleTree {
@@ -189,24 +201,29 @@ impl BinaryMerkleTree {
     ///
     /// A new BinaryMerkleTree
     pub fn build<T: AsRef<[u8]>>(leaves: &[T]) -> Self {
-        if leaves.is_empty() {
-            return Self {
-                nodes: vec![],
-                n_leaves: 0,
-            };
-        }
+        Self::build_with_hasher(leaves, Sha256Hasher)
+    }
+
+    /// Build a Merkle tree from pre-computed leaf hashes.
+    pub fn build_from_hashes(leaf_hashes: Vec<HashValue>) -> Self {
+        Self::build_from_hashes_with_hasher(leaf_hashes, Sha256Hasher)
+    }
+}
 
+impl<H: MerkleHasher> BinaryMerkleTree<H> {
+    /// Build a Merkle tree from leaf data using the given hasher.
+    pub fn build_with_hasher<T: AsRef<[u8]>>(leaves: &[T], _hasher: H) -> Self {
         // Hash all leaves
         let leaf_hashes: Vec<Node> = leaves
             .iter()
-            .map(|leaf| Node::Hash(hash_leaf(leaf.as_ref())))
+            .map(|leaf| Node::Hash(H::hash_leaf(leaf.as_ref())))
             .collect();
 
         Self::build_from_leaf_nodes(leaf_hashes)
     }
 
-    /// Build a Merkle tree from pre-computed leaf hashes.
-    pub fn build_from_hashes(leaf_hashes: Vec<HashValue>) -> Self {
+    /// Build a Merkle tree from leaf hashes pre-computed with the given hasher.
+    pub fn build_from_hashes_with_hasher(leaf_hashes: Vec<HashValue>, _hasher: H) -> Self {
         let nodes: Vec<Node> = leaf_hashes.into_iter().map(Node::from).collect();
         Self::build_from_leaf_nodes(nodes)
     }
@@ -218,6 +235,7 @@ impl BinaryMerkleTree {
             return Self {
                 nodes: vec![],
                 n_leaves: 0,
+                hasher: PhantomData,
             };
         }
 
@@ -251,14 +269,18 @@ impl BinaryMerkleTree {
             level_nodes /= 2;
         }
 
-        Self { nodes, n_leaves }
+        Self {
+            nodes,
+            n_leaves,
+            hasher: PhantomData,
+        }
     }
 
     /// Compute parent node from two children
     fn compute_parent(left: &Node, right: &Node) -> Node {
         match (left, right) {
             (Node::Empty, Node::Empty) => Node::Empty,
-            _ => Node::Hash(hash_internal(&left.hash(), &right.hash())),
+            _ => Node::Hash(H::hash_internal(&left.hash(), &right.hash())),
         }
     }
 
@@ -301,7 +323,7 @@ impl BinaryMerkleTree {
     /// # Returns
     ///
     /// A BinaryMerkleProof if the index is valid
-    pub fn get_proof(&self, leaf_index: usize) -> MerkleResult<BinaryMerkleProof> {
+    pub fn get_proof(&self, leaf_index: usize) -> MerkleResult<BinaryMerkleProof<H>> {
         if leaf_index >= self.n_leaves {
             return Err(MerkleError::IndexOutOfBounds {
                 index: leaf_index,
@@ -473,6 +495,31 @@ mod tests {
         assert!(proof.is_rightmost(3), "Leaf 3 should be rightmost");
     }
 
+    #[test]
+    fn test_pluggable_hashers() {
+        use crate::hasher::{Blake3Hasher, Keccak256Hasher};
+
+        let leaves: Vec<Vec<u8>> = (0..5).map(|i| format!("leaf{}", i).into_bytes()).collect();
+        let sha = BinaryMerkleTree::build_with_hasher(&leaves, Sha256Hasher);
+        let blake = BinaryMerkleTree::build_with_hasher(&leaves, Blake3Hasher);
+        let keccak = BinaryMerkleTree::build_with_hasher(&leaves, Keccak256Hasher);
+
+        assert_eq!(sha.root(), BinaryMerkleTree::build(&leaves).root());
+        assert_ne!(sha.root(), blake.root());
+        assert_ne!(sha.root(), keccak.root());
+        assert_ne!(blake.root(), keccak.root());
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert!(blake.get_proof(i).unwrap().verify(&blake.root(), leaf, i).is_ok());
+            assert!(keccak.get_proof(i).unwrap().verify(&keccak.root(), leaf, i).is_ok());
+        }
+
+        // Same siblings, different hasher: the proof no longer reaches the root
+        let siblings = keccak.get_proof(0).unwrap().siblings;
+        let proof = BinaryMerkleProof::<Sha256Hasher>::new(siblings);
+        assert!(proof.verify(&keccak.root(), &leaves[0], 0).is_err());
+    }
+
     #[test]
     fn test_deterministic() {
         let leaves = vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()];
@@ -35,4 +35,20 @@ pub enum MerkleError {
     /// Invalid input
     #[error("Invalid input: {0}")]
     InvalidInput(String),
+
+    /// A node referenced by the tree is missing from storage
+    #[error("Node not found: {0}")]
+    NodeNotFound(String),
+
+    /// The requested version has not been committed
+    #[error("Version not found: {0}")]
+    VersionNotFound(u64),
+
+    /// The requested version has been pruned
+    #[error("Version {version} has been pruned (oldest readable: {min_readable})")]
+    VersionPruned { version: u64, min_readable: u64 },
+
+    /// Error reported by the underlying node storage
+    #[error("Storage error: {0}")]
+    StorageError(String),
 }
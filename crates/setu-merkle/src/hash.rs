@@ -1,9 +1,9 @@
 //! Hash utilities and types for merkle trees.
 
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
 use std::fmt;
 
+use crate::hasher::{MerkleHasher, Sha256Hasher};
 use crate::{MerkleError, MerkleResult, HASH_LENGTH};
 
 /// A 256-bit hash value used as keys and node hashes in merkle trees.
@@ -130,44 +130,32 @@ pub mod prefix {
     pub const SPARSE_LEAF: &[u8] = &[0x02];
     /// Prefix for sparse merkle tree internal nodes
     pub const SPARSE_INTERNAL: &[u8] = &[0x03];
+    /// Prefix for Merkle Mountain Range leaves
+    pub const MMR_LEAF: &[u8] = &[0x04];
+    /// Prefix for Merkle Mountain Range internal nodes
+    pub const MMR_INTERNAL: &[u8] = &[0x05];
+    /// Prefix for Merkle Mountain Range roots
+    pub const MMR_ROOT: &[u8] = &[0x06];
 }
 
 /// Hash data using SHA-256
 pub fn sha256(data: &[u8]) -> HashValue {
-    let mut hasher = Sha256::new();
-    hasher.update(data);
-    let result = hasher.finalize();
-    let mut bytes = [0u8; HASH_LENGTH];
-    bytes.copy_from_slice(&result);
-    HashValue(bytes)
+    Sha256Hasher::hash(data)
 }
 
 /// Hash data with a domain separation prefix
 pub fn sha256_with_prefix(prefix: &[u8], data: &[u8]) -> HashValue {
-    let mut hasher = Sha256::new();
-    hasher.update(prefix);
-    hasher.update(data);
-    let result = hasher.finalize();
-    let mut bytes = [0u8; HASH_LENGTH];
-    bytes.copy_from_slice(&result);
-    HashValue(bytes)
+    Sha256Hasher::hash_with_prefix(prefix, data)
 }
 
 /// Hash two child hashes to create parent hash (for binary merkle tree)
 pub fn hash_internal(left: &HashValue, right: &HashValue) -> HashValue {
-    let mut hasher = Sha256::new();
-    hasher.update(prefix::INTERNAL);
-    hasher.update(left.as_bytes());
-    hasher.update(right.as_bytes());
-    let result = hasher.finalize();
-    let mut bytes = [0u8; HASH_LENGTH];
-    bytes.copy_from_slice(&result);
-    HashValue(bytes)
+    Sha256Hasher::hash_internal(left, right)
 }
 
 /// Hash leaf data (for binary merkle tree)
 pub fn hash_leaf(data: &[u8]) -> HashValue {
-    sha256_with_prefix(prefix::LEAF, data)
+    Sha256Hasher::hash_leaf(data)
 }
 
 #[cfg(test)]
@@ -0,0 +1,159 @@
+//! Pluggable hash functions for Merkle trees.
+//!
+//! Trees and proofs in this crate are generic over a [`MerkleHasher`] and
+//! default to [`Sha256Hasher`], so existing roots are unchanged. The domain
+//! separation prefixes in [`prefix`](crate::hash::prefix) are applied the same
+//! way whatever the hash function, so switching hashers changes the digests but
+//! never the shape of a tree or its proofs.
+//!
+//! - [`Sha256Hasher`]: the default
+//! - [`Blake3Hasher`]: considerably faster for large state trees
+//! - [`Keccak256Hasher`]: cheap to verify on EVM chains, for bridge proofs
+//!
+//! # Example
+//!
+//! ```
+//! use setu_merkle::binary::BinaryMerkleTree;
+//! use setu_merkle::hasher::Keccak256Hasher;
+//!
+//! let leaves = vec![b"leaf0".to_vec(), b"leaf1".to_vec()];
+//! let tree = BinaryMerkleTree::build_with_hasher(&leaves, Keccak256Hasher);
+//!
+//! let proof = tree.get_proof(1).unwrap();
+//! assert!(proof.verify(&tree.root(), &leaves[1], 1).is_ok());
+//! assert_ne!(tree.root(), BinaryMerkleTree::build(&leaves).root());
+//! ```
+
+use sha2::Digest;
+use std::fmt::Debug;
+use std::sync::OnceLock;
+
+use crate::hash::{prefix, HashValue};
+
+/// Preimage of the placeholder hash for empty sparse subtrees
+const SPARSE_EMPTY: &[u8] = b"SPARSE_EMPTY";
+
+/// A 256-bit hash function used to build Merkle trees.
+///
+/// Hashers are stateless marker types; trees carry them as a type parameter
+/// only, so a tree and its proofs must agree on the hasher to verify.
+pub trait MerkleHasher:
+    Clone + Copy + Debug + Default + PartialEq + Eq + Send + Sync + 'static
+{
+    /// Hash the concatenation of `parts`.
+    fn hash_parts(parts: &[&[u8]]) -> HashValue;
+
+    /// Placeholder hash of an empty sparse subtree, `hash("SPARSE_EMPTY")`.
+    ///
+    /// Requested on every sparse tree update, so implementations cache it.
+    fn sparse_empty_hash() -> HashValue;
+
+    /// Hash data
+    fn hash(data: &[u8]) -> HashValue {
+        Self::hash_parts(&[data])
+    }
+
+    /// Hash data with a domain separation prefix
+    fn hash_with_prefix(prefix: &[u8], data: &[u8]) -> HashValue {
+        Self::hash_parts(&[prefix, data])
+    }
+
+    /// Hash leaf data (for binary merkle tree)
+    fn hash_leaf(data: &[u8]) -> HashValue {
+        Self::hash_with_prefix(prefix::LEAF, data)
+    }
+
+    /// Hash two child hashes to create parent hash (for binary merkle tree)
+    fn hash_internal(left: &HashValue, right: &HashValue) -> HashValue {
+        Self::hash_parts(&[prefix::INTERNAL, left.as_bytes(), right.as_bytes()])
+    }
+}
+
+/// SHA-256, the default hasher.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sha256Hasher;
+
+impl MerkleHasher for Sha256Hasher {
+    fn hash_parts(parts: &[&[u8]]) -> HashValue {
+        let mut hasher = sha2::Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        HashValue::new(hasher.finalize().into())
+    }
+
+    fn sparse_empty_hash() -> HashValue {
+        static EMPTY: OnceLock<HashValue> = OnceLock::new();
+        *EMPTY.get_or_init(|| Self::hash(SPARSE_EMPTY))
+    }
+}
+
+/// BLAKE3 with a 256-bit output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Blake3Hasher;
+
+impl MerkleHasher for Blake3Hasher {
+    fn hash_parts(parts: &[&[u8]]) -> HashValue {
+        let mut hasher = blake3::Hasher::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        HashValue::new(*hasher.finalize().as_bytes())
+    }
+
+    fn sparse_empty_hash() -> HashValue {
+        static EMPTY: OnceLock<HashValue> = OnceLock::new();
+        *EMPTY.get_or_init(|| Self::hash(SPARSE_EMPTY))
+    }
+}
+
+/// Keccak-256 as used by Ethereum (not the padded NIST SHA3-256).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Keccak256Hasher;
+
+impl MerkleHasher for Keccak256Hasher {
+    fn hash_parts(parts: &[&[u8]]) -> HashValue {
+        let mut hasher = sha3::Keccak256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        HashValue::new(hasher.finalize().into())
+    }
+
+    fn sparse_empty_hash() -> HashValue {
+        static EMPTY: OnceLock<HashValue> = OnceLock::new();
+        *EMPTY.get_or_init(|| Self::hash(SPARSE_EMPTY))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_digests() {
+        assert_eq!(
+            Sha256Hasher::hash(b"abc").to_string(),
+            "0xba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            Blake3Hasher::hash(b"abc").to_string(),
+            "0x6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        assert_eq!(
+            Keccak256Hasher::hash(b"").to_string(),
+            "0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+    }
+
+    #[test]
+    fn test_parts_match_concatenation() {
+        fn check<H: MerkleHasher>() {
+            assert_eq!(H::hash_parts(&[b"ab", b"", b"c"]), H::hash(b"abc"));
+            assert_eq!(H::sparse_empty_hash(), H::hash(SPARSE_EMPTY));
+        }
+        check::<Sha256Hasher>();
+        check::<Blake3Hasher>();
+        check::<Keccak256Hasher>();
+    }
+}
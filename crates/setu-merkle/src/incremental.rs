@@ -0,0 +1,296 @@
+//! Incremental (append-only) binary Merkle tree.
+//!
+//! [`IncrementalMerkleTree`] produces the same roots and proofs as
+//! [`BinaryMerkleTree`](crate::binary::BinaryMerkleTree) over the same leaves,
+//! but accepts leaves one at a time and keeps only the frontier: the root of
+//! each completed left subtree, one per level. This suits event lists that
+//! arrive over the lifetime of a consensus frame.
+//!
+//! Proofs need the siblings of a leaf, which the frontier alone forgets. Leaves
+//! appended with [`append_tracked`](IncrementalMerkleTree::append_tracked) keep
+//! their authentication path up to date as the tree grows, at O(log n) extra
+//! state per tracked leaf.
+//!
+//! # Example
+//!
+//! ```
+//! use setu_merkle::binary::BinaryMerkleTree;
+//! use setu_merkle::incremental::IncrementalMerkleTree;
+//!
+//! let leaves: Vec<Vec<u8>> = (0..5).map(|i| format!("event{}", i).into_bytes()).collect();
+//!
+//! let mut tree = IncrementalMerkleTree::new();
+//! tree.append(&leaves[0]);
+//! let tracked = tree.append_tracked(&leaves[1]);
+//! for leaf in &leaves[2..] {
+//!     tree.append(leaf);
+//! }
+//!
+//! assert_eq!(tree.root(), BinaryMerkleTree::build(&leaves).root());
+//! let proof = tree.get_proof(tracked).unwrap();
+//! assert!(proof.verify(&tree.root(), &leaves[1], tracked).is_ok());
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+use crate::binary::{BinaryMerkleProof, Node};
+use crate::error::{MerkleError, MerkleResult};
+use crate::hash::HashValue;
+use crate::hasher::{MerkleHasher, Sha256Hasher};
+
+/// An append-only binary Merkle tree that stores O(log n) hashes.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct IncrementalMerkleTree<H = Sha256Hasher> {
+    /// `frontier[h]` is the last completed subtree of height `h`. It is only
+    /// meaningful while bit `h` of `n_leaves` is set.
+    frontier: Vec<HashValue>,
+    /// Number of leaves appended so far
+    n_leaves: usize,
+    /// Known siblings of tracked leaves, indexed by height
+    witnesses: BTreeMap<usize, Vec<Option<HashValue>>>,
+    #[serde(skip)]
+    hasher: PhantomData<H>,
+}
+
+impl IncrementalMerkleTree {
+    /// Create an empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<H: MerkleHasher> IncrementalMerkleTree<H> {
+    /// Create an empty tree using the given hasher.
+    pub fn with_hasher(_hasher: H) -> Self {
+        Self {
+            frontier: Vec::new(),
+            n_leaves: 0,
+            witnesses: BTreeMap::new(),
+            hasher: PhantomData,
+        }
+    }
+
+    /// Get the number of leaves in the tree.
+    pub fn num_leaves(&self) -> usize {
+        self.n_leaves
+    }
+
+    /// Check if the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.n_leaves == 0
+    }
+
+    /// Append a leaf and return its index.
+    pub fn append(&mut self, leaf: &[u8]) -> usize {
+        let index = self.n_leaves;
+        let mut node = H::hash_leaf(leaf);
+        let mut height = 0;
+        let mut size = index;
+
+        // Merge with every completed left sibling, bottom-up
+        loop {
+            // Tracked leaves under the sibling subtree now have this node as a sibling
+            let sibling_start = ((index >> height) ^ 1) << height;
+            for witness in self
+                .witnesses
+                .range_mut(sibling_start..sibling_start + (1 << height))
+                .map(|(_, witness)| witness)
+            {
+                set_sibling(witness, height, node);
+            }
+            if size & 1 == 0 {
+                break;
+            }
+            node = H::hash_internal(&self.frontier[height], &node);
+            size >>= 1;
+            height += 1;
+        }
+
+        if self.frontier.len() == height {
+            self.frontier.push(node);
+        } else {
+            self.frontier[height] = node;
+        }
+        self.n_leaves += 1;
+        index
+    }
+
+    /// Append a leaf and keep what is needed to prove it later.
+    pub fn append_tracked(&mut self, leaf: &[u8]) -> usize {
+        let index = self.n_leaves;
+
+        // Left siblings are already complete and sit on the frontier
+        let mut witness = Vec::new();
+        for height in 0..self.frontier.len() {
+            if (index >> height) & 1 == 1 {
+                set_sibling(&mut witness, height, self.frontier[height]);
+            }
+        }
+        self.witnesses.insert(index, witness);
+
+        self.append(leaf)
+    }
+
+    /// Stop tracking a leaf, releasing its authentication path.
+    pub fn untrack(&mut self, leaf_index: usize) {
+        self.witnesses.remove(&leaf_index);
+    }
+
+    /// Indices of the tracked leaves, in order.
+    pub fn tracked_leaves(&self) -> impl Iterator<Item = usize> + '_ {
+        self.witnesses.keys().copied()
+    }
+
+    /// Get the root hash of the tree.
+    ///
+    /// Matches [`BinaryMerkleTree::root`](crate::binary::BinaryMerkleTree::root)
+    /// over the leaves appended so far.
+    pub fn root(&self) -> HashValue {
+        match self.n_leaves {
+            0 => HashValue::zero(),
+            n => self.partial_subtree(tree_depth(n)).hash(),
+        }
+    }
+
+    /// Get a proof of inclusion for a tracked leaf against the current root.
+    pub fn get_proof(&self, leaf_index: usize) -> MerkleResult<BinaryMerkleProof<H>> {
+        if leaf_index >= self.n_leaves {
+            return Err(MerkleError::IndexOutOfBounds {
+                index: leaf_index,
+                size: self.n_leaves,
+            });
+        }
+        let witness = self.witnesses.get(&leaf_index).ok_or_else(|| {
+            MerkleError::InvalidInput(format!("Leaf {} is not tracked", leaf_index))
+        })?;
+
+        let siblings = (0..tree_depth(self.n_leaves))
+            .map(|height| match witness.get(height).copied().flatten() {
+                Some(hash) => Node::Hash(hash),
+                // Only a right sibling can still be incomplete
+                None if ((leaf_index >> height) ^ 1) << height >= self.n_leaves => Node::Empty,
+                None => self.partial_subtree(height),
+            })
+            .collect();
+
+        Ok(BinaryMerkleProof::new(siblings))
+    }
+
+    /// Hash of the rightmost, incomplete subtree of height `height`, padded
+    /// with empty nodes the way [`BinaryMerkleTree`](crate::binary::BinaryMerkleTree) pads.
+    fn partial_subtree(&self, height: usize) -> Node {
+        if self.n_leaves == 1 << height {
+            return Node::Hash(self.frontier[height]);
+        }
+        let mut node = Node::Empty;
+        for h in 0..height {
+            node = if (self.n_leaves >> h) & 1 == 1 {
+                Node::Hash(H::hash_internal(&self.frontier[h], &node.hash()))
+            } else if node.is_empty() {
+                Node::Empty
+            } else {
+                Node::Hash(H::hash_internal(&node.hash(), &HashValue::zero()))
+            };
+        }
+        node
+    }
+}
+
+/// Depth of a binary Merkle tree with `n_leaves` leaves.
+fn tree_depth(n_leaves: usize) -> usize {
+    (usize::BITS - n_leaves.saturating_sub(1).leading_zeros()) as usize
+}
+
+fn set_sibling(witness: &mut Vec<Option<HashValue>>, height: usize, hash: HashValue) {
+    if witness.len() <= height {
+        witness.resize(height + 1, None);
+    }
+    witness[height] = Some(hash);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::BinaryMerkleTree;
+
+    fn leaves(n: usize) -> Vec<Vec<u8>> {
+        (0..n).map(|i| format!("leaf{}", i).into_bytes()).collect()
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        let tree = IncrementalMerkleTree::new();
+        assert!(tree.is_empty());
+        assert_eq!(tree.root(), HashValue::zero());
+        assert!(matches!(tree.get_proof(0), Err(MerkleError::IndexOutOfBounds { .. })));
+    }
+
+    #[test]
+    fn test_roots_match_binary_tree() {
+        let leaves = leaves(70);
+        let mut tree = IncrementalMerkleTree::new();
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert_eq!(tree.append(leaf), i);
+            assert_eq!(tree.root(), BinaryMerkleTree::build(&leaves[..=i]).root());
+        }
+        // Only one hash per level is kept
+        assert_eq!(tree.frontier.len(), 7);
+    }
+
+    #[test]
+    fn test_proofs_match_binary_tree_at_every_size() {
+        let leaves = leaves(40);
+        let mut tree = IncrementalMerkleTree::new();
+        for leaf in &leaves {
+            tree.append_tracked(leaf);
+
+            let n = tree.num_leaves();
+            let full = BinaryMerkleTree::build(&leaves[..n]);
+            for (i, leaf) in leaves[..n].iter().enumerate() {
+                let proof = tree.get_proof(i).unwrap();
+                assert_eq!(proof, full.get_proof(i).unwrap(), "leaf {} of {}", i, n);
+                assert!(proof.verify(&tree.root(), leaf, i).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_with_hasher_matches_binary_tree() {
+        use crate::hasher::Blake3Hasher;
+
+        let leaves = leaves(13);
+        let mut tree = IncrementalMerkleTree::with_hasher(Blake3Hasher);
+        for leaf in &leaves {
+            tree.append_tracked(leaf);
+        }
+        let full = BinaryMerkleTree::build_with_hasher(&leaves, Blake3Hasher);
+        assert_eq!(tree.root(), full.root());
+        assert_ne!(tree.root(), BinaryMerkleTree::build(&leaves).root());
+        assert_eq!(tree.get_proof(6).unwrap(), full.get_proof(6).unwrap());
+    }
+
+    #[test]
+    fn test_untracked_leaves() {
+        let leaves = leaves(10);
+        let mut tree = IncrementalMerkleTree::new();
+        for (i, leaf) in leaves.iter().enumerate() {
+            if i == 3 || i == 8 {
+                tree.append_tracked(leaf);
+            } else {
+                tree.append(leaf);
+            }
+        }
+
+        assert_eq!(tree.tracked_leaves().collect::<Vec<_>>(), vec![3, 8]);
+        assert!(matches!(tree.get_proof(4), Err(MerkleError::InvalidInput(_))));
+        let proof = tree.get_proof(3).unwrap();
+        assert!(proof.verify(&tree.root(), &leaves[3], 3).is_ok());
+
+        tree.untrack(3);
+        assert!(tree.get_proof(3).is_err());
+        assert!(tree.get_proof(8).is_ok());
+    }
+}
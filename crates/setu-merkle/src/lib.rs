@@ -5,7 +5,17 @@
 //! This crate provides two types of Merkle trees:
 //!
 //! - [`binary::BinaryMerkleTree`]: A simple binary Merkle tree for ordered data commitments
+//! - [`incremental::IncrementalMerkleTree`]: The same tree built leaf by leaf in O(log n) space
 //! - [`sparse::SparseMerkleTree`]: A 256-bit sparse Merkle tree for key-value storage
+//! - [`persistent::PersistentSparseMerkleTree`]: The same sparse tree backed by versioned
+//!   node storage ([`storage::TreeReader`] / [`storage::TreeWriter`])
+//! - [`versioned::VersionedSparseMerkleTree`]: Version history with historical proofs and pruning
+//! - [`multiproof::SparseMerkleMultiProof`] / [`multiproof::SparseMerkleRangeProof`]: Batched
+//!   proofs over many keys or a key range of a sparse tree
+//! - [`mmr::MerkleMountainRange`]: An append-only accumulator over the anchor chain
+//!
+//! All trees and their proofs are generic over a [`hasher::MerkleHasher`]
+//! (SHA-256 by default, or BLAKE3 / Keccak-256).
 //!
 //! ## Design Philosophy
 //!
@@ -20,12 +30,29 @@
 pub mod binary;
 pub mod error;
 pub mod hash;
+pub mod hasher;
+pub mod incremental;
+pub mod mmr;
+pub mod multiproof;
+pub mod persistent;
 pub mod sparse;
+pub mod storage;
+pub mod versioned;
 
 pub use binary::{BinaryMerkleProof, BinaryMerkleTree};
 pub use error::{MerkleError, MerkleResult};
 pub use hash::HashValue;
+pub use hasher::{Blake3Hasher, Keccak256Hasher, MerkleHasher, Sha256Hasher};
+pub use incremental::IncrementalMerkleTree;
+pub use mmr::{MerkleMountainRange, MmrConsistencyProof, MmrProof};
+pub use multiproof::{MultiProofValues, RangeEntries, SparseMerkleMultiProof, SparseMerkleRangeProof};
+pub use persistent::PersistentSparseMerkleTree;
 pub use sparse::{SparseMerkleProof, SparseMerkleTree};
+pub use storage::{
+    MemoryTreeStore, NodeKey, StaleNodeIndex, StaleNodeIndexStore, StorageHasher, TreeNode,
+    TreeReader, TreeUpdateBatch, TreeWriter, Version,
+};
+pub use versioned::{PruningPolicy, VersionedSparseMerkleTree};
 
 /// The length of hash digests used in merkle trees (32 bytes = 256 bits)
 pub const HASH_LENGTH: usize = 32;
@@ -0,0 +1,510 @@
+//! Merkle Mountain Range accumulator.
+//!
+//! An append-only commitment to a growing list. Setu accumulates anchor IDs in
+//! an MMR so that any historical anchor can be proven part of the chain without
+//! walking `previous_anchor` links.
+//!
+//! # Design
+//!
+//! - The leaves form one perfect binary tree ("peak") per set bit of the leaf
+//!   count, largest first
+//! - Appending a leaf merges equal-height peaks, touching O(log n) nodes
+//! - The root hashes the leaf count together with all peaks, so a root commits
+//!   to the exact size of the list
+//! - Inclusion proofs show a leaf under one peak; consistency proofs show that
+//!   an older MMR is a prefix of a newer one
+//!
+//! # Example
+//!
+//! ```
+//! use setu_merkle::mmr::MerkleMountainRange;
+//!
+//! let mut mmr = MerkleMountainRange::new();
+//! for anchor_id in [b"anchor0", b"anchor1", b"anchor2"] {
+//!     mmr.append(anchor_id);
+//! }
+//! let old_root = mmr.root();
+//! mmr.append(b"anchor3");
+//!
+//! let proof = mmr.get_proof(1).unwrap();
+//! assert!(proof.verify(&mmr.root(), b"anchor1", 1).is_ok());
+//!
+//! let consistency = mmr.get_consistency_proof(3).unwrap();
+//! assert!(consistency.verify(&old_root, &mmr.root()).is_ok());
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+use crate::error::{MerkleError, MerkleResult};
+use crate::hash::{prefix, HashValue};
+use crate::hasher::{MerkleHasher, Sha256Hasher};
+
+/// An append-only Merkle Mountain Range.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct MerkleMountainRange<H = Sha256Hasher> {
+    /// Node hashes by height; `levels[0]` holds the leaf hashes
+    levels: Vec<Vec<HashValue>>,
+    #[serde(skip)]
+    hasher: PhantomData<H>,
+}
+
+impl MerkleMountainRange {
+    /// Create an empty MMR.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<H: MerkleHasher> MerkleMountainRange<H> {
+    /// Create an empty MMR using the given hasher.
+    pub fn with_hasher(_hasher: H) -> Self {
+        Self {
+            levels: Vec::new(),
+            hasher: PhantomData,
+        }
+    }
+
+    /// Get the number of leaves.
+    pub fn leaf_count(&self) -> u64 {
+        self.levels.first().map_or(0, |leaves| leaves.len() as u64)
+    }
+
+    /// Check if the MMR is empty.
+    pub fn is_empty(&self) -> bool {
+        self.leaf_count() == 0
+    }
+
+    /// Append a leaf and return its index.
+    pub fn append(&mut self, leaf: &[u8]) -> u64 {
+        let index = self.leaf_count();
+        let mut hash = hash_mmr_leaf::<H>(leaf);
+        let mut height = 0;
+        loop {
+            if self.levels.len() == height {
+                self.levels.push(Vec::new());
+            }
+            let level = &mut self.levels[height];
+            level.push(hash);
+            // An odd count means the new node has no partner yet
+            if level.len() % 2 == 1 {
+                break;
+            }
+            hash = hash_mmr_internal::<H>(&level[level.len() - 2], &level[level.len() - 1]);
+            height += 1;
+        }
+        index
+    }
+
+    /// Get the peak hashes, largest tree first.
+    pub fn peaks(&self) -> Vec<HashValue> {
+        self.peaks_at(self.leaf_count())
+    }
+
+    /// Get the root hash.
+    pub fn root(&self) -> HashValue {
+        bag_peaks::<H>(self.leaf_count(), &self.peaks())
+    }
+
+    /// Get the root hash the MMR had when it held `leaf_count` leaves.
+    pub fn root_at(&self, leaf_count: u64) -> MerkleResult<HashValue> {
+        self.check_leaf_count(leaf_count)?;
+        Ok(bag_peaks::<H>(leaf_count, &self.peaks_at(leaf_count)))
+    }
+
+    /// Get a proof that the leaf at `leaf_index` is in the current MMR.
+    pub fn get_proof(&self, leaf_index: u64) -> MerkleResult<MmrProof<H>> {
+        self.get_proof_at(leaf_index, self.leaf_count())
+    }
+
+    /// Get a proof that the leaf at `leaf_index` was in the MMR when it held
+    /// `leaf_count` leaves, verifiable against [`root_at`](Self::root_at).
+    pub fn get_proof_at(&self, leaf_index: u64, leaf_count: u64) -> MerkleResult<MmrProof<H>> {
+        self.check_leaf_count(leaf_count)?;
+        if leaf_index >= leaf_count {
+            return Err(MerkleError::IndexOutOfBounds {
+                index: leaf_index as usize,
+                size: leaf_count as usize,
+            });
+        }
+
+        let positions = peak_positions(leaf_count);
+        let peak = find_peak(&positions, leaf_index);
+        let (height, _) = positions[peak];
+
+        // Node indices are global per level, so the sibling is always `index ^ 1`
+        let siblings = (0..height)
+            .map(|level| self.levels[level][((leaf_index >> level) ^ 1) as usize])
+            .collect();
+        let mut peaks = self.peaks_at(leaf_count);
+        peaks.remove(peak);
+
+        Ok(MmrProof {
+            leaf_count,
+            siblings,
+            peaks,
+            hasher: PhantomData,
+        })
+    }
+
+    /// Get a proof that the MMR with `old_leaf_count` leaves is a prefix of the
+    /// current one.
+    pub fn get_consistency_proof(
+        &self,
+        old_leaf_count: u64,
+    ) -> MerkleResult<MmrConsistencyProof<H>> {
+        self.check_leaf_count(old_leaf_count)?;
+        let new_leaf_count = self.leaf_count();
+
+        let mut right_hashes = Vec::new();
+        for (height, start) in peak_positions(new_leaf_count) {
+            self.collect_right_hashes(height, start >> height, old_leaf_count, &mut right_hashes);
+        }
+
+        Ok(MmrConsistencyProof {
+            old_leaf_count,
+            new_leaf_count,
+            old_peaks: self.peaks_at(old_leaf_count),
+            right_hashes,
+            hasher: PhantomData,
+        })
+    }
+
+    /// Collect the maximal subtrees of node `(height, index)` that lie
+    /// entirely after the first `old_leaf_count` leaves.
+    fn collect_right_hashes(
+        &self,
+        height: usize,
+        index: u64,
+        old_leaf_count: u64,
+        right_hashes: &mut Vec<HashValue>,
+    ) {
+        let start = index << height;
+        let end = start + (1 << height);
+        if end <= old_leaf_count {
+            // One of the old peaks
+        } else if start >= old_leaf_count {
+            right_hashes.push(self.levels[height][index as usize]);
+        } else {
+            self.collect_right_hashes(height - 1, index * 2, old_leaf_count, right_hashes);
+            self.collect_right_hashes(height - 1, index * 2 + 1, old_leaf_count, right_hashes);
+        }
+    }
+
+    fn peaks_at(&self, leaf_count: u64) -> Vec<HashValue> {
+        peak_positions(leaf_count)
+            .into_iter()
+            .map(|(height, start)| self.levels[height][(start >> height) as usize])
+            .collect()
+    }
+
+    fn check_leaf_count(&self, leaf_count: u64) -> MerkleResult<()> {
+        if leaf_count > self.leaf_count() {
+            return Err(MerkleError::InvalidInput(format!(
+                "MMR has {} leaves, cannot use size {}",
+                self.leaf_count(),
+                leaf_count
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A proof that a leaf is in an MMR of a given size.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct MmrProof<H = Sha256Hasher> {
+    /// Number of leaves in the MMR the proof was made for
+    leaf_count: u64,
+    /// Siblings from the leaf up to its peak (bottom-up)
+    siblings: Vec<HashValue>,
+    /// All other peaks, largest tree first
+    peaks: Vec<HashValue>,
+    #[serde(skip)]
+    hasher: PhantomData<H>,
+}
+
+impl<H: MerkleHasher> MmrProof<H> {
+    /// Number of leaves in the MMR the proof was made for.
+    pub fn leaf_count(&self) -> u64 {
+        self.leaf_count
+    }
+
+    /// Verify that `leaf` is at `leaf_index` in the MMR with root `root`.
+    pub fn verify(&self, root: &HashValue, leaf: &[u8], leaf_index: u64) -> MerkleResult<()> {
+        if leaf_index >= self.leaf_count {
+            return Err(MerkleError::InvalidProof(format!(
+                "Leaf index {} out of range for {} leaves",
+                leaf_index, self.leaf_count
+            )));
+        }
+
+        let positions = peak_positions(self.leaf_count);
+        let peak = find_peak(&positions, leaf_index);
+        let (height, _) = positions[peak];
+        if self.siblings.len() != height || self.peaks.len() + 1 != positions.len() {
+            return Err(MerkleError::InvalidProof(
+                "Proof shape does not match the MMR size".to_string(),
+            ));
+        }
+
+        let mut current = hash_mmr_leaf::<H>(leaf);
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            current = if (leaf_index >> level) & 1 == 1 {
+                hash_mmr_internal::<H>(sibling, &current)
+            } else {
+                hash_mmr_internal::<H>(&current, sibling)
+            };
+        }
+
+        let mut peaks = self.peaks.clone();
+        peaks.insert(peak, current);
+        check_root(root, &bag_peaks::<H>(self.leaf_count, &peaks))
+    }
+}
+
+/// A proof that an older MMR is a prefix of a newer one.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct MmrConsistencyProof<H = Sha256Hasher> {
+    old_leaf_count: u64,
+    new_leaf_count: u64,
+    /// Peaks of the old MMR, largest tree first
+    old_peaks: Vec<HashValue>,
+    /// Subtrees of the new MMR covering only leaves appended since, left to right
+    right_hashes: Vec<HashValue>,
+    #[serde(skip)]
+    hasher: PhantomData<H>,
+}
+
+impl<H: MerkleHasher> MmrConsistencyProof<H> {
+    /// Number of leaves in the older MMR.
+    pub fn old_leaf_count(&self) -> u64 {
+        self.old_leaf_count
+    }
+
+    /// Number of leaves in the newer MMR.
+    pub fn new_leaf_count(&self) -> u64 {
+        self.new_leaf_count
+    }
+
+    /// Verify that the MMR with root `old_root` is a prefix of the one with
+    /// root `new_root`.
+    pub fn verify(&self, old_root: &HashValue, new_root: &HashValue) -> MerkleResult<()> {
+        if self.old_leaf_count > self.new_leaf_count {
+            return Err(MerkleError::InvalidProof(format!(
+                "Old size {} is larger than new size {}",
+                self.old_leaf_count, self.new_leaf_count
+            )));
+        }
+        check_root(old_root, &bag_peaks::<H>(self.old_leaf_count, &self.old_peaks))?;
+
+        let mut old_peaks = self.old_peaks.iter();
+        let mut right_hashes = self.right_hashes.iter();
+        let mut new_peaks = Vec::new();
+        for (height, start) in peak_positions(self.new_leaf_count) {
+            new_peaks.push(self.rebuild(height, start >> height, &mut old_peaks, &mut right_hashes)?);
+        }
+        if old_peaks.next().is_some() || right_hashes.next().is_some() {
+            return Err(MerkleError::InvalidProof(
+                "Proof has unused hashes".to_string(),
+            ));
+        }
+
+        check_root(new_root, &bag_peaks::<H>(self.new_leaf_count, &new_peaks))
+    }
+
+    /// Rebuild node `(height, index)` of the new MMR from old peaks and new subtrees.
+    fn rebuild<'a>(
+        &self,
+        height: usize,
+        index: u64,
+        old_peaks: &mut impl Iterator<Item = &'a HashValue>,
+        right_hashes: &mut impl Iterator<Item = &'a HashValue>,
+    ) -> MerkleResult<HashValue> {
+        let start = index << height;
+        let end = start + (1 << height);
+        let next = if end <= self.old_leaf_count {
+            old_peaks.next()
+        } else if start >= self.old_leaf_count {
+            right_hashes.next()
+        } else {
+            let left = self.rebuild(height - 1, index * 2, old_peaks, right_hashes)?;
+            let right = self.rebuild(height - 1, index * 2 + 1, old_peaks, right_hashes)?;
+            return Ok(hash_mmr_internal::<H>(&left, &right));
+        };
+        next.copied()
+            .ok_or_else(|| MerkleError::InvalidProof("Proof is missing hashes".to_string()))
+    }
+}
+
+/// Height and first leaf of each peak for `leaf_count` leaves, largest first.
+fn peak_positions(leaf_count: u64) -> Vec<(usize, u64)> {
+    let mut positions = Vec::new();
+    let mut start = 0;
+    for height in (0..u64::BITS as usize).rev() {
+        if (leaf_count >> height) & 1 == 1 {
+            positions.push((height, start));
+            start += 1 << height;
+        }
+    }
+    positions
+}
+
+/// Index of the peak whose tree holds `leaf_index`.
+fn find_peak(positions: &[(usize, u64)], leaf_index: u64) -> usize {
+    positions
+        .iter()
+        .position(|(height, start)| leaf_index < start + (1 << height))
+        .expect("leaf index is below the leaf count")
+}
+
+fn hash_mmr_leaf<H: MerkleHasher>(data: &[u8]) -> HashValue {
+    H::hash_with_prefix(prefix::MMR_LEAF, data)
+}
+
+fn hash_mmr_internal<H: MerkleHasher>(left: &HashValue, right: &HashValue) -> HashValue {
+    H::hash_parts(&[prefix::MMR_INTERNAL, left.as_bytes(), right.as_bytes()])
+}
+
+/// Commit to the leaf count and the peaks.
+fn bag_peaks<H: MerkleHasher>(leaf_count: u64, peaks: &[HashValue]) -> HashValue {
+    let leaf_count = leaf_count.to_le_bytes();
+    let mut parts: Vec<&[u8]> = vec![prefix::MMR_ROOT, &leaf_count];
+    parts.extend(peaks.iter().map(|peak| peak.as_bytes().as_slice()));
+    H::hash_parts(&parts)
+}
+
+fn check_root(expected: &HashValue, computed: &HashValue) -> MerkleResult<()> {
+    if expected == computed {
+        Ok(())
+    } else {
+        Err(MerkleError::InvalidProof(format!(
+            "Root mismatch: expected {}, computed {}",
+            expected, computed
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(i: u64) -> Vec<u8> {
+        format!("anchor{}", i).into_bytes()
+    }
+
+    fn build(n: u64) -> MerkleMountainRange {
+        let mut mmr = MerkleMountainRange::new();
+        for i in 0..n {
+            assert_eq!(mmr.append(&leaf(i)), i);
+        }
+        mmr
+    }
+
+    #[test]
+    fn test_peaks_follow_leaf_count_bits() {
+        let mmr = build(11);
+        assert_eq!(mmr.leaf_count(), 11);
+        assert_eq!(mmr.peaks().len(), 3);
+        assert_eq!(peak_positions(11), vec![(3, 0), (1, 8), (0, 10)]);
+
+        assert!(MerkleMountainRange::new().is_empty());
+        assert_ne!(MerkleMountainRange::new().root(), build(1).root());
+    }
+
+    #[test]
+    fn test_root_is_history_independent() {
+        // Appending the same leaves always gives the same roots
+        let mmr = build(20);
+        for n in 0..=20 {
+            assert_eq!(mmr.root_at(n).unwrap(), build(n).root());
+        }
+        assert!(mmr.root_at(21).is_err());
+    }
+
+    #[test]
+    fn test_inclusion_proofs() {
+        for n in 1..=33 {
+            let mmr = build(n);
+            let root = mmr.root();
+            for i in 0..n {
+                let proof = mmr.get_proof(i).unwrap();
+                assert_eq!(proof.leaf_count(), n);
+                assert!(proof.verify(&root, &leaf(i), i).is_ok());
+                assert!(proof.verify(&root, &leaf(i + 1), i).is_err());
+                if n > 1 {
+                    assert!(proof.verify(&root, &leaf(i), (i + 1) % n).is_err());
+                }
+            }
+            assert!(mmr.get_proof(n).is_err());
+        }
+    }
+
+    #[test]
+    fn test_historical_proofs() {
+        let mut mmr = build(5);
+        let old_root = mmr.root();
+        let proof = mmr.get_proof(2).unwrap();
+        mmr.append(&leaf(5));
+        mmr.append(&leaf(6));
+
+        assert!(proof.verify(&mmr.root(), &leaf(2), 2).is_err());
+        assert!(mmr.get_proof(2).unwrap().verify(&mmr.root(), &leaf(2), 2).is_ok());
+        assert_eq!(mmr.get_proof_at(2, 5).unwrap(), proof);
+        assert!(proof.verify(&old_root, &leaf(2), 2).is_ok());
+        assert!(mmr.get_proof_at(5, 5).is_err());
+        assert!(mmr.get_proof_at(2, 8).is_err());
+    }
+
+    #[test]
+    fn test_consistency_proofs() {
+        let mmr = build(27);
+        let new_root = mmr.root();
+        for old in 0..=27 {
+            let old_root = mmr.root_at(old).unwrap();
+            let proof = mmr.get_consistency_proof(old).unwrap();
+            assert_eq!((proof.old_leaf_count(), proof.new_leaf_count()), (old, 27));
+            assert!(proof.verify(&old_root, &new_root).is_ok());
+        }
+
+        // A diverging history is rejected
+        let mut forked = build(10);
+        forked.append(b"forked");
+        let proof = mmr.get_consistency_proof(11).unwrap();
+        assert!(proof.verify(&forked.root(), &new_root).is_err());
+        assert!(proof.verify(&mmr.root_at(11).unwrap(), &forked.root()).is_err());
+
+        assert!(mmr.get_consistency_proof(28).is_err());
+    }
+
+    #[test]
+    fn test_with_hasher() {
+        use crate::hasher::Keccak256Hasher;
+
+        let mut mmr = MerkleMountainRange::with_hasher(Keccak256Hasher);
+        for i in 0..9 {
+            mmr.append(&leaf(i));
+        }
+        assert_ne!(mmr.root(), build(9).root());
+
+        let proof = mmr.get_proof(4).unwrap();
+        assert!(proof.verify(&mmr.root(), &leaf(4), 4).is_ok());
+        let old_root = mmr.root_at(5).unwrap();
+        let proof = mmr.get_consistency_proof(5).unwrap();
+        assert!(proof.verify(&old_root, &mmr.root()).is_ok());
+    }
+
+    #[test]
+    fn test_proof_serialization() {
+        let mmr = build(13);
+        let proof = mmr.get_proof(9).unwrap();
+        let decoded: MmrProof = bcs::from_bytes(&bcs::to_bytes(&proof).unwrap()).unwrap();
+        assert_eq!(decoded, proof);
+
+        let proof = mmr.get_consistency_proof(6).unwrap();
+        let decoded: MmrConsistencyProof = bcs::from_bytes(&bcs::to_bytes(&proof).unwrap()).unwrap();
+        assert_eq!(decoded, proof);
+    }
+}
@@ -0,0 +1,744 @@
+//! Batched proofs for the sparse Merkle tree.
+//!
+//! A [`SparseMerkleProof`](crate::sparse::SparseMerkleProof) covers one key and
+//! carries its full sibling path, so proving many keys separately repeats every
+//! sibling they share near the root.
+//!
+//! - [`SparseMerkleMultiProof`]: (non-)inclusion of any set of keys, with each
+//!   shared sibling included once
+//! - [`SparseMerkleRangeProof`]: the complete set of leaves in a key interval
+//!
+//! Both proofs describe the part of the tree the verifier rebuilds: the hashes
+//! of subtrees it does not need (`siblings`) and the nodes where its paths end
+//! (`terminals`), each in depth-first, left-to-right order. The verifier decides
+//! which subtrees to enter from the keys or range alone, so a proof cannot hide
+//! a leaf that falls inside them.
+//!
+//! # Example
+//!
+//! ```
+//! use setu_merkle::sparse::SparseMerkleTree;
+//! use setu_merkle::HashValue;
+//!
+//! let mut tree = SparseMerkleTree::new();
+//! let coin = HashValue::new([1u8; 32]);
+//! let profile = HashValue::new([2u8; 32]);
+//! let missing = HashValue::new([3u8; 32]);
+//! tree.insert(coin, b"100".to_vec());
+//! tree.insert(profile, b"alice".to_vec());
+//!
+//! let proof = tree.get_multi_proof(&[coin, profile, missing]).unwrap();
+//! let entries = vec![
+//!     (coin, Some(b"100".to_vec())),
+//!     (profile, Some(b"alice".to_vec())),
+//!     (missing, None),
+//! ];
+//! assert!(proof.verify(&tree.root(), &entries).is_ok());
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::iter::Peekable;
+use std::marker::PhantomData;
+use std::slice;
+
+use crate::error::{MerkleError, MerkleResult};
+use crate::hash::HashValue;
+use crate::hasher::{MerkleHasher, Sha256Hasher};
+use crate::sparse::{empty_hash, hash_internal, hash_value, SparseMerkleLeafNode};
+use crate::storage::LeafNode;
+use crate::HASH_LENGTH;
+
+/// Entries proven by a range proof, in key order.
+pub type RangeEntries = Vec<(HashValue, Vec<u8>)>;
+
+/// Values proven by a multiproof, in the order of the requested keys.
+pub type MultiProofValues = Vec<Option<Vec<u8>>>;
+
+/// Where a rebuilt path ends: an empty subtree or a subtree holding one leaf.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofTerminal {
+    /// Depth of the subtree (0 = tree root)
+    pub depth: u16,
+    /// The only leaf in the subtree, or `None` if it is empty
+    pub leaf: Option<SparseMerkleLeafNode>,
+}
+
+/// A proof of (non-)inclusion for several keys against one root.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct SparseMerkleMultiProof<H = Sha256Hasher> {
+    /// Hashes of the subtrees off the proven paths
+    siblings: Vec<HashValue>,
+    /// Ends of the proven paths, in key order
+    terminals: Vec<ProofTerminal>,
+    #[serde(skip)]
+    hasher: PhantomData<H>,
+}
+
+impl<H: MerkleHasher> SparseMerkleMultiProof<H> {
+    /// Generate a proof for `keys` from a tree rooted at `root`.
+    ///
+    /// Also returns the leaves the proven paths end at.
+    pub(crate) fn generate<S: ProofSource<Hasher = H>>(
+        source: &S,
+        root: S::NodeRef,
+        keys: &[HashValue],
+    ) -> MerkleResult<(Self, Vec<LeafNode>)> {
+        let keys = sorted_keys(keys.iter().copied())?;
+        let (siblings, terminals, leaves) =
+            generate(source, root, |path, depth| contains_key(&keys, path, depth))?;
+        let proof = Self {
+            siblings,
+            terminals,
+            hasher: PhantomData,
+        };
+        Ok((proof, leaves))
+    }
+
+    /// Number of sibling hashes in the proof.
+    pub fn num_siblings(&self) -> usize {
+        self.siblings.len()
+    }
+
+    /// Verify the values of several keys.
+    ///
+    /// Each entry is `(key, Some(value))` for a key expected in the tree or
+    /// `(key, None)` for a key expected to be absent. Keys may be in any order
+    /// but must be unique.
+    pub fn verify(
+        &self,
+        root: &HashValue,
+        entries: &[(HashValue, Option<Vec<u8>>)],
+    ) -> MerkleResult<()> {
+        let mut entries: Vec<&(HashValue, Option<Vec<u8>>)> = entries.iter().collect();
+        entries.sort_by_key(|(key, _)| *key);
+        let keys = sorted_keys(entries.iter().map(|(key, _)| *key))?;
+
+        let reached = rebuild::<H, _>(&self.siblings, &self.terminals, root, |path, depth| {
+            contains_key(&keys, path, depth)
+        })?;
+
+        // Terminals come out in key order, so one pass matches every key to its own
+        let mut reached = reached.iter().peekable();
+        for (key, value) in entries {
+            while reached.next_if(|terminal| !terminal.covers(key)).is_some() {}
+            let terminal = reached.peek().ok_or_else(|| {
+                MerkleError::InvalidProof(format!("No proven path for key {}", key))
+            })?;
+
+            match (value, terminal.leaf) {
+                (Some(value), Some(leaf)) if &leaf.key == key => {
+                    if leaf.value_hash != hash_value::<H>(value) {
+                        return Err(MerkleError::InvalidProof(format!(
+                            "Value hash mismatch for key {}",
+                            key
+                        )));
+                    }
+                }
+                (Some(_), _) => {
+                    return Err(MerkleError::InvalidProof(format!(
+                        "Key {} is not in the tree",
+                        key
+                    )));
+                }
+                (None, Some(leaf)) if &leaf.key == key => {
+                    return Err(MerkleError::InvalidProof(format!(
+                        "Key {} exists in tree, cannot prove non-inclusion",
+                        key
+                    )));
+                }
+                (None, _) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Serialize the proof.
+    pub fn to_bytes(&self) -> MerkleResult<Vec<u8>> {
+        bcs::to_bytes(self).map_err(|e| MerkleError::SerializationError(e.to_string()))
+    }
+
+    /// Deserialize a proof produced by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> MerkleResult<Self> {
+        bcs::from_bytes(bytes).map_err(|e| MerkleError::SerializationError(e.to_string()))
+    }
+}
+
+/// A proof that a list of leaves is everything the tree holds in a key range.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct SparseMerkleRangeProof<H = Sha256Hasher> {
+    /// Hashes of the subtrees entirely outside the range
+    siblings: Vec<HashValue>,
+    /// Ends of the paths covering the range, in key order
+    terminals: Vec<ProofTerminal>,
+    #[serde(skip)]
+    hasher: PhantomData<H>,
+}
+
+impl<H: MerkleHasher> SparseMerkleRangeProof<H> {
+    /// Generate a proof for the inclusive range `[start, end]` from a tree
+    /// rooted at `root`.
+    ///
+    /// Also returns the leaves inside the range, in key order.
+    pub(crate) fn generate<S: ProofSource<Hasher = H>>(
+        source: &S,
+        root: S::NodeRef,
+        start: &HashValue,
+        end: &HashValue,
+    ) -> MerkleResult<(Self, Vec<LeafNode>)> {
+        check_range(start, end)?;
+        let (siblings, terminals, mut leaves) =
+            generate(source, root, |path, depth| overlaps(start, end, path, depth))?;
+        leaves.retain(|leaf| &leaf.key >= start && &leaf.key <= end);
+        let proof = Self {
+            siblings,
+            terminals,
+            hasher: PhantomData,
+        };
+        Ok((proof, leaves))
+    }
+
+    /// Number of sibling hashes in the proof.
+    pub fn num_siblings(&self) -> usize {
+        self.siblings.len()
+    }
+
+    /// Verify that `entries` are exactly the leaves with keys in `[start, end]`.
+    ///
+    /// `entries` must be sorted by key, as returned with the proof.
+    pub fn verify(
+        &self,
+        root: &HashValue,
+        start: &HashValue,
+        end: &HashValue,
+        entries: &[(HashValue, Vec<u8>)],
+    ) -> MerkleResult<()> {
+        check_range(start, end)?;
+        let reached = rebuild::<H, _>(&self.siblings, &self.terminals, root, |path, depth| {
+            overlaps(start, end, path, depth)
+        })?;
+
+        let mut in_range = reached
+            .iter()
+            .filter_map(|terminal| terminal.leaf)
+            .filter(|leaf| &leaf.key >= start && &leaf.key <= end);
+        for (key, value) in entries {
+            match in_range.next() {
+                Some(leaf) if &leaf.key == key => {
+                    if leaf.value_hash != hash_value::<H>(value) {
+                        return Err(MerkleError::InvalidProof(format!(
+                            "Value hash mismatch for key {}",
+                            key
+                        )));
+                    }
+                }
+                Some(leaf) => {
+                    return Err(MerkleError::InvalidProof(format!(
+                        "Expected key {} in range, found {}",
+                        key, leaf.key
+                    )));
+                }
+                None => {
+                    return Err(MerkleError::InvalidProof(format!(
+                        "Key {} is not in the range",
+                        key
+                    )));
+                }
+            }
+        }
+        if let Some(leaf) = in_range.next() {
+            return Err(MerkleError::InvalidProof(format!(
+                "Range is missing key {}",
+                leaf.key
+            )));
+        }
+        Ok(())
+    }
+
+    /// Serialize the proof.
+    pub fn to_bytes(&self) -> MerkleResult<Vec<u8>> {
+        bcs::to_bytes(self).map_err(|e| MerkleError::SerializationError(e.to_string()))
+    }
+
+    /// Deserialize a proof produced by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> MerkleResult<Self> {
+        bcs::from_bytes(bytes).map_err(|e| MerkleError::SerializationError(e.to_string()))
+    }
+}
+
+/// A tree node as seen by proof generation.
+pub(crate) enum ProofNode<R> {
+    Empty,
+    Leaf(LeafNode),
+    /// Hash of each child and how to look it up
+    Internal {
+        left: (HashValue, R),
+        right: (HashValue, R),
+    },
+}
+
+/// A tree that batched proofs can be generated from.
+pub(crate) trait ProofSource {
+    /// Hash function the tree is built with
+    type Hasher: MerkleHasher;
+    /// How a node is looked up
+    type NodeRef;
+
+    fn node(&self, node_ref: &Self::NodeRef) -> MerkleResult<ProofNode<Self::NodeRef>>;
+}
+
+/// Walk every subtree `wants` selects, recording siblings, terminals and leaves.
+fn generate<S, F>(
+    source: &S,
+    root: S::NodeRef,
+    wants: F,
+) -> MerkleResult<(Vec<HashValue>, Vec<ProofTerminal>, Vec<LeafNode>)>
+where
+    S: ProofSource,
+    F: Fn(&HashValue, usize) -> bool,
+{
+    let mut generator = Generator {
+        source,
+        wants,
+        siblings: Vec::new(),
+        terminals: Vec::new(),
+        leaves: Vec::new(),
+    };
+    generator.visit(root, HashValue::zero(), 0)?;
+    Ok((generator.siblings, generator.terminals, generator.leaves))
+}
+
+struct Generator<'a, S, F> {
+    source: &'a S,
+    wants: F,
+    siblings: Vec<HashValue>,
+    terminals: Vec<ProofTerminal>,
+    leaves: Vec<LeafNode>,
+}
+
+impl<S, F> Generator<'_, S, F>
+where
+    S: ProofSource,
+    F: Fn(&HashValue, usize) -> bool,
+{
+    fn visit(&mut self, node_ref: S::NodeRef, path: HashValue, depth: usize) -> MerkleResult<()> {
+        match self.source.node(&node_ref)? {
+            ProofNode::Empty => self.terminals.push(ProofTerminal {
+                depth: depth as u16,
+                leaf: None,
+            }),
+            ProofNode::Leaf(leaf) => {
+                self.terminals.push(ProofTerminal {
+                    depth: depth as u16,
+                    leaf: Some(leaf.proof_leaf()),
+                });
+                self.leaves.push(leaf);
+            }
+            ProofNode::Internal { left, right } => {
+                if depth >= HASH_LENGTH * 8 {
+                    return Err(MerkleError::InvalidInput(
+                        "Tree is deeper than the key length".to_string(),
+                    ));
+                }
+                for (is_right, (hash, child)) in [(false, left), (true, right)] {
+                    let child_path = child_path(&path, depth, is_right);
+                    if (self.wants)(&child_path, depth + 1) {
+                        self.visit(child, child_path, depth + 1)?;
+                    } else {
+                        self.siblings.push(hash);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A terminal reached while rebuilding the tree, with its position.
+struct Reached<'a> {
+    path: HashValue,
+    depth: usize,
+    leaf: Option<&'a SparseMerkleLeafNode>,
+}
+
+impl Reached<'_> {
+    /// Whether `key` falls inside this terminal's subtree
+    fn covers(&self, key: &HashValue) -> bool {
+        key.common_prefix_bits(&self.path) >= self.depth
+    }
+}
+
+/// Rebuild the root from a proof, entering exactly the subtrees `wants` selects.
+///
+/// Fails unless the proof is fully consumed and hashes to `root`.
+fn rebuild<'a, H, F>(
+    siblings: &'a [HashValue],
+    terminals: &'a [ProofTerminal],
+    root: &HashValue,
+    wants: F,
+) -> MerkleResult<Vec<Reached<'a>>>
+where
+    H: MerkleHasher,
+    F: Fn(&HashValue, usize) -> bool,
+{
+    let mut rebuilder = Rebuilder {
+        siblings: siblings.iter(),
+        terminals: terminals.iter().peekable(),
+        wants,
+        reached: Vec::new(),
+        hasher: PhantomData::<H>,
+    };
+    let computed_root = rebuilder.visit(HashValue::zero(), 0)?;
+
+    if rebuilder.siblings.next().is_some() || rebuilder.terminals.next().is_some() {
+        return Err(MerkleError::InvalidProof(
+            "Proof has unused siblings or terminals".to_string(),
+        ));
+    }
+    if &computed_root != root {
+        return Err(MerkleError::InvalidProof(format!(
+            "Root mismatch: expected {}, computed {}",
+            root, computed_root
+        )));
+    }
+    Ok(rebuilder.reached)
+}
+
+struct Rebuilder<'a, H, F> {
+    siblings: slice::Iter<'a, HashValue>,
+    terminals: Peekable<slice::Iter<'a, ProofTerminal>>,
+    wants: F,
+    reached: Vec<Reached<'a>>,
+    hasher: PhantomData<H>,
+}
+
+impl<'a, H, F> Rebuilder<'a, H, F>
+where
+    H: MerkleHasher,
+    F: Fn(&HashValue, usize) -> bool,
+{
+    fn visit(&mut self, path: HashValue, depth: usize) -> MerkleResult<HashValue> {
+        let terminal_depth = self
+            .terminals
+            .peek()
+            .map(|terminal| terminal.depth as usize)
+            .ok_or_else(|| MerkleError::InvalidProof("Proof is missing terminals".to_string()))?;
+
+        if terminal_depth == depth {
+            let leaf = self.terminals.next().and_then(|terminal| terminal.leaf.as_ref());
+            let hash = match leaf {
+                Some(leaf) if leaf.key.common_prefix_bits(&path) < depth => {
+                    return Err(MerkleError::InvalidProof(format!(
+                        "Leaf {} is off its key path",
+                        leaf.key
+                    )));
+                }
+                Some(leaf) => leaf.hash_with::<H>(),
+                None => empty_hash::<H>(),
+            };
+            self.reached.push(Reached { path, depth, leaf });
+            return Ok(hash);
+        }
+        if terminal_depth < depth || depth >= HASH_LENGTH * 8 {
+            return Err(MerkleError::InvalidProof(format!(
+                "Unexpected terminal depth {} at depth {}",
+                terminal_depth, depth
+            )));
+        }
+
+        let mut children = [empty_hash::<H>(); 2];
+        for (child, is_right) in children.iter_mut().zip([false, true]) {
+            let child_path = child_path(&path, depth, is_right);
+            *child = if (self.wants)(&child_path, depth + 1) {
+                self.visit(child_path, depth + 1)?
+            } else {
+                *self.siblings.next().ok_or_else(|| {
+                    MerkleError::InvalidProof("Proof is missing siblings".to_string())
+                })?
+            };
+        }
+        Ok(hash_internal::<H>(&children[0], &children[1]))
+    }
+}
+
+/// Sort keys and reject duplicates.
+fn sorted_keys(keys: impl Iterator<Item = HashValue>) -> MerkleResult<Vec<HashValue>> {
+    let mut keys: Vec<HashValue> = keys.collect();
+    keys.sort();
+    if let Some(pair) = keys.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(MerkleError::InvalidInput(format!("Duplicate key {}", pair[0])));
+    }
+    Ok(keys)
+}
+
+fn check_range(start: &HashValue, end: &HashValue) -> MerkleResult<()> {
+    if start > end {
+        return Err(MerkleError::InvalidInput(format!(
+            "Range start {} is after end {}",
+            start, end
+        )));
+    }
+    Ok(())
+}
+
+/// Path of a child, given its parent's path and depth.
+///
+/// Bits at and below the parent's depth are zero in `path`.
+fn child_path(path: &HashValue, depth: usize, right: bool) -> HashValue {
+    let mut bytes = *path.as_bytes();
+    if right {
+        bytes[depth / 8] |= 0x80 >> (depth % 8);
+    }
+    HashValue::new(bytes)
+}
+
+/// Smallest and largest key in the subtree at `path` and `depth`.
+fn subtree_bounds(path: &HashValue, depth: usize) -> (HashValue, HashValue) {
+    let mut min = *path.as_bytes();
+    let mut max = min;
+    for bit in depth..HASH_LENGTH * 8 {
+        let mask = 0x80 >> (bit % 8);
+        min[bit / 8] &= !mask;
+        max[bit / 8] |= mask;
+    }
+    (HashValue::new(min), HashValue::new(max))
+}
+
+/// Whether any of the sorted `keys` falls in the subtree at `path` and `depth`.
+fn contains_key(keys: &[HashValue], path: &HashValue, depth: usize) -> bool {
+    let (min, max) = subtree_bounds(path, depth);
+    let first = keys.partition_point(|key| key < &min);
+    keys.get(first).is_some_and(|key| key <= &max)
+}
+
+/// Whether `[start, end]` overlaps the subtree at `path` and `depth`.
+fn overlaps(start: &HashValue, end: &HashValue, path: &HashValue, depth: usize) -> bool {
+    let (min, max) = subtree_bounds(path, depth);
+    start <= &max && &min <= end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistent::PersistentSparseMerkleTree;
+    use crate::sparse::SparseMerkleTree;
+    use crate::storage::{MemoryTreeStore, TreeWriter};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::BTreeMap;
+
+    fn test_key(byte: u8) -> HashValue {
+        HashValue::new([byte; 32])
+    }
+
+    fn random_tree(rng: &mut StdRng, n: usize) -> (SparseMerkleTree, BTreeMap<HashValue, Vec<u8>>) {
+        let entries: BTreeMap<HashValue, Vec<u8>> = (0..n)
+            .map(|i| (HashValue::new(rng.gen()), format!("value{}", i).into_bytes()))
+            .collect();
+        let mut tree = SparseMerkleTree::new();
+        tree.batch_insert(entries.clone().into_iter().collect());
+        (tree, entries)
+    }
+
+    #[test]
+    fn test_multi_proof_shares_siblings() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let (tree, entries) = random_tree(&mut rng, 500);
+        let root = tree.root();
+
+        let mut keys: Vec<HashValue> = entries.keys().step_by(10).copied().collect();
+        keys.extend((0..10).map(|_| HashValue::new(rng.gen())));
+
+        let proof = tree.get_multi_proof(&keys).unwrap();
+        let claims: Vec<_> = keys.iter().map(|k| (*k, entries.get(k).cloned())).collect();
+        assert!(proof.verify(&root, &claims).is_ok());
+
+        let separate: usize = keys.iter().map(|k| tree.get_proof(k).depth()).sum();
+        assert!(proof.num_siblings() < separate);
+    }
+
+    #[test]
+    fn test_multi_proof_rejects_false_claims() {
+        let mut tree = SparseMerkleTree::new();
+        for byte in [0x00, 0x20, 0x80, 0xc0] {
+            tree.insert(test_key(byte), vec![byte]);
+        }
+        let root = tree.root();
+        let keys = [test_key(0x20), test_key(0x40), test_key(0xc0)];
+        let proof = tree.get_multi_proof(&keys).unwrap();
+
+        let honest = vec![
+            (keys[0], Some(vec![0x20])),
+            (keys[1], None),
+            (keys[2], Some(vec![0xc0])),
+        ];
+        assert!(proof.verify(&root, &honest).is_ok());
+
+        let mut wrong_value = honest.clone();
+        wrong_value[0].1 = Some(vec![0xff]);
+        assert!(proof.verify(&root, &wrong_value).is_err());
+
+        let mut hidden = honest.clone();
+        hidden[2].1 = None;
+        assert!(proof.verify(&root, &hidden).is_err());
+
+        let mut invented = honest.clone();
+        invented[1].1 = Some(vec![0x40]);
+        assert!(proof.verify(&root, &invented).is_err());
+
+        // A key the proof was not generated for
+        let mut other = honest.clone();
+        other.push((test_key(0x00), Some(vec![0x00])));
+        assert!(proof.verify(&root, &other).is_err());
+
+        let mut duplicate = honest.clone();
+        duplicate.push(honest[0].clone());
+        assert!(matches!(
+            proof.verify(&root, &duplicate),
+            Err(MerkleError::InvalidInput(_))
+        ));
+
+        assert!(proof.verify(&test_key(0x99), &honest).is_err());
+    }
+
+    #[test]
+    fn test_range_proof() {
+        let mut rng = StdRng::seed_from_u64(12);
+        let (tree, entries) = random_tree(&mut rng, 300);
+        let root = tree.root();
+
+        for _ in 0..20 {
+            let a = HashValue::new(rng.gen());
+            let b = HashValue::new(rng.gen());
+            let (start, end) = if a <= b { (a, b) } else { (b, a) };
+
+            let (leaves, proof) = tree.get_range_proof(&start, &end).unwrap();
+            let expected: Vec<_> = entries
+                .range(start..=end)
+                .map(|(k, v)| (*k, v.clone()))
+                .collect();
+            assert_eq!(leaves, expected);
+            assert!(proof.verify(&root, &start, &end, &leaves).is_ok());
+
+            if !leaves.is_empty() {
+                let mut omitted = leaves.clone();
+                omitted.remove(omitted.len() / 2);
+                assert!(proof.verify(&root, &start, &end, &omitted).is_err());
+
+                let mut altered = leaves.clone();
+                altered[0].1.push(0);
+                assert!(proof.verify(&root, &start, &end, &altered).is_err());
+            }
+            // Widening the range to the next key cannot reuse the same entries
+            if let Some((next, _)) = entries.range(end..).find(|(k, _)| **k != end) {
+                assert!(proof.verify(&root, &start, next, &leaves).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_range_proof_edges() {
+        let mut tree = SparseMerkleTree::new();
+        let (start, end) = (HashValue::zero(), HashValue::new([0xff; 32]));
+
+        let (leaves, proof) = tree.get_range_proof(&start, &end).unwrap();
+        assert!(leaves.is_empty());
+        assert!(proof.verify(&tree.root(), &start, &end, &[]).is_ok());
+
+        tree.insert(test_key(0x10), b"a".to_vec());
+        tree.insert(test_key(0x30), b"b".to_vec());
+        let root = tree.root();
+
+        // Single key and empty ranges
+        let (leaves, proof) = tree.get_range_proof(&test_key(0x10), &test_key(0x10)).unwrap();
+        assert_eq!(leaves, vec![(test_key(0x10), b"a".to_vec())]);
+        assert!(proof.verify(&root, &test_key(0x10), &test_key(0x10), &leaves).is_ok());
+
+        let (leaves, proof) = tree.get_range_proof(&test_key(0x11), &test_key(0x2f)).unwrap();
+        assert!(leaves.is_empty());
+        assert!(proof.verify(&root, &test_key(0x11), &test_key(0x2f), &[]).is_ok());
+
+        let (leaves, proof) = tree.get_range_proof(&start, &end).unwrap();
+        assert_eq!(leaves.len(), 2);
+        assert!(proof.verify(&root, &start, &end, &leaves).is_ok());
+
+        assert!(matches!(
+            tree.get_range_proof(&end, &start),
+            Err(MerkleError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_serialization_roundtrip() {
+        let mut rng = StdRng::seed_from_u64(13);
+        let (tree, entries) = random_tree(&mut rng, 100);
+        let root = tree.root();
+
+        let keys: Vec<HashValue> = entries.keys().take(5).copied().collect();
+        let proof = tree.get_multi_proof(&keys).unwrap();
+        let decoded = SparseMerkleMultiProof::from_bytes(&proof.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded, proof);
+        let claims: Vec<_> = keys.iter().map(|k| (*k, entries.get(k).cloned())).collect();
+        assert!(decoded.verify(&root, &claims).is_ok());
+
+        let (start, end) = (keys[0], keys[4]);
+        let (leaves, proof) = tree.get_range_proof(&start, &end).unwrap();
+        let decoded = SparseMerkleRangeProof::from_bytes(&proof.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded, proof);
+        assert!(decoded.verify(&root, &start, &end, &leaves).is_ok());
+
+        let garbage: MerkleResult<SparseMerkleMultiProof> =
+            SparseMerkleMultiProof::from_bytes(&[0xff, 0xff]);
+        assert!(garbage.is_err());
+    }
+
+    #[test]
+    fn test_proofs_with_hasher() {
+        use crate::hasher::Keccak256Hasher;
+
+        let mut tree = SparseMerkleTree::with_hasher(Keccak256Hasher);
+        for byte in [0x10, 0x50, 0x90, 0xd0] {
+            tree.insert(test_key(byte), vec![byte]);
+        }
+        let root = tree.root();
+
+        let keys = [test_key(0x50), test_key(0x60)];
+        let proof = tree.get_multi_proof(&keys).unwrap();
+        let claims = vec![(keys[0], Some(vec![0x50])), (keys[1], None)];
+        assert!(proof.verify(&root, &claims).is_ok());
+
+        let (leaves, proof) = tree.get_range_proof(&test_key(0x40), &test_key(0xa0)).unwrap();
+        assert_eq!(leaves.len(), 2);
+        assert!(proof.verify(&root, &test_key(0x40), &test_key(0xa0), &leaves).is_ok());
+
+        // Re-read as a SHA-256 proof, it no longer reaches the root
+        let proof: SparseMerkleRangeProof = bcs::from_bytes(&proof.to_bytes().unwrap()).unwrap();
+        assert!(proof.verify(&root, &test_key(0x40), &test_key(0xa0), &leaves).is_err());
+    }
+
+    #[test]
+    fn test_persistent_proofs_match_in_memory() {
+        let mut rng = StdRng::seed_from_u64(14);
+        let (tree, entries) = random_tree(&mut rng, 200);
+
+        let store = MemoryTreeStore::new();
+        let persistent = PersistentSparseMerkleTree::new(&store);
+        let value_set = entries.iter().map(|(k, v)| (*k, Some(v.clone()))).collect();
+        let (root, batch) = persistent.put_value_set(value_set, 0).unwrap();
+        store.write_tree_update_batch(&batch).unwrap();
+        assert_eq!(root, tree.root());
+
+        let mut keys: Vec<HashValue> = entries.keys().step_by(7).copied().collect();
+        keys.push(HashValue::new(rng.gen()));
+        let (values, proof) = persistent.get_with_multi_proof(&keys, 0).unwrap();
+        assert_eq!(proof, tree.get_multi_proof(&keys).unwrap());
+        let claims: Vec<_> = keys.iter().copied().zip(values).collect();
+        assert!(proof.verify(&root, &claims).is_ok());
+
+        let (start, end) = (keys[1], keys[5]);
+        let (leaves, proof) = persistent.get_range_with_proof(&start, &end, 0).unwrap();
+        assert_eq!((leaves.clone(), proof.clone()), tree.get_range_proof(&start, &end).unwrap());
+        assert!(proof.verify(&root, &start, &end, &leaves).is_ok());
+    }
+}
@@ -0,0 +1,620 @@
+//! Persistent, versioned sparse Merkle tree.
+//!
+//! [`PersistentSparseMerkleTree`] has the same shape and root hashes as
+//! [`SparseMerkleTree`](crate::sparse::SparseMerkleTree), but keeps its nodes in
+//! a [`TreeReader`] backend instead of in memory. Each batch of updates is
+//! committed under a new version; only the nodes on changed paths are written,
+//! and older versions remain readable.
+//!
+//! Nodes are hashed with a [`MerkleHasher`], [`StorageHasher`] (SHA-256) by
+//! default; a store must always be read with the hasher that wrote it.
+//!
+//! # Example
+//!
+//! ```
+//! use setu_merkle::persistent::PersistentSparseMerkleTree;
+//! use setu_merkle::storage::{MemoryTreeStore, TreeWriter};
+//! use setu_merkle::HashValue;
+//!
+//! let store = MemoryTreeStore::new();
+//! let tree = PersistentSparseMerkleTree::new(&store);
+//!
+//! let key = HashValue::from_slice(&[1u8; 32]).unwrap();
+//! let (root, batch) = tree.put_value_set(vec![(key, Some(b"value".to_vec()))], 0).unwrap();
+//! store.write_tree_update_batch(&batch).unwrap();
+//!
+//! assert_eq!(tree.get_root_hash(0).unwrap(), root);
+//! assert_eq!(tree.get(&key, 0).unwrap(), Some(b"value".to_vec()));
+//! ```
+
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+use crate::error::MerkleResult;
+use crate::hash::HashValue;
+use crate::hasher::MerkleHasher;
+use crate::multiproof::{
+    MultiProofValues, ProofNode, ProofSource, RangeEntries, SparseMerkleMultiProof,
+    SparseMerkleRangeProof,
+};
+use crate::sparse::{empty_hash, SparseMerkleProof};
+use crate::storage::{
+    Child, InternalNode, LeafNode, NodeKey, StaleNodeIndex, StorageHasher, TreeNode, TreeReader,
+    TreeUpdateBatch, Version,
+};
+
+/// Result of updating a subtree during a batch commit.
+enum Subtree {
+    /// No leaves remain below this position
+    Empty,
+    /// Exactly one leaf remains; it floats up until it has a sibling.
+    /// `node_key` is set if the leaf is already persisted somewhere.
+    Leaf {
+        leaf: LeafNode,
+        node_key: Option<NodeKey>,
+    },
+    /// An internal node persisted at this position
+    Internal { hash: HashValue, version: Version },
+}
+
+/// A sparse Merkle tree whose nodes live in external storage.
+///
+/// The tree itself is stateless: it reads nodes through `reader` and returns
+/// the nodes to persist as a [`TreeUpdateBatch`], which the caller writes with
+/// a [`TreeWriter`](crate::storage::TreeWriter).
+pub struct PersistentSparseMerkleTree<'a, R, H = StorageHasher> {
+    reader: &'a R,
+    hasher: PhantomData<H>,
+}
+
+impl<'a, R: TreeReader> PersistentSparseMerkleTree<'a, R> {
+    /// Create a tree view over the given storage.
+    pub fn new(reader: &'a R) -> Self {
+        Self::with_hasher(reader, StorageHasher::default())
+    }
+}
+
+impl<'a, R: TreeReader, H: MerkleHasher> PersistentSparseMerkleTree<'a, R, H> {
+    /// Create a tree view over storage written with the given hasher.
+    pub fn with_hasher(reader: &'a R, _hasher: H) -> Self {
+        Self {
+            reader,
+            hasher: PhantomData,
+        }
+    }
+
+    /// Get the root hash of the tree at a version.
+    pub fn get_root_hash(&self, version: Version) -> MerkleResult<HashValue> {
+        let root = self.reader.get_node(&NodeKey::new_empty_path(version))?;
+        Ok(root.hash_with::<H>())
+    }
+
+    /// Get a value by key at a version.
+    pub fn get(&self, key: &HashValue, version: Version) -> MerkleResult<Option<Vec<u8>>> {
+        Ok(self.get_with_proof(key, version)?.0)
+    }
+
+    /// Get a value by key at a version together with a proof of (non-)inclusion.
+    pub fn get_with_proof(
+        &self,
+        key: &HashValue,
+        version: Version,
+    ) -> MerkleResult<(Option<Vec<u8>>, SparseMerkleProof<H>)> {
+        let mut node_key = NodeKey::new_empty_path(version);
+        let mut siblings = Vec::new();
+
+        loop {
+            match self.reader.get_node(&node_key)? {
+                TreeNode::Null => {
+                    siblings.reverse();
+                    return Ok((None, SparseMerkleProof::new(siblings, None)));
+                }
+                TreeNode::Leaf(leaf) => {
+                    siblings.reverse();
+                    let value = (&leaf.key == key).then(|| leaf.value.clone());
+                    return Ok((value, SparseMerkleProof::new(siblings, Some(leaf.proof_leaf()))));
+                }
+                TreeNode::Internal(internal) => {
+                    let bit = key.bit(node_key.depth());
+                    let sibling = internal.child(!bit).map(|c| c.hash).unwrap_or_else(empty_hash::<H>);
+                    siblings.push(sibling);
+                    match internal.child(bit) {
+                        Some(child) => node_key = node_key.gen_child_node_key(child.version, bit),
+                        None => {
+                            siblings.reverse();
+                            return Ok((None, SparseMerkleProof::new(siblings, None)));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Get the values of several keys at a version with one proof covering all of them.
+    ///
+    /// Values are returned in the order of `keys`.
+    pub fn get_with_multi_proof(
+        &self,
+        keys: &[HashValue],
+        version: Version,
+    ) -> MerkleResult<(MultiProofValues, SparseMerkleMultiProof<H>)> {
+        let root = Some(NodeKey::new_empty_path(version));
+        let (proof, leaves) = SparseMerkleMultiProof::generate(self, root, keys)?;
+        let values = keys
+            .iter()
+            .map(|key| leaves.iter().find(|leaf| &leaf.key == key).map(|leaf| leaf.value.clone()))
+            .collect();
+        Ok((values, proof))
+    }
+
+    /// Get all entries with keys in `[start, end]` at a version, with a proof
+    /// that the list is complete.
+    pub fn get_range_with_proof(
+        &self,
+        start: &HashValue,
+        end: &HashValue,
+        version: Version,
+    ) -> MerkleResult<(RangeEntries, SparseMerkleRangeProof<H>)> {
+        let root = Some(NodeKey::new_empty_path(version));
+        let (proof, leaves) = SparseMerkleRangeProof::generate(self, root, start, end)?;
+        let entries = leaves.into_iter().map(|leaf| (leaf.key, leaf.value)).collect();
+        Ok((entries, proof))
+    }
+
+    /// Apply a batch of updates on top of `version - 1` and commit it as `version`.
+    ///
+    /// `None` values delete the key. Version 0 starts from an empty tree.
+    /// Returns the new root hash and the nodes to persist; nothing is written
+    /// to storage by this call.
+    pub fn put_value_set(
+        &self,
+        value_set: Vec<(HashValue, Option<Vec<u8>>)>,
+        version: Version,
+    ) -> MerkleResult<(HashValue, TreeUpdateBatch)> {
+        // Later updates to the same key win
+        let updates: BTreeMap<HashValue, Option<Vec<u8>>> = value_set.into_iter().collect();
+        let updates: Vec<(HashValue, Option<Vec<u8>>)> = updates.into_iter().collect();
+
+        let base_root = version.checked_sub(1).map(NodeKey::new_empty_path);
+
+        let mut batch = TreeUpdateBatch::default();
+        let root_key = NodeKey::new_empty_path(version);
+        let subtree = self.update_subtree(base_root, root_key, &updates, version, &mut batch)?;
+
+        // Every version gets its own root node so it can be looked up directly
+        let root_node = match subtree {
+            Subtree::Empty => TreeNode::Null,
+            Subtree::Leaf { leaf, node_key } => {
+                if let Some(node_key) = node_key {
+                    mark_stale(&mut batch, node_key, version);
+                }
+                TreeNode::Leaf(leaf)
+            }
+            Subtree::Internal { version: v, .. } if v == version => {
+                let root_hash = batch.node_batch[&root_key].hash_with::<H>();
+                return Ok((root_hash, batch));
+            }
+            Subtree::Internal { version: v, .. } => {
+                let base_key = root_key.with_version(v);
+                mark_stale(&mut batch, base_key, version);
+                self.reader.get_node(&base_key)?
+            }
+        };
+        let root_hash = root_node.hash_with::<H>();
+        batch.node_batch.insert(root_key, root_node);
+
+        Ok((root_hash, batch))
+    }
+
+    /// Update the subtree at `position` whose current node (if any) is `existing`.
+    fn update_subtree(
+        &self,
+        existing: Option<NodeKey>,
+        position: NodeKey,
+        updates: &[(HashValue, Option<Vec<u8>>)],
+        version: Version,
+        batch: &mut TreeUpdateBatch,
+    ) -> MerkleResult<Subtree> {
+        let existing_node = match existing {
+            Some(node_key) => self.reader.get_node(&node_key)?,
+            None => TreeNode::Null,
+        };
+
+        match existing_node {
+            TreeNode::Null => {
+                if let Some(node_key) = existing {
+                    mark_stale(batch, node_key, version);
+                }
+                let leaves = updates
+                    .iter()
+                    .filter_map(|(k, v)| v.as_ref().map(|v| LeafNode::new_with::<H>(*k, v.clone())))
+                    .collect();
+                self.build_subtree(position, leaves, version, batch)
+            }
+            TreeNode::Leaf(existing_leaf) => {
+                let overridden = updates.iter().any(|(k, _)| k == &existing_leaf.key);
+                let mut leaves: Vec<LeafNode> = updates
+                    .iter()
+                    .filter_map(|(k, v)| v.as_ref().map(|v| LeafNode::new_with::<H>(*k, v.clone())))
+                    .collect();
+                if !overridden {
+                    leaves.push(existing_leaf.clone());
+                    leaves.sort_by_key(|leaf| leaf.key);
+                }
+
+                // Untouched leaf that stays alone: keep the persisted copy
+                if leaves.len() == 1 && leaves[0] == existing_leaf {
+                    return Ok(Subtree::Leaf {
+                        leaf: existing_leaf,
+                        node_key: existing,
+                    });
+                }
+                if let Some(node_key) = existing {
+                    mark_stale(batch, node_key, version);
+                }
+                self.build_subtree(position, leaves, version, batch)
+            }
+            TreeNode::Internal(internal) => {
+                let node_key = existing.expect("internal node has a key");
+                if updates.is_empty() {
+                    return Ok(Subtree::Internal {
+                        hash: internal.hash_with::<H>(),
+                        version: node_key.version(),
+                    });
+                }
+                mark_stale(batch, node_key, version);
+
+                let depth = position.depth();
+                let mid = updates.partition_point(|(k, _)| !k.bit(depth));
+                let (left_updates, right_updates) = updates.split_at(mid);
+
+                let left = self.update_subtree(
+                    internal.left.map(|c| node_key.gen_child_node_key(c.version, false)),
+                    position.gen_child_node_key(version, false),
+                    left_updates,
+                    version,
+                    batch,
+                )?;
+                let right = self.update_subtree(
+                    internal.right.map(|c| node_key.gen_child_node_key(c.version, true)),
+                    position.gen_child_node_key(version, true),
+                    right_updates,
+                    version,
+                    batch,
+                )?;
+
+                Ok(Self::combine(position, left, right, version, batch))
+            }
+        }
+    }
+
+    /// Build a fresh subtree at `position` from leaves sorted by key.
+    fn build_subtree(
+        &self,
+        position: NodeKey,
+        mut leaves: Vec<LeafNode>,
+        version: Version,
+        batch: &mut TreeUpdateBatch,
+    ) -> MerkleResult<Subtree> {
+        match leaves.len() {
+            0 => Ok(Subtree::Empty),
+            1 => Ok(Subtree::Leaf {
+                leaf: leaves.remove(0),
+                node_key: None,
+            }),
+            _ => {
+                let depth = position.depth();
+                let mid = leaves.partition_point(|leaf| !leaf.key.bit(depth));
+                let right_leaves = leaves.split_off(mid);
+
+                let left = self.build_subtree(
+                    position.gen_child_node_key(version, false),
+                    leaves,
+                    version,
+                    batch,
+                )?;
+                let right = self.build_subtree(
+                    position.gen_child_node_key(version, true),
+                    right_leaves,
+                    version,
+                    batch,
+                )?;
+
+                Ok(Self::combine(position, left, right, version, batch))
+            }
+        }
+    }
+
+    /// Join two updated children into the subtree at `position`.
+    fn combine(
+        position: NodeKey,
+        left: Subtree,
+        right: Subtree,
+        version: Version,
+        batch: &mut TreeUpdateBatch,
+    ) -> Subtree {
+        match (left, right) {
+            (Subtree::Empty, Subtree::Empty) => Subtree::Empty,
+            (leaf @ Subtree::Leaf { .. }, Subtree::Empty)
+            | (Subtree::Empty, leaf @ Subtree::Leaf { .. }) => leaf,
+            (left, right) => {
+                let internal = InternalNode {
+                    left: Self::place_child(position, left, false, version, batch),
+                    right: Self::place_child(position, right, true, version, batch),
+                };
+                let hash = internal.hash_with::<H>();
+                batch.node_batch.insert(position, TreeNode::Internal(internal));
+                Subtree::Internal { hash, version }
+            }
+        }
+    }
+
+    /// Turn an updated child into a reference, writing the leaf if it moved.
+    fn place_child(
+        parent: NodeKey,
+        subtree: Subtree,
+        right: bool,
+        version: Version,
+        batch: &mut TreeUpdateBatch,
+    ) -> Option<Child> {
+        match subtree {
+            Subtree::Empty => None,
+            Subtree::Internal { hash, version } => Some(Child {
+                hash,
+                version,
+                is_leaf: false,
+            }),
+            Subtree::Leaf { leaf, node_key } => {
+                let position = parent.gen_child_node_key(version, right);
+                let hash = leaf.hash_with::<H>();
+                let leaf_version = match node_key {
+                    Some(existing) if existing.same_position(&position) => existing.version(),
+                    _ => {
+                        // The leaf moved, so any persisted copy is superseded
+                        if let Some(existing) = node_key {
+                            mark_stale(batch, existing, version);
+                        }
+                        batch.node_batch.insert(position, TreeNode::Leaf(leaf));
+                        version
+                    }
+                };
+                Some(Child {
+                    hash,
+                    version: leaf_version,
+                    is_leaf: true,
+                })
+            }
+        }
+    }
+}
+
+impl<R: TreeReader, H: MerkleHasher> ProofSource for PersistentSparseMerkleTree<'_, R, H> {
+    type Hasher = H;
+    /// `None` for an empty child
+    type NodeRef = Option<NodeKey>;
+
+    fn node(&self, node_key: &Option<NodeKey>) -> MerkleResult<ProofNode<Option<NodeKey>>> {
+        let Some(node_key) = node_key else {
+            return Ok(ProofNode::Empty);
+        };
+        Ok(match self.reader.get_node(node_key)? {
+            TreeNode::Null => ProofNode::Empty,
+            TreeNode::Leaf(leaf) => ProofNode::Leaf(leaf),
+            TreeNode::Internal(internal) => {
+                let child = |right: bool| {
+                    let hash = internal.child(right).map(|c| c.hash).unwrap_or_else(empty_hash::<H>);
+                    let key = internal
+                        .child(right)
+                        .map(|c| node_key.gen_child_node_key(c.version, right));
+                    (hash, key)
+                };
+                ProofNode::Internal {
+                    left: child(false),
+                    right: child(true),
+                }
+            }
+        })
+    }
+}
+
+/// Record that `node_key` is superseded as of `version`.
+fn mark_stale(batch: &mut TreeUpdateBatch, node_key: NodeKey, version: Version) {
+    batch.stale_node_index_batch.push(StaleNodeIndex {
+        stale_since_version: version,
+        node_key,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sparse::SparseMerkleTree;
+    use crate::storage::{MemoryTreeStore, TreeWriter};
+
+    fn test_key(byte: u8) -> HashValue {
+        HashValue::new([byte; 32])
+    }
+
+    fn commit(
+        store: &MemoryTreeStore,
+        value_set: Vec<(HashValue, Option<Vec<u8>>)>,
+        version: Version,
+    ) -> (HashValue, TreeUpdateBatch) {
+        let tree = PersistentSparseMerkleTree::new(store);
+        let (root, batch) = tree.put_value_set(value_set, version).unwrap();
+        store.write_tree_update_batch(&batch).unwrap();
+        (root, batch)
+    }
+
+    #[test]
+    fn test_empty_version() {
+        let store = MemoryTreeStore::new();
+        let (root, _) = commit(&store, vec![], 0);
+
+        assert_eq!(root, SparseMerkleTree::new().root());
+        let tree = PersistentSparseMerkleTree::new(&store);
+        assert_eq!(tree.get(&test_key(1), 0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_matches_in_memory_root() {
+        let store = MemoryTreeStore::new();
+        let mut memory = SparseMerkleTree::new();
+
+        for version in 0..5u8 {
+            let entries: Vec<_> = (0..8u8)
+                .map(|i| (test_key(version * 8 + i), vec![version, i]))
+                .collect();
+            memory.batch_insert(entries.clone());
+            let (root, _) = commit(
+                &store,
+                entries.into_iter().map(|(k, v)| (k, Some(v))).collect(),
+                version as Version,
+            );
+            assert_eq!(root, memory.root());
+        }
+    }
+
+    #[test]
+    fn test_historical_reads() {
+        let store = MemoryTreeStore::new();
+        let key = test_key(7);
+        let (root0, _) = commit(&store, vec![(key, Some(b"old".to_vec()))], 0);
+        let (root1, _) = commit(&store, vec![(key, Some(b"new".to_vec()))], 1);
+        let (root2, _) = commit(&store, vec![(key, None)], 2);
+
+        let tree = PersistentSparseMerkleTree::new(&store);
+        assert_eq!(tree.get_root_hash(0).unwrap(), root0);
+        assert_eq!(tree.get_root_hash(1).unwrap(), root1);
+        assert_eq!(tree.get_root_hash(2).unwrap(), root2);
+        assert_eq!(tree.get(&key, 0).unwrap(), Some(b"old".to_vec()));
+        assert_eq!(tree.get(&key, 1).unwrap(), Some(b"new".to_vec()));
+        assert_eq!(tree.get(&key, 2).unwrap(), None);
+        assert_eq!(root2, empty_hash::<StorageHasher>());
+    }
+
+    #[test]
+    fn test_proofs() {
+        let store = MemoryTreeStore::new();
+        let entries: Vec<_> = (0..16u8)
+            .map(|i| (test_key(i * 16), Some(vec![i])))
+            .collect();
+        let (root, _) = commit(&store, entries, 0);
+        let tree = PersistentSparseMerkleTree::new(&store);
+
+        for i in 0..16u8 {
+            let (value, proof) = tree.get_with_proof(&test_key(i * 16), 0).unwrap();
+            assert_eq!(value, Some(vec![i]));
+            assert!(proof.verify_inclusion(&root, &test_key(i * 16), &[i]).is_ok());
+        }
+
+        let absent = test_key(3);
+        let (value, proof) = tree.get_with_proof(&absent, 0).unwrap();
+        assert_eq!(value, None);
+        assert!(proof.verify_non_inclusion(&root, &absent).is_ok());
+    }
+
+    #[test]
+    fn test_custom_hasher() {
+        use crate::hasher::Blake3Hasher;
+
+        let store = MemoryTreeStore::new();
+        let mut memory = SparseMerkleTree::with_hasher(Blake3Hasher);
+        let mut sha256 = SparseMerkleTree::new();
+        let entries: Vec<_> = (0..16u8).map(|i| (test_key(i * 16), vec![i])).collect();
+        memory.batch_insert(entries.clone());
+        sha256.batch_insert(entries.clone());
+
+        let tree = PersistentSparseMerkleTree::with_hasher(&store, Blake3Hasher);
+        let (root, batch) = tree
+            .put_value_set(entries.into_iter().map(|(k, v)| (k, Some(v))).collect(), 0)
+            .unwrap();
+        store.write_tree_update_batch(&batch).unwrap();
+        assert_eq!(root, memory.root());
+        assert_ne!(root, sha256.root());
+
+        let (value, proof) = tree.get_with_proof(&test_key(32), 0).unwrap();
+        assert_eq!(value, Some(vec![2]));
+        assert!(proof.verify_inclusion(&root, &test_key(32), &[2]).is_ok());
+    }
+
+    #[test]
+    fn test_writes_only_changed_nodes() {
+        let store = MemoryTreeStore::new();
+        let entries: Vec<_> = (0..=255u8).map(|i| (test_key(i), Some(vec![i]))).collect();
+        commit(&store, entries, 0);
+
+        // Updating one key rewrites its path (at most 8 internal nodes + leaf) and the root
+        let (_, batch) = commit(&store, vec![(test_key(42), Some(b"x".to_vec()))], 1);
+        assert!(batch.node_batch.len() <= 9);
+
+        // An empty batch only re-roots the previous version
+        let (root2, batch) = commit(&store, vec![], 2);
+        assert_eq!(batch.node_batch.len(), 1);
+        let tree = PersistentSparseMerkleTree::new(&store);
+        assert_eq!(root2, tree.get_root_hash(1).unwrap());
+    }
+
+    #[test]
+    fn test_stale_nodes_are_exactly_replaced_nodes() {
+        let store = MemoryTreeStore::new();
+        commit(&store, (0..64u8).map(|i| (test_key(i * 4), Some(vec![i]))).collect(), 0);
+
+        let updates = vec![
+            (test_key(8), Some(b"x".to_vec())),
+            (test_key(9), Some(b"new".to_vec())),
+            (test_key(12), None),
+        ];
+        let (_, batch) = commit(&store, updates, 1);
+
+        // Every stale node existed before, and nothing reachable at version 1 is stale
+        let mut reachable = Vec::new();
+        let mut pending = vec![NodeKey::new_empty_path(1)];
+        while let Some(node_key) = pending.pop() {
+            reachable.push(node_key);
+            if let TreeNode::Internal(internal) = store.get_node(&node_key).unwrap() {
+                for right in [false, true] {
+                    if let Some(child) = internal.child(right) {
+                        pending.push(node_key.gen_child_node_key(child.version, right));
+                    }
+                }
+            }
+        }
+        for index in &batch.stale_node_index_batch {
+            assert_eq!(index.stale_since_version, 1);
+            assert_eq!(index.node_key.version(), 0);
+            assert!(!reachable.contains(&index.node_key));
+        }
+        assert!(batch
+            .stale_node_index_batch
+            .iter()
+            .any(|index| index.node_key == NodeKey::new_empty_path(0)));
+    }
+
+    #[test]
+    fn test_delete_collapses_to_leaf() {
+        let store = MemoryTreeStore::new();
+        let mut memory = SparseMerkleTree::new();
+        for i in 0..4u8 {
+            memory.insert(test_key(i), vec![i]);
+        }
+        commit(
+            &store,
+            (0..4u8).map(|i| (test_key(i), Some(vec![i]))).collect(),
+            0,
+        );
+
+        memory.remove(&test_key(1));
+        memory.remove(&test_key(2));
+        memory.remove(&test_key(3));
+        let (root, _) = commit(
+            &store,
+            vec![(test_key(1), None), (test_key(2), None), (test_key(3), None)],
+            1,
+        );
+
+        assert_eq!(root, memory.root());
+        let tree = PersistentSparseMerkleTree::new(&store);
+        assert_eq!(tree.get(&test_key(0), 1).unwrap(), Some(vec![0]));
+    }
+}
@@ -8,6 +8,8 @@
 //! - 256-bit key space (matches ObjectId and Address)
 //! - Efficient empty subtree handling (lazy evaluation)
 //! - Non-inclusion proofs
+//! - Multi-key and range proofs (see [`crate::multiproof`])
+//! - Pluggable hash function (see [`crate::hasher`])
 //! - Version/snapshot support for state history
 //!
 //! # Design
@@ -32,30 +34,25 @@
 //! assert_eq!(tree.get(&key), Some(&b"value".to_vec()));
 //! ```
 
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::marker::PhantomData;
 
 use crate::error::{MerkleError, MerkleResult};
 use crate::hash::{prefix, HashValue};
+use crate::hasher::{MerkleHasher, Sha256Hasher};
+use crate::multiproof::{
+    ProofNode, ProofSource, RangeEntries, SparseMerkleMultiProof, SparseMerkleRangeProof,
+};
+use crate::storage::LeafNode;
 use crate::HASH_LENGTH;
 
 /// Placeholder hash for empty subtrees.
-/// This is the hash of an empty node, computed as SHA256("SPARSE_EMPTY").
-fn empty_hash() -> HashValue {
-    lazy_static::initialize(&EMPTY_HASH);
-    *EMPTY_HASH
-}
-
-lazy_static::lazy_static! {
-    static ref EMPTY_HASH: HashValue = {
-        let mut hasher = Sha256::new();
-        hasher.update(b"SPARSE_EMPTY");
-        let result = hasher.finalize();
-        let mut bytes = [0u8; HASH_LENGTH];
-        bytes.copy_from_slice(&result);
-        HashValue::new(bytes)
-    };
+/// This is the hash of an empty node, computed as H("SPARSE_EMPTY").
+pub(crate) fn empty_hash<H: MerkleHasher>() -> HashValue {
+    H::sparse_empty_hash()
 }
 
 /// A node in the sparse Merkle tree.
@@ -76,41 +73,31 @@ pub enum SparseMerkleNode {
 }
 
 impl SparseMerkleNode {
-    /// Compute the hash of this node
+    /// Compute the hash of this node with the default hasher
     pub fn hash(&self) -> HashValue {
+        self.hash_with::<Sha256Hasher>()
+    }
+
+    /// Compute the hash of this node with `H`
+    pub fn hash_with<H: MerkleHasher>(&self) -> HashValue {
         match self {
-            SparseMerkleNode::Empty => empty_hash(),
-            SparseMerkleNode::Leaf { key, value_hash } => {
-                let mut hasher = Sha256::new();
-                hasher.update(prefix::SPARSE_LEAF);
-                hasher.update(key.as_bytes());
-                hasher.update(value_hash.as_bytes());
-                let result = hasher.finalize();
-                let mut bytes = [0u8; HASH_LENGTH];
-                bytes.copy_from_slice(&result);
-                HashValue::new(bytes)
-            }
-            SparseMerkleNode::Internal { left, right } => {
-                let mut hasher = Sha256::new();
-                hasher.update(prefix::SPARSE_INTERNAL);
-                hasher.update(left.as_bytes());
-                hasher.update(right.as_bytes());
-                let result = hasher.finalize();
-                let mut bytes = [0u8; HASH_LENGTH];
-                bytes.copy_from_slice(&result);
-                HashValue::new(bytes)
-            }
+            SparseMerkleNode::Empty => empty_hash::<H>(),
+            SparseMerkleNode::Leaf { key, value_hash } => hash_leaf::<H>(key, value_hash),
+            SparseMerkleNode::Internal { left, right } => hash_internal::<H>(left, right),
         }
     }
 }
 
 /// A proof of inclusion or non-inclusion in the sparse Merkle tree.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub struct SparseMerkleProof {
+#[serde(bound = "")]
+pub struct SparseMerkleProof<H = Sha256Hasher> {
     /// The sibling hashes from leaf to root (bottom-up)
     siblings: Vec<HashValue>,
     /// The leaf node at the end of the path (if any)
     leaf: Option<SparseMerkleLeafNode>,
+    #[serde(skip)]
+    hasher: PhantomData<H>,
 }
 
 /// A leaf node for inclusion in proofs
@@ -121,23 +108,25 @@ pub struct SparseMerkleLeafNode {
 }
 
 impl SparseMerkleLeafNode {
-    /// Compute the hash of this leaf
+    /// Compute the hash of this leaf with the default hasher
     pub fn hash(&self) -> HashValue {
-        let mut hasher = Sha256::new();
-        hasher.update(prefix::SPARSE_LEAF);
-        hasher.update(self.key.as_bytes());
-        hasher.update(self.value_hash.as_bytes());
-        let result = hasher.finalize();
-        let mut bytes = [0u8; HASH_LENGTH];
-        bytes.copy_from_slice(&result);
-        HashValue::new(bytes)
+        self.hash_with::<Sha256Hasher>()
+    }
+
+    /// Compute the hash of this leaf with `H`
+    pub fn hash_with<H: MerkleHasher>(&self) -> HashValue {
+        hash_leaf::<H>(&self.key, &self.value_hash)
     }
 }
 
-impl SparseMerkleProof {
+impl<H: MerkleHasher> SparseMerkleProof<H> {
     /// Create a new proof
     pub fn new(siblings: Vec<HashValue>, leaf: Option<SparseMerkleLeafNode>) -> Self {
-        Self { siblings, leaf }
+        Self {
+            siblings,
+            leaf,
+            hasher: PhantomData,
+        }
     }
 
     /// Get the depth of this proof
@@ -162,7 +151,7 @@ impl SparseMerkleProof {
         key: &HashValue,
         value: &[u8],
     ) -> MerkleResult<()> {
-        let value_hash = hash_value(value);
+        let value_hash = hash_value::<H>(value);
         
         // Must have a leaf that matches
         let leaf = self.leaf.as_ref().ok_or_else(|| {
@@ -183,7 +172,7 @@ impl SparseMerkleProof {
         }
 
         // Compute root from proof
-        let computed_root = self.compute_root_from_leaf(key, &leaf.hash())?;
+        let computed_root = self.compute_root_from_leaf(key, &leaf.hash_with::<H>())?;
         
         if &computed_root == root {
             Ok(())
@@ -209,8 +198,8 @@ impl SparseMerkleProof {
         let (leaf_hash, computed_root) = match &self.leaf {
             None => {
                 // Empty subtree case
-                let computed = self.compute_root_from_leaf(key, &empty_hash())?;
-                (empty_hash(), computed)
+                let computed = self.compute_root_from_leaf(key, &empty_hash::<H>())?;
+                (empty_hash::<H>(), computed)
             }
             Some(leaf) => {
                 // There's a different leaf at this position
@@ -228,8 +217,9 @@ impl SparseMerkleProof {
                     ));
                 }
                 
-                let computed = self.compute_root_from_leaf(&leaf.key, &leaf.hash())?;
-                (leaf.hash(), computed)
+                let leaf_hash = leaf.hash_with::<H>();
+                let computed = self.compute_root_from_leaf(&leaf.key, &leaf_hash)?;
+                (leaf_hash, computed)
             }
         };
 
@@ -245,20 +235,28 @@ impl SparseMerkleProof {
 
     /// Compute root hash from a leaf hash traversing up the path
     fn compute_root_from_leaf(&self, key: &HashValue, leaf_hash: &HashValue) -> MerkleResult<HashValue> {
+        let depth = self.siblings.len();
+        if depth > HASH_LENGTH * 8 {
+            return Err(MerkleError::InvalidProof(format!(
+                "Proof depth {} exceeds key length",
+                depth
+            )));
+        }
+
         let mut current = *leaf_hash;
         
         // Traverse from bottom to top
         for (i, sibling) in self.siblings.iter().enumerate() {
-            // Bit index from the bottom (reverse order)
-            let bit_index = 255 - i;
+            // Siblings are bottom-up, so the i-th one sits at depth `depth - 1 - i`
+            let bit_index = depth - 1 - i;
             let bit = key.bit(bit_index);
             
             current = if bit {
                 // Current node is right child
-                hash_internal(sibling, &current)
+                hash_internal::<H>(sibling, &current)
             } else {
                 // Current node is left child
-                hash_internal(&current, sibling)
+                hash_internal::<H>(&current, sibling)
             };
         }
         
@@ -267,25 +265,18 @@ impl SparseMerkleProof {
 }
 
 /// Hash a value for storage in the tree
-fn hash_value(value: &[u8]) -> HashValue {
-    let mut hasher = Sha256::new();
-    hasher.update(value);
-    let result = hasher.finalize();
-    let mut bytes = [0u8; HASH_LENGTH];
-    bytes.copy_from_slice(&result);
-    HashValue::new(bytes)
+pub(crate) fn hash_value<H: MerkleHasher>(value: &[u8]) -> HashValue {
+    H::hash(value)
+}
+
+/// Hash a key and its value hash to create a leaf node hash
+fn hash_leaf<H: MerkleHasher>(key: &HashValue, value_hash: &HashValue) -> HashValue {
+    H::hash_parts(&[prefix::SPARSE_LEAF, key.as_bytes(), value_hash.as_bytes()])
 }
 
 /// Hash two children to create internal node hash
-fn hash_internal(left: &HashValue, right: &HashValue) -> HashValue {
-    let mut hasher = Sha256::new();
-    hasher.update(prefix::SPARSE_INTERNAL);
-    hasher.update(left.as_bytes());
-    hasher.update(right.as_bytes());
-    let result = hasher.finalize();
-    let mut bytes = [0u8; HASH_LENGTH];
-    bytes.copy_from_slice(&result);
-    HashValue::new(bytes)
+pub(crate) fn hash_internal<H: MerkleHasher>(left: &HashValue, right: &HashValue) -> HashValue {
+    H::hash_parts(&[prefix::SPARSE_INTERNAL, left.as_bytes(), right.as_bytes()])
 }
 
 /// A sparse Merkle tree for key-value storage.
@@ -293,29 +284,43 @@ fn hash_internal(left: &HashValue, right: &HashValue) -> HashValue {
 /// Keys are 256-bit hashes, values are arbitrary bytes.
 /// The tree efficiently handles sparse data by not storing empty subtrees.
 #[derive(Clone, Debug)]
-pub struct SparseMerkleTree {
+pub struct SparseMerkleTree<H = Sha256Hasher> {
     /// The root hash of the tree
     root_hash: HashValue,
-    /// Key-value store (simplified in-memory implementation)
-    /// In production, this would be backed by a database
+    /// Key-value store (in-memory; see `PersistentSparseMerkleTree` for
+    /// the storage-backed variant)
     leaves: HashMap<HashValue, Vec<u8>>,
-    /// Cached internal node hashes
-    nodes: HashMap<HashValue, SparseMerkleNode>,
+    /// Leaf and internal nodes, keyed by hash
+    nodes: HashMap<HashValue, SparseMerkleNode, BuildNodeHasher>,
+    hasher: PhantomData<H>,
 }
 
-impl Default for SparseMerkleTree {
+impl<H: MerkleHasher> Default for SparseMerkleTree<H> {
     fn default() -> Self {
-        Self::new()
+        Self::with_hasher(H::default())
     }
 }
 
 impl SparseMerkleTree {
     /// Create a new empty sparse Merkle tree.
     pub fn new() -> Self {
+        Self::with_hasher(Sha256Hasher)
+    }
+
+    /// Restore from a snapshot.
+    pub fn restore(snapshot: SparseMerkleTreeSnapshot) -> Self {
+        Self::restore_with_hasher(snapshot, Sha256Hasher)
+    }
+}
+
+impl<H: MerkleHasher> SparseMerkleTree<H> {
+    /// Create a new empty sparse Merkle tree using the given hasher.
+    pub fn with_hasher(_hasher: H) -> Self {
         Self {
-            root_hash: empty_hash(),
+            root_hash: empty_hash::<H>(),
             leaves: HashMap::new(),
-            nodes: HashMap::new(),
+            nodes: HashMap::default(),
+            hasher: PhantomData,
         }
     }
 
@@ -348,8 +353,9 @@ impl SparseMerkleTree {
     ///
     /// Returns the old value if the key already existed.
     pub fn insert(&mut self, key: HashValue, value: Vec<u8>) -> Option<Vec<u8>> {
+        let value_hash = hash_value::<H>(&value);
         let old_value = self.leaves.insert(key, value);
-        self.rebuild_tree();
+        self.apply_updates(&[(key, Some(value_hash))]);
         old_value
     }
 
@@ -359,7 +365,7 @@ impl SparseMerkleTree {
     pub fn remove(&mut self, key: &HashValue) -> Option<Vec<u8>> {
         let old_value = self.leaves.remove(key);
         if old_value.is_some() {
-            self.rebuild_tree();
+            self.apply_updates(&[(*key, None)]);
         }
         old_value
     }
@@ -368,122 +374,177 @@ impl SparseMerkleTree {
     ///
     /// More efficient than individual inserts.
     pub fn batch_insert(&mut self, entries: Vec<(HashValue, Vec<u8>)>) {
-        for (key, value) in entries {
-            self.leaves.insert(key, value);
-        }
-        self.rebuild_tree();
+        self.batch_update(entries.into_iter().map(|(k, v)| (k, Some(v))).collect());
     }
 
-    /// Get a proof for a key (inclusion or non-inclusion).
-    pub fn get_proof(&self, key: &HashValue) -> SparseMerkleProof {
-        if self.leaves.is_empty() {
-            return SparseMerkleProof::new(vec![], None);
+    /// Apply a batch of inserts (`Some`) and removals (`None`).
+    ///
+    /// Updates are sorted and applied bottom-up in a single pass, so every
+    /// internal node on a changed path is hashed once per batch. Disjoint
+    /// subtrees of large batches are updated in parallel.
+    pub fn batch_update(&mut self, entries: Vec<(HashValue, Option<Vec<u8>>)>) {
+        let mut updates: Vec<(HashValue, Option<HashValue>)> = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            updates.push((key, value.as_deref().map(hash_value::<H>)));
+            match value {
+                Some(value) => self.leaves.insert(key, value),
+                None => self.leaves.remove(&key),
+            };
         }
 
-        // Build proof by traversing from root to leaf position
-        let siblings = Vec::new();
-        let mut current_leaves: Vec<_> = self.leaves.iter()
-            .map(|(k, v)| (*k, hash_value(v)))
-            .collect();
+        // Stable sort, then keep the last entry for each key
+        updates.par_sort_by_key(|(k, _)| *k);
+        updates.dedup_by(|later, earlier| {
+            let same_key = later.0 == earlier.0;
+            if same_key {
+                *earlier = *later;
+            }
+            same_key
+        });
+        self.apply_updates(&updates);
+    }
 
-        // Sort by key for consistent ordering
-        current_leaves.sort_by_key(|(k, _)| *k);
-
-        // Find the leaf at this position (if any)
-        let target_leaf = current_leaves.iter()
-            .find(|(k, _)| k == key)
-            .map(|(k, vh)| SparseMerkleLeafNode {
-                key: *k,
-                value_hash: *vh,
-            });
-
-        // If key not found, find the leaf that would be a neighbor
-        let neighbor_leaf = if target_leaf.is_none() {
-            current_leaves.iter()
-                .filter(|(k, _)| {
-                    k.common_prefix_bits(key) > 0 || current_leaves.len() == 1
-                })
-                .max_by_key(|(k, _)| k.common_prefix_bits(key))
-                .map(|(k, vh)| SparseMerkleLeafNode {
-                    key: *k,
-                    value_hash: *vh,
-                })
-        } else {
-            None
+    /// Get a proof for a key (inclusion or non-inclusion).
+    pub fn get_proof(&self, key: &HashValue) -> SparseMerkleProof<H> {
+        // Walk down the key's path, collecting the hash of the other side at each level
+        let mut siblings = Vec::new();
+        let mut current = self.root_hash;
+        let mut depth = 0;
+        let proof_leaf = loop {
+            match self.nodes.get(&current) {
+                Some(SparseMerkleNode::Internal { left, right }) => {
+                    let (next, other) = if key.bit(depth) { (right, left) } else { (left, right) };
+                    siblings.push(*other);
+                    current = *next;
+                    depth += 1;
+                }
+                // Whatever leaf ends up alone on the path is either the key itself
+                // or the neighbor that proves its absence
+                Some(SparseMerkleNode::Leaf { key, value_hash }) => {
+                    break Some(SparseMerkleLeafNode {
+                        key: *key,
+                        value_hash: *value_hash,
+                    });
+                }
+                Some(SparseMerkleNode::Empty) | None => break None,
+            }
         };
 
-        let proof_leaf = target_leaf.or(neighbor_leaf);
+        siblings.reverse();
+        SparseMerkleProof::new(siblings, proof_leaf)
+    }
 
-        // Build siblings (simplified - in production would traverse actual tree)
-        // For a proper implementation, we'd need to store the tree structure
-        // Here we compute the necessary siblings on-the-fly
+    /// Get a single proof for several keys, sharing siblings between them.
+    pub fn get_multi_proof(&self, keys: &[HashValue]) -> MerkleResult<SparseMerkleMultiProof<H>> {
+        Ok(SparseMerkleMultiProof::generate(self, self.root_hash, keys)?.0)
+    }
 
-        SparseMerkleProof::new(siblings, proof_leaf)
+    /// Get all entries with keys in `[start, end]` and a proof that the list is complete.
+    pub fn get_range_proof(
+        &self,
+        start: &HashValue,
+        end: &HashValue,
+    ) -> MerkleResult<(RangeEntries, SparseMerkleRangeProof<H>)> {
+        let (proof, leaves) = SparseMerkleRangeProof::generate(self, self.root_hash, start, end)?;
+        let entries = leaves.into_iter().map(|leaf| (leaf.key, leaf.value)).collect();
+        Ok((entries, proof))
     }
 
-    /// Rebuild the tree from leaves (simplified implementation).
-    ///
-    /// In production, this would be an incremental update.
+    /// Rebuild the tree from leaves.
     fn rebuild_tree(&mut self) {
         self.nodes.clear();
+        self.root_hash = empty_hash::<H>();
 
-        if self.leaves.is_empty() {
-            self.root_hash = empty_hash();
-            return;
-        }
-
-        // Collect all leaf hashes with their keys
-        let mut leaf_hashes: Vec<(HashValue, HashValue)> = self.leaves
-            .iter()
-            .map(|(k, v)| {
-                let value_hash = hash_value(v);
-                let leaf_node = SparseMerkleNode::Leaf {
-                    key: *k,
-                    value_hash,
-                };
-                (*k, leaf_node.hash())
-            })
+        let mut updates: Vec<(HashValue, Option<HashValue>)> = self.leaves
+            .par_iter()
+            .map(|(k, v)| (*k, Some(hash_value::<H>(v))))
             .collect();
-
-        // Sort by key for deterministic ordering
-        leaf_hashes.sort_by_key(|(k, _)| *k);
-
-        // Build tree bottom-up
-        self.root_hash = self.build_subtree(&leaf_hashes, 0);
+        updates.par_sort_unstable_by_key(|(k, _)| *k);
+        self.apply_updates(&updates);
     }
 
-    /// Recursively build a subtree from sorted leaves.
-    fn build_subtree(&mut self, leaves: &[(HashValue, HashValue)], depth: usize) -> HashValue {
-        if leaves.is_empty() {
-            return empty_hash();
-        }
+    /// Apply sorted, de-duplicated `(key, value_hash)` updates to the node set.
+    fn apply_updates(&mut self, updates: &[(HashValue, Option<HashValue>)]) {
+        let mut changes = NodeChanges::default();
+        let root = self.update_subtree(self.root_hash, 0, updates, &mut changes);
 
-        if leaves.len() == 1 {
-            return leaves[0].1;
+        // Removed nodes are never shared with added ones: every non-empty
+        // subtree hash commits to the full keys below it, hence to its position
+        for hash in changes.removed {
+            self.nodes.remove(&hash);
         }
+        self.nodes.reserve(changes.added.len());
+        self.nodes.extend(changes.added);
+        self.root_hash = root.hash::<H>();
+    }
 
-        if depth >= 256 {
-            // Should not happen with proper keys
-            return leaves[0].1;
+    /// Update the subtree with hash `hash` at `depth`.
+    fn update_subtree(
+        &self,
+        hash: HashValue,
+        depth: usize,
+        updates: &[(HashValue, Option<HashValue>)],
+        changes: &mut NodeChanges,
+    ) -> SubtreeHash {
+        let node = self.nodes.get(&hash);
+        if updates.is_empty() {
+            return match node {
+                Some(SparseMerkleNode::Internal { .. }) => SubtreeHash::Internal(hash),
+                Some(SparseMerkleNode::Leaf { .. }) => SubtreeHash::Leaf(hash),
+                Some(SparseMerkleNode::Empty) | None => SubtreeHash::Empty,
+            };
         }
 
-        // Partition leaves by bit at current depth
-        let (left_leaves, right_leaves): (Vec<_>, Vec<_>) = leaves
-            .iter()
-            .partition(|(k, _)| !k.bit(depth));
-
-        let left_hash = self.build_subtree(&left_leaves, depth + 1);
-        let right_hash = self.build_subtree(&right_leaves, depth + 1);
-
-        let internal = SparseMerkleNode::Internal {
-            left: left_hash,
-            right: right_hash,
-        };
-        let hash = internal.hash();
-
-        self.nodes.insert(hash, internal);
-
-        hash
+        match node {
+            Some(SparseMerkleNode::Internal { left, right }) => {
+                changes.removed.push(hash);
+                let mid = updates.partition_point(|(k, _)| !k.bit(depth));
+                let (left_updates, right_updates) = updates.split_at(mid);
+
+                let (left, right) = if updates.len() >= PARALLEL_THRESHOLD {
+                    let ((left, left_changes), (right, right_changes)) = rayon::join(
+                        || {
+                            let mut changes = NodeChanges::default();
+                            let left = self.update_subtree(*left, depth + 1, left_updates, &mut changes);
+                            (left, changes)
+                        },
+                        || {
+                            let mut changes = NodeChanges::default();
+                            let right = self.update_subtree(*right, depth + 1, right_updates, &mut changes);
+                            (right, changes)
+                        },
+                    );
+                    changes.append(left_changes);
+                    changes.append(right_changes);
+                    (left, right)
+                } else {
+                    (
+                        self.update_subtree(*left, depth + 1, left_updates, changes),
+                        self.update_subtree(*right, depth + 1, right_updates, changes),
+                    )
+                };
+                combine::<H>(left, right, changes)
+            }
+            Some(SparseMerkleNode::Leaf { key, value_hash }) => {
+                changes.removed.push(hash);
+                let mut leaves: Vec<(HashValue, HashValue)> = updates
+                    .iter()
+                    .filter_map(|(k, vh)| vh.map(|vh| (*k, vh)))
+                    .collect();
+                if updates.binary_search_by_key(key, |(k, _)| *k).is_err() {
+                    let pos = leaves.partition_point(|(k, _)| k < key);
+                    leaves.insert(pos, (*key, *value_hash));
+                }
+                build_subtree::<H>(&leaves, depth, changes)
+            }
+            Some(SparseMerkleNode::Empty) | None => {
+                let leaves: Vec<(HashValue, HashValue)> = updates
+                    .iter()
+                    .filter_map(|(k, vh)| vh.map(|vh| (*k, vh)))
+                    .collect();
+                build_subtree::<H>(&leaves, depth, changes)
+            }
+        }
     }
 
     /// Create a snapshot of the current tree state.
@@ -494,18 +555,164 @@ impl SparseMerkleTree {
         }
     }
 
-    /// Restore from a snapshot.
-    pub fn restore(snapshot: SparseMerkleTreeSnapshot) -> Self {
+    /// Restore from a snapshot taken of a tree using the given hasher.
+    pub fn restore_with_hasher(snapshot: SparseMerkleTreeSnapshot, _hasher: H) -> Self {
         let mut tree = Self {
             root_hash: snapshot.root_hash,
             leaves: snapshot.leaves,
-            nodes: HashMap::new(),
+            nodes: HashMap::default(),
+            hasher: PhantomData,
         };
         tree.rebuild_tree();
         tree
     }
 }
 
+impl<H: MerkleHasher> ProofSource for SparseMerkleTree<H> {
+    type Hasher = H;
+    type NodeRef = HashValue;
+
+    fn node(&self, hash: &HashValue) -> MerkleResult<ProofNode<HashValue>> {
+        Ok(match self.nodes.get(hash) {
+            Some(SparseMerkleNode::Internal { left, right }) => ProofNode::Internal {
+                left: (*left, *left),
+                right: (*right, *right),
+            },
+            Some(SparseMerkleNode::Leaf { key, value_hash }) => ProofNode::Leaf(LeafNode {
+                key: *key,
+                value_hash: *value_hash,
+                value: self.leaves.get(key).cloned().unwrap_or_default(),
+            }),
+            Some(SparseMerkleNode::Empty) | None => ProofNode::Empty,
+        })
+    }
+}
+
+/// Hasher for maps keyed by node hashes.
+///
+/// Node hashes are already uniformly distributed, so the first 8 bytes make a
+/// good hash without running SipHash over all 32.
+#[derive(Default)]
+struct NodeHasher(u64);
+
+impl Hasher for NodeHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8).take(1) {
+            let mut buf = [0u8; 8];
+            buf[..chunk.len()].copy_from_slice(chunk);
+            self.0 ^= u64::from_le_bytes(buf);
+        }
+    }
+
+    // The array length prefix carries no information
+    fn write_usize(&mut self, _: usize) {}
+}
+
+type BuildNodeHasher = BuildHasherDefault<NodeHasher>;
+
+/// Batches at least this large are split across threads.
+const PARALLEL_THRESHOLD: usize = 1024;
+
+/// Shape of an updated subtree, identified by its hash.
+#[derive(Clone, Copy, Debug)]
+enum SubtreeHash {
+    Empty,
+    /// A single leaf, which floats up until it has a sibling
+    Leaf(HashValue),
+    Internal(HashValue),
+}
+
+impl SubtreeHash {
+    fn hash<H: MerkleHasher>(&self) -> HashValue {
+        match self {
+            SubtreeHash::Empty => empty_hash::<H>(),
+            SubtreeHash::Leaf(hash) | SubtreeHash::Internal(hash) => *hash,
+        }
+    }
+}
+
+/// Nodes created and superseded by a batch update.
+#[derive(Default)]
+struct NodeChanges {
+    added: Vec<(HashValue, SparseMerkleNode)>,
+    removed: Vec<HashValue>,
+}
+
+impl NodeChanges {
+    fn append(&mut self, mut other: NodeChanges) {
+        self.added.append(&mut other.added);
+        self.removed.append(&mut other.removed);
+    }
+}
+
+/// Join two updated children into their parent.
+fn combine<H: MerkleHasher>(left: SubtreeHash, right: SubtreeHash, changes: &mut NodeChanges) -> SubtreeHash {
+    match (left, right) {
+        (SubtreeHash::Empty, SubtreeHash::Empty) => SubtreeHash::Empty,
+        (leaf @ SubtreeHash::Leaf(_), SubtreeHash::Empty)
+        | (SubtreeHash::Empty, leaf @ SubtreeHash::Leaf(_)) => leaf,
+        (left, right) => {
+            let node = SparseMerkleNode::Internal {
+                left: left.hash::<H>(),
+                right: right.hash::<H>(),
+            };
+            let hash = node.hash_with::<H>();
+            changes.added.push((hash, node));
+            SubtreeHash::Internal(hash)
+        }
+    }
+}
+
+/// Build a fresh subtree at `depth` from sorted `(key, value_hash)` leaves.
+fn build_subtree<H: MerkleHasher>(
+    leaves: &[(HashValue, HashValue)],
+    depth: usize,
+    changes: &mut NodeChanges,
+) -> SubtreeHash {
+    match leaves {
+        [] => SubtreeHash::Empty,
+        [(key, value_hash)] => {
+            let node = SparseMerkleNode::Leaf {
+                key: *key,
+                value_hash: *value_hash,
+            };
+            let hash = node.hash_with::<H>();
+            changes.added.push((hash, node));
+            SubtreeHash::Leaf(hash)
+        }
+        _ => {
+            let mid = leaves.partition_point(|(k, _)| !k.bit(depth));
+            let (left_leaves, right_leaves) = leaves.split_at(mid);
+
+            let (left, right) = if leaves.len() >= PARALLEL_THRESHOLD {
+                let ((left, left_changes), (right, right_changes)) = rayon::join(
+                    || {
+                        let mut changes = NodeChanges::default();
+                        (build_subtree::<H>(left_leaves, depth + 1, &mut changes), changes)
+                    },
+                    || {
+                        let mut changes = NodeChanges::default();
+                        (build_subtree::<H>(right_leaves, depth + 1, &mut changes), changes)
+                    },
+                );
+                changes.append(left_changes);
+                changes.append(right_changes);
+                (left, right)
+            } else {
+                (
+                    build_subtree::<H>(left_leaves, depth + 1, changes),
+                    build_subtree::<H>(right_leaves, depth + 1, changes),
+                )
+            };
+            combine::<H>(left, right, changes)
+        }
+    }
+}
+
 /// A snapshot of a sparse Merkle tree state.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SparseMerkleTreeSnapshot {
@@ -525,7 +732,7 @@ mod tests {
     fn test_empty_tree() {
         let tree = SparseMerkleTree::new();
         assert!(tree.is_empty());
-        assert_eq!(tree.root(), empty_hash());
+        assert_eq!(tree.root(), empty_hash::<Sha256Hasher>());
     }
 
     #[test]
@@ -539,7 +746,7 @@ mod tests {
         assert!(!tree.is_empty());
         assert_eq!(tree.len(), 1);
         assert_eq!(tree.get(&key), Some(&value));
-        assert_ne!(tree.root(), empty_hash());
+        assert_ne!(tree.root(), empty_hash::<Sha256Hasher>());
     }
 
     #[test]
@@ -588,7 +795,7 @@ mod tests {
         let removed = tree.remove(&key);
         assert_eq!(removed, Some(b"value".to_vec()));
         assert!(tree.is_empty());
-        assert_eq!(tree.root(), empty_hash());
+        assert_eq!(tree.root(), empty_hash::<Sha256Hasher>());
     }
 
     #[test]
@@ -647,6 +854,81 @@ mod tests {
         assert!(tree.contains(&key));
     }
 
+    #[test]
+    fn test_proofs_verify() {
+        let mut tree = SparseMerkleTree::new();
+        let mut key_a = [0u8; 32];
+        key_a[0] = 0b0000_0000;
+        let mut key_b = [0u8; 32];
+        key_b[0] = 0b0010_0000;
+        let mut key_c = [0u8; 32];
+        key_c[0] = 0b1000_0000;
+
+        tree.insert(HashValue::new(key_a), b"a".to_vec());
+        tree.insert(HashValue::new(key_b), b"b".to_vec());
+        tree.insert(HashValue::new(key_c), b"c".to_vec());
+        let root = tree.root();
+
+        for (key, value) in [(key_a, b"a"), (key_b, b"b"), (key_c, b"c")] {
+            let proof = tree.get_proof(&HashValue::new(key));
+            assert!(proof.verify_inclusion(&root, &HashValue::new(key), value).is_ok());
+        }
+
+        // Lands in an empty subtree (prefix 01)
+        let absent_empty = HashValue::new([0x40; 32]);
+        let proof = tree.get_proof(&absent_empty);
+        assert!(proof.verify_non_inclusion(&root, &absent_empty).is_ok());
+
+        // Lands on a neighboring leaf (prefix 10)
+        let absent_leaf = HashValue::new([0x80; 32]);
+        let proof = tree.get_proof(&absent_leaf);
+        assert!(proof.verify_non_inclusion(&root, &absent_leaf).is_ok());
+        assert!(proof.verify_inclusion(&root, &absent_leaf, b"c").is_err());
+    }
+
+    #[test]
+    fn test_large_batch_matches_sequential() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let entries: Vec<(HashValue, Vec<u8>)> = (0..5000)
+            .map(|i: u32| (HashValue::new(rng.gen()), i.to_le_bytes().to_vec()))
+            .collect();
+
+        let mut batched = SparseMerkleTree::new();
+        batched.batch_insert(entries[..2500].to_vec());
+        batched.batch_insert(entries[2500..].to_vec());
+
+        let mut sequential = SparseMerkleTree::new();
+        for (key, value) in &entries {
+            sequential.insert(*key, value.clone());
+        }
+        assert_eq!(batched.root(), sequential.root());
+
+        // Mixed updates and removals in one batch
+        let mut updates: Vec<(HashValue, Option<Vec<u8>>)> = entries[..2000]
+            .iter()
+            .map(|(k, _)| (*k, None))
+            .collect();
+        updates.extend(entries[2000..3000].iter().map(|(k, _)| (*k, Some(b"updated".to_vec()))));
+        batched.batch_update(updates);
+
+        for (key, _) in &entries[..2000] {
+            sequential.remove(key);
+        }
+        for (key, _) in &entries[2000..3000] {
+            sequential.insert(*key, b"updated".to_vec());
+        }
+        assert_eq!(batched.root(), sequential.root());
+        assert_eq!(batched.len(), 3000);
+
+        let root = batched.root();
+        let (key, _) = &entries[2500];
+        assert!(batched.get_proof(key).verify_inclusion(&root, key, b"updated").is_ok());
+        let (key, _) = &entries[10];
+        assert!(batched.get_proof(key).verify_non_inclusion(&root, key).is_ok());
+    }
+
     #[test]
     fn test_different_keys_different_roots() {
         let mut tree1 = SparseMerkleTree::new();
@@ -657,4 +939,32 @@ mod tests {
 
         assert_ne!(tree1.root(), tree2.root());
     }
+
+    #[test]
+    fn test_pluggable_hashers() {
+        use crate::hasher::{Blake3Hasher, Keccak256Hasher};
+
+        let entries: Vec<_> = (0..50u8).map(|i| (test_key(i * 5), vec![i])).collect();
+        let mut sha = SparseMerkleTree::new();
+        let mut blake = SparseMerkleTree::with_hasher(Blake3Hasher);
+        let mut keccak = SparseMerkleTree::with_hasher(Keccak256Hasher);
+        assert_eq!(blake.root(), Blake3Hasher::sparse_empty_hash());
+        sha.batch_insert(entries.clone());
+        blake.batch_insert(entries.clone());
+        keccak.batch_insert(entries.clone());
+
+        assert_ne!(sha.root(), blake.root());
+        assert_ne!(sha.root(), keccak.root());
+        assert_ne!(blake.root(), keccak.root());
+
+        let (key, value) = &entries[17];
+        let proof = keccak.get_proof(key);
+        assert!(proof.verify_inclusion(&keccak.root(), key, value).is_ok());
+        assert!(proof.verify_inclusion(&sha.root(), key, value).is_err());
+        let absent = test_key(3);
+        assert!(blake.get_proof(&absent).verify_non_inclusion(&blake.root(), &absent).is_ok());
+
+        let restored = SparseMerkleTree::restore_with_hasher(blake.snapshot(), Blake3Hasher);
+        assert_eq!(restored.root(), blake.root());
+    }
 }
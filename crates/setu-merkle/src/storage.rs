@@ -0,0 +1,399 @@
+//! Storage abstraction for the persistent sparse Merkle tree.
+//!
+//! Nodes are addressed by a [`NodeKey`], which combines the version that wrote
+//! the node with its position (bit path) in the tree. Because a node is never
+//! overwritten in place, the root of every committed version stays readable.
+//!
+//! Backends implement [`TreeReader`] and [`TreeWriter`]. [`MemoryTreeStore`] is
+//! provided for tests and light clients; `setu-storage` provides a RocksDB
+//! implementation.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::RwLock;
+
+use crate::error::{MerkleError, MerkleResult};
+use crate::hash::HashValue;
+use crate::hasher::{MerkleHasher, Sha256Hasher};
+use crate::sparse::{empty_hash, hash_internal, SparseMerkleLeafNode};
+use crate::HASH_LENGTH;
+
+/// Version number of a committed batch of tree updates.
+pub type Version = u64;
+
+/// Default hash function of persisted trees.
+///
+/// Stored nodes carry their children's hashes, so a store is only readable with
+/// the hasher that wrote it. With the default, persisted trees produce the same
+/// roots as the default [`SparseMerkleTree`](crate::sparse::SparseMerkleTree).
+pub type StorageHasher = Sha256Hasher;
+
+/// Position of a node in the tree plus the version that wrote it.
+///
+/// The path is the first `depth` bits of `path`; remaining bits are always zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NodeKey {
+    version: Version,
+    depth: u16,
+    path: HashValue,
+}
+
+impl NodeKey {
+    /// Key of the root node written at `version`.
+    pub fn new_empty_path(version: Version) -> Self {
+        Self {
+            version,
+            depth: 0,
+            path: HashValue::zero(),
+        }
+    }
+
+    /// Version that wrote this node.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Depth of this node (0 for the root).
+    pub fn depth(&self) -> usize {
+        self.depth as usize
+    }
+
+    /// The bit path from the root to this node, zero-padded.
+    pub fn path(&self) -> &HashValue {
+        &self.path
+    }
+
+    /// Key of the child on the given side, written at `version`.
+    pub fn gen_child_node_key(&self, version: Version, right: bool) -> Self {
+        let depth = self.depth();
+        assert!(depth < HASH_LENGTH * 8, "cannot descend below a full-length path");
+        let mut bytes = *self.path.as_bytes();
+        if right {
+            bytes[depth / 8] |= 0x80 >> (depth % 8);
+        }
+        Self {
+            version,
+            depth: self.depth + 1,
+            path: HashValue::new(bytes),
+        }
+    }
+
+    /// The same position at another version.
+    pub fn with_version(&self, version: Version) -> Self {
+        Self { version, ..*self }
+    }
+
+    /// Check whether two keys address the same position (ignoring version).
+    pub fn same_position(&self, other: &NodeKey) -> bool {
+        self.depth == other.depth && self.path == other.path
+    }
+}
+
+// Big-endian version first so keys sort by version in byte order
+impl bincode::Encode for NodeKey {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        bincode::Encode::encode(&self.version.to_be_bytes(), encoder)?;
+        bincode::Encode::encode(&self.depth.to_be_bytes(), encoder)?;
+        bincode::Encode::encode(self.path.as_bytes(), encoder)
+    }
+}
+
+impl<Context> bincode::Decode<Context> for NodeKey {
+    fn decode<D: bincode::de::Decoder<Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        let version: [u8; 8] = bincode::Decode::decode(decoder)?;
+        let depth: [u8; 2] = bincode::Decode::decode(decoder)?;
+        let path: [u8; HASH_LENGTH] = bincode::Decode::decode(decoder)?;
+        Ok(Self {
+            version: Version::from_be_bytes(version),
+            depth: u16::from_be_bytes(depth),
+            path: HashValue::new(path),
+        })
+    }
+}
+
+/// Records that a node is no longer part of the tree as of `stale_since_version`.
+///
+/// The node is still needed to read versions before `stale_since_version`, and
+/// may be deleted once those versions are pruned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct StaleNodeIndex {
+    pub stale_since_version: Version,
+    pub node_key: NodeKey,
+}
+
+// Ordered by the version the node went stale, so pruning can scan a prefix
+impl bincode::Encode for StaleNodeIndex {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        bincode::Encode::encode(&self.stale_since_version.to_be_bytes(), encoder)?;
+        bincode::Encode::encode(&self.node_key, encoder)
+    }
+}
+
+impl<Context> bincode::Decode<Context> for StaleNodeIndex {
+    fn decode<D: bincode::de::Decoder<Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        let stale_since_version: [u8; 8] = bincode::Decode::decode(decoder)?;
+        Ok(Self {
+            stale_since_version: Version::from_be_bytes(stale_since_version),
+            node_key: bincode::Decode::decode(decoder)?,
+        })
+    }
+}
+
+/// Reference from an internal node to one of its children.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Child {
+    /// Hash of the child subtree
+    pub hash: HashValue,
+    /// Version at which the child node was written
+    pub version: Version,
+    /// Whether the child is a leaf
+    pub is_leaf: bool,
+}
+
+/// An internal node. At least two leaves live below every internal node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InternalNode {
+    pub left: Option<Child>,
+    pub right: Option<Child>,
+}
+
+impl InternalNode {
+    /// Compute the hash of this node with the default hasher
+    pub fn hash(&self) -> HashValue {
+        self.hash_with::<StorageHasher>()
+    }
+
+    /// Compute the hash of this node with `H`
+    pub fn hash_with<H: MerkleHasher>(&self) -> HashValue {
+        let left = self.left.map(|c| c.hash).unwrap_or_else(empty_hash::<H>);
+        let right = self.right.map(|c| c.hash).unwrap_or_else(empty_hash::<H>);
+        hash_internal::<H>(&left, &right)
+    }
+
+    /// Get the child on the given side
+    pub fn child(&self, right: bool) -> Option<&Child> {
+        if right {
+            self.right.as_ref()
+        } else {
+            self.left.as_ref()
+        }
+    }
+}
+
+/// A leaf node holding a key and its value.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeafNode {
+    pub key: HashValue,
+    pub value_hash: HashValue,
+    pub value: Vec<u8>,
+}
+
+impl LeafNode {
+    /// Create a leaf for a key-value pair, hashing the value with the default hasher
+    pub fn new(key: HashValue, value: Vec<u8>) -> Self {
+        Self::new_with::<StorageHasher>(key, value)
+    }
+
+    /// Create a leaf for a key-value pair, hashing the value with `H`
+    pub fn new_with<H: MerkleHasher>(key: HashValue, value: Vec<u8>) -> Self {
+        Self {
+            key,
+            value_hash: crate::sparse::hash_value::<H>(&value),
+            value,
+        }
+    }
+
+    /// Compute the hash of this leaf with the default hasher
+    pub fn hash(&self) -> HashValue {
+        self.hash_with::<StorageHasher>()
+    }
+
+    /// Compute the hash of this leaf with `H`
+    pub fn hash_with<H: MerkleHasher>(&self) -> HashValue {
+        self.proof_leaf().hash_with::<H>()
+    }
+
+    /// The leaf as it appears in proofs
+    pub fn proof_leaf(&self) -> SparseMerkleLeafNode {
+        SparseMerkleLeafNode {
+            key: self.key,
+            value_hash: self.value_hash,
+        }
+    }
+}
+
+/// A node as persisted in storage.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TreeNode {
+    /// Root of an empty tree
+    Null,
+    Internal(InternalNode),
+    Leaf(LeafNode),
+}
+
+impl TreeNode {
+    /// Compute the hash of this node with the default hasher
+    pub fn hash(&self) -> HashValue {
+        self.hash_with::<StorageHasher>()
+    }
+
+    /// Compute the hash of this node with `H`
+    pub fn hash_with<H: MerkleHasher>(&self) -> HashValue {
+        match self {
+            TreeNode::Null => empty_hash::<H>(),
+            TreeNode::Internal(node) => node.hash_with::<H>(),
+            TreeNode::Leaf(leaf) => leaf.hash_with::<H>(),
+        }
+    }
+}
+
+/// New nodes produced by a batch update, keyed by position and version.
+pub type NodeBatch = BTreeMap<NodeKey, TreeNode>;
+
+/// Everything a batch update needs to persist.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TreeUpdateBatch {
+    pub node_batch: NodeBatch,
+    /// Nodes superseded by this batch
+    pub stale_node_index_batch: Vec<StaleNodeIndex>,
+}
+
+/// Read access to tree nodes.
+pub trait TreeReader {
+    /// Get a node, or `None` if it does not exist.
+    fn get_node_option(&self, node_key: &NodeKey) -> MerkleResult<Option<TreeNode>>;
+
+    /// Get a node, failing if it does not exist.
+    fn get_node(&self, node_key: &NodeKey) -> MerkleResult<TreeNode> {
+        self.get_node_option(node_key)?
+            .ok_or_else(|| MerkleError::NodeNotFound(format!("{:?}", node_key)))
+    }
+}
+
+/// Write access to tree nodes.
+pub trait TreeWriter {
+    /// Persist a batch update atomically.
+    fn write_tree_update_batch(&self, batch: &TreeUpdateBatch) -> MerkleResult<()>;
+}
+
+/// Access to the stale node index, used to prune old versions.
+pub trait StaleNodeIndexStore {
+    /// Get stale node entries with `stale_since_version <= up_to_version`.
+    fn get_stale_node_indices(&self, up_to_version: Version) -> MerkleResult<Vec<StaleNodeIndex>>;
+
+    /// Delete the given stale nodes and their index entries atomically.
+    fn purge_stale_nodes(&self, indices: &[StaleNodeIndex]) -> MerkleResult<()>;
+}
+
+/// An in-memory node store.
+#[derive(Debug, Default)]
+pub struct MemoryTreeStore {
+    nodes: RwLock<HashMap<NodeKey, TreeNode>>,
+    stale_node_index: RwLock<BTreeSet<StaleNodeIndex>>,
+}
+
+impl MemoryTreeStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of nodes currently stored
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.read().unwrap().len()
+    }
+}
+
+impl TreeReader for MemoryTreeStore {
+    fn get_node_option(&self, node_key: &NodeKey) -> MerkleResult<Option<TreeNode>> {
+        Ok(self.nodes.read().unwrap().get(node_key).cloned())
+    }
+}
+
+impl TreeWriter for MemoryTreeStore {
+    fn write_tree_update_batch(&self, batch: &TreeUpdateBatch) -> MerkleResult<()> {
+        let mut nodes = self.nodes.write().unwrap();
+        for (key, node) in &batch.node_batch {
+            nodes.insert(*key, node.clone());
+        }
+        self.stale_node_index
+            .write()
+            .unwrap()
+            .extend(batch.stale_node_index_batch.iter().copied());
+        Ok(())
+    }
+}
+
+impl StaleNodeIndexStore for MemoryTreeStore {
+    fn get_stale_node_indices(&self, up_to_version: Version) -> MerkleResult<Vec<StaleNodeIndex>> {
+        Ok(self
+            .stale_node_index
+            .read()
+            .unwrap()
+            .iter()
+            .take_while(|index| index.stale_since_version <= up_to_version)
+            .copied()
+            .collect())
+    }
+
+    fn purge_stale_nodes(&self, indices: &[StaleNodeIndex]) -> MerkleResult<()> {
+        let mut nodes = self.nodes.write().unwrap();
+        let mut stale_node_index = self.stale_node_index.write().unwrap();
+        for index in indices {
+            nodes.remove(&index.node_key);
+            stale_node_index.remove(index);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_child_node_key() {
+        let root = NodeKey::new_empty_path(3);
+        let right = root.gen_child_node_key(4, true);
+        let right_left = right.gen_child_node_key(4, false);
+
+        assert_eq!(right.depth(), 1);
+        assert_eq!(right.version(), 4);
+        assert!(right.path().bit(0));
+        assert_eq!(right_left.depth(), 2);
+        assert!(right_left.path().bit(0));
+        assert!(!right_left.path().bit(1));
+        assert!(right_left.same_position(&right_left.with_version(9)));
+        assert!(!right.same_position(&root));
+    }
+
+    #[test]
+    fn test_key_encoding_order() {
+        let config = bincode::config::standard();
+        let encode = |index: &StaleNodeIndex| bincode::encode_to_vec(index, config).unwrap();
+
+        let a = StaleNodeIndex {
+            stale_since_version: 255,
+            node_key: NodeKey::new_empty_path(1000).gen_child_node_key(1000, true),
+        };
+        let b = StaleNodeIndex {
+            stale_since_version: 256,
+            node_key: NodeKey::new_empty_path(0),
+        };
+        assert!(encode(&a) < encode(&b));
+
+        let (decoded, _): (StaleNodeIndex, usize) =
+            bincode::decode_from_slice(&encode(&a), config).unwrap();
+        assert_eq!(decoded, a);
+    }
+}
@@ -0,0 +1,356 @@
+//! Versioned state history for the sparse Merkle tree.
+//!
+//! [`VersionedSparseMerkleTree`] commits each batch of updates as a new version
+//! on top of a [`PersistentSparseMerkleTree`]. Older versions stay queryable,
+//! with proofs, until the [`PruningPolicy`] removes them. Like the persistent
+//! tree it hashes with SHA-256 unless given another [`MerkleHasher`].
+//!
+//! # Example
+//!
+//! ```
+//! use setu_merkle::storage::MemoryTreeStore;
+//! use setu_merkle::versioned::{PruningPolicy, VersionedSparseMerkleTree};
+//! use setu_merkle::HashValue;
+//!
+//! let mut tree = VersionedSparseMerkleTree::new(MemoryTreeStore::new(), PruningPolicy::KeepAll);
+//! let coin = HashValue::from_slice(&[7u8; 32]).unwrap();
+//!
+//! let (v0, root0) = tree.commit(vec![(coin, Some(b"100".to_vec()))]).unwrap();
+//! tree.commit(vec![(coin, Some(b"40".to_vec()))]).unwrap();
+//!
+//! let (balance, proof) = tree.get_proof_at(&coin, v0).unwrap();
+//! assert_eq!(balance, Some(b"100".to_vec()));
+//! assert!(proof.verify_inclusion(&root0, &coin, b"100").is_ok());
+//! ```
+
+use std::marker::PhantomData;
+
+use crate::error::{MerkleError, MerkleResult};
+use crate::hash::HashValue;
+use crate::hasher::MerkleHasher;
+use crate::multiproof::{
+    MultiProofValues, RangeEntries, SparseMerkleMultiProof, SparseMerkleRangeProof,
+};
+use crate::persistent::PersistentSparseMerkleTree;
+use crate::sparse::SparseMerkleProof;
+use crate::storage::{StaleNodeIndexStore, StorageHasher, TreeReader, TreeWriter, Version};
+
+/// How many committed versions to keep readable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PruningPolicy {
+    /// Never prune
+    KeepAll,
+    /// Keep only the latest N versions (N >= 1)
+    KeepLatest(u64),
+}
+
+/// A sparse Merkle tree with native version history.
+///
+/// Every [`commit`](Self::commit) produces a new version and root. Reads and
+/// proofs can target any version that has not been pruned.
+pub struct VersionedSparseMerkleTree<S, H = StorageHasher> {
+    store: S,
+    latest_version: Option<Version>,
+    /// Oldest version whose nodes are guaranteed to be present
+    min_readable_version: Version,
+    pruning_policy: PruningPolicy,
+    hasher: PhantomData<H>,
+}
+
+impl<S> VersionedSparseMerkleTree<S>
+where
+    S: TreeReader + TreeWriter + StaleNodeIndexStore,
+{
+    /// Create a tree over an empty store.
+    pub fn new(store: S, pruning_policy: PruningPolicy) -> Self {
+        Self::with_hasher(store, pruning_policy, StorageHasher::default())
+    }
+
+    /// Resume a tree whose latest committed version is `latest_version`.
+    ///
+    /// `min_readable_version` is the oldest version that was not pruned.
+    pub fn open(
+        store: S,
+        latest_version: Version,
+        min_readable_version: Version,
+        pruning_policy: PruningPolicy,
+    ) -> MerkleResult<Self> {
+        Self::open_with_hasher(
+            store,
+            latest_version,
+            min_readable_version,
+            pruning_policy,
+            StorageHasher::default(),
+        )
+    }
+}
+
+impl<S, H> VersionedSparseMerkleTree<S, H>
+where
+    S: TreeReader + TreeWriter + StaleNodeIndexStore,
+    H: MerkleHasher,
+{
+    /// Create a tree over an empty store using the given hasher.
+    pub fn with_hasher(store: S, pruning_policy: PruningPolicy, _hasher: H) -> Self {
+        Self {
+            store,
+            latest_version: None,
+            min_readable_version: 0,
+            pruning_policy,
+            hasher: PhantomData,
+        }
+    }
+
+    /// Resume a tree written with the given hasher, as [`open`](VersionedSparseMerkleTree::open).
+    pub fn open_with_hasher(
+        store: S,
+        latest_version: Version,
+        min_readable_version: Version,
+        pruning_policy: PruningPolicy,
+        _hasher: H,
+    ) -> MerkleResult<Self> {
+        let tree = Self {
+            store,
+            latest_version: Some(latest_version),
+            min_readable_version,
+            pruning_policy,
+            hasher: PhantomData,
+        };
+        // Fail early if the store does not actually contain that version
+        tree.root_at(latest_version)?;
+        Ok(tree)
+    }
+
+    /// The underlying node store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// The latest committed version, if any.
+    pub fn latest_version(&self) -> Option<Version> {
+        self.latest_version
+    }
+
+    /// The oldest version that can still be read.
+    pub fn min_readable_version(&self) -> Version {
+        self.min_readable_version
+    }
+
+    /// The root hash of the latest version.
+    pub fn root(&self) -> MerkleResult<HashValue> {
+        match self.latest_version {
+            Some(version) => self.root_at(version),
+            None => Ok(crate::sparse::empty_hash::<H>()),
+        }
+    }
+
+    /// Apply a batch of updates as a new version.
+    ///
+    /// `None` values delete the key. Returns the new version and root hash.
+    /// Versions that fall outside the pruning policy are pruned afterwards.
+    pub fn commit(
+        &mut self,
+        value_set: Vec<(HashValue, Option<Vec<u8>>)>,
+    ) -> MerkleResult<(Version, HashValue)> {
+        let version = self.latest_version.map_or(0, |v| v + 1);
+        let (root, batch) =
+            self.tree().put_value_set(value_set, version)?;
+        self.store.write_tree_update_batch(&batch)?;
+        self.latest_version = Some(version);
+
+        if let PruningPolicy::KeepLatest(n) = self.pruning_policy {
+            let n = n.max(1);
+            if version + 1 > n {
+                self.prune(version + 1 - n)?;
+            }
+        }
+
+        Ok((version, root))
+    }
+
+    /// Get the root hash at a version.
+    pub fn root_at(&self, version: Version) -> MerkleResult<HashValue> {
+        self.check_readable(version)?;
+        self.tree().get_root_hash(version)
+    }
+
+    /// Get a value by key at a version.
+    pub fn get_at(&self, key: &HashValue, version: Version) -> MerkleResult<Option<Vec<u8>>> {
+        self.check_readable(version)?;
+        self.tree().get(key, version)
+    }
+
+    /// Get a value by key at a version with a proof against that version's root.
+    pub fn get_proof_at(
+        &self,
+        key: &HashValue,
+        version: Version,
+    ) -> MerkleResult<(Option<Vec<u8>>, SparseMerkleProof<H>)> {
+        self.check_readable(version)?;
+        self.tree().get_with_proof(key, version)
+    }
+
+    /// Get the values of several keys at a version with one proof covering all of them.
+    pub fn get_multi_proof_at(
+        &self,
+        keys: &[HashValue],
+        version: Version,
+    ) -> MerkleResult<(MultiProofValues, SparseMerkleMultiProof<H>)> {
+        self.check_readable(version)?;
+        self.tree().get_with_multi_proof(keys, version)
+    }
+
+    /// Get all entries with keys in `[start, end]` at a version, with a range proof.
+    pub fn get_range_proof_at(
+        &self,
+        start: &HashValue,
+        end: &HashValue,
+        version: Version,
+    ) -> MerkleResult<(RangeEntries, SparseMerkleRangeProof<H>)> {
+        self.check_readable(version)?;
+        self.tree().get_range_with_proof(start, end, version)
+    }
+
+    /// Delete nodes that are only needed by versions older than `min_readable_version`.
+    ///
+    /// Returns the number of nodes removed.
+    pub fn prune(&mut self, min_readable_version: Version) -> MerkleResult<usize> {
+        if min_readable_version <= self.min_readable_version {
+            return Ok(0);
+        }
+        let latest = self.latest_version.ok_or(MerkleError::EmptyTree)?;
+        if min_readable_version > latest {
+            return Err(MerkleError::InvalidInput(format!(
+                "Cannot prune past the latest version {}",
+                latest
+            )));
+        }
+
+        // A node stale since version S is only read by versions < S
+        let indices = self.store.get_stale_node_indices(min_readable_version)?;
+        self.store.purge_stale_nodes(&indices)?;
+        self.min_readable_version = min_readable_version;
+        Ok(indices.len())
+    }
+
+    fn tree(&self) -> PersistentSparseMerkleTree<'_, S, H> {
+        PersistentSparseMerkleTree::with_hasher(&self.store, H::default())
+    }
+
+    fn check_readable(&self, version: Version) -> MerkleResult<()> {
+        match self.latest_version {
+            Some(latest) if version > latest => Err(MerkleError::VersionNotFound(version)),
+            None => Err(MerkleError::VersionNotFound(version)),
+            _ if version < self.min_readable_version => Err(MerkleError::VersionPruned {
+                version,
+                min_readable: self.min_readable_version,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryTreeStore;
+
+    fn test_key(byte: u8) -> HashValue {
+        HashValue::new([byte; 32])
+    }
+
+    #[test]
+    fn test_versions_and_roots() {
+        let mut tree = VersionedSparseMerkleTree::new(MemoryTreeStore::new(), PruningPolicy::KeepAll);
+        let key = test_key(1);
+
+        let mut roots = Vec::new();
+        for i in 0..5u8 {
+            let (version, root) = tree.commit(vec![(key, Some(vec![i]))]).unwrap();
+            assert_eq!(version, i as Version);
+            roots.push(root);
+        }
+
+        assert_eq!(tree.latest_version(), Some(4));
+        assert_eq!(tree.root().unwrap(), roots[4]);
+        for (version, root) in roots.iter().enumerate() {
+            let version = version as Version;
+            assert_eq!(tree.root_at(version).unwrap(), *root);
+            let (value, proof) = tree.get_proof_at(&key, version).unwrap();
+            assert_eq!(value, Some(vec![version as u8]));
+            assert!(proof.verify_inclusion(root, &key, &[version as u8]).is_ok());
+        }
+        assert!(matches!(tree.get_at(&key, 5), Err(MerkleError::VersionNotFound(5))));
+    }
+
+    #[test]
+    fn test_keep_latest_prunes_old_versions() {
+        let mut tree =
+            VersionedSparseMerkleTree::new(MemoryTreeStore::new(), PruningPolicy::KeepLatest(2));
+
+        let keys: Vec<_> = (0..32u8).map(|i| test_key(i * 8)).collect();
+        tree.commit(keys.iter().map(|k| (*k, Some(b"init".to_vec()))).collect())
+            .unwrap();
+        let after_genesis = tree.store().num_nodes();
+
+        for round in 0..20u8 {
+            let key = keys[round as usize % keys.len()];
+            tree.commit(vec![(key, Some(vec![round]))]).unwrap();
+        }
+
+        // Two versions of a single-key update cost far less than 20 full paths
+        assert_eq!(tree.min_readable_version(), 19);
+        assert!(tree.store().num_nodes() < after_genesis + 2 * 8);
+        assert!(matches!(
+            tree.get_at(&keys[0], 18),
+            Err(MerkleError::VersionPruned { version: 18, min_readable: 19 })
+        ));
+
+        // Every remaining version is still complete
+        for version in 19..=20 {
+            let root = tree.root_at(version).unwrap();
+            for key in &keys {
+                let (value, proof) = tree.get_proof_at(key, version).unwrap();
+                let value = value.unwrap();
+                assert!(proof.verify_inclusion(&root, key, &value).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_explicit_prune() {
+        let mut tree = VersionedSparseMerkleTree::new(MemoryTreeStore::new(), PruningPolicy::KeepAll);
+        let key = test_key(3);
+        tree.commit(vec![(key, Some(b"a".to_vec()))]).unwrap();
+        tree.commit(vec![(key, Some(b"b".to_vec()))]).unwrap();
+
+        assert!(tree.prune(5).is_err());
+        assert_eq!(tree.prune(1).unwrap(), 1);
+        assert_eq!(tree.get_at(&key, 1).unwrap(), Some(b"b".to_vec()));
+        assert!(tree.get_at(&key, 0).is_err());
+    }
+
+    #[test]
+    fn test_historical_multi_and_range_proofs() {
+        let mut tree = VersionedSparseMerkleTree::new(MemoryTreeStore::new(), PruningPolicy::KeepAll);
+        let keys: Vec<_> = (1..=4u8).map(|i| test_key(i * 0x30)).collect();
+        let (v0, root0) = tree
+            .commit(keys.iter().map(|k| (*k, Some(b"old".to_vec()))).collect())
+            .unwrap();
+        tree.commit(vec![(keys[1], None), (keys[2], Some(b"new".to_vec()))]).unwrap();
+
+        let (values, proof) = tree.get_multi_proof_at(&keys[1..3], v0).unwrap();
+        assert_eq!(values, vec![Some(b"old".to_vec()), Some(b"old".to_vec())]);
+        let claims: Vec<_> = keys[1..3].iter().copied().zip(values).collect();
+        assert!(proof.verify(&root0, &claims).is_ok());
+
+        let (start, end) = (keys[0], keys[2]);
+        let (entries, proof) = tree.get_range_proof_at(&start, &end, v0).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(proof.verify(&root0, &start, &end, &entries).is_ok());
+
+        let (entries, proof) = tree.get_range_proof_at(&start, &end, v0 + 1).unwrap();
+        assert_eq!(entries, vec![(keys[0], b"old".to_vec()), (keys[2], b"new".to_vec())]);
+        assert!(proof.verify(&tree.root().unwrap(), &start, &end, &entries).is_ok());
+    }
+}
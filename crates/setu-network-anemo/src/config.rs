@@ -4,9 +4,13 @@
 //! Configuration for Anemo-based network
 
 use serde::{Deserialize, Serialize};
+use setu_keys::SetuKeyPair;
+use std::fmt;
+use std::path::PathBuf;
+use zeroize::Zeroizing;
 
 /// Configuration for Anemo network
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AnemoConfig {
     /// Listen address (e.g., "0.0.0.0:9000")
     pub listen_addr: String,
@@ -16,7 +20,13 @@ pub struct AnemoConfig {
 
     /// Ed25519 private key (32 bytes)
     /// If None, a random key will be generated
-    pub private_key: Option<[u8; 32]>,
+    /// Never serialized or printed; persist the identity with `key_file`
+    #[serde(skip)]
+    pub private_key: Option<Zeroizing<[u8; 32]>>,
+
+    /// setu-keys Ed25519 key file holding the node identity
+    /// Takes precedence over `private_key`
+    pub key_file: Option<PathBuf>,
 
     /// QUIC configuration
     pub quic: QuicConfig,
@@ -28,6 +38,20 @@ pub struct AnemoConfig {
     pub timeouts: TimeoutConfig,
 }
 
+impl fmt::Debug for AnemoConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnemoConfig")
+            .field("listen_addr", &self.listen_addr)
+            .field("server_name", &self.server_name)
+            .field("private_key", &self.private_key.as_ref().map(|_| "<redacted>"))
+            .field("key_file", &self.key_file)
+            .field("quic", &self.quic)
+            .field("connection_limits", &self.connection_limits)
+            .field("timeouts", &self.timeouts)
+            .finish()
+    }
+}
+
 /// QUIC protocol configuration
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct QuicConfig {
@@ -103,6 +127,7 @@ impl Default for AnemoConfig {
             listen_addr: "0.0.0.0:9000".to_string(),
             server_name: crate::DEFAULT_SERVER_NAME.to_string(),
             private_key: None,
+            key_file: None,
             quic: QuicConfig::default(),
             connection_limits: ConnectionLimits::default(),
             timeouts: TimeoutConfig::default(),
@@ -152,6 +177,20 @@ impl Default for TimeoutConfig {
 }
 
 impl AnemoConfig {
+    /// Use the given setu-keys keypair as the node identity
+    pub fn with_keypair(mut self, keypair: &SetuKeyPair) -> crate::Result<Self> {
+        self.private_key = Some(keypair.ed25519_private_key()?);
+        Ok(self)
+    }
+
+    /// Resolve the node's private key from `key_file` or `private_key`
+    pub fn network_private_key(&self) -> crate::Result<Option<Zeroizing<[u8; 32]>>> {
+        match &self.key_file {
+            Some(path) => Ok(Some(crate::identity::load_identity(path)?.ed25519_private_key()?)),
+            None => Ok(self.private_key.clone()),
+        }
+    }
+
     /// Convert to Anemo's Config type
     pub fn to_anemo_config(&self) -> anemo::Config {
         let mut config = anemo::Config::default();
@@ -211,3 +250,24 @@ impl Default for NetworkConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use setu_keys::SignatureScheme;
+
+    #[test]
+    fn test_private_key_is_not_exposed() {
+        let keypair = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let config = AnemoConfig::default().with_keypair(&keypair).unwrap();
+        let secret = keypair.ed25519_private_key().unwrap();
+
+        let debug = format!("{:?}", config);
+        assert!(debug.contains("<redacted>"));
+        assert!(!debug.contains(&format!("{:?}", *secret)));
+        let json = serde_json::to_value(&config).unwrap();
+        assert!(json.get("private_key").is_none());
+
+        assert_eq!(*config.network_private_key().unwrap().unwrap(), *secret);
+    }
+}
@@ -37,8 +37,8 @@ pub use builder::{Builder, UnstartedDiscovery};
 pub use server::{Discovery, DiscoveryServer, Server};
 
 use anemo::PeerId;
-use ed25519_consensus::{Signature, SigningKey, VerificationKey};
 use serde::{Deserialize, Serialize};
+use setu_keys::{KeyHistory, PublicKey, SetuAddress, SetuKeyPair, Signature};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -83,8 +83,8 @@ impl NodeInfo {
         }
     }
 
-    /// Sign this node info with the given key
-    pub fn sign(self, key: &SigningKey) -> SignedNodeInfo {
+    /// Sign this node info with the node's identity key
+    pub fn sign(self, key: &SetuKeyPair) -> SignedNodeInfo {
         let bytes = bcs::to_bytes(&self).expect("BCS serialization should not fail");
         let signature = key.sign(&bytes);
         SignedNodeInfo {
@@ -116,12 +116,40 @@ pub struct SignedNodeInfo {
 
 impl SignedNodeInfo {
     /// Verify the signature using the peer's public key
-    pub fn verify(&self, key: &VerificationKey) -> bool {
+    pub fn verify(&self, key: &PublicKey) -> bool {
         let bytes = match bcs::to_bytes(&self.info) {
             Ok(b) => b,
             Err(_) => return false,
         };
-        key.verify(&self.signature, &bytes).is_ok()
+        key.verify(&bytes, &self.signature).is_ok()
+    }
+
+    /// Verify the signature against the key behind the advertised peer ID
+    pub fn verify_peer(&self) -> bool {
+        match crate::identity::public_key_of(&self.info.peer_id) {
+            Ok(key) => self.verify(&key),
+            Err(_) => false,
+        }
+    }
+
+    /// Setu address of the node that signed this info
+    pub fn signer_address(&self) -> Option<SetuAddress> {
+        crate::identity::address_of(&self.info.peer_id).ok()
+    }
+
+    /// Verify against the key the node holds at `epoch`, honouring key rotations.
+    ///
+    /// A rotated node gets a new peer ID, so the info must also be advertised
+    /// under the peer ID of that key.
+    pub fn verify_at_epoch(&self, history: &KeyHistory, epoch: u64) -> bool {
+        if history.node_id() != self.info.node_id {
+            return false;
+        }
+        let key = history.key_at(epoch);
+        if crate::identity::peer_id_of(key) != Some(self.info.peer_id) {
+            return false;
+        }
+        self.verify(key)
     }
 }
 
@@ -272,3 +300,50 @@ impl DiscoveryEventLoop {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::peer_id_of;
+    use setu_keys::{KeyRotation, SignatureScheme};
+
+    fn signed_info(node_id: &str, key: &SetuKeyPair) -> SignedNodeInfo {
+        NodeInfo::new(
+            node_id.to_string(),
+            peer_id_of(&key.public()).unwrap(),
+            vec!["127.0.0.1:9000".to_string()],
+            NodeType::Validator,
+        )
+        .sign(key)
+    }
+
+    #[test]
+    fn test_signed_info_maps_to_signer_address() {
+        let key = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let info = signed_info("v1", &key);
+
+        assert!(info.verify(&key.public()));
+        assert!(info.verify_peer());
+        assert_eq!(info.signer_address(), Some(key.address()));
+
+        // Signed by a key other than the advertised peer ID
+        let mut forged = signed_info("v1", &SetuKeyPair::generate(SignatureScheme::ED25519));
+        forged.info.peer_id = info.info.peer_id;
+        assert!(!forged.verify_peer());
+    }
+
+    #[test]
+    fn test_verify_at_epoch_honours_rotation() {
+        let old = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let new = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let mut history = KeyHistory::new("v1".to_string(), old.public());
+        history.apply(KeyRotation::new("v1", &old, &new, 10), 0).unwrap();
+
+        let old_info = signed_info("v1", &old);
+        let new_info = signed_info("v1", &new);
+        assert!(old_info.verify_at_epoch(&history, 9));
+        assert!(!old_info.verify_at_epoch(&history, 10));
+        assert!(new_info.verify_at_epoch(&history, 10));
+        assert!(!signed_info("v2", &new).verify_at_epoch(&history, 10));
+    }
+}
@@ -23,6 +23,9 @@ pub enum AnemoError {
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
 
+    #[error("Key error: {0}")]
+    Key(#[from] setu_keys::KeyError),
+
     #[error("Serialization error: {0}")]
     Serialization(String),
 
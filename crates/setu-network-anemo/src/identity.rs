@@ -0,0 +1,76 @@
+// Copyright (c) Setu Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Node identity backed by setu-keys
+//!
+//! A node's network identity is its setu-keys Ed25519 keypair: the anemo
+//! `PeerId` is the Ed25519 public key, discovery info is signed with the same
+//! key, and so a peer maps to exactly one `SetuAddress`.
+
+use crate::{error::Result, AnemoError};
+use anemo::PeerId;
+use setu_keys::{read_keypair_from_file, PublicKey, SetuAddress, SetuKeyPair, SignatureScheme};
+use std::path::Path;
+
+/// Load a node identity from a setu-keys key file.
+///
+/// The key must be Ed25519, since anemo uses it for TLS.
+pub fn load_identity<P: AsRef<Path>>(path: P) -> Result<SetuKeyPair> {
+    let keypair = read_keypair_from_file(path)?;
+    if keypair.scheme() != SignatureScheme::ED25519 {
+        return Err(AnemoError::InvalidConfig(format!(
+            "Node identity must be an ed25519 key, got {}",
+            keypair.scheme()
+        )));
+    }
+    Ok(keypair)
+}
+
+/// Peer ID of a node with the given public key, if it can be a network identity.
+pub fn peer_id_of(public_key: &PublicKey) -> Option<PeerId> {
+    match public_key {
+        PublicKey::Ed25519(pk) => Some(PeerId(pk.to_bytes())),
+        _ => None,
+    }
+}
+
+/// Public key behind a peer ID.
+pub fn public_key_of(peer_id: &PeerId) -> Result<PublicKey> {
+    Ok(PublicKey::from_bytes(SignatureScheme::ED25519, &peer_id.0)?)
+}
+
+/// Setu address of the node behind a peer ID.
+pub fn address_of(peer_id: &PeerId) -> Result<SetuAddress> {
+    Ok(SetuAddress::from(&public_key_of(peer_id)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use setu_keys::write_keypair_to_file;
+
+    #[test]
+    fn test_peer_id_maps_to_address() {
+        let keypair = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let peer_id = peer_id_of(&keypair.public()).unwrap();
+
+        assert_eq!(public_key_of(&peer_id).unwrap(), keypair.public());
+        assert_eq!(address_of(&peer_id).unwrap(), keypair.address());
+
+        let bls = SetuKeyPair::generate(SignatureScheme::BLS12381);
+        assert!(peer_id_of(&bls.public()).is_none());
+    }
+
+    #[test]
+    fn test_load_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("node.key");
+
+        let keypair = SetuKeyPair::generate(SignatureScheme::ED25519);
+        write_keypair_to_file(&keypair, &path).unwrap();
+        assert_eq!(load_identity(&path).unwrap().address(), keypair.address());
+
+        write_keypair_to_file(&SetuKeyPair::generate(SignatureScheme::Secp256k1), &path).unwrap();
+        assert!(load_identity(&path).is_err());
+    }
+}
@@ -64,6 +64,7 @@
 pub mod config;
 pub mod discovery;
 pub mod error;
+pub mod identity;
 pub mod metrics;
 pub mod peer_manager;
 pub mod service;
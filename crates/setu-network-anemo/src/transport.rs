@@ -30,18 +30,18 @@ impl AnemoTransport {
         // Convert to Anemo config
         let anemo_config = config.to_anemo_config();
 
-        // Generate or use provided private key
-        let private_key = config.private_key.unwrap_or_else(|| {
+        // Use the configured node identity, or generate a throwaway key
+        let private_key = config.network_private_key()?.unwrap_or_else(|| {
             let mut rng = rand::thread_rng();
-            let mut key = [0u8; 32];
-            rand::RngCore::fill_bytes(&mut rng, &mut key);
+            let mut key = zeroize::Zeroizing::new([0u8; 32]);
+            rand::RngCore::fill_bytes(&mut rng, key.as_mut());
             key
         });
 
         // Build the network
         let network = Network::bind(listen_addr)
             .server_name(&config.server_name)
-            .private_key(private_key)
+            .private_key(*private_key)
             .config(anemo_config)
             .start(Self::create_service())?;
 
@@ -166,6 +166,25 @@ mod tests {
         assert!(transport.local_addr().port() > 0);
     }
 
+    #[tokio::test]
+    async fn test_peer_id_from_setu_key() {
+        use setu_keys::{SetuKeyPair, SignatureScheme};
+
+        let keypair = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let config = AnemoConfig {
+            listen_addr: "127.0.0.1:0".to_string(),
+            ..Default::default()
+        }
+        .with_keypair(&keypair)
+        .unwrap();
+
+        let transport = AnemoTransport::new(&config).await.unwrap();
+        assert_eq!(
+            crate::identity::address_of(&transport.peer_id()).unwrap(),
+            keypair.address()
+        );
+    }
+
     #[tokio::test]
     async fn test_peer_connection() {
         let config1 = AnemoConfig {
@@ -0,0 +1,377 @@
+//! Key management command handlers
+
+use anyhow::{anyhow, bail, Result};
+use colored::Colorize;
+use setu_keys::key_derive::{default_derivation_path, derive_key_pair_from_mnemonic};
+use setu_keys::keypair_file::read_key;
+use setu_keys::{
+    read_encrypted_keypair_from_file, write_encrypted_keypair_to_file, write_keypair_to_file,
+    AccountKeystore, DerivationPath, FileBasedKeystore, KdfParams, KeyError, KeyIdentity,
+    KeyRotation, PublicKey, SetuAddress, SetuKeyPair, Signature, SignatureScheme, WordCount,
+};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Environment variable read instead of prompting for the passphrase
+pub const PASSPHRASE_ENV: &str = "SETU_KEYSTORE_PASSPHRASE";
+
+pub async fn handle(action: crate::KeysAction, keystore: Option<String>) -> Result<()> {
+    let path = keystore.map(PathBuf::from).unwrap_or_else(default_keystore_path);
+
+    match action {
+        crate::KeysAction::New { alias, scheme, derivation_path, word_length } => {
+            let scheme = SignatureScheme::from_str(&scheme)?;
+            let derivation_path = parse_derivation_path(derivation_path)?;
+            let word_count = word_length.map(|w| WordCount::from_str(&w)).transpose()?;
+            let mut ks = open_or_create_keystore(&path)?;
+
+            let key = ks.generate(alias, scheme, derivation_path, word_count).await?;
+            let alias = ks.get_alias(&key.address)?;
+
+            println!("{} Generated new {} key", "✓".green().bold(), key.scheme);
+            println!("  Alias:   {}", alias.cyan());
+            println!("  Address: {}", key.address.to_string().cyan());
+            if let Some(mnemonic) = key.mnemonic {
+                println!();
+                println!("{} Write down the recovery phrase and keep it offline:",
+                    "!".yellow().bold()
+                );
+                println!("  {}", mnemonic);
+            }
+            Ok(())
+        }
+
+        crate::KeysAction::Import { input, alias, scheme, derivation_path } => {
+            let mut ks = open_or_create_keystore(&path)?;
+
+            let address = match input {
+                None => {
+                    let scheme = SignatureScheme::from_str(&scheme)?;
+                    let phrase = rpassword::prompt_password("Recovery phrase: ")?;
+                    let derivation_path = parse_derivation_path(derivation_path)?;
+                    ks.import_from_mnemonic(phrase.trim(), scheme, derivation_path, alias).await?
+                }
+                Some(input) => {
+                    let keypair = if Path::new(&input).exists() {
+                        read_key_file(Path::new(&input))?
+                    } else {
+                        SetuKeyPair::decode_base64(input.trim())?
+                    };
+                    let address = keypair.address();
+                    ks.import(alias, keypair).await?;
+                    address
+                }
+            };
+
+            println!("{} Imported key", "✓".green().bold());
+            println!("  Alias:   {}", ks.get_alias(&address)?.cyan());
+            println!("  Address: {}", address.to_string().cyan());
+            Ok(())
+        }
+
+        crate::KeysAction::Export { key, output, insecure_plaintext } => {
+            if !path.exists() {
+                bail!("No keystore found at {}", path.display());
+            }
+
+            let ks = open_keystore(&path)?;
+            let address = resolve_key(&ks, &key)?;
+            let keypair = ks.export(&address)?;
+
+            match output {
+                Some(output) if insecure_plaintext => {
+                    write_keypair_to_file(keypair, &output)?;
+                    println!("{} Exported {} unencrypted to {}. Do not share it.",
+                        "!".yellow().bold(),
+                        address,
+                        output.cyan()
+                    );
+                }
+                Some(output) => {
+                    let passphrase = new_passphrase("Key file passphrase: ")?;
+                    write_encrypted_keypair_to_file(keypair, &output, &passphrase, KdfParams::default())?;
+                    println!("{} Exported {} to {}",
+                        "✓".green().bold(),
+                        address,
+                        output.cyan()
+                    );
+                }
+                None => {
+                    println!("{} This is the unencrypted private key. Do not share it.",
+                        "!".yellow().bold()
+                    );
+                    println!("{}", keypair.encode_base64());
+                }
+            }
+            Ok(())
+        }
+
+        crate::KeysAction::Backup { output } => {
+            if !path.exists() {
+                bail!("No keystore found at {}", path.display());
+            }
+
+            let ks = open_keystore(&path)?;
+            let passphrase = new_passphrase("New keystore passphrase: ")?;
+            ks.backup(Path::new(&output), &passphrase).await?;
+
+            println!("{} Backed up {} key(s) to {}",
+                "✓".green().bold(),
+                ks.entries().len(),
+                output.cyan()
+            );
+            println!("  Restore with 'setu keys --keystore {} list', or copy it back.", output);
+            Ok(())
+        }
+
+        crate::KeysAction::List => {
+            if !path.exists() {
+                println!("{} No keystore found at {}. Run 'setu keys new' first.",
+                    "✗".red().bold(),
+                    path.display()
+                );
+                return Ok(());
+            }
+
+            let ks = open_keystore(&path)?;
+            let mut entries = ks.addresses_with_alias();
+            entries.sort_by(|a, b| a.1.alias.cmp(&b.1.alias));
+
+            println!("{}", "Keys:".bold());
+            for (address, alias) in entries {
+                println!("  {:<20} {}", alias.alias.cyan(), address);
+            }
+            println!();
+            println!("Keystore: {}", path.display().to_string().dimmed());
+            if !ks.is_encrypted() {
+                println!("{} Keystore is not encrypted. Run 'setu keys migrate'.",
+                    "!".yellow().bold()
+                );
+            }
+            Ok(())
+        }
+
+        crate::KeysAction::Migrate => {
+            if !path.exists() {
+                bail!("No keystore found at {}", path.display());
+            }
+
+            let mut ks = match FileBasedKeystore::load_or_create(&path) {
+                Ok(ks) => ks,
+                Err(KeyError::PassphraseRequired(_)) => {
+                    println!("{} Keystore is already encrypted", "✓".green().bold());
+                    return Ok(());
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            let passphrase = new_passphrase("New keystore passphrase: ")?;
+            ks.set_passphrase(&passphrase).await?;
+
+            println!("{} Encrypted {} key(s) in {}",
+                "✓".green().bold(),
+                ks.entries().len(),
+                path.display().to_string().cyan()
+            );
+            Ok(())
+        }
+
+        crate::KeysAction::ChangePassphrase => {
+            if !path.exists() {
+                bail!("No keystore found at {}", path.display());
+            }
+
+            let mut ks = open_keystore(&path)?;
+            let passphrase = new_passphrase("New keystore passphrase: ")?;
+            ks.set_passphrase(&passphrase).await?;
+
+            println!("{} Passphrase changed", "✓".green().bold());
+            Ok(())
+        }
+
+        crate::KeysAction::Sign { key, data, file } => {
+            if !path.exists() {
+                bail!("No keystore found at {}", path.display());
+            }
+
+            let msg = read_data(data, file)?;
+            let ks = open_keystore(&path)?;
+            let address = resolve_key(&ks, &key)?;
+            let signature = ks.sign(&address, &msg)?;
+
+            println!("Address:    {}", address.to_string().cyan());
+            println!("Public key: {}", ks.export(&address)?.public().encode_base64());
+            println!("Signature:  {}", signature.encode_base64());
+            Ok(())
+        }
+
+        crate::KeysAction::Verify { public_key, signature, data, file } => {
+            let msg = read_data(data, file)?;
+            let public_key = PublicKey::decode_base64(&public_key)?;
+            let signature = Signature::decode_base64(&signature)?;
+
+            match public_key.verify(&msg, &signature) {
+                Ok(()) => {
+                    println!("{} Valid signature by {}",
+                        "✓".green().bold(),
+                        SetuAddress::from(&public_key).to_string().cyan()
+                    );
+                    Ok(())
+                }
+                Err(e) => bail!("Invalid signature: {}", e),
+            }
+        }
+
+        crate::KeysAction::Derive { scheme, derivation_path, count } => {
+            let scheme = SignatureScheme::from_str(&scheme)?;
+            let paths = match parse_derivation_path(derivation_path)? {
+                Some(path) => vec![path],
+                None => (0..count)
+                    .map(|i| default_derivation_path(&scheme, i))
+                    .collect::<Result<Vec<_>, _>>()?,
+            };
+            let phrase = rpassword::prompt_password("Recovery phrase: ")?;
+
+            println!("{}", format!("{} addresses:", scheme).bold());
+            for path in paths {
+                let (address, keypair) =
+                    derive_key_pair_from_mnemonic(phrase.trim(), &scheme, Some(path.clone()))?;
+                println!("  {:<28} {}  {}",
+                    path.to_string(),
+                    address.to_string().cyan(),
+                    keypair.public().encode_base64().dimmed()
+                );
+            }
+            Ok(())
+        }
+
+        crate::KeysAction::Rotate { node_id, old, epoch, scheme, alias, output } => {
+            if !path.exists() {
+                bail!("No keystore found at {}", path.display());
+            }
+
+            let scheme = SignatureScheme::from_str(&scheme)?;
+            let mut ks = open_keystore(&path)?;
+            let old_address = resolve_key(&ks, &old)?;
+            let old_key = ks.export(&old_address)?.clone();
+
+            let new_key = ks.generate(alias, scheme, None, None).await?;
+            let new_alias = ks.get_alias(&new_key.address)?;
+            let rotation = KeyRotation::new(&node_id, &old_key, ks.export(&new_key.address)?, epoch);
+            let json = serde_json::to_string_pretty(&rotation)?;
+
+            println!("{} Rotated key for {} from epoch {}",
+                "✓".green().bold(),
+                node_id.cyan(),
+                epoch
+            );
+            println!("  Old:     {}", old_address.to_string().dimmed());
+            println!("  New:     {} ({})", new_key.address.to_string().cyan(), new_alias.cyan());
+            match output {
+                Some(output) => {
+                    std::fs::write(&output, json)?;
+                    println!("  Rotation written to {}", output.cyan());
+                }
+                None => {
+                    println!();
+                    println!("{}", json);
+                }
+            }
+            println!();
+            println!("Submit the rotation to the network, and keep the old key until epoch {}.", epoch);
+            if let Some(mnemonic) = new_key.mnemonic {
+                println!();
+                println!("{} Write down the recovery phrase for the new key and keep it offline:",
+                    "!".yellow().bold()
+                );
+                println!("  {}", mnemonic);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Default keystore location, next to the config file
+fn default_keystore_path() -> PathBuf {
+    let home = dirs::home_dir().expect("Failed to get home directory");
+    home.join(".setu").join("setu.keystore")
+}
+
+/// Open the keystore, creating an encrypted one if it does not exist yet.
+fn open_or_create_keystore(path: &Path) -> Result<FileBasedKeystore> {
+    if path.exists() {
+        return open_keystore(path);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let passphrase = new_passphrase("New keystore passphrase: ")?;
+    Ok(FileBasedKeystore::load_or_create_encrypted(path, &passphrase)?)
+}
+
+/// Open a keystore, asking for the passphrase only if it is encrypted.
+fn open_keystore(path: &Path) -> Result<FileBasedKeystore> {
+    match FileBasedKeystore::load_or_create(path) {
+        Err(KeyError::PassphraseRequired(_)) => {
+            let passphrase = read_passphrase("Keystore passphrase: ")?;
+            Ok(FileBasedKeystore::load_or_create_encrypted(path, &passphrase)?)
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Read the passphrase from the environment or the terminal.
+fn read_passphrase(prompt: &str) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    Ok(rpassword::prompt_password(prompt)?)
+}
+
+/// Read a new passphrase, asking twice when prompting.
+fn new_passphrase(prompt: &str) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase = rpassword::prompt_password(prompt)?;
+    if passphrase.is_empty() {
+        bail!("Passphrase must not be empty");
+    }
+    let confirm = rpassword::prompt_password("Confirm passphrase: ")?;
+    if passphrase != confirm {
+        bail!("Passphrases do not match");
+    }
+    Ok(passphrase)
+}
+
+/// Resolve an alias or address to a key in the keystore.
+fn resolve_key(ks: &FileBasedKeystore, key: &str) -> Result<SetuAddress> {
+    Ok(ks.get_by_identity(&KeyIdentity::from_str(key)?)?)
+}
+
+/// Read a key file, asking for its passphrase if it is encrypted.
+fn read_key_file(path: &Path) -> Result<SetuKeyPair> {
+    match read_key(path) {
+        Err(KeyError::PassphraseRequired(_)) => {
+            let passphrase = rpassword::prompt_password("Key file passphrase: ")?;
+            Ok(read_encrypted_keypair_from_file(path, &passphrase)?)
+        }
+        result => Ok(result?),
+    }
+}
+
+fn parse_derivation_path(path: Option<String>) -> Result<Option<DerivationPath>> {
+    path.map(|p| {
+        DerivationPath::from_str(&p).map_err(|e| anyhow!("Invalid derivation path {}: {}", p, e))
+    })
+    .transpose()
+}
+
+/// Message bytes from hex `--data` or the contents of `--file`.
+fn read_data(data: Option<String>, file: Option<String>) -> Result<Vec<u8>> {
+    match (data, file) {
+        (_, Some(file)) => Ok(std::fs::read(file)?),
+        (Some(data), None) => Ok(hex::decode(data.trim_start_matches("0x"))?),
+        (None, None) => bail!("Either --data or --file is required"),
+    }
+}
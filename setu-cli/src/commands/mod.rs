@@ -1,6 +1,7 @@
 //! Command handlers
 
 pub mod config;
+pub mod keys;
 pub mod solver;
 pub mod validator;
 pub mod router;
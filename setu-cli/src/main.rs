@@ -5,6 +5,7 @@
 //!   setu solver register --id solver-1 --address 127.0.0.1 --port 8001 --shard shard-1
 //!   setu router status
 //!   setu transfer submit --from alice --to bob --amount 1000
+//!   setu keys new --alias validator-1
 
 mod commands;
 mod config;
@@ -65,6 +66,17 @@ enum Commands {
         #[command(subcommand)]
         action: ConfigAction,
     },
+    
+    /// Key management
+    #[command(aliases = ["key", "keytool"])]
+    Keys {
+        /// Keystore file path (default: ~/.setu/setu.keystore)
+        #[arg(long, global = true)]
+        keystore: Option<String>,
+        
+        #[command(subcommand)]
+        action: KeysAction,
+    },
 }
 
 #[derive(Subcommand)]
@@ -250,6 +262,153 @@ enum ConfigAction {
     },
 }
 
+#[derive(Subcommand)]
+enum KeysAction {
+    /// Generate a new key in the keystore
+    #[command(alias = "generate")]
+    New {
+        /// Alias for the key
+        #[arg(long)]
+        alias: Option<String>,
+        
+        /// Signature scheme (ed25519/secp256k1/secp256r1/bls12381)
+        #[arg(long, default_value = "ed25519")]
+        scheme: String,
+        
+        /// Derivation path (default: the scheme's standard path)
+        #[arg(long)]
+        derivation_path: Option<String>,
+        
+        /// Number of words in the recovery phrase (12/15/18/21/24)
+        #[arg(long)]
+        word_length: Option<String>,
+    },
+    
+    /// Import a key from a recovery phrase, a Base64 private key or a key file
+    Import {
+        /// Base64 `flag || privkey`, or a key file (Base64 or hex).
+        /// Prompts for a recovery phrase if omitted.
+        input: Option<String>,
+        
+        /// Alias for the key
+        #[arg(long)]
+        alias: Option<String>,
+        
+        /// Signature scheme when recovering from a phrase
+        #[arg(long, default_value = "ed25519")]
+        scheme: String,
+        
+        /// Derivation path when recovering from a phrase
+        #[arg(long)]
+        derivation_path: Option<String>,
+    },
+    
+    /// Export a private key as Base64 `flag || privkey`
+    Export {
+        /// Key to export (alias or address)
+        key: String,
+        
+        /// Write to a passphrase-encrypted key file instead of printing
+        #[arg(long)]
+        output: Option<String>,
+        
+        /// Write the key file unencrypted
+        #[arg(long, requires = "output")]
+        insecure_plaintext: bool,
+    },
+    
+    /// Write an encrypted backup of the keystore
+    Backup {
+        /// Backup file path
+        output: String,
+    },
+    
+    /// List keys in the keystore
+    List,
+    
+    /// Sign arbitrary bytes
+    Sign {
+        /// Signing key (alias or address)
+        #[arg(long)]
+        key: String,
+        
+        /// Hex-encoded data to sign
+        #[arg(long, conflicts_with = "file", required_unless_present = "file")]
+        data: Option<String>,
+        
+        /// Sign the contents of a file
+        #[arg(long)]
+        file: Option<String>,
+    },
+    
+    /// Verify a signature
+    Verify {
+        /// Base64 public key (`flag || pubkey`)
+        #[arg(long)]
+        public_key: String,
+        
+        /// Base64 signature (`flag || sig`)
+        #[arg(long)]
+        signature: String,
+        
+        /// Hex-encoded signed data
+        #[arg(long, conflicts_with = "file", required_unless_present = "file")]
+        data: Option<String>,
+        
+        /// Verify over the contents of a file
+        #[arg(long)]
+        file: Option<String>,
+    },
+    
+    /// Derive addresses from a recovery phrase without importing them
+    Derive {
+        /// Signature scheme (ed25519/secp256k1/secp256r1/bls12381)
+        #[arg(long, default_value = "ed25519")]
+        scheme: String,
+        
+        /// Derivation path (default: the first N standard paths)
+        #[arg(long)]
+        derivation_path: Option<String>,
+        
+        /// Number of standard paths to derive when no path is given
+        #[arg(long, default_value = "1")]
+        count: u32,
+    },
+    
+    /// Encrypt a plaintext keystore in place
+    Migrate,
+    
+    /// Change the keystore passphrase
+    ChangePassphrase,
+    
+    /// Rotate a node's key, producing a rotation signed by the old and new keys
+    Rotate {
+        /// Node ID the key belongs to
+        #[arg(long)]
+        node_id: String,
+        
+        /// Current key (alias or address)
+        #[arg(long)]
+        old: String,
+        
+        /// First epoch in which the new key is used
+        #[arg(long)]
+        epoch: u64,
+        
+        /// Signature scheme of the new key (ed25519/secp256k1/secp256r1/bls12381)
+        #[arg(long, default_value = "ed25519")]
+        scheme: String,
+        
+        /// Alias for the new key
+        #[arg(long)]
+        alias: Option<String>,
+        
+        /// Write the rotation to this file instead of stdout
+        #[arg(long)]
+        output: Option<String>,
+    },
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
@@ -284,6 +443,9 @@ async fn main() -> anyhow::Result<()> {
         Commands::Config { action } => {
             commands::config::handle(action).await?;
         }
+        Commands::Keys { keystore, action } => {
+            commands::keys::handle(action, keystore).await?;
+        }
     }
     
     Ok(())
@@ -2,10 +2,22 @@
 //!
 //! This module handles the actual execution of transfers,
 //! including state changes and result generation.
+//!
+//! Execution reads objects from an [`ObjectStore`] and records the objects it
+//! changes as [`StateChange`]s keyed `object:<id>`, carrying the object as read
+//! and as written. They are committed with the versions execution read them
+//! at: if another execution updated one of those objects in the meantime the
+//! commit fails with [`SetuError::VersionConflict`], and the event has to be
+//! re-executed against the new state or rejected.
 
 use core_types::Transfer;
-use setu_types::event::{ExecutionResult, StateChange};
-use tracing::{info, debug};
+use setu_storage::{ObjectChange, ObjectStore, StoredObject};
+use setu_types::event::{EventId, ExecutionResult, StateChange};
+use setu_types::{generate_object_id, Address, Coin, CoinData, Object, SetuError};
+use tracing::{info, debug, warn};
+
+/// Key prefix of state changes that carry a stored object
+const OBJECT_KEY_PREFIX: &str = "object:";
 
 /// Executor for transfer execution
 pub struct Executor {
@@ -18,7 +30,7 @@ impl Executor {
         Self { node_id }
     }
     
-    /// Execute a transfer in TEE environment
+    /// Execute a transfer for `event_id` in TEE environment, reading state from `store`
     /// 
     /// TODO: This is a placeholder implementation
     /// Future work:
@@ -26,7 +38,12 @@ impl Executor {
     /// 2. Execute transfer logic in secure enclave
     /// 3. Generate cryptographic proof
     /// 4. Return execution result with proof
-    pub async fn execute_in_tee(&self, transfer: &Transfer) -> anyhow::Result<ExecutionResult> {
+    pub async fn execute_in_tee<S: ObjectStore>(
+        &self,
+        store: &S,
+        event_id: &EventId,
+        transfer: &Transfer,
+    ) -> anyhow::Result<ExecutionResult> {
         info!(
             node_id = %self.node_id,
             transfer_id = %transfer.id,
@@ -38,7 +55,7 @@ impl Executor {
         
         // TODO: Replace with actual TEE execution
         // For now, simulate successful execution
-        let state_changes = self.compute_state_changes(transfer)?;
+        let state_changes = self.compute_state_changes(store, event_id, transfer)?;
         
         debug!(
             transfer_id = %transfer.id,
@@ -58,13 +75,17 @@ impl Executor {
     
     /// Compute state changes for a transfer
     /// 
-    /// TODO: This is a placeholder implementation
-    /// Future work:
-    /// 1. Read current state from storage
-    /// 2. Apply transfer logic
-    /// 3. Compute new state
-    /// 4. Generate state change records
-    fn compute_state_changes(&self, transfer: &Transfer) -> anyhow::Result<Vec<StateChange>> {
+    /// If the sender owns a coin holding the amount, the amount is split off
+    /// into a new coin for the recipient.
+    /// 
+    /// TODO: Senders without coins in the store still get simulated balance
+    /// changes, which are not backed by objects and never committed
+    fn compute_state_changes<S: ObjectStore>(
+        &self,
+        store: &S,
+        event_id: &EventId,
+        transfer: &Transfer,
+    ) -> anyhow::Result<Vec<StateChange>> {
         debug!(
             transfer_id = %transfer.id,
             from = %transfer.from,
@@ -73,8 +94,30 @@ impl Executor {
             "Computing state changes"
         );
         
-        // TODO: Replace with actual state computation
-        // For now, create placeholder state changes
+        let amount = u64::try_from(transfer.amount)?;
+        let coins = store.get_coins_by_owner(&Address::from_str_id(&transfer.from), None, None)?;
+        if let Some(mut coin) = coins.into_iter().find(|coin| coin.value() >= amount) {
+            let read = StoredObject::new(&coin)?;
+            let withdrawn = coin.data.balance.withdraw(amount).map_err(anyhow::Error::msg)?;
+            coin.increment_version();
+            
+            // Keyed by the event, so equal transfers create distinct coins
+            let id = generate_object_id(format!("coin:{}:{}", event_id, transfer.to).as_bytes());
+            let received: Coin = Object::new_owned(
+                id,
+                Address::from_str_id(&transfer.to),
+                CoinData {
+                    coin_type: coin.coin_type().clone(),
+                    balance: withdrawn,
+                },
+            );
+            
+            return Ok(vec![
+                object_state_change(Some(&read), Some(&StoredObject::new(&coin)?))?,
+                object_state_change(None, Some(&StoredObject::new(&received)?))?,
+            ]);
+        }
+        
         let changes = vec![
             StateChange {
                 key: format!("balance:{}", transfer.from),
@@ -96,30 +139,68 @@ impl Executor {
         Ok(changes)
     }
     
-    /// Apply state changes to local storage
+    /// Apply the state changes of `event_id` to `store`
     /// 
-    /// TODO: This is a placeholder implementation
-    /// Future work:
-    /// 1. Validate state changes
-    /// 2. Update local state storage
-    /// 3. Persist to disk
-    /// 4. Update indices
-    pub async fn apply_state_changes(&self, changes: &[StateChange]) -> anyhow::Result<()> {
+    /// Object changes are committed together through
+    /// [`commit_object_changes`](Self::commit_object_changes), so an object
+    /// updated since execution read it fails the whole event with a version
+    /// conflict. Simulated changes that carry no object are skipped.
+    pub async fn apply_state_changes<S: ObjectStore>(
+        &self,
+        store: &S,
+        event_id: &EventId,
+        changes: &[StateChange],
+    ) -> anyhow::Result<()> {
         info!(
             node_id = %self.node_id,
+            event_id = %event_id,
             changes_count = changes.len(),
-            "Applying state changes (simulated)"
+            "Applying state changes"
         );
         
-        // TODO: Replace with actual state application
+        let mut object_changes = Vec::new();
         for change in changes {
-            debug!(
-                key = %change.key,
-                "Applied state change"
-            );
+            match to_object_change(change)? {
+                Some(object_change) => object_changes.push(object_change),
+                None => debug!(key = %change.key, "Skipped simulated state change"),
+            }
+        }
+        if object_changes.is_empty() {
+            return Ok(());
+        }
+        self.commit_object_changes(store, event_id, &object_changes)
+    }
+    
+    /// Commit the object changes of an executed event
+    /// 
+    /// Changes are applied atomically. A version conflict is returned as a
+    /// [`SetuError::VersionConflict`], see [`is_version_conflict`].
+    pub fn commit_object_changes<S: ObjectStore>(
+        &self,
+        store: &S,
+        event_id: &EventId,
+        changes: &[ObjectChange],
+    ) -> anyhow::Result<()> {
+        match store.apply_event_changes(event_id, changes) {
+            Ok(()) => {
+                debug!(
+                    event_id = %event_id,
+                    changes_count = changes.len(),
+                    "Object changes committed"
+                );
+                Ok(())
+            }
+            Err(e @ SetuError::VersionConflict { .. }) => {
+                warn!(
+                    node_id = %self.node_id,
+                    event_id = %event_id,
+                    error = %e,
+                    "Object changed since execution read it"
+                );
+                Err(e.into())
+            }
+            Err(e) => Err(e.into()),
         }
-        
-        Ok(())
     }
     
     /// Validate execution result
@@ -158,10 +239,51 @@ impl Executor {
     }
 }
 
+/// State change recording an object as read (`None` if created) and as
+/// written (`None` if deleted)
+pub fn object_state_change(
+    old: Option<&StoredObject>,
+    new: Option<&StoredObject>,
+) -> anyhow::Result<StateChange> {
+    let id = old.or(new).map(|object| object.id).ok_or_else(|| anyhow::anyhow!("Empty object change"))?;
+    Ok(StateChange {
+        key: format!("{}{}", OBJECT_KEY_PREFIX, id),
+        old_value: old.map(bcs::to_bytes).transpose()?,
+        new_value: new.map(bcs::to_bytes).transpose()?,
+    })
+}
+
+/// The object change a state change records, `None` if it carries no object
+fn to_object_change(change: &StateChange) -> anyhow::Result<Option<ObjectChange>> {
+    if !change.key.starts_with(OBJECT_KEY_PREFIX) {
+        return Ok(None);
+    }
+    let old: Option<StoredObject> = change.old_value.as_deref().map(bcs::from_bytes).transpose()?;
+    let new: Option<StoredObject> = change.new_value.as_deref().map(bcs::from_bytes).transpose()?;
+    Ok(Some(match (old, new) {
+        // Only replaces the object if it is still the one execution read
+        (Some(old), Some(object)) => ObjectChange::Update {
+            object,
+            expected_version: old.version,
+            expected_digest: old.digest,
+        },
+        (None, Some(object)) => ObjectChange::Put(object),
+        (Some(old), None) => ObjectChange::Delete(old.id),
+        (None, None) => anyhow::bail!("State change {} carries no object", change.key),
+    }))
+}
+
+/// Whether an execution failed because an object it read was updated
+/// concurrently, in which case re-executing it may succeed
+pub fn is_version_conflict(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<SetuError>(), Some(SetuError::VersionConflict { .. }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use core_types::{Vlc, TransferType};
+    use setu_storage::{MemoryBackend, RocksObjectStore};
     
     fn create_test_transfer() -> Transfer {
         Transfer {
@@ -181,9 +303,10 @@ mod tests {
     #[tokio::test]
     async fn test_execute_in_tee() {
         let executor = Executor::new("test-solver".to_string());
+        let store = RocksObjectStore::new(MemoryBackend::new());
         let transfer = create_test_transfer();
         
-        let result = executor.execute_in_tee(&transfer).await;
+        let result = executor.execute_in_tee(&store, &"e1".to_string(), &transfer).await;
         assert!(result.is_ok());
         
         let execution_result = result.unwrap();
@@ -194,6 +317,7 @@ mod tests {
     #[tokio::test]
     async fn test_apply_state_changes() {
         let executor = Executor::new("test-solver".to_string());
+        let store = RocksObjectStore::new(MemoryBackend::new());
         let changes = vec![
             StateChange {
                 key: "balance:alice".to_string(),
@@ -202,10 +326,37 @@ mod tests {
             },
         ];
         
-        let result = executor.apply_state_changes(&changes).await;
+        let result = executor.apply_state_changes(&store, &"e1".to_string(), &changes).await;
         assert!(result.is_ok());
     }
     
+    #[tokio::test]
+    async fn test_transfer_splits_sender_coin() {
+        let executor = Executor::new("test-solver".to_string());
+        let store = RocksObjectStore::new(MemoryBackend::new());
+        let (alice, bob) = (Address::from_str_id("alice"), Address::from_str_id("bob"));
+        let coin = Coin::new(alice, 1000);
+        store.store_coin(&coin).unwrap();
+        
+        let event_id = "e1".to_string();
+        let result = executor.execute_in_tee(&store, &event_id, &create_test_transfer()).await.unwrap();
+        assert_eq!(result.state_changes.len(), 2);
+        // Nothing changes until the result is applied
+        assert_eq!(store.get_coin(&coin.metadata.id).unwrap().unwrap().value(), 1000);
+        
+        executor.apply_state_changes(&store, &event_id, &result.state_changes).await.unwrap();
+        let sent = store.get_coin(&coin.metadata.id).unwrap().unwrap();
+        assert_eq!(sent.value(), 900);
+        assert_eq!(sent.version(), coin.version() + 1);
+        let received = store.get_coins_by_owner(&bob, None, None).unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].value(), 100);
+        
+        // Applying the same result again conflicts with the applied one
+        let err = executor.apply_state_changes(&store, &event_id, &result.state_changes).await.unwrap_err();
+        assert!(is_version_conflict(&err));
+    }
+    
     #[test]
     fn test_validate_result() {
         let executor = Executor::new("test-solver".to_string());
@@ -232,5 +383,28 @@ mod tests {
         
         assert!(executor.validate_result(&invalid_result).is_err());
     }
+    
+    #[test]
+    fn test_commit_surfaces_version_conflict() {
+        let executor = Executor::new("test-solver".to_string());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = RocksObjectStore::open(temp_dir.path()).unwrap();
+        let coin = Coin::new(Address::from_str_id("alice"), 1000);
+        let (version, digest) = (coin.version(), *coin.digest());
+        store.store_coin(&coin).unwrap();
+        
+        // Both events executed against the same version of the coin
+        let mut first = coin.clone();
+        first.transfer_to(Address::from_str_id("bob"));
+        let mut second = coin.clone();
+        second.transfer_to(Address::from_str_id("carol"));
+        
+        let changes = [ObjectChange::update(&first, version, digest).unwrap()];
+        executor.commit_object_changes(&store, &"e1".to_string(), &changes).unwrap();
+        
+        let changes = [ObjectChange::update(&second, version, digest).unwrap()];
+        let err = executor.commit_object_changes(&store, &"e2".to_string(), &changes).unwrap_err();
+        assert!(is_version_conflict(&err));
+        assert!(!is_version_conflict(&anyhow::anyhow!("other failure")));
+    }
 }
-
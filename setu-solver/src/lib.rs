@@ -10,20 +10,24 @@ mod executor;
 mod dependency;
 mod tee;
 
-pub use executor::Executor;
+pub use executor::{Executor, is_version_conflict};
 pub use dependency::{DependencyTracker, DependencyStats};
 pub use tee::{TeeEnvironment, TeeProof, EnclaveInfo};
 
 use core_types::Transfer;
 use setu_core::{NodeConfig, ShardManager};
+use setu_storage::{MemoryBackend, ObjectStore, RocksObjectStore};
 use setu_types::event::{Event, EventType, EventId};
 use setu_vlc::{VLCSnapshot, VectorClock};
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use tracing::{info, error, debug};
+use tracing::{info, error, debug, warn};
 
-/// Solver node
-pub struct Solver {
+/// How often an event is executed before a version conflict rejects it
+const MAX_EXECUTION_ATTEMPTS: usize = 3;
+
+/// Solver node, executing against the objects in `S`
+pub struct Solver<S: ObjectStore = RocksObjectStore<MemoryBackend>> {
     config: NodeConfig,
     shard_manager: Arc<ShardManager>,
     transfer_rx: mpsc::UnboundedReceiver<Transfer>,
@@ -36,14 +40,29 @@ pub struct Solver {
     tee: TeeEnvironment,
     /// Current VLC state
     vlc: VectorClock,
+    /// Objects transfers are executed against and applied to
+    object_store: S,
 }
 
 impl Solver {
-    /// Create a new solver with channels
+    /// Create a new solver with channels and an in-memory object store
     pub fn new(
         config: NodeConfig,
         transfer_rx: mpsc::UnboundedReceiver<Transfer>,
         event_tx: mpsc::UnboundedSender<Event>,
+    ) -> Self {
+        let object_store = RocksObjectStore::new(MemoryBackend::new());
+        Self::with_object_store(config, transfer_rx, event_tx, object_store)
+    }
+}
+
+impl<S: ObjectStore> Solver<S> {
+    /// Create a new solver with channels, executing against `object_store`
+    pub fn with_object_store(
+        config: NodeConfig,
+        transfer_rx: mpsc::UnboundedReceiver<Transfer>,
+        event_tx: mpsc::UnboundedSender<Event>,
+        object_store: S,
     ) -> Self {
         info!(
             node_id = %config.node_id,
@@ -65,6 +84,7 @@ impl Solver {
             dependency_tracker,
             tee,
             vlc,
+            object_store,
         }
     }
     
@@ -123,30 +143,7 @@ impl Solver {
             "Dependencies resolved"
         );
         
-        // Step 2: Execute in TEE
-        let execution_result = self.execute_in_tee(transfer).await?;
-        debug!(
-            transfer_id = %transfer.id,
-            success = execution_result.success,
-            "TEE execution completed"
-        );
-        
-        // Step 3: Apply state changes
-        self.apply_state_changes(&execution_result.state_changes).await?;
-        debug!(
-            transfer_id = %transfer.id,
-            changes_count = execution_result.state_changes.len(),
-            "State changes applied"
-        );
-        
-        // Step 4: Generate TEE proof
-        let _proof = self.generate_proof(transfer, &execution_result).await?;
-        debug!(
-            transfer_id = %transfer.id,
-            "TEE proof generated"
-        );
-        
-        // Step 5: Update VLC
+        // Step 2: Update VLC
         let vlc_snapshot = self.update_vlc(transfer);
         debug!(
             transfer_id = %transfer.id,
@@ -154,7 +151,7 @@ impl Solver {
             "VLC updated"
         );
         
-        // Step 6: Create event
+        // Step 3: Create event, whose ID the applied changes are recorded under
         let mut event = Event::new(
             EventType::Transfer,
             parent_ids.clone(),
@@ -162,6 +159,17 @@ impl Solver {
             self.config.node_id.clone(),
         );
         
+        // Steps 4-5: Execute and apply, re-executing if state read by the
+        // execution changed before it was applied
+        let execution_result = self.execute_and_apply(&event.id, transfer).await?;
+        
+        // Step 6: Generate TEE proof
+        let _proof = self.generate_proof(transfer, &execution_result).await?;
+        debug!(
+            transfer_id = %transfer.id,
+            "TEE proof generated"
+        );
+        
         // Attach transfer and execution result
         event = event.with_transfer(setu_types::event::Transfer {
             from: transfer.from.clone(),
@@ -191,19 +199,75 @@ impl Solver {
         Ok(event)
     }
     
+    /// Execute a transfer in TEE and apply its state changes
+    /// 
+    /// On a version conflict the transfer is executed again against the new
+    /// state, up to [`MAX_EXECUTION_ATTEMPTS`] times before it is rejected.
+    async fn execute_and_apply(
+        &self,
+        event_id: &EventId,
+        transfer: &Transfer,
+    ) -> anyhow::Result<setu_types::event::ExecutionResult> {
+        let mut attempt = 1;
+        loop {
+            let execution_result = self.execute_in_tee(event_id, transfer).await?;
+            debug!(
+                transfer_id = %transfer.id,
+                success = execution_result.success,
+                attempt,
+                "TEE execution completed"
+            );
+            
+            match self.apply_state_changes(event_id, &execution_result.state_changes).await {
+                Ok(()) => {
+                    debug!(
+                        transfer_id = %transfer.id,
+                        changes_count = execution_result.state_changes.len(),
+                        "State changes applied"
+                    );
+                    return Ok(execution_result);
+                }
+                Err(e) if is_version_conflict(&e) && attempt < MAX_EXECUTION_ATTEMPTS => {
+                    warn!(
+                        transfer_id = %transfer.id,
+                        attempt,
+                        error = %e,
+                        "Version conflict, re-executing transfer"
+                    );
+                    attempt += 1;
+                }
+                Err(e) if is_version_conflict(&e) => {
+                    return Err(e.context(format!(
+                        "Transfer {} rejected after {} conflicting executions",
+                        transfer.id, attempt
+                    )));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    
     /// Find dependencies for a transfer
     async fn find_dependencies(&self, transfer: &Transfer) -> Vec<EventId> {
         self.dependency_tracker.find_dependencies(transfer).await
     }
     
     /// Execute transfer in TEE environment
-    async fn execute_in_tee(&self, transfer: &Transfer) -> anyhow::Result<setu_types::event::ExecutionResult> {
-        self.executor.execute_in_tee(transfer).await
+    async fn execute_in_tee(
+        &self,
+        event_id: &EventId,
+        transfer: &Transfer,
+    ) -> anyhow::Result<setu_types::event::ExecutionResult> {
+        self.executor.execute_in_tee(&self.object_store, event_id, transfer).await
     }
     
-    /// Apply state changes to local storage
-    async fn apply_state_changes(&self, changes: &[setu_types::event::StateChange]) -> anyhow::Result<()> {
-        self.executor.apply_state_changes(changes).await
+    /// Apply state changes to the object store
+    async fn apply_state_changes(
+        &self,
+        event_id: &EventId,
+        changes: &[setu_types::event::StateChange],
+    ) -> anyhow::Result<()> {
+        self.executor.apply_state_changes(&self.object_store, event_id, changes).await
     }
     
     /// Generate TEE proof for execution
@@ -243,4 +307,141 @@ impl Solver {
     pub fn node_id(&self) -> &str {
         &self.config.node_id
     }
+    
+    /// Objects the solver executes against
+    pub fn object_store(&self) -> &S {
+        &self.object_store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core_types::{TransferType, Vlc};
+    use setu_storage::{ObjectChange, ObjectVersionRecord, StoredObject};
+    use setu_types::{Address, Anchor, Coin, ObjectId, SetuResult};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    
+    /// Object store where another writer updates every object an event is
+    /// about to update, for the next `contended_commits` commits
+    struct ContendedStore {
+        inner: RocksObjectStore<MemoryBackend>,
+        contended_commits: AtomicUsize,
+    }
+    
+    impl ObjectStore for ContendedStore {
+        fn get_stored(&self, id: &ObjectId) -> SetuResult<Option<StoredObject>> {
+            self.inner.get_stored(id)
+        }
+        
+        fn get_stored_by_owner(
+            &self,
+            owner: &Address,
+            type_tag: &str,
+            cursor: Option<ObjectId>,
+            limit: Option<usize>,
+        ) -> SetuResult<Vec<StoredObject>> {
+            self.inner.get_stored_by_owner(owner, type_tag, cursor, limit)
+        }
+        
+        fn get_stored_by_index(&self, type_tag: &str, index: &str, key: &Address) -> SetuResult<Vec<StoredObject>> {
+            self.inner.get_stored_by_index(type_tag, index, key)
+        }
+        
+        fn apply_changes(&self, changes: &[ObjectChange]) -> SetuResult<()> {
+            self.inner.apply_changes(changes)
+        }
+        
+        fn apply_event_changes(&self, event_id: &EventId, changes: &[ObjectChange]) -> SetuResult<()> {
+            let contended = self.contended_commits
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if contended {
+                for change in changes {
+                    if let ObjectChange::Update { object, .. } = change {
+                        let mut coin = self.inner.get_coin(&object.id)?.unwrap();
+                        coin.increment_version();
+                        self.inner.store_coin(&coin)?;
+                    }
+                }
+            }
+            self.inner.apply_event_changes(event_id, changes)
+        }
+        
+        fn get_version_history(&self, id: &ObjectId) -> SetuResult<Vec<ObjectVersionRecord>> {
+            self.inner.get_version_history(id)
+        }
+        
+        fn get_stored_at_version(&self, id: &ObjectId, version: u64) -> SetuResult<Option<StoredObject>> {
+            self.inner.get_stored_at_version(id, version)
+        }
+        
+        fn get_stored_as_of_anchor(&self, id: &ObjectId, anchor: &Anchor) -> SetuResult<Option<StoredObject>> {
+            self.inner.get_stored_as_of_anchor(id, anchor)
+        }
+    }
+    
+    fn create_solver(contended_commits: usize) -> (Solver<ContendedStore>, Coin) {
+        let store = ContendedStore {
+            inner: RocksObjectStore::new(MemoryBackend::new()),
+            contended_commits: AtomicUsize::new(contended_commits),
+        };
+        let coin = Coin::new(Address::from_str_id("alice"), 1000);
+        store.store_coin(&coin).unwrap();
+        
+        let (_transfer_tx, transfer_rx) = mpsc::unbounded_channel();
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        let solver = Solver::with_object_store(NodeConfig::default(), transfer_rx, event_tx, store);
+        (solver, coin)
+    }
+    
+    fn create_transfer() -> Transfer {
+        Transfer {
+            id: "transfer-1".to_string(),
+            from: "alice".to_string(),
+            to: "bob".to_string(),
+            amount: 100,
+            transfer_type: TransferType::FluxTransfer,
+            resources: vec![],
+            vlc: Vlc::new(),
+            power: 0,
+            preferred_solver: None,
+            shard_id: None,
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_conflict_is_re_executed() {
+        let (mut solver, coin) = create_solver(MAX_EXECUTION_ATTEMPTS - 1);
+        
+        let event = solver.execute_transfer(&create_transfer()).await.unwrap();
+        
+        // Executed against the coin as the other writer left it
+        let sent = solver.object_store().get_coin(&coin.metadata.id).unwrap().unwrap();
+        assert_eq!(sent.value(), 900);
+        assert_eq!(sent.version(), coin.version() + MAX_EXECUTION_ATTEMPTS as u64);
+        let history = solver.object_store().get_version_history(&coin.metadata.id).unwrap();
+        assert_eq!(history.last().unwrap().produced_by, Some(event.id));
+        let received = solver.object_store()
+            .get_coins_by_owner(&Address::from_str_id("bob"), None, None)
+            .unwrap();
+        assert_eq!(received[0].value(), 100);
+    }
+    
+    #[tokio::test]
+    async fn test_conflict_rejects_after_max_attempts() {
+        let (mut solver, coin) = create_solver(MAX_EXECUTION_ATTEMPTS);
+        
+        let err = solver.execute_transfer(&create_transfer()).await.unwrap_err();
+        assert!(is_version_conflict(&err));
+        assert!(err.to_string().contains("rejected"));
+        
+        // Only the other writer's updates were applied
+        let coin_now = solver.object_store().get_coin(&coin.metadata.id).unwrap().unwrap();
+        assert_eq!(coin_now.value(), 1000);
+        assert!(solver.object_store()
+            .get_coins_by_owner(&Address::from_str_id("bob"), None, None)
+            .unwrap()
+            .is_empty());
+    }
 }
@@ -4,8 +4,13 @@ use setu_types::{
     SetuError, SetuResult,
 };
 use setu_consensus::{ConsensusEngine, ConsensusMessage, ValidatorSet};
+use setu_keys::{DoubleSignGuard, LocalSigner, SetuKeyPair, Signer};
 use setu_network::{NetworkConfig, NetworkService, NetworkEvent, NetworkClient, PeerRole};
-use setu_storage::{StateStore, EventStore, AnchorStore, CFStore};
+use setu_storage::{
+    AnchorStore, CFStore, EventStore, MemoryAnchorStore, MemoryCFStore, MemoryEventStore,
+    RocksAnchorStore, RocksCFStore, RocksDBConfig, RocksEventStore, SetuDB, StateStore,
+};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock};
 
@@ -15,6 +20,14 @@ use crate::verifier::{EventVerifier, CFVerifier};
 pub struct ValidatorConfig {
     pub consensus: ConsensusConfig,
     pub network: NetworkConfig,
+    /// RocksDB holding events, anchors and frames; kept in memory if `None`
+    pub storage: Option<RocksDBConfig>,
+    /// Ed25519 key file of the node identity, which signs consensus votes;
+    /// votes are unsigned and unchecked if `None`
+    pub key_file: Option<PathBuf>,
+    /// Double-sign guard of the vote signer; defaults to the key file with a
+    /// `.guard` extension
+    pub guard_file: Option<PathBuf>,
 }
 
 impl Default for ValidatorConfig {
@@ -22,8 +35,39 @@ impl Default for ValidatorConfig {
         Self {
             consensus: ConsensusConfig::default(),
             network: NetworkConfig::default(),
+            storage: None,
+            key_file: None,
+            guard_file: None,
+        }
+    }
+}
+
+/// Event, anchor and frame stores of a validator
+struct Stores {
+    event_store: Arc<dyn EventStore>,
+    anchor_store: Arc<dyn AnchorStore>,
+    cf_store: Arc<dyn CFStore>,
+}
+
+impl Stores {
+    fn in_memory() -> Self {
+        Self {
+            event_store: Arc::new(MemoryEventStore::new()),
+            anchor_store: Arc::new(MemoryAnchorStore::new()),
+            cf_store: Arc::new(MemoryCFStore::new()),
         }
     }
+
+    /// Open the stores on one shared database
+    fn open(config: RocksDBConfig) -> SetuResult<Self> {
+        let db = SetuDB::open(config)
+            .map_err(|e| SetuError::StorageError(format!("Failed to open database: {}", e)))?;
+        Ok(Self {
+            event_store: Arc::new(RocksEventStore::new(db.clone())),
+            anchor_store: Arc::new(RocksAnchorStore::new(db.clone())?),
+            cf_store: Arc::new(RocksCFStore::new(db)?),
+        })
+    }
 }
 
 pub struct Validator {
@@ -33,30 +77,72 @@ pub struct Validator {
     consensus_engine: Arc<ConsensusEngine>,
     network: Option<Arc<NetworkService>>,
     state_store: Arc<StateStore>,
-    event_store: Arc<EventStore>,
-    anchor_store: Arc<AnchorStore>,
-    cf_store: Arc<CFStore>,
+    event_store: Arc<dyn EventStore>,
+    anchor_store: Arc<dyn AnchorStore>,
+    cf_store: Arc<dyn CFStore>,
     event_verifier: Arc<EventVerifier>,
     cf_verifier: Arc<CFVerifier>,
     running: Arc<RwLock<bool>>,
 }
 
 impl Validator {
+    /// Create a validator with the default config, keeping its stores in memory
     pub fn new(node_info: NodeInfo, is_leader: bool) -> Self {
-        Self::with_config(node_info, is_leader, ValidatorConfig::default())
+        Self::with_stores(node_info, is_leader, ValidatorConfig::default(), Stores::in_memory(), None)
     }
 
-    pub fn with_config(node_info: NodeInfo, is_leader: bool, config: ValidatorConfig) -> Self {
+    /// Create a validator, opening its stores as `config.storage` says and
+    /// signing votes with the identity in `config.key_file`
+    pub fn with_config(mut node_info: NodeInfo, is_leader: bool, config: ValidatorConfig) -> SetuResult<Self> {
+        let stores = match config.storage.clone() {
+            Some(db_config) => Stores::open(db_config)?,
+            None => Stores::in_memory(),
+        };
+        let signer = match &config.key_file {
+            Some(key_file) => {
+                let keypair = setu_network_anemo::identity::load_identity(key_file)
+                    .map_err(|e| SetuError::InvalidData(format!("Failed to load node key: {}", e)))?;
+                let guard_file = config
+                    .guard_file
+                    .clone()
+                    .unwrap_or_else(|| key_file.with_extension("guard"));
+                let guard = DoubleSignGuard::open(&guard_file)
+                    .map_err(|e| SetuError::InvalidData(format!("Failed to open double-sign guard: {}", e)))?;
+                Some(Self::register_identity(&mut node_info, keypair, guard))
+            }
+            None => None,
+        };
+        Ok(Self::with_stores(node_info, is_leader, config, stores, signer))
+    }
+
+    /// Record the identity's public key as the node's key and make its vote signer
+    fn register_identity(node_info: &mut NodeInfo, keypair: SetuKeyPair, guard: DoubleSignGuard) -> Arc<dyn Signer> {
+        let pk = keypair.public();
+        node_info.public_key = [vec![pk.scheme().flag()], pk.as_bytes()].concat();
+        Arc::new(LocalSigner::with_guard(keypair, guard))
+    }
+
+    fn with_stores(
+        node_info: NodeInfo,
+        is_leader: bool,
+        config: ValidatorConfig,
+        stores: Stores,
+        signer: Option<Arc<dyn Signer>>,
+    ) -> Self {
         let validator_info = ValidatorInfo::new(node_info.clone(), is_leader);
         
         let mut validator_set = ValidatorSet::new();
         validator_set.add_validator(validator_info.clone());
 
-        let consensus_engine = Arc::new(ConsensusEngine::new(
+        let mut consensus_engine = ConsensusEngine::new(
             config.consensus.clone(),
             node_info.id.clone(),
             validator_set,
-        ));
+        );
+        if let Some(signer) = signer {
+            consensus_engine = consensus_engine.with_signer(signer);
+        }
+        let consensus_engine = Arc::new(consensus_engine);
 
         Self {
             config: config.clone(),
@@ -65,9 +151,9 @@ impl Validator {
             consensus_engine,
             network: None,
             state_store: Arc::new(StateStore::new()),
-            event_store: Arc::new(EventStore::new()),
-            anchor_store: Arc::new(AnchorStore::new()),
-            cf_store: Arc::new(CFStore::new()),
+            event_store: stores.event_store,
+            anchor_store: stores.anchor_store,
+            cf_store: stores.cf_store,
             event_verifier: Arc::new(EventVerifier::new(node_info.id.clone())),
             cf_verifier: Arc::new(CFVerifier::new(
                 node_info.id.clone(),
@@ -129,7 +215,7 @@ impl Validator {
                             let _ = consensus_engine.receive_vote(vote).await;
                         }
                         NetworkEvent::CFFinalized { cf, .. } => {
-                            cf_store.mark_finalized(&cf.id).await;
+                            let _ = cf_store.mark_finalized(&cf.id).await;
                             tracing::info!("CF finalized: {}", cf.id);
                         }
                         _ => {}
@@ -182,8 +268,8 @@ impl Validator {
     pub async fn get_stats(&self) -> ValidatorStats {
         let validator_info = self.validator_info.read().await;
         let dag_stats = self.consensus_engine.get_dag_stats().await;
-        let finalized_cfs = self.cf_store.finalized_count().await;
-        let pending_cfs = self.cf_store.pending_count().await;
+        let finalized_cfs = self.cf_store.finalized_count().await.unwrap_or(0);
+        let pending_cfs = self.cf_store.pending_count().await.unwrap_or(0);
 
         ValidatorStats {
             node_id: self.node_info.id.clone(),
@@ -193,7 +279,7 @@ impl Validator {
             dag_max_depth: dag_stats.max_depth,
             finalized_cfs,
             pending_cfs,
-            event_count: self.event_store.count().await,
+            event_count: self.event_store.count().await.unwrap_or(0),
         }
     }
 
@@ -254,4 +340,63 @@ mod tests {
         let stats = validator.get_stats().await;
         assert_eq!(stats.dag_node_count, 1);
     }
+
+    #[tokio::test]
+    async fn test_rocksdb_storage() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = ValidatorConfig {
+            storage: Some(RocksDBConfig::new(temp_dir.path())),
+            ..Default::default()
+        };
+        let node = NodeInfo::new_validator("v1".to_string(), "127.0.0.1".to_string(), 8000);
+        let event = Event::genesis(
+            "v1".to_string(),
+            VLCSnapshot {
+                vector_clock: VectorClock::new(),
+                logical_time: 0,
+                physical_time: 0,
+            },
+        );
+
+        {
+            let validator = Validator::with_config(node.clone(), true, config.clone()).unwrap();
+            validator.submit_event(event).await.unwrap();
+        }
+
+        // Events outlive the validator
+        let validator = Validator::with_config(node, true, config).unwrap();
+        assert_eq!(validator.get_stats().await.event_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_identity_signs_votes() {
+        use setu_keys::{write_keypair_to_file, SetuAddress, SignatureScheme};
+        use setu_network_anemo::identity::{address_of, peer_id_of};
+        use setu_network_anemo::{NodeInfo as DiscoveryInfo, NodeType};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let key_file = temp_dir.path().join("node.key");
+        let keypair = SetuKeyPair::generate(SignatureScheme::ED25519);
+        write_keypair_to_file(&keypair, &key_file).unwrap();
+        let config = ValidatorConfig {
+            key_file: Some(key_file),
+            ..Default::default()
+        };
+        let node = NodeInfo::new_validator("v1".to_string(), "127.0.0.1".to_string(), 8000);
+        let validator = Validator::with_config(node, true, config).unwrap();
+
+        // Network, discovery and votes all speak for the key in the key file
+        let peer_id = peer_id_of(&keypair.public()).unwrap();
+        let discovery = DiscoveryInfo::new("v1".to_string(), peer_id, vec![], NodeType::Validator).sign(&keypair);
+        assert!(discovery.verify_peer());
+        let vote_signer = SetuAddress::from(&validator.consensus_engine.signer().unwrap().public_key().await.unwrap());
+        assert_eq!(address_of(&peer_id).unwrap(), keypair.address());
+        assert_eq!(discovery.signer_address(), Some(keypair.address()));
+        assert_eq!(vote_signer, keypair.address());
+
+        // The identity is the node's registered key, so its votes verify
+        let vote = Vote::new("v1".to_string(), "cf".to_string(), true);
+        let vote = validator.consensus_engine.sign_vote(vote).await.unwrap();
+        assert!(validator.consensus_engine.receive_vote(vote).await.is_ok());
+    }
 }
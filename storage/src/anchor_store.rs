@@ -1,23 +1,77 @@
+use async_trait::async_trait;
 use setu_types::{Anchor, AnchorId, ConsensusFrame, CFId, CFStatus, SetuResult};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Storage for the anchor chain
+#[async_trait]
+pub trait AnchorStore: Send + Sync {
+    /// Append an anchor to the chain
+    async fn store(&self, anchor: Anchor) -> SetuResult<()>;
+    
+    /// Get an anchor by ID
+    async fn get(&self, anchor_id: &AnchorId) -> SetuResult<Option<Anchor>>;
+    
+    /// Get the most recently stored anchor
+    async fn get_latest(&self) -> SetuResult<Option<Anchor>>;
+    
+    /// Get the anchor at a depth
+    async fn get_by_depth(&self, depth: u64) -> SetuResult<Option<Anchor>>;
+    
+    /// Number of stored anchors
+    async fn count(&self) -> SetuResult<usize>;
+    
+    /// Anchor IDs in the order they were stored
+    async fn get_chain(&self) -> SetuResult<Vec<AnchorId>>;
+}
+
+/// Storage for consensus frames, tracking which are pending and finalized
+#[async_trait]
+pub trait CFStore: Send + Sync {
+    /// Store a frame as pending, or as finalized if it already is
+    async fn store(&self, cf: ConsensusFrame) -> SetuResult<()>;
+    
+    /// Get a frame by ID
+    async fn get(&self, cf_id: &CFId) -> SetuResult<Option<ConsensusFrame>>;
+    
+    /// Finalize a pending frame
+    async fn mark_finalized(&self, cf_id: &CFId) -> SetuResult<()>;
+    
+    /// Frames that are not finalized yet
+    async fn get_pending(&self) -> SetuResult<Vec<ConsensusFrame>>;
+    
+    /// Finalized frames in finalization order
+    async fn get_finalized(&self) -> SetuResult<Vec<ConsensusFrame>>;
+    
+    /// Most recently finalized frame
+    async fn latest_finalized(&self) -> SetuResult<Option<ConsensusFrame>>;
+    
+    /// Number of finalized frames
+    async fn finalized_count(&self) -> SetuResult<usize>;
+    
+    /// Number of pending frames
+    async fn pending_count(&self) -> SetuResult<usize>;
+}
+
 #[derive(Debug)]
-pub struct AnchorStore {
+pub struct MemoryAnchorStore {
     anchors: Arc<RwLock<HashMap<AnchorId, Anchor>>>,
     chain: Arc<RwLock<Vec<AnchorId>>>,
 }
 
-impl AnchorStore {
+impl MemoryAnchorStore {
     pub fn new() -> Self {
         Self {
             anchors: Arc::new(RwLock::new(HashMap::new())),
             chain: Arc::new(RwLock::new(Vec::new())),
         }
     }
+}
 
-    pub async fn store(&self, anchor: Anchor) -> SetuResult<()> {
+#[async_trait]
+impl AnchorStore for MemoryAnchorStore {
+    async fn store(&self, anchor: Anchor) -> SetuResult<()> {
         let anchor_id = anchor.id.clone();
 
         let mut anchors = self.anchors.write().await;
@@ -29,33 +83,33 @@ impl AnchorStore {
         Ok(())
     }
 
-    pub async fn get(&self, anchor_id: &AnchorId) -> Option<Anchor> {
+    async fn get(&self, anchor_id: &AnchorId) -> SetuResult<Option<Anchor>> {
         let anchors = self.anchors.read().await;
-        anchors.get(anchor_id).cloned()
+        Ok(anchors.get(anchor_id).cloned())
     }
 
-    pub async fn get_latest(&self) -> Option<Anchor> {
+    async fn get_latest(&self) -> SetuResult<Option<Anchor>> {
         let chain = self.chain.read().await;
         let anchors = self.anchors.read().await;
 
-        chain.last().and_then(|id| anchors.get(id).cloned())
+        Ok(chain.last().and_then(|id| anchors.get(id).cloned()))
     }
 
-    pub async fn get_by_depth(&self, depth: u64) -> Option<Anchor> {
+    async fn get_by_depth(&self, depth: u64) -> SetuResult<Option<Anchor>> {
         let anchors = self.anchors.read().await;
-        anchors.values().find(|a| a.depth == depth).cloned()
+        Ok(anchors.values().find(|a| a.depth == depth).cloned())
     }
 
-    pub async fn count(&self) -> usize {
-        self.anchors.read().await.len()
+    async fn count(&self) -> SetuResult<usize> {
+        Ok(self.anchors.read().await.len())
     }
 
-    pub async fn get_chain(&self) -> Vec<AnchorId> {
-        self.chain.read().await.clone()
+    async fn get_chain(&self) -> SetuResult<Vec<AnchorId>> {
+        Ok(self.chain.read().await.clone())
     }
 }
 
-impl Clone for AnchorStore {
+impl Clone for MemoryAnchorStore {
     fn clone(&self) -> Self {
         Self {
             anchors: Arc::clone(&self.anchors),
@@ -64,20 +118,20 @@ impl Clone for AnchorStore {
     }
 }
 
-impl Default for AnchorStore {
+impl Default for MemoryAnchorStore {
     fn default() -> Self {
         Self::new()
     }
 }
 
 #[derive(Debug)]
-pub struct CFStore {
+pub struct MemoryCFStore {
     frames: Arc<RwLock<HashMap<CFId, ConsensusFrame>>>,
     pending: Arc<RwLock<Vec<CFId>>>,
     finalized: Arc<RwLock<Vec<CFId>>>,
 }
 
-impl CFStore {
+impl MemoryCFStore {
     pub fn new() -> Self {
         Self {
             frames: Arc::new(RwLock::new(HashMap::new())),
@@ -85,8 +139,11 @@ impl CFStore {
             finalized: Arc::new(RwLock::new(Vec::new())),
         }
     }
+}
 
-    pub async fn store(&self, cf: ConsensusFrame) -> SetuResult<()> {
+#[async_trait]
+impl CFStore for MemoryCFStore {
+    async fn store(&self, cf: ConsensusFrame) -> SetuResult<()> {
         let cf_id = cf.id.clone();
         let is_finalized = cf.status == CFStatus::Finalized;
 
@@ -104,12 +161,12 @@ impl CFStore {
         Ok(())
     }
 
-    pub async fn get(&self, cf_id: &CFId) -> Option<ConsensusFrame> {
+    async fn get(&self, cf_id: &CFId) -> SetuResult<Option<ConsensusFrame>> {
         let frames = self.frames.read().await;
-        frames.get(cf_id).cloned()
+        Ok(frames.get(cf_id).cloned())
     }
 
-    pub async fn mark_finalized(&self, cf_id: &CFId) {
+    async fn mark_finalized(&self, cf_id: &CFId) -> SetuResult<()> {
         {
             let mut frames = self.frames.write().await;
             if let Some(cf) = frames.get_mut(cf_id) {
@@ -122,45 +179,47 @@ impl CFStore {
 
         let mut finalized = self.finalized.write().await;
         finalized.push(cf_id.clone());
+        
+        Ok(())
     }
 
-    pub async fn get_pending(&self) -> Vec<ConsensusFrame> {
+    async fn get_pending(&self) -> SetuResult<Vec<ConsensusFrame>> {
         let pending = self.pending.read().await;
         let frames = self.frames.read().await;
 
-        pending
+        Ok(pending
             .iter()
             .filter_map(|id| frames.get(id).cloned())
-            .collect()
+            .collect())
     }
 
-    pub async fn get_finalized(&self) -> Vec<ConsensusFrame> {
+    async fn get_finalized(&self) -> SetuResult<Vec<ConsensusFrame>> {
         let finalized = self.finalized.read().await;
         let frames = self.frames.read().await;
 
-        finalized
+        Ok(finalized
             .iter()
             .filter_map(|id| frames.get(id).cloned())
-            .collect()
+            .collect())
     }
 
-    pub async fn latest_finalized(&self) -> Option<ConsensusFrame> {
+    async fn latest_finalized(&self) -> SetuResult<Option<ConsensusFrame>> {
         let finalized = self.finalized.read().await;
         let frames = self.frames.read().await;
 
-        finalized.last().and_then(|id| frames.get(id).cloned())
+        Ok(finalized.last().and_then(|id| frames.get(id).cloned()))
     }
 
-    pub async fn finalized_count(&self) -> usize {
-        self.finalized.read().await.len()
+    async fn finalized_count(&self) -> SetuResult<usize> {
+        Ok(self.finalized.read().await.len())
     }
 
-    pub async fn pending_count(&self) -> usize {
-        self.pending.read().await.len()
+    async fn pending_count(&self) -> SetuResult<usize> {
+        Ok(self.pending.read().await.len())
     }
 }
 
-impl Clone for CFStore {
+impl Clone for MemoryCFStore {
     fn clone(&self) -> Self {
         Self {
             frames: Arc::clone(&self.frames),
@@ -170,7 +229,7 @@ impl Clone for CFStore {
     }
 }
 
-impl Default for CFStore {
+impl Default for MemoryCFStore {
     fn default() -> Self {
         Self::new()
     }
@@ -191,13 +250,14 @@ mod tests {
             },
             format!("state_root_{}", depth),
             None,
+            String::new(),
             depth,
         )
     }
 
     #[tokio::test]
     async fn test_anchor_store() {
-        let store = AnchorStore::new();
+        let store = MemoryAnchorStore::new();
         
         let anchor1 = create_anchor(0);
         let anchor2 = create_anchor(1);
@@ -205,25 +265,25 @@ mod tests {
         store.store(anchor1).await.unwrap();
         store.store(anchor2.clone()).await.unwrap();
 
-        assert_eq!(store.count().await, 2);
+        assert_eq!(store.count().await.unwrap(), 2);
         
-        let latest = store.get_latest().await.unwrap();
+        let latest = store.get_latest().await.unwrap().unwrap();
         assert_eq!(latest.depth, 1);
     }
 
     #[tokio::test]
     async fn test_cf_store() {
-        let store = CFStore::new();
+        let store = MemoryCFStore::new();
         
         let anchor = create_anchor(0);
         let cf = ConsensusFrame::new(anchor, "validator1".to_string());
         let cf_id = cf.id.clone();
 
         store.store(cf).await.unwrap();
-        assert_eq!(store.pending_count().await, 1);
+        assert_eq!(store.pending_count().await.unwrap(), 1);
 
-        store.mark_finalized(&cf_id).await;
-        assert_eq!(store.pending_count().await, 0);
-        assert_eq!(store.finalized_count().await, 1);
+        store.mark_finalized(&cf_id).await.unwrap();
+        assert_eq!(store.pending_count().await.unwrap(), 0);
+        assert_eq!(store.finalized_count().await.unwrap(), 1);
     }
 }
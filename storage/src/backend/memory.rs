@@ -0,0 +1,153 @@
+//! In-memory implementation of KvBackend
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::{KvBackend, KvBatch, KvEntry, KvIter};
+use crate::rocks::ColumnFamily;
+use crate::rocks::error::Result;
+
+type Tables = HashMap<ColumnFamily, BTreeMap<Vec<u8>, Vec<u8>>>;
+
+/// Column families kept in `BTreeMap`s, ordered by key bytes like RocksDB.
+///
+/// Nothing is persisted. Clones share the same data, so several stores can
+/// work on one in-memory database the way they share a [`SetuDB`](crate::SetuDB).
+/// Iterators walk a copy of the entries taken when they were created.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBackend {
+    tables: Arc<RwLock<Tables>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Tables> {
+        self.tables.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Tables> {
+        self.tables.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Copy the entries of `cf` from `start` while `keep` holds into an iterator
+    fn collect_range<'a>(
+        &self,
+        cf: ColumnFamily,
+        start: &[u8],
+        keep: impl Fn(&[u8]) -> bool,
+    ) -> KvIter<'a> {
+        let entries: Vec<KvEntry> = match self.read().get(&cf) {
+            Some(table) => table
+                .range::<[u8], _>((Bound::Included(start), Bound::Unbounded))
+                .take_while(|(key, _)| keep(key))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            None => Vec::new(),
+        };
+        Box::new(entries.into_iter().map(Ok))
+    }
+}
+
+impl KvBackend for MemoryBackend {
+    fn get_raw(&self, cf: ColumnFamily, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.read().get(&cf).and_then(|table| table.get(key).cloned()))
+    }
+
+    fn multi_get_raw(&self, cf: ColumnFamily, keys: &[Vec<u8>]) -> Result<Vec<Option<Vec<u8>>>> {
+        let tables = self.read();
+        let table = tables.get(&cf);
+        Ok(keys
+            .iter()
+            .map(|key| table.and_then(|table| table.get(key).cloned()))
+            .collect())
+    }
+
+    fn put_raw(&self, cf: ColumnFamily, key: &[u8], value: &[u8]) -> Result<()> {
+        self.write().entry(cf).or_default().insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete_raw(&self, cf: ColumnFamily, key: &[u8]) -> Result<()> {
+        if let Some(table) = self.write().get_mut(&cf) {
+            table.remove(key);
+        }
+        Ok(())
+    }
+
+    fn write_batch(&self, batch: KvBatch) -> Result<()> {
+        // One write lock for the whole batch, so readers never see part of it
+        let mut tables = self.write();
+        for (cf, key, value) in batch.into_ops() {
+            let table = tables.entry(cf).or_default();
+            match value {
+                Some(value) => {
+                    table.insert(key, value);
+                }
+                None => {
+                    table.remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn iter_raw(&self, cf: ColumnFamily, start: &[u8]) -> Result<KvIter<'_>> {
+        Ok(self.collect_range(cf, start, |_| true))
+    }
+
+    fn prefix_iter_raw(&self, cf: ColumnFamily, prefix: &[u8], start: &[u8]) -> Result<KvIter<'_>> {
+        Ok(self.collect_range(cf, start, |key| key.starts_with(prefix)))
+    }
+
+    fn last_raw(&self, cf: ColumnFamily) -> Result<Option<KvEntry>> {
+        Ok(self
+            .read()
+            .get(&cf)
+            .and_then(|table| table.last_key_value())
+            .map(|(key, value)| (key.clone(), value.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordered_prefix_iteration() {
+        let backend = MemoryBackend::new();
+        for (owner, id) in [(2u64, 1u64), (1, 2), (1, 1), (3, 1)] {
+            backend.put(ColumnFamily::Objects, &(owner, id), &id).unwrap();
+        }
+
+        let entries: Vec<((u64, u64), u64)> = backend
+            .prefix_iter(ColumnFamily::Objects, &1u64)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(entries, vec![((1, 1), 1), ((1, 2), 2)]);
+        assert_eq!(backend.count(ColumnFamily::Objects).unwrap(), 4);
+        assert_eq!(backend.count(ColumnFamily::Events).unwrap(), 0);
+
+        let last: Option<((u64, u64), u64)> = backend.last(ColumnFamily::Objects).unwrap();
+        assert_eq!(last, Some(((3, 1), 1)));
+    }
+
+    #[test]
+    fn test_batch_and_shared_clones() {
+        let backend = MemoryBackend::new();
+        let clone = backend.clone();
+        backend.put(ColumnFamily::Objects, &1u64, &"one".to_string()).unwrap();
+
+        let mut batch = clone.batch();
+        clone.batch_put(&mut batch, ColumnFamily::Objects, &2u64, &"two".to_string()).unwrap();
+        clone.batch_delete(&mut batch, ColumnFamily::Objects, &1u64).unwrap();
+        clone.write_batch(batch).unwrap();
+
+        let values: Vec<Option<String>> = backend.multi_get(ColumnFamily::Objects, &[1u64, 2]).unwrap();
+        assert_eq!(values, vec![None, Some("two".to_string())]);
+    }
+}
@@ -0,0 +1,272 @@
+//! Key-value backends for storage
+//!
+//! [`KvBackend`] is what a store needs from its database: point reads and
+//! writes, atomic batches and ordered iteration, per column family. Backends
+//! only move bytes; the typed methods encode keys with bincode's standard
+//! config and values with BCS, so every backend stores exactly the same bytes.
+//!
+//! Bincode encodes integers as little-endian varints, so the bytes of an
+//! integer key do not sort by its value. Tuple keys still share the encoding
+//! of their leading fields, which is what prefix scans rely on, but keys that
+//! must iterate in numeric order have to be big-endian byte arrays such as
+//! `u64::to_be_bytes()`.
+//!
+//! - [`SetuDB`](crate::SetuDB): RocksDB
+//! - [`MemoryBackend`]: `BTreeMap`s, for tests and simulations
+
+mod memory;
+
+pub use memory::MemoryBackend;
+
+use bincode::Encode;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::rocks::{ColumnFamily, StorageError, Transaction};
+use crate::rocks::error::Result;
+
+/// An encoded key and value
+pub type KvEntry = (Vec<u8>, Vec<u8>);
+
+/// Iterator over encoded entries in key order
+pub type KvIter<'a> = Box<dyn Iterator<Item = Result<KvEntry>> + 'a>;
+
+/// Writes applied atomically by [`KvBackend::write_batch`]
+#[derive(Debug, Clone, Default)]
+pub struct KvBatch {
+    /// `(cf, key, value)` in order, `None` for deletes
+    ops: Vec<(ColumnFamily, Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl KvBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put_raw(&mut self, cf: ColumnFamily, key: Vec<u8>, value: Vec<u8>) {
+        self.ops.push((cf, key, Some(value)));
+    }
+
+    pub fn delete_raw(&mut self, cf: ColumnFamily, key: Vec<u8>) {
+        self.ops.push((cf, key, None));
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// The operations in the order they were added
+    pub fn into_ops(self) -> Vec<(ColumnFamily, Vec<u8>, Option<Vec<u8>>)> {
+        self.ops
+    }
+}
+
+/// Serialize a key using bincode
+pub(crate) fn encode_key<K: Encode>(key: &K) -> Result<Vec<u8>> {
+    bincode::encode_to_vec(key, bincode::config::standard())
+        .map_err(|e| StorageError::Serialization(e.to_string()))
+}
+
+/// Deserialize a key using bincode
+pub(crate) fn decode_key<K: bincode::Decode<()>>(bytes: &[u8]) -> Result<K> {
+    bincode::decode_from_slice(bytes, bincode::config::standard())
+        .map(|(key, _)| key)
+        .map_err(|e| StorageError::Deserialization(e.to_string()))
+}
+
+/// Serialize a value using BCS (Binary Canonical Serialization)
+pub(crate) fn encode_value<V: Serialize>(value: &V) -> Result<Vec<u8>> {
+    bcs::to_bytes(value)
+        .map_err(|e| StorageError::Serialization(e.to_string()))
+}
+
+/// Deserialize a value using BCS
+pub(crate) fn decode_value<V: DeserializeOwned>(bytes: &[u8]) -> Result<V> {
+    bcs::from_bytes(bytes)
+        .map_err(|e| StorageError::Deserialization(e.to_string()))
+}
+
+fn decode_entry<K, V>(entry: Result<KvEntry>) -> Result<(K, V)>
+where
+    K: bincode::Decode<()>,
+    V: DeserializeOwned,
+{
+    let (key_bytes, value_bytes) = entry?;
+    Ok((decode_key(&key_bytes)?, decode_value(&value_bytes)?))
+}
+
+/// Ordered key-value storage split into column families.
+///
+/// Implementors provide the byte-level `*_raw` operations and
+/// [`write_batch`](Self::write_batch); the typed operations are built on them.
+/// Clones must share the same underlying data.
+pub trait KvBackend: Clone + Send + Sync {
+    /// Get the value stored under `key`
+    fn get_raw(&self, cf: ColumnFamily, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Get the values stored under several keys, in the order of `keys`
+    fn multi_get_raw(&self, cf: ColumnFamily, keys: &[Vec<u8>]) -> Result<Vec<Option<Vec<u8>>>>;
+
+    fn put_raw(&self, cf: ColumnFamily, key: &[u8], value: &[u8]) -> Result<()>;
+
+    fn delete_raw(&self, cf: ColumnFamily, key: &[u8]) -> Result<()>;
+
+    /// Apply all operations of a batch atomically
+    fn write_batch(&self, batch: KvBatch) -> Result<()>;
+
+    /// Entries with keys at or after `start`, in key order
+    fn iter_raw(&self, cf: ColumnFamily, start: &[u8]) -> Result<KvIter<'_>>;
+
+    /// Entries with keys starting with `prefix`, from the first key at or
+    /// after `start`, in key order
+    fn prefix_iter_raw(&self, cf: ColumnFamily, prefix: &[u8], start: &[u8]) -> Result<KvIter<'_>> {
+        let prefix = prefix.to_vec();
+        Ok(Box::new(self.iter_raw(cf, start)?.take_while(move |entry| match entry {
+            Ok((key_bytes, _)) => key_bytes.starts_with(&prefix),
+            Err(_) => true,
+        })))
+    }
+
+    /// The entry with the largest key
+    fn last_raw(&self, cf: ColumnFamily) -> Result<Option<KvEntry>>;
+
+    /// Put a key-value pair into a column family
+    fn put<K, V>(&self, cf: ColumnFamily, key: &K, value: &V) -> Result<()>
+    where
+        K: Encode,
+        V: Serialize,
+    {
+        self.put_raw(cf, &encode_key(key)?, &encode_value(value)?)
+    }
+
+    /// Get a value by key from a column family
+    fn get<K, V>(&self, cf: ColumnFamily, key: &K) -> Result<Option<V>>
+    where
+        K: Encode,
+        V: DeserializeOwned,
+    {
+        match self.get_raw(cf, &encode_key(key)?)? {
+            Some(bytes) => Ok(Some(decode_value(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Delete a key from a column family
+    fn delete<K: Encode>(&self, cf: ColumnFamily, key: &K) -> Result<()> {
+        self.delete_raw(cf, &encode_key(key)?)
+    }
+
+    /// Check if a key exists in a column family
+    fn exists<K: Encode>(&self, cf: ColumnFamily, key: &K) -> Result<bool> {
+        Ok(self.get_raw(cf, &encode_key(key)?)?.is_some())
+    }
+
+    /// Multi-get: retrieve multiple values at once
+    fn multi_get<K, V>(&self, cf: ColumnFamily, keys: &[K]) -> Result<Vec<Option<V>>>
+    where
+        K: Encode,
+        V: DeserializeOwned,
+    {
+        let key_bytes: Vec<_> = keys.iter().map(encode_key).collect::<Result<_>>()?;
+        self.multi_get_raw(cf, &key_bytes)?
+            .into_iter()
+            .map(|value| value.map(|bytes| decode_value(&bytes)).transpose())
+            .collect()
+    }
+
+    /// Create a new write batch
+    fn batch(&self) -> KvBatch {
+        KvBatch::new()
+    }
+
+    /// Add a put operation to a write batch
+    fn batch_put<K, V>(&self, batch: &mut KvBatch, cf: ColumnFamily, key: &K, value: &V) -> Result<()>
+    where
+        K: Encode,
+        V: Serialize,
+    {
+        batch.put_raw(cf, encode_key(key)?, encode_value(value)?);
+        Ok(())
+    }
+
+    /// Add a delete operation to a write batch
+    fn batch_delete<K: Encode>(&self, batch: &mut KvBatch, cf: ColumnFamily, key: &K) -> Result<()> {
+        batch.delete_raw(cf, encode_key(key)?);
+        Ok(())
+    }
+
+    /// Start a transaction whose writes are committed in one batch
+    fn transaction(&self) -> Transaction<'_, Self> {
+        Transaction::new(self)
+    }
+
+    /// Iterate over all key-value pairs in a column family
+    fn iter<'a, K, V>(&'a self, cf: ColumnFamily) -> Result<impl Iterator<Item = Result<(K, V)>> + 'a>
+    where
+        K: DeserializeOwned + bincode::Decode<()> + 'a,
+        V: DeserializeOwned + 'a,
+    {
+        Ok(self.iter_raw(cf, &[])?.map(decode_entry))
+    }
+
+    /// Iterate over the keys starting with the encoding of `prefix`
+    fn prefix_iter<'a, P, K, V>(&'a self, cf: ColumnFamily, prefix: &P) -> Result<impl Iterator<Item = Result<(K, V)>> + 'a>
+    where
+        P: Encode,
+        K: DeserializeOwned + bincode::Decode<()> + 'a,
+        V: DeserializeOwned + 'a,
+    {
+        self.prefix_iter_from(cf, prefix, prefix)
+    }
+
+    /// Iterate over the keys starting with the encoding of `prefix`, from the
+    /// first key at or after `start`
+    fn prefix_iter_from<'a, P, S, K, V>(
+        &'a self,
+        cf: ColumnFamily,
+        prefix: &P,
+        start: &S,
+    ) -> Result<impl Iterator<Item = Result<(K, V)>> + 'a>
+    where
+        P: Encode,
+        S: Encode,
+        K: DeserializeOwned + bincode::Decode<()> + 'a,
+        V: DeserializeOwned + 'a,
+    {
+        let iter = self.prefix_iter_raw(cf, &encode_key(prefix)?, &encode_key(start)?)?;
+        Ok(iter.map(decode_entry))
+    }
+
+    /// Get the entry with the largest key in a column family
+    fn last<K, V>(&self, cf: ColumnFamily) -> Result<Option<(K, V)>>
+    where
+        K: DeserializeOwned + bincode::Decode<()>,
+        V: DeserializeOwned,
+    {
+        self.last_raw(cf)?.map(|entry| decode_entry(Ok(entry))).transpose()
+    }
+
+    /// Count the keys in a column family
+    fn count(&self, cf: ColumnFamily) -> Result<usize> {
+        let mut count = 0;
+        for entry in self.iter_raw(cf, &[])? {
+            entry?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Count the keys starting with the encoding of `prefix`
+    fn count_prefix<P: Encode>(&self, cf: ColumnFamily, prefix: &P) -> Result<usize> {
+        let prefix_bytes = encode_key(prefix)?;
+        let mut count = 0;
+        for entry in self.prefix_iter_raw(cf, &prefix_bytes, &prefix_bytes)? {
+            entry?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
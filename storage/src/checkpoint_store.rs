@@ -0,0 +1,371 @@
+//! Finalized-anchor checkpoints for fast node bootstrap
+//!
+//! A checkpoint is the object state as of a finalized anchor. Its state root
+//! is the sparse Merkle root over the objects, keyed by object ID, and must
+//! equal the anchor's `state_root`.
+//!
+//! Checkpoints are exported as a stream: a magic header and format version,
+//! the BCS-encoded [`Checkpoint`], then every object as a length-prefixed BCS
+//! [`StoredObject`]. Importing takes the finalized anchor the node already
+//! trusts, recomputes the state root from the streamed objects and refuses the
+//! archive unless it is for that anchor and matches its state root, so a new
+//! node can trust the state without replaying the DAG. The anchor carried in
+//! the archive is never trusted on its own.
+
+use crate::object_store::{ObjectStore, ObjectChange, ObjectVersionRecord, StoredObject};
+use crate::rocks::{SetuDB, ColumnFamily, KvBackend, StorageError};
+use crate::rocks_object_store::{RocksObjectStore, WriteContext};
+use serde::{Deserialize, Serialize};
+use setu_merkle::{HashValue, SparseMerkleTree};
+use setu_types::{Anchor, ObjectId, SetuResult, SetuError};
+use std::io::{Read, Write};
+
+/// First bytes of every checkpoint archive
+pub const CHECKPOINT_MAGIC: [u8; 8] = *b"SETUCKPT";
+
+/// Archive layout version; bump when the stream format changes
+pub const CHECKPOINT_FORMAT_VERSION: u32 = 1;
+
+/// Upper bound on one encoded record, to reject corrupt length prefixes
+const MAX_RECORD_LEN: usize = 64 * 1024 * 1024;
+
+/// Object state committed to by a finalized anchor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub anchor: Anchor,
+    pub object_count: u64,
+}
+
+impl Checkpoint {
+    pub fn depth(&self) -> u64 {
+        self.anchor.depth
+    }
+
+    pub fn state_root(&self) -> &str {
+        &self.anchor.state_root
+    }
+}
+
+/// Hex sparse Merkle root over `objects`, keyed by object ID
+pub fn compute_state_root<'a>(objects: impl IntoIterator<Item = &'a StoredObject>) -> SetuResult<String> {
+    let mut tree = SparseMerkleTree::new();
+    for object in objects {
+        let value = bcs::to_bytes(object).map_err(|e| SetuError::InvalidData(e.to_string()))?;
+        tree.insert(HashValue::new(*object.id.as_bytes()), value);
+    }
+    Ok(hex::encode(tree.root().as_bytes()))
+}
+
+/// Checkpoints persisted in the `checkpoints` column family, keyed by anchor depth
+pub struct RocksCheckpointStore {
+    db: SetuDB,
+    objects: RocksObjectStore,
+}
+
+fn to_setu_error(e: StorageError) -> SetuError {
+    SetuError::StorageError(e.to_string())
+}
+
+fn io_error(e: std::io::Error) -> SetuError {
+    SetuError::StorageError(format!("Checkpoint stream: {}", e))
+}
+
+impl RocksCheckpointStore {
+    pub fn new(db: SetuDB) -> Self {
+        Self {
+            objects: RocksObjectStore::new(db.clone()),
+            db,
+        }
+    }
+
+    pub fn open(path: impl AsRef<std::path::Path>) -> SetuResult<Self> {
+        let db = SetuDB::open_default(path)
+            .map_err(|e| SetuError::StorageError(format!("Failed to open database: {}", e)))?;
+        Ok(Self::new(db))
+    }
+
+    /// Record a checkpoint of the object state as of a finalized anchor.
+    ///
+    /// The state is read from the object version history, so it stays
+    /// available only while the versions it needs are retained.
+    pub fn create(&self, anchor: &Anchor) -> SetuResult<Checkpoint> {
+        let objects = self.objects_as_of(anchor)?;
+        verify_state_root(anchor, &objects)?;
+
+        let checkpoint = Checkpoint {
+            anchor: anchor.clone(),
+            object_count: objects.len() as u64,
+        };
+        self.db.put(ColumnFamily::Checkpoints, &anchor.depth.to_be_bytes(), &checkpoint)
+            .map_err(to_setu_error)?;
+        Ok(checkpoint)
+    }
+
+    pub fn get(&self, depth: u64) -> SetuResult<Option<Checkpoint>> {
+        self.db.get(ColumnFamily::Checkpoints, &depth.to_be_bytes()).map_err(to_setu_error)
+    }
+
+    pub fn get_latest(&self) -> SetuResult<Option<Checkpoint>> {
+        let last: Option<([u8; 8], Checkpoint)> = self.db.last(ColumnFamily::Checkpoints)
+            .map_err(to_setu_error)?;
+        Ok(last.map(|(_, checkpoint)| checkpoint))
+    }
+
+    /// Stream the checkpoint at `depth` to `out`
+    pub fn export<W: Write>(&self, depth: u64, mut out: W) -> SetuResult<Checkpoint> {
+        let checkpoint = self.get(depth)?
+            .ok_or_else(|| SetuError::NotFound(format!("Checkpoint at depth {}", depth)))?;
+        let objects = self.objects_as_of(&checkpoint.anchor)?;
+        // Fails if versions the checkpoint needs have been pruned since
+        verify_state_root(&checkpoint.anchor, &objects)?;
+
+        out.write_all(&CHECKPOINT_MAGIC).map_err(io_error)?;
+        out.write_all(&CHECKPOINT_FORMAT_VERSION.to_le_bytes()).map_err(io_error)?;
+        write_record(&mut out, &checkpoint)?;
+        for object in &objects {
+            write_record(&mut out, object)?;
+        }
+        out.flush().map_err(io_error)?;
+        Ok(checkpoint)
+    }
+
+    /// Load a checkpoint streamed by [`export`](Self::export) into this database.
+    ///
+    /// `trusted` is the finalized anchor the caller already trusts, e.g. from
+    /// a finality proof. The archive must be for that anchor and its objects
+    /// must hash to the anchor's state root. The database must hold no objects
+    /// yet; storing the anchor in the node's `AnchorStore` is left to the caller.
+    pub fn import<R: Read>(&self, mut input: R, trusted: &Anchor) -> SetuResult<Checkpoint> {
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic).map_err(io_error)?;
+        if magic != CHECKPOINT_MAGIC {
+            return Err(SetuError::InvalidData("Not a checkpoint archive".to_string()));
+        }
+        let mut version = [0u8; 4];
+        input.read_exact(&mut version).map_err(io_error)?;
+        let version = u32::from_le_bytes(version);
+        if version != CHECKPOINT_FORMAT_VERSION {
+            return Err(SetuError::InvalidData(format!(
+                "Unsupported checkpoint format version {}", version
+            )));
+        }
+
+        let archived: Checkpoint = read_record(&mut input)?;
+        if archived.anchor.id != trusted.id {
+            return Err(SetuError::InvalidData(format!(
+                "Checkpoint is for anchor {}, expected {}",
+                archived.anchor.id, trusted.id
+            )));
+        }
+        let objects = (0..archived.object_count)
+            .map(|_| read_record::<StoredObject, _>(&mut input))
+            .collect::<SetuResult<Vec<_>>>()?;
+        // Only the trusted anchor's state root counts, not the archived copy
+        verify_state_root(trusted, &objects)?;
+        let checkpoint = Checkpoint {
+            anchor: trusted.clone(),
+            object_count: archived.object_count,
+        };
+
+        if self.db.count(ColumnFamily::Objects).map_err(to_setu_error)? > 0 {
+            return Err(SetuError::InvalidData("Cannot import a checkpoint into a database with objects".to_string()));
+        }
+        let ctx = WriteContext {
+            anchor_depth: Some(checkpoint.depth()),
+            ..Default::default()
+        };
+        let mut txn = self.db.transaction();
+        for object in objects {
+            RocksObjectStore::stage(&mut txn, &ObjectChange::Put(object), &ctx)?;
+        }
+        txn.put(ColumnFamily::Checkpoints, &checkpoint.depth().to_be_bytes(), &checkpoint)
+            .map_err(to_setu_error)?;
+        txn.commit().map_err(to_setu_error)?;
+        Ok(checkpoint)
+    }
+
+    /// Every object that exists as of `anchor`, in ID order
+    fn objects_as_of(&self, anchor: &Anchor) -> SetuResult<Vec<StoredObject>> {
+        let mut ids: Vec<ObjectId> = Vec::new();
+        let versions = self.db
+            .iter::<(ObjectId, [u8; 8]), ObjectVersionRecord>(ColumnFamily::ObjectVersions)
+            .map_err(to_setu_error)?;
+        for entry in versions {
+            let ((id, _), _) = entry.map_err(to_setu_error)?;
+            if ids.last() != Some(&id) {
+                ids.push(id);
+            }
+        }
+
+        let mut objects = Vec::new();
+        for id in ids {
+            if let Some(object) = self.objects.get_stored_as_of_anchor(&id, anchor)? {
+                objects.push(object);
+            }
+        }
+        Ok(objects)
+    }
+}
+
+fn verify_state_root(anchor: &Anchor, objects: &[StoredObject]) -> SetuResult<()> {
+    let state_root = compute_state_root(objects)?;
+    if state_root != anchor.state_root {
+        return Err(SetuError::InvalidData(format!(
+            "State root {} does not match anchor {} state root {}",
+            state_root, anchor.id, anchor.state_root
+        )));
+    }
+    Ok(())
+}
+
+fn write_record<T: Serialize, W: Write>(out: &mut W, record: &T) -> SetuResult<()> {
+    let bytes = bcs::to_bytes(record).map_err(|e| SetuError::InvalidData(e.to_string()))?;
+    out.write_all(&(bytes.len() as u32).to_le_bytes()).map_err(io_error)?;
+    out.write_all(&bytes).map_err(io_error)
+}
+
+fn read_record<T: serde::de::DeserializeOwned, R: Read>(input: &mut R) -> SetuResult<T> {
+    let mut len = [0u8; 4];
+    input.read_exact(&mut len).map_err(io_error)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_RECORD_LEN {
+        return Err(SetuError::InvalidData(format!("Checkpoint record of {} bytes", len)));
+    }
+    let mut bytes = vec![0u8; len];
+    input.read_exact(&mut bytes).map_err(io_error)?;
+    bcs::from_bytes(&bytes).map_err(|e| SetuError::InvalidData(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnchorStore, RocksAnchorStore};
+    use setu_types::{Address, Coin, VectorClock, VLCSnapshot};
+    use tempfile::TempDir;
+
+    fn create_anchor(event_ids: &[&str], state_root: String, depth: u64) -> Anchor {
+        Anchor::new(
+            event_ids.iter().map(|id| id.to_string()).collect(),
+            VLCSnapshot {
+                vector_clock: VectorClock::new(),
+                logical_time: depth,
+                physical_time: depth,
+            },
+            state_root,
+            None,
+            String::new(),
+            depth,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_export_import() {
+        let source_dir = TempDir::new().unwrap();
+        let db = SetuDB::open_default(source_dir.path()).unwrap();
+        let objects = RocksObjectStore::new(db.clone());
+        let anchors = RocksAnchorStore::new(db.clone()).unwrap();
+        let checkpoints = RocksCheckpointStore::new(db);
+
+        let alice = Address::from_str_id("alice");
+        let mut coin = Coin::new(alice, 1000);
+        let other = Coin::new(alice, 50);
+        objects.apply_event_changes(&"e1".to_string(), &[
+            ObjectChange::put(&coin).unwrap(),
+            ObjectChange::put(&other).unwrap(),
+        ]).unwrap();
+        let state_root = compute_state_root(&[StoredObject::new(&coin).unwrap(), StoredObject::new(&other).unwrap()]).unwrap();
+        let anchor = create_anchor(&["e1"], state_root, 0);
+        anchors.store(anchor.clone()).await.unwrap();
+
+        // A later, not yet anchored change is not part of the checkpoint
+        coin.transfer_to(Address::from_str_id("bob"));
+        objects.apply_event_changes(&"e2".to_string(), &[ObjectChange::put(&coin).unwrap()]).unwrap();
+
+        checkpoints.create(&anchor).unwrap();
+        assert_eq!(checkpoints.get_latest().unwrap().unwrap().object_count, 2);
+        let mut archive = Vec::new();
+        checkpoints.export(0, &mut archive).unwrap();
+
+        let target_dir = TempDir::new().unwrap();
+        let target = RocksCheckpointStore::open(target_dir.path()).unwrap();
+        let imported = target.import(archive.as_slice(), &anchor).unwrap();
+        assert_eq!(imported.anchor.id, anchor.id);
+        assert_eq!(target.get(0).unwrap().unwrap().object_count, 2);
+
+        let restored = target.objects.get_coin(&coin.metadata.id).unwrap().unwrap();
+        assert!(restored.is_owned_by(&alice));
+        assert_eq!(target.objects.get_coins_by_owner(&alice, None, None).unwrap().len(), 2);
+
+        // Imported objects count as finalized under the anchor
+        assert_eq!(target.create(&anchor).unwrap().object_count, 2);
+        
+        // Importing twice would mix two states
+        assert!(target.import(archive.as_slice(), &anchor).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_mismatched_state_root() {
+        let source_dir = TempDir::new().unwrap();
+        let checkpoints = RocksCheckpointStore::open(source_dir.path()).unwrap();
+        let anchors = RocksAnchorStore::new(checkpoints.db.clone()).unwrap();
+        let coin = Coin::new(Address::from_str_id("alice"), 1000);
+        checkpoints.objects.apply_event_changes(&"e1".to_string(), &[ObjectChange::put(&coin).unwrap()]).unwrap();
+
+        let anchor = create_anchor(&["e1"], "not_the_state_root".to_string(), 0);
+        anchors.store(anchor.clone()).await.unwrap();
+        assert!(checkpoints.create(&anchor).is_err());
+
+        // A tampered archive fails verification on import
+        let state_root = compute_state_root(&[StoredObject::new(&coin).unwrap()]).unwrap();
+        let anchor = create_anchor(&[], state_root, 1);
+        anchors.store(anchor.clone()).await.unwrap();
+        checkpoints.create(&anchor).unwrap();
+        let mut archive = Vec::new();
+        checkpoints.export(1, &mut archive).unwrap();
+        let last = archive.len() - 1;
+        archive[last] ^= 0xff;
+
+        let target_dir = TempDir::new().unwrap();
+        let target = RocksCheckpointStore::open(target_dir.path()).unwrap();
+        assert!(target.import(archive.as_slice(), &anchor).is_err());
+        assert_eq!(target.db.count(ColumnFamily::Objects).unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_forged_anchor() {
+        let coin = Coin::new(Address::from_str_id("alice"), 1000);
+        let state_root = compute_state_root(&[StoredObject::new(&coin).unwrap()]).unwrap();
+        let trusted = create_anchor(&["e1"], state_root, 0);
+
+        // A self-consistent archive for an anchor made up by the exporter
+        let forged_dir = TempDir::new().unwrap();
+        let forged_store = RocksCheckpointStore::open(forged_dir.path()).unwrap();
+        let forged_anchors = RocksAnchorStore::new(forged_store.db.clone()).unwrap();
+        let forged_coin = Coin::new(Address::from_str_id("mallory"), 1_000_000);
+        forged_store.objects.apply_event_changes(&"e1".to_string(), &[ObjectChange::put(&forged_coin).unwrap()]).unwrap();
+        let forged_root = compute_state_root(&[StoredObject::new(&forged_coin).unwrap()]).unwrap();
+        let forged = create_anchor(&["e1"], forged_root, 0);
+        assert!(forged.verify_id());
+        forged_anchors.store(forged.clone()).await.unwrap();
+        forged_store.create(&forged).unwrap();
+        let mut archive = Vec::new();
+        forged_store.export(0, &mut archive).unwrap();
+
+        let target_dir = TempDir::new().unwrap();
+        let target = RocksCheckpointStore::open(target_dir.path()).unwrap();
+        assert!(target.import(archive.as_slice(), &trusted).is_err());
+        assert_eq!(target.db.count(ColumnFamily::Objects).unwrap(), 0);
+
+        // Claiming the trusted anchor's ID does not help: the objects must
+        // still match the trusted state root
+        let mut claimed = forged;
+        claimed.id = trusted.id.clone();
+        let mut archive = Vec::new();
+        archive.extend_from_slice(&CHECKPOINT_MAGIC);
+        archive.extend_from_slice(&CHECKPOINT_FORMAT_VERSION.to_le_bytes());
+        write_record(&mut archive, &Checkpoint { anchor: claimed, object_count: 1 }).unwrap();
+        write_record(&mut archive, &StoredObject::new(&forged_coin).unwrap()).unwrap();
+        assert!(target.import(archive.as_slice(), &trusted).is_err());
+        assert_eq!(target.db.count(ColumnFamily::Objects).unwrap(), 0);
+    }
+}
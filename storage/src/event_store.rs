@@ -1,16 +1,48 @@
+use async_trait::async_trait;
 use setu_types::{Event, EventId, EventStatus, SetuResult};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Storage for DAG events, indexed by creator and status
+#[async_trait]
+pub trait EventStore: Send + Sync {
+    /// Store an event and update indexes
+    async fn store(&self, event: Event) -> SetuResult<()>;
+    
+    /// Get an event by ID
+    async fn get(&self, event_id: &EventId) -> SetuResult<Option<Event>>;
+    
+    /// Get the events that exist among `event_ids`, in order
+    async fn get_many(&self, event_ids: &[EventId]) -> SetuResult<Vec<Event>>;
+    
+    /// Change the status of an event; unknown events are ignored
+    async fn update_status(&self, event_id: &EventId, new_status: EventStatus) -> SetuResult<()>;
+    
+    /// Get all events created by a node
+    async fn get_by_creator(&self, creator: &str) -> SetuResult<Vec<Event>>;
+    
+    /// Get all events with a status
+    async fn get_by_status(&self, status: EventStatus) -> SetuResult<Vec<Event>>;
+    
+    /// Number of stored events
+    async fn count(&self) -> SetuResult<usize>;
+    
+    /// Number of events with a status
+    async fn count_by_status(&self, status: EventStatus) -> SetuResult<usize>;
+    
+    /// Check whether an event is stored
+    async fn exists(&self, event_id: &EventId) -> SetuResult<bool>;
+}
+
 #[derive(Debug)]
-pub struct EventStore {
+pub struct MemoryEventStore {
     events: Arc<RwLock<HashMap<EventId, Event>>>,
     by_creator: Arc<RwLock<HashMap<String, Vec<EventId>>>>,
     by_status: Arc<RwLock<HashMap<EventStatus, Vec<EventId>>>>,
 }
 
-impl EventStore {
+impl MemoryEventStore {
     pub fn new() -> Self {
         Self {
             events: Arc::new(RwLock::new(HashMap::new())),
@@ -18,8 +50,11 @@ impl EventStore {
             by_status: Arc::new(RwLock::new(HashMap::new())),
         }
     }
+}
 
-    pub async fn store(&self, event: Event) -> SetuResult<()> {
+#[async_trait]
+impl EventStore for MemoryEventStore {
+    async fn store(&self, event: Event) -> SetuResult<()> {
         let event_id = event.id.clone();
         let creator = event.creator.clone();
         let status = event.status;
@@ -42,20 +77,20 @@ impl EventStore {
         Ok(())
     }
 
-    pub async fn get(&self, event_id: &EventId) -> Option<Event> {
+    async fn get(&self, event_id: &EventId) -> SetuResult<Option<Event>> {
         let events = self.events.read().await;
-        events.get(event_id).cloned()
+        Ok(events.get(event_id).cloned())
     }
 
-    pub async fn get_many(&self, event_ids: &[EventId]) -> Vec<Event> {
+    async fn get_many(&self, event_ids: &[EventId]) -> SetuResult<Vec<Event>> {
         let events = self.events.read().await;
-        event_ids
+        Ok(event_ids
             .iter()
             .filter_map(|id| events.get(id).cloned())
-            .collect()
+            .collect())
     }
 
-    pub async fn update_status(&self, event_id: &EventId, new_status: EventStatus) {
+    async fn update_status(&self, event_id: &EventId, new_status: EventStatus) -> SetuResult<()> {
         let old_status = {
             let mut events = self.events.write().await;
             if let Some(event) = events.get_mut(event_id) {
@@ -79,51 +114,53 @@ impl EventStore {
                 .or_insert_with(Vec::new)
                 .push(event_id.clone());
         }
+        
+        Ok(())
     }
 
-    pub async fn get_by_creator(&self, creator: &str) -> Vec<Event> {
+    async fn get_by_creator(&self, creator: &str) -> SetuResult<Vec<Event>> {
         let by_creator = self.by_creator.read().await;
         let events = self.events.read().await;
 
-        by_creator
+        Ok(by_creator
             .get(creator)
             .map(|ids| {
                 ids.iter()
                     .filter_map(|id| events.get(id).cloned())
                     .collect()
             })
-            .unwrap_or_default()
+            .unwrap_or_default())
     }
 
-    pub async fn get_by_status(&self, status: EventStatus) -> Vec<Event> {
+    async fn get_by_status(&self, status: EventStatus) -> SetuResult<Vec<Event>> {
         let by_status = self.by_status.read().await;
         let events = self.events.read().await;
 
-        by_status
+        Ok(by_status
             .get(&status)
             .map(|ids| {
                 ids.iter()
                     .filter_map(|id| events.get(id).cloned())
                     .collect()
             })
-            .unwrap_or_default()
+            .unwrap_or_default())
     }
 
-    pub async fn count(&self) -> usize {
-        self.events.read().await.len()
+    async fn count(&self) -> SetuResult<usize> {
+        Ok(self.events.read().await.len())
     }
 
-    pub async fn count_by_status(&self, status: EventStatus) -> usize {
+    async fn count_by_status(&self, status: EventStatus) -> SetuResult<usize> {
         let by_status = self.by_status.read().await;
-        by_status.get(&status).map(|v| v.len()).unwrap_or(0)
+        Ok(by_status.get(&status).map(|v| v.len()).unwrap_or(0))
     }
 
-    pub async fn exists(&self, event_id: &EventId) -> bool {
-        self.events.read().await.contains_key(event_id)
+    async fn exists(&self, event_id: &EventId) -> SetuResult<bool> {
+        Ok(self.events.read().await.contains_key(event_id))
     }
 }
 
-impl Clone for EventStore {
+impl Clone for MemoryEventStore {
     fn clone(&self) -> Self {
         Self {
             events: Arc::clone(&self.events),
@@ -133,7 +170,7 @@ impl Clone for EventStore {
     }
 }
 
-impl Default for EventStore {
+impl Default for MemoryEventStore {
     fn default() -> Self {
         Self::new()
     }
@@ -159,39 +196,39 @@ mod tests {
 
     #[tokio::test]
     async fn test_store_and_get() {
-        let store = EventStore::new();
+        let store = MemoryEventStore::new();
         let event = create_event("node1");
         let event_id = event.id.clone();
 
         store.store(event).await.unwrap();
 
-        let retrieved = store.get(&event_id).await;
+        let retrieved = store.get(&event_id).await.unwrap();
         assert!(retrieved.is_some());
         assert_eq!(retrieved.unwrap().id, event_id);
     }
 
     #[tokio::test]
     async fn test_get_by_creator() {
-        let store = EventStore::new();
+        let store = MemoryEventStore::new();
         
         store.store(create_event("node1")).await.unwrap();
         store.store(create_event("node1")).await.unwrap();
         store.store(create_event("node2")).await.unwrap();
 
-        let node1_events = store.get_by_creator("node1").await;
+        let node1_events = store.get_by_creator("node1").await.unwrap();
         assert_eq!(node1_events.len(), 2);
     }
 
     #[tokio::test]
     async fn test_update_status() {
-        let store = EventStore::new();
+        let store = MemoryEventStore::new();
         let event = create_event("node1");
         let event_id = event.id.clone();
 
         store.store(event).await.unwrap();
-        store.update_status(&event_id, EventStatus::Executed).await;
+        store.update_status(&event_id, EventStatus::Executed).await.unwrap();
 
-        let updated = store.get(&event_id).await.unwrap();
+        let updated = store.get(&event_id).await.unwrap().unwrap();
         assert_eq!(updated.status, EventStatus::Executed);
     }
 }
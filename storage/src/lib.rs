@@ -3,9 +3,15 @@ pub mod event_store;
 pub mod anchor_store;
 pub mod object_store;
 
-// RocksDB storage implementation
+// Key-value backends and the RocksDB storage implementation
+pub mod backend;
 pub mod rocks;
 pub mod rocks_object_store;
+pub mod rocks_event_store;
+pub mod rocks_anchor_store;
+pub mod merkle_store;
+pub mod checkpoint_store;
+pub mod metrics;
 
 pub use state::*;
 pub use event_store::*;
@@ -14,4 +20,10 @@ pub use object_store::*;
 
 // Re-export RocksDB types
 pub use rocks::{SetuDB, RocksDBConfig, ColumnFamily, StorageError};
+pub use backend::{KvBackend, KvBatch, MemoryBackend};
 pub use rocks_object_store::RocksObjectStore;
+pub use rocks_event_store::RocksEventStore;
+pub use rocks_anchor_store::{RocksAnchorStore, RocksCFStore};
+pub use merkle_store::RocksTreeStore;
+pub use checkpoint_store::{Checkpoint, RocksCheckpointStore, compute_state_root};
+pub use metrics::{StorageMetrics, DBStatsCollector};
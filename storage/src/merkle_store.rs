@@ -0,0 +1,156 @@
+//! RocksDB-backed node storage for the persistent sparse Merkle tree
+
+use crate::rocks::{SetuDB, ColumnFamily, KvBackend, StorageError};
+use setu_merkle::{MerkleError, MerkleResult};
+use setu_merkle::storage::{
+    NodeKey, StaleNodeIndex, StaleNodeIndexStore, TreeNode, TreeReader, TreeUpdateBatch,
+    TreeWriter, Version,
+};
+
+/// Stores sparse Merkle tree nodes in the `merkle_nodes` column family.
+///
+/// Nodes are keyed by `(version, path)`, so every committed version of the
+/// tree stays readable until it is explicitly pruned. Superseded nodes are
+/// indexed in `merkle_stale_nodes`, ordered by the version they went stale.
+pub struct RocksTreeStore {
+    db: SetuDB,
+}
+
+impl RocksTreeStore {
+    pub fn new(db: SetuDB) -> Self {
+        Self { db }
+    }
+
+    pub fn open(path: impl AsRef<std::path::Path>) -> MerkleResult<Self> {
+        let db = SetuDB::open_default(path).map_err(to_merkle_error)?;
+        Ok(Self::new(db))
+    }
+}
+
+fn to_merkle_error(e: StorageError) -> MerkleError {
+    MerkleError::StorageError(e.to_string())
+}
+
+impl TreeReader for RocksTreeStore {
+    fn get_node_option(&self, node_key: &NodeKey) -> MerkleResult<Option<TreeNode>> {
+        self.db.get(ColumnFamily::MerkleNodes, node_key).map_err(to_merkle_error)
+    }
+}
+
+impl TreeWriter for RocksTreeStore {
+    fn write_tree_update_batch(&self, batch: &TreeUpdateBatch) -> MerkleResult<()> {
+        let mut write_batch = self.db.batch();
+        for (node_key, node) in &batch.node_batch {
+            self.db
+                .batch_put(&mut write_batch, ColumnFamily::MerkleNodes, node_key, node)
+                .map_err(to_merkle_error)?;
+        }
+        for index in &batch.stale_node_index_batch {
+            self.db
+                .batch_put(&mut write_batch, ColumnFamily::MerkleStaleNodes, index, &())
+                .map_err(to_merkle_error)?;
+        }
+        self.db.write_batch(write_batch).map_err(to_merkle_error)
+    }
+}
+
+impl StaleNodeIndexStore for RocksTreeStore {
+    fn get_stale_node_indices(&self, up_to_version: Version) -> MerkleResult<Vec<StaleNodeIndex>> {
+        let entries = self.db
+            .iter::<StaleNodeIndex, ()>(ColumnFamily::MerkleStaleNodes)
+            .map_err(to_merkle_error)?;
+
+        let mut indices = Vec::new();
+        // Keys sort by stale_since_version, so stop at the first newer entry
+        for entry in entries {
+            let (index, ()) = entry.map_err(to_merkle_error)?;
+            if index.stale_since_version > up_to_version {
+                break;
+            }
+            indices.push(index);
+        }
+        Ok(indices)
+    }
+
+    fn purge_stale_nodes(&self, indices: &[StaleNodeIndex]) -> MerkleResult<()> {
+        let mut write_batch = self.db.batch();
+        for index in indices {
+            self.db
+                .batch_delete(&mut write_batch, ColumnFamily::MerkleNodes, &index.node_key)
+                .map_err(to_merkle_error)?;
+            self.db
+                .batch_delete(&mut write_batch, ColumnFamily::MerkleStaleNodes, index)
+                .map_err(to_merkle_error)?;
+        }
+        self.db.write_batch(write_batch).map_err(to_merkle_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use setu_merkle::{HashValue, PersistentSparseMerkleTree, PruningPolicy, VersionedSparseMerkleTree};
+
+    fn setup_test_store() -> (RocksTreeStore, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let store = RocksTreeStore::open(temp_dir.path()).unwrap();
+        (store, temp_dir)
+    }
+
+    #[test]
+    fn test_versions_survive_reopen() {
+        let temp_dir = TempDir::new().unwrap();
+        let key = HashValue::new([7u8; 32]);
+        let other = HashValue::new([9u8; 32]);
+
+        let (root0, root1) = {
+            let store = RocksTreeStore::open(temp_dir.path()).unwrap();
+            let tree = PersistentSparseMerkleTree::new(&store);
+            let (root0, batch) = tree
+                .put_value_set(vec![(key, Some(b"v0".to_vec())), (other, Some(b"o".to_vec()))], 0)
+                .unwrap();
+            store.write_tree_update_batch(&batch).unwrap();
+            let (root1, batch) = tree.put_value_set(vec![(key, Some(b"v1".to_vec()))], 1).unwrap();
+            store.write_tree_update_batch(&batch).unwrap();
+            (root0, root1)
+        };
+
+        let store = RocksTreeStore::open(temp_dir.path()).unwrap();
+        let tree = PersistentSparseMerkleTree::new(&store);
+        assert_eq!(tree.get_root_hash(0).unwrap(), root0);
+        assert_eq!(tree.get_root_hash(1).unwrap(), root1);
+        assert_eq!(tree.get(&key, 0).unwrap(), Some(b"v0".to_vec()));
+        assert_eq!(tree.get(&key, 1).unwrap(), Some(b"v1".to_vec()));
+
+        let (value, proof) = tree.get_with_proof(&other, 1).unwrap();
+        assert_eq!(value, Some(b"o".to_vec()));
+        assert!(proof.verify_inclusion(&root1, &other, b"o").is_ok());
+    }
+
+    #[test]
+    fn test_pruning() {
+        let (store, _temp) = setup_test_store();
+        let mut tree = VersionedSparseMerkleTree::new(store, PruningPolicy::KeepLatest(1));
+        let key = HashValue::new([1u8; 32]);
+        let other = HashValue::new([2u8; 32]);
+
+        tree.commit(vec![(key, Some(b"a".to_vec())), (other, Some(b"o".to_vec()))]).unwrap();
+        tree.commit(vec![(key, Some(b"b".to_vec()))]).unwrap();
+        let (version, root) = tree.commit(vec![(key, Some(b"c".to_vec()))]).unwrap();
+
+        assert_eq!(tree.min_readable_version(), version);
+        assert!(tree.store().get_stale_node_indices(version).unwrap().is_empty());
+        assert!(tree.get_at(&key, version - 1).is_err());
+        let (value, proof) = tree.get_proof_at(&other, version).unwrap();
+        assert_eq!(value, Some(b"o".to_vec()));
+        assert!(proof.verify_inclusion(&root, &other, b"o").is_ok());
+    }
+
+    #[test]
+    fn test_missing_version() {
+        let (store, _temp) = setup_test_store();
+        let tree = PersistentSparseMerkleTree::new(&store);
+        assert!(matches!(tree.get_root_hash(5), Err(MerkleError::NodeNotFound(_))));
+    }
+}
@@ -0,0 +1,216 @@
+//! Prometheus metrics for storage
+//!
+//! - [`StorageMetrics`]: latency of `SetuDB` operations, recorded as they run
+//!   once attached with [`SetuDB::with_metrics`]
+//! - [`DBStatsCollector`]: RocksDB properties and statistics (column family
+//!   sizes, estimated keys, memtable usage, compactions, block cache), read
+//!   each time the registry is gathered
+//!
+//! Both register into a caller-provided registry, so they can share the one
+//! used for the network metrics.
+//!
+//! ## Usage
+//!
+//! ```ignore
+//! let registry = prometheus::Registry::new();
+//! let metrics = Arc::new(StorageMetrics::new(&registry));
+//! let db = SetuDB::open(config)?.with_metrics(metrics);
+//! DBStatsCollector::register(&registry, &db);
+//! ```
+
+use crate::rocks::{SetuDB, ColumnFamily};
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{
+    exponential_buckets, Gauge, Histogram, HistogramOpts, HistogramVec, IntGauge, IntGaugeVec,
+    Opts, Registry,
+};
+use rocksdb::statistics::Ticker;
+
+/// Latency of database operations
+pub struct StorageMetrics {
+    /// `put` latency (seconds)
+    pub put_latency: Histogram,
+    /// `get` latency (seconds)
+    pub get_latency: Histogram,
+    /// `multi_get` latency (seconds)
+    pub multi_get_latency: Histogram,
+    /// `write_batch` latency (seconds)
+    pub write_batch_latency: Histogram,
+}
+
+impl StorageMetrics {
+    /// Create metrics registered with the given registry
+    pub fn new(registry: &Registry) -> Self {
+        let latency = HistogramVec::new(
+            HistogramOpts::new("storage_operation_latency_seconds", "Latency of storage operations")
+                .buckets(exponential_buckets(0.000_01, 2.0, 18).unwrap()),
+            &["operation"],
+        )
+        .unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+
+        Self {
+            put_latency: latency.with_label_values(&["put"]),
+            get_latency: latency.with_label_values(&["get"]),
+            multi_get_latency: latency.with_label_values(&["multi_get"]),
+            write_batch_latency: latency.with_label_values(&["write_batch"]),
+        }
+    }
+
+    /// Create disabled (no-op) metrics
+    pub fn disabled() -> Self {
+        Self {
+            put_latency: Histogram::with_opts(HistogramOpts::new("disabled", "disabled")).unwrap(),
+            get_latency: Histogram::with_opts(HistogramOpts::new("disabled", "disabled")).unwrap(),
+            multi_get_latency: Histogram::with_opts(HistogramOpts::new("disabled", "disabled")).unwrap(),
+            write_batch_latency: Histogram::with_opts(HistogramOpts::new("disabled", "disabled")).unwrap(),
+        }
+    }
+}
+
+/// Per column family properties, as `(property, metric name, help)`
+const CF_PROPERTIES: [(&str, &str, &str); 5] = [
+    ("rocksdb.total-sst-files-size", "storage_cf_sst_size_bytes", "Total size of the SST files of a column family"),
+    ("rocksdb.estimate-num-keys", "storage_cf_estimated_keys", "Estimated number of keys in a column family"),
+    ("rocksdb.cur-size-all-mem-tables", "storage_cf_memtable_bytes", "Size of the memtables of a column family"),
+    ("rocksdb.estimate-pending-compaction-bytes", "storage_cf_pending_compaction_bytes", "Estimated bytes compaction needs to rewrite"),
+    ("rocksdb.block-cache-usage", "storage_cf_block_cache_usage_bytes", "Memory used by block cache entries of a column family"),
+];
+
+/// Statistics tickers, as `(ticker, metric name, help)`; cumulative since open
+const TICKERS: [(Ticker, &str, &str); 5] = [
+    (Ticker::BlockCacheHit, "storage_block_cache_hits", "Block cache hits since the database was opened"),
+    (Ticker::BlockCacheMiss, "storage_block_cache_misses", "Block cache misses since the database was opened"),
+    (Ticker::CompactReadBytes, "storage_compaction_read_bytes", "Bytes read by compactions since the database was opened"),
+    (Ticker::CompactWriteBytes, "storage_compaction_write_bytes", "Bytes written by compactions since the database was opened"),
+    (Ticker::StallMicros, "storage_write_stall_micros", "Time writes were stalled since the database was opened"),
+];
+
+/// Collector reading RocksDB properties and statistics when gathered
+pub struct DBStatsCollector {
+    db: SetuDB,
+    /// Gauges labelled by column family, in `CF_PROPERTIES` order
+    cf_gauges: Vec<IntGaugeVec>,
+    /// Gauges in `TICKERS` order
+    ticker_gauges: Vec<IntGauge>,
+    running_compactions: IntGauge,
+    block_cache_hit_ratio: Gauge,
+}
+
+impl DBStatsCollector {
+    pub fn new(db: SetuDB) -> Self {
+        Self {
+            db,
+            cf_gauges: CF_PROPERTIES
+                .iter()
+                .map(|(_, name, help)| IntGaugeVec::new(Opts::new(*name, *help), &["cf"]).unwrap())
+                .collect(),
+            ticker_gauges: TICKERS
+                .iter()
+                .map(|(_, name, help)| IntGauge::new(*name, *help).unwrap())
+                .collect(),
+            running_compactions: IntGauge::new(
+                "storage_running_compactions",
+                "Number of compactions currently running",
+            )
+            .unwrap(),
+            block_cache_hit_ratio: Gauge::new(
+                "storage_block_cache_hit_ratio",
+                "Share of block cache lookups that hit since the database was opened",
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Register a collector for `db` with the given registry
+    pub fn register(registry: &Registry, db: &SetuDB) {
+        registry.register(Box::new(Self::new(db.clone()))).unwrap();
+    }
+
+    /// Read the current values into the gauges
+    fn update(&self) {
+        for cf in ColumnFamily::all() {
+            for ((property, _, _), gauge) in CF_PROPERTIES.iter().zip(&self.cf_gauges) {
+                // A property this RocksDB build does not support is just left out
+                if let Ok(Some(value)) = self.db.property_int_value(cf, property) {
+                    gauge.with_label_values(&[cf.name()]).set(value as i64);
+                }
+            }
+        }
+
+        if let Ok(Some(value)) = self.db.inner().property_int_value("rocksdb.num-running-compactions") {
+            self.running_compactions.set(value as i64);
+        }
+
+        for ((ticker, _, _), gauge) in TICKERS.iter().zip(&self.ticker_gauges) {
+            gauge.set(self.db.ticker_count(*ticker) as i64);
+        }
+
+        let hits = self.db.ticker_count(Ticker::BlockCacheHit);
+        let lookups = hits + self.db.ticker_count(Ticker::BlockCacheMiss);
+        if lookups > 0 {
+            self.block_cache_hit_ratio.set(hits as f64 / lookups as f64);
+        }
+    }
+}
+
+impl Collector for DBStatsCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.cf_gauges
+            .iter()
+            .flat_map(|gauge| gauge.desc())
+            .chain(self.ticker_gauges.iter().flat_map(|gauge| gauge.desc()))
+            .chain(self.running_compactions.desc())
+            .chain(self.block_cache_hit_ratio.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.update();
+        self.cf_gauges
+            .iter()
+            .flat_map(|gauge| gauge.collect())
+            .chain(self.ticker_gauges.iter().flat_map(|gauge| gauge.collect()))
+            .chain(self.running_compactions.collect())
+            .chain(self.block_cache_hit_ratio.collect())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+    use crate::rocks::KvBackend;
+
+    #[test]
+    fn test_storage_metrics() {
+        let temp_dir = TempDir::new().unwrap();
+        let registry = Registry::new();
+        let metrics = Arc::new(StorageMetrics::new(&registry));
+        let db = SetuDB::open_default(temp_dir.path()).unwrap().with_metrics(metrics.clone());
+        DBStatsCollector::register(&registry, &db);
+
+        db.put(ColumnFamily::Objects, &1u64, &"one".to_string()).unwrap();
+        let _: Option<String> = db.get(ColumnFamily::Objects, &1u64).unwrap();
+        let _: Vec<Option<String>> = db.multi_get(ColumnFamily::Objects, &[1u64, 2]).unwrap();
+        // Clones of the database record into the same metrics
+        db.clone().write_batch(db.batch()).unwrap();
+
+        assert_eq!(metrics.put_latency.get_sample_count(), 1);
+        assert_eq!(metrics.get_latency.get_sample_count(), 1);
+        assert_eq!(metrics.multi_get_latency.get_sample_count(), 1);
+        assert_eq!(metrics.write_batch_latency.get_sample_count(), 1);
+
+        let families = registry.gather();
+        let names: Vec<&str> = families.iter().map(|family| family.get_name()).collect();
+        assert!(names.contains(&"storage_operation_latency_seconds"));
+        assert!(names.contains(&"storage_cf_estimated_keys"));
+        assert!(names.contains(&"storage_block_cache_hit_ratio"));
+
+        // Registering the collector twice in one registry is refused
+        assert!(registry.register(Box::new(DBStatsCollector::new(db))).is_err());
+    }
+}
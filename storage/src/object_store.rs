@@ -1,87 +1,398 @@
-//! Object Store trait for Coin/Profile/Credential/RelationGraph storage operations
+//! Object Store trait for storing Setu objects of any type
+use serde::{Deserialize, Serialize};
 use setu_types::{
-    ObjectId, Address,
-    Coin, Profile, Credential, RelationGraph, AccountView,
-    SetuResult,
+    ObjectId, ObjectDigest, Address, Object, ObjectData, Ownership, Anchor, EventId,
+    Coin, CoinData, Profile, ProfileData, Credential, CredentialData,
+    RelationGraph, RelationGraphData, AccountView,
+    SetuResult, SetuError,
 };
 
+/// An object as kept by the store: the encoded object plus everything needed
+/// to index it without knowing its type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredObject {
+    pub id: ObjectId,
+    pub version: u64,
+    pub digest: ObjectDigest,
+    /// [`ObjectData::TYPE_TAG`] of the payload
+    pub type_tag: String,
+    pub ownership: Ownership,
+    /// Named secondary index entries, see [`ObjectData::secondary_indexes`]
+    pub indexes: Vec<(String, Address)>,
+    /// BCS-encoded `Object<T>`
+    pub bytes: Vec<u8>,
+}
+
+impl StoredObject {
+    pub fn new<T: ObjectData>(object: &Object<T>) -> SetuResult<Self> {
+        let bytes = bcs::to_bytes(object).map_err(|e| SetuError::StorageError(e.to_string()))?;
+        Ok(Self {
+            id: object.metadata.id,
+            version: object.metadata.version,
+            digest: object.metadata.digest,
+            type_tag: T::TYPE_TAG.to_string(),
+            ownership: object.metadata.ownership,
+            indexes: object
+                .data
+                .secondary_indexes()
+                .into_iter()
+                .map(|(name, key)| (name.to_string(), key))
+                .collect(),
+            bytes,
+        })
+    }
+
+    /// Decode the object, or `None` if it is not a `T`
+    pub fn decode<T: ObjectData>(&self) -> SetuResult<Option<Object<T>>> {
+        if self.type_tag != T::TYPE_TAG {
+            return Ok(None);
+        }
+        bcs::from_bytes(&self.bytes)
+            .map(Some)
+            .map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+}
+
+/// One version of an object in its history, mirroring
+/// `core_types::ObjectVersion`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectVersionRecord {
+    pub object_id: ObjectId,
+    pub version: u64,
+    /// Event whose execution produced this version, if written by one
+    pub produced_by: Option<EventId>,
+    /// The object at this version, or `None` if the version is its deletion
+    pub object: Option<StoredObject>,
+    /// Depth of an anchor the version was already finalized under when
+    /// written, e.g. for versions loaded from a checkpoint
+    pub anchor_depth: Option<u64>,
+    /// Whether older versions of the object are missing, because they were
+    /// pruned or never stored on this node
+    pub pruned_before: bool,
+}
+
+/// A single object mutation, applied together with others by
+/// [`ObjectStore::apply_changes`]
+#[derive(Debug, Clone)]
+pub enum ObjectChange {
+    /// Insert or replace an object
+    Put(StoredObject),
+    /// Delete an object of any type
+    Delete(ObjectId),
+    /// Replace an object only if the stored one is still at the expected
+    /// version and digest, failing with [`SetuError::VersionConflict`] otherwise
+    Update {
+        object: StoredObject,
+        expected_version: u64,
+        expected_digest: ObjectDigest,
+    },
+}
+
+impl ObjectChange {
+    pub fn put<T: ObjectData>(object: &Object<T>) -> SetuResult<Self> {
+        StoredObject::new(object).map(Self::Put)
+    }
+
+    pub fn update<T: ObjectData>(
+        object: &Object<T>,
+        expected_version: u64,
+        expected_digest: ObjectDigest,
+    ) -> SetuResult<Self> {
+        Ok(Self::Update {
+            object: StoredObject::new(object)?,
+            expected_version,
+            expected_digest,
+        })
+    }
+
+    /// ID of the object the change applies to
+    pub fn object_id(&self) -> &ObjectId {
+        match self {
+            Self::Put(object) | Self::Update { object, .. } => &object.id,
+            Self::Delete(id) => id,
+        }
+    }
+
+    /// Fail with [`SetuError::VersionConflict`] unless `current` is what this
+    /// change expects to replace
+    pub fn check_expected(&self, current: Option<&StoredObject>) -> SetuResult<()> {
+        let Self::Update { object, expected_version, expected_digest } = self else {
+            return Ok(());
+        };
+        match current {
+            Some(current) if current.version == *expected_version && current.digest == *expected_digest => Ok(()),
+            _ => Err(SetuError::VersionConflict {
+                object_id: object.id,
+                expected_version: *expected_version,
+                expected_digest: *expected_digest,
+                actual: current.map(|current| (current.version, current.digest)),
+            }),
+        }
+    }
+}
+
 /// Object store interface for storing and retrieving Setu objects
-/// 
-/// Address is the root of ownership. All objects are owned by an Address.
+///
+/// Objects of every type are stored by ID with their type tag and indexed by
+/// their `Ownership`. Address is the root of ownership.
 pub trait ObjectStore {
+    // ========== Generic operations ==========
+
+    /// Get a stored object of any type
+    fn get_stored(&self, id: &ObjectId) -> SetuResult<Option<StoredObject>>;
+
+    /// Stored objects of type `type_tag` owned by an address, ordered by ID,
+    /// with IDs after `cursor`
+    fn get_stored_by_owner(
+        &self,
+        owner: &Address,
+        type_tag: &str,
+        cursor: Option<ObjectId>,
+        limit: Option<usize>,
+    ) -> SetuResult<Vec<StoredObject>>;
+
+    /// Stored objects of type `type_tag` listed under `key` in a secondary index
+    fn get_stored_by_index(&self, type_tag: &str, index: &str, key: &Address) -> SetuResult<Vec<StoredObject>>;
+
+    /// Apply changes atomically: either all of them (with their index
+    /// updates) are persisted, or none are. Later changes see earlier ones.
+    fn apply_changes(&self, changes: &[ObjectChange]) -> SetuResult<()>;
+
+    /// Apply the changes made by executing an event, recording the event as
+    /// the producer of the new object versions
+    fn apply_event_changes(&self, event_id: &EventId, changes: &[ObjectChange]) -> SetuResult<()>;
+
+    /// Recorded versions of an object that have not been pruned, oldest first
+    fn get_version_history(&self, id: &ObjectId) -> SetuResult<Vec<ObjectVersionRecord>>;
+
+    /// Get an object as it was at `version`
+    ///
+    /// `None` if the version is unknown, pruned or the object's deletion.
+    fn get_stored_at_version(&self, id: &ObjectId, version: u64) -> SetuResult<Option<StoredObject>>;
+
+    /// Get an object as it was once `anchor` was finalized: its latest version
+    /// produced by an event in `anchor` or an earlier anchor
+    ///
+    /// Only versions produced by an anchored event, or loaded at a known
+    /// anchor, count; versions written outside of events are skipped. `None`
+    /// if the object did not exist then or was deleted, an error if the
+    /// versions needed to tell were pruned.
+    fn get_stored_as_of_anchor(&self, id: &ObjectId, anchor: &Anchor) -> SetuResult<Option<StoredObject>>;
+
+    /// Store an object (replacing any object with the same ID) and update indexes
+    fn store_object<T: ObjectData>(&self, object: &Object<T>) -> SetuResult<ObjectId> {
+        self.apply_changes(&[ObjectChange::put(object)?])?;
+        Ok(object.metadata.id)
+    }
+
+    /// Get an object by ID, or `None` if there is no `T` with that ID
+    fn get_object<T: ObjectData>(&self, id: &ObjectId) -> SetuResult<Option<Object<T>>> {
+        match self.get_stored(id)? {
+            Some(stored) => stored.decode(),
+            None => Ok(None),
+        }
+    }
+
+    /// Get an object as it was at `version`, or `None` if there is no such
+    /// version of a `T`
+    fn get_object_at_version<T: ObjectData>(&self, id: &ObjectId, version: u64) -> SetuResult<Option<Object<T>>> {
+        match self.get_stored_at_version(id, version)? {
+            Some(stored) => stored.decode(),
+            None => Ok(None),
+        }
+    }
+
+    /// Get an object as it was once `anchor` was finalized
+    fn get_object_as_of_anchor<T: ObjectData>(&self, id: &ObjectId, anchor: &Anchor) -> SetuResult<Option<Object<T>>> {
+        match self.get_stored_as_of_anchor(id, anchor)? {
+            Some(stored) => stored.decode(),
+            None => Ok(None),
+        }
+    }
+
+    /// Replace an object only if the stored one is still at `expected_version`
+    /// with `expected_digest`, i.e. nobody updated it since it was read
+    fn update_object<T: ObjectData>(
+        &self,
+        object: &Object<T>,
+        expected_version: u64,
+        expected_digest: ObjectDigest,
+    ) -> SetuResult<()> {
+        self.apply_changes(&[ObjectChange::update(object, expected_version, expected_digest)?])
+    }
+
+    /// Delete an object of any type and clean up indexes
+    fn delete_object(&self, id: &ObjectId) -> SetuResult<()> {
+        self.apply_changes(&[ObjectChange::Delete(*id)])
+    }
+
+    /// Get objects of type `T` owned by an address, ordered by ID
+    ///
+    /// Returns up to `limit` objects (all if `None`) with IDs after `cursor`;
+    /// pass the ID of the last object of a page to get the next one.
+    fn get_objects_by_owner<T: ObjectData>(
+        &self,
+        owner: &Address,
+        cursor: Option<ObjectId>,
+        limit: Option<usize>,
+    ) -> SetuResult<Vec<Object<T>>> {
+        decode_all(self.get_stored_by_owner(owner, T::TYPE_TAG, cursor, limit)?)
+    }
+
+    /// Get objects of type `T` listed under `key` in a secondary index
+    fn get_objects_by_index<T: ObjectData>(&self, index: &str, key: &Address) -> SetuResult<Vec<Object<T>>> {
+        decode_all(self.get_stored_by_index(T::TYPE_TAG, index, key)?)
+    }
+
     // ========== Coin operations ==========
-    
+
     /// Store a coin and update indexes
-    fn store_coin(&self, coin: &Coin) -> SetuResult<ObjectId>;
-    
+    fn store_coin(&self, coin: &Coin) -> SetuResult<ObjectId> {
+        self.store_object(coin)
+    }
+
     /// Get a coin by ID
-    fn get_coin(&self, id: &ObjectId) -> SetuResult<Option<Coin>>;
-    
-    /// Get all coins owned by an address
-    fn get_coins_by_owner(&self, owner: &Address) -> SetuResult<Vec<Coin>>;
-    
-    /// Update a coin (replaces existing)
-    fn update_coin(&self, coin: &Coin) -> SetuResult<()>;
-    
+    fn get_coin(&self, id: &ObjectId) -> SetuResult<Option<Coin>> {
+        self.get_object(id)
+    }
+
+    /// Get coins owned by an address, ordered by ID
+    ///
+    /// Returns up to `limit` coins (all if `None`) with IDs after `cursor`;
+    /// pass the ID of the last coin of a page to get the next one.
+    fn get_coins_by_owner(
+        &self,
+        owner: &Address,
+        cursor: Option<ObjectId>,
+        limit: Option<usize>,
+    ) -> SetuResult<Vec<Coin>> {
+        self.get_objects_by_owner(owner, cursor, limit)
+    }
+
+    /// Update a coin read at `expected_version` with `expected_digest`,
+    /// see [`update_object`](Self::update_object)
+    fn update_coin(&self, coin: &Coin, expected_version: u64, expected_digest: ObjectDigest) -> SetuResult<()> {
+        self.update_object(coin, expected_version, expected_digest)
+    }
+
     /// Delete a coin and clean up indexes
-    fn delete_coin(&self, id: &ObjectId) -> SetuResult<()>;
-    
+    fn delete_coin(&self, id: &ObjectId) -> SetuResult<()> {
+        delete_typed::<CoinData, _>(self, id)
+    }
+
     // ========== Profile operations ==========
-    
+
     /// Store a profile and update indexes
-    fn store_profile(&self, profile: &Profile) -> SetuResult<ObjectId>;
-    
+    fn store_profile(&self, profile: &Profile) -> SetuResult<ObjectId> {
+        self.store_object(profile)
+    }
+
     /// Get a profile by ID
-    fn get_profile(&self, id: &ObjectId) -> SetuResult<Option<Profile>>;
-    
+    fn get_profile(&self, id: &ObjectId) -> SetuResult<Option<Profile>> {
+        self.get_object(id)
+    }
+
     /// Get profile by owner address (one address has at most one profile)
-    fn get_profile_by_address(&self, address: &Address) -> SetuResult<Option<Profile>>;
-    
-    /// Update a profile (replaces existing)
-    fn update_profile(&self, profile: &Profile) -> SetuResult<()>;
-    
+    fn get_profile_by_address(&self, address: &Address) -> SetuResult<Option<Profile>> {
+        Ok(self.get_objects_by_owner(address, None, Some(1))?.pop())
+    }
+
+    /// Update a profile read at `expected_version` with `expected_digest`,
+    /// see [`update_object`](Self::update_object)
+    fn update_profile(&self, profile: &Profile, expected_version: u64, expected_digest: ObjectDigest) -> SetuResult<()> {
+        self.update_object(profile, expected_version, expected_digest)
+    }
+
     /// Delete a profile and clean up indexes
-    fn delete_profile(&self, id: &ObjectId) -> SetuResult<()>;
-    
+    fn delete_profile(&self, id: &ObjectId) -> SetuResult<()> {
+        delete_typed::<ProfileData, _>(self, id)
+    }
+
     // ========== Credential operations ==========
-    
+
     /// Store a credential and update indexes
-    fn store_credential(&self, credential: &Credential) -> SetuResult<ObjectId>;
-    
+    fn store_credential(&self, credential: &Credential) -> SetuResult<ObjectId> {
+        self.store_object(credential)
+    }
+
     /// Get a credential by ID
-    fn get_credential(&self, id: &ObjectId) -> SetuResult<Option<Credential>>;
-    
+    fn get_credential(&self, id: &ObjectId) -> SetuResult<Option<Credential>> {
+        self.get_object(id)
+    }
+
     /// Get all credentials for an address (holder)
-    fn get_credentials_by_holder(&self, holder: &Address) -> SetuResult<Vec<Credential>>;
-    
+    fn get_credentials_by_holder(&self, holder: &Address) -> SetuResult<Vec<Credential>> {
+        self.get_objects_by_owner(holder, None, None)
+    }
+
     /// Get credentials issued by an address
-    fn get_credentials_by_issuer(&self, issuer: &Address) -> SetuResult<Vec<Credential>>;
-    
-    /// Update a credential (replaces existing)
-    fn update_credential(&self, credential: &Credential) -> SetuResult<()>;
-    
+    fn get_credentials_by_issuer(&self, issuer: &Address) -> SetuResult<Vec<Credential>> {
+        self.get_objects_by_index("issuer", issuer)
+    }
+
+    /// Update a credential read at `expected_version` with `expected_digest`,
+    /// see [`update_object`](Self::update_object)
+    fn update_credential(&self, credential: &Credential, expected_version: u64, expected_digest: ObjectDigest) -> SetuResult<()> {
+        self.update_object(credential, expected_version, expected_digest)
+    }
+
     /// Delete a credential and clean up indexes
-    fn delete_credential(&self, id: &ObjectId) -> SetuResult<()>;
-    
+    fn delete_credential(&self, id: &ObjectId) -> SetuResult<()> {
+        delete_typed::<CredentialData, _>(self, id)
+    }
+
     // ========== RelationGraph operations ==========
-    
+
     /// Store a relation graph and update indexes
-    fn store_graph(&self, graph: &RelationGraph) -> SetuResult<ObjectId>;
-    
+    fn store_graph(&self, graph: &RelationGraph) -> SetuResult<ObjectId> {
+        self.store_object(graph)
+    }
+
     /// Get a relation graph by ID
-    fn get_graph(&self, id: &ObjectId) -> SetuResult<Option<RelationGraph>>;
-    
+    fn get_graph(&self, id: &ObjectId) -> SetuResult<Option<RelationGraph>> {
+        self.get_object(id)
+    }
+
     /// Get all relation graphs owned by an address
-    fn get_graphs_by_owner(&self, owner: &Address) -> SetuResult<Vec<RelationGraph>>;
-    
-    /// Update a relation graph (replaces existing)
-    fn update_graph(&self, graph: &RelationGraph) -> SetuResult<()>;
-    
+    fn get_graphs_by_owner(&self, owner: &Address) -> SetuResult<Vec<RelationGraph>> {
+        self.get_objects_by_owner(owner, None, None)
+    }
+
+    /// Update a relation graph read at `expected_version` with `expected_digest`,
+    /// see [`update_object`](Self::update_object)
+    fn update_graph(&self, graph: &RelationGraph, expected_version: u64, expected_digest: ObjectDigest) -> SetuResult<()> {
+        self.update_object(graph, expected_version, expected_digest)
+    }
+
     /// Delete a relation graph and clean up indexes
-    fn delete_graph(&self, id: &ObjectId) -> SetuResult<()>;
-    
+    fn delete_graph(&self, id: &ObjectId) -> SetuResult<()> {
+        delete_typed::<RelationGraphData, _>(self, id)
+    }
+
     // ========== Aggregation ==========
-    
+
     /// Load AccountView (aggregates Profile, Credentials, Coins, and Graphs)
-    fn load_account_view(&self, address: &Address) -> SetuResult<AccountView>;
+    fn load_account_view(&self, address: &Address) -> SetuResult<AccountView> {
+        let profile = self.get_profile_by_address(address)?;
+        let credentials = self.get_credentials_by_holder(address)?;
+        let coins = self.get_coins_by_owner(address, None, None)?;
+        let graphs = self.get_graphs_by_owner(address)?;
+        Ok(AccountView::new(*address, profile, credentials, coins, graphs))
+    }
+}
+
+fn decode_all<T: ObjectData>(stored: Vec<StoredObject>) -> SetuResult<Vec<Object<T>>> {
+    let mut objects = Vec::with_capacity(stored.len());
+    for stored in stored {
+        objects.extend(stored.decode()?);
+    }
+    Ok(objects)
+}
+
+/// Delete `id` only if it is a `T`, so a typed delete never removes another type
+fn delete_typed<T: ObjectData, S: ObjectStore + ?Sized>(store: &S, id: &ObjectId) -> SetuResult<()> {
+    match store.get_stored(id)? {
+        Some(stored) if stored.type_tag == T::TYPE_TAG => store.delete_object(id),
+        _ => Ok(()),
+    }
 }
@@ -2,6 +2,10 @@
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ColumnFamily {
     Objects,
+    ObjectsByOwner,
+    ObjectsByIndex,
+    ObjectVersions,
+    // Per-type object layout, emptied by migration into `Objects`
     Coins,
     CoinsByOwner,
     Profiles,
@@ -12,14 +16,29 @@ pub enum ColumnFamily {
     RelationGraphs,
     GraphsByOwner,
     Events,
+    EventsByCreator,
+    EventsByStatus,
     Anchors,
+    AnchorChain,
+    EventAnchors,
+    AnchorsByDepth,
+    ConsensusFrames,
+    PendingFrames,
+    FinalizedFrames,
     Checkpoints,
+    MerkleNodes,
+    MerkleStaleNodes,
+    /// Database-wide settings such as the schema version and entry counts
+    Metadata,
 }
 
 impl ColumnFamily {
     pub fn name(&self) -> &'static str {
         match self {
             Self::Objects => "objects",
+            Self::ObjectsByOwner => "objects_by_owner",
+            Self::ObjectsByIndex => "objects_by_index",
+            Self::ObjectVersions => "object_versions",
             Self::Coins => "coins",
             Self::CoinsByOwner => "coins_by_owner",
             Self::Profiles => "profiles",
@@ -30,14 +49,28 @@ impl ColumnFamily {
             Self::RelationGraphs => "relation_graphs",
             Self::GraphsByOwner => "graphs_by_owner",
             Self::Events => "events",
+            Self::EventsByCreator => "events_by_creator",
+            Self::EventsByStatus => "events_by_status",
             Self::Anchors => "anchors",
+            Self::AnchorChain => "anchor_chain",
+            Self::EventAnchors => "event_anchors",
+            Self::AnchorsByDepth => "anchors_by_depth",
+            Self::ConsensusFrames => "consensus_frames",
+            Self::PendingFrames => "pending_frames",
+            Self::FinalizedFrames => "finalized_frames",
             Self::Checkpoints => "checkpoints",
+            Self::MerkleNodes => "merkle_nodes",
+            Self::MerkleStaleNodes => "merkle_stale_nodes",
+            Self::Metadata => "metadata",
         }
     }
     
     pub fn all() -> Vec<Self> {
         vec![
             Self::Objects,
+            Self::ObjectsByOwner,
+            Self::ObjectsByIndex,
+            Self::ObjectVersions,
             Self::Coins,
             Self::CoinsByOwner,
             Self::Profiles,
@@ -48,8 +81,19 @@ impl ColumnFamily {
             Self::RelationGraphs,
             Self::GraphsByOwner,
             Self::Events,
+            Self::EventsByCreator,
+            Self::EventsByStatus,
             Self::Anchors,
+            Self::AnchorChain,
+            Self::EventAnchors,
+            Self::AnchorsByDepth,
+            Self::ConsensusFrames,
+            Self::PendingFrames,
+            Self::FinalizedFrames,
             Self::Checkpoints,
+            Self::MerkleNodes,
+            Self::MerkleStaleNodes,
+            Self::Metadata,
         ]
     }
     
@@ -59,7 +103,7 @@ impl ColumnFamily {
             .map(|cf| {
                 let mut opts = rocksdb::Options::default();
                 match cf {
-                    Self::Objects => {
+                    Self::Objects | Self::ObjectVersions => {
                         opts.set_write_buffer_size(128 * 1024 * 1024);
                         opts.set_max_write_buffer_number(4);
                     }
@@ -67,18 +111,31 @@ impl ColumnFamily {
                         opts.set_write_buffer_size(64 * 1024 * 1024);
                         opts.set_max_write_buffer_number(3);
                     }
+                    Self::ObjectsByOwner | Self::ObjectsByIndex |
                     Self::CoinsByOwner | Self::GraphsByOwner | Self::ProfileByAddress |
                     Self::CredentialsByHolder | Self::CredentialsByIssuer => {
                         opts.set_write_buffer_size(32 * 1024 * 1024);
                         opts.set_compression_type(rocksdb::DBCompressionType::Zstd);
                     }
-                    Self::Events | Self::Anchors => {
+                    Self::Events | Self::Anchors | Self::ConsensusFrames => {
                         opts.set_write_buffer_size(64 * 1024 * 1024);
                         opts.set_max_write_buffer_number(6);
                     }
+                    Self::EventsByCreator | Self::EventsByStatus | Self::AnchorChain | Self::EventAnchors |
+                    Self::AnchorsByDepth | Self::PendingFrames | Self::FinalizedFrames => {
+                        opts.set_write_buffer_size(16 * 1024 * 1024);
+                        opts.set_compression_type(rocksdb::DBCompressionType::Zstd);
+                    }
                     Self::Checkpoints => {
                         opts.set_write_buffer_size(16 * 1024 * 1024);
                     }
+                    Self::MerkleNodes => {
+                        opts.set_write_buffer_size(64 * 1024 * 1024);
+                        opts.set_max_write_buffer_number(4);
+                    }
+                    Self::MerkleStaleNodes | Self::Metadata => {
+                        opts.set_write_buffer_size(16 * 1024 * 1024);
+                    }
                 }
                 rocksdb::ColumnFamilyDescriptor::new(cf.name(), opts)
             })
@@ -0,0 +1,38 @@
+//! Entry counts kept in the `metadata` column family
+//!
+//! Stores that report how many entries they hold keep the number under a key
+//! in `metadata` and update it in the same batch as the entries, so every store
+//! opened on the database reads the same count and none has to scan.
+
+use super::ColumnFamily;
+use super::error::Result;
+use crate::backend::{KvBackend, KvBatch};
+
+/// Number of entries in `events`
+pub(crate) const EVENT_COUNT_KEY: &str = "event_count";
+/// Number of entries in `anchors`
+pub(crate) const ANCHOR_COUNT_KEY: &str = "anchor_count";
+/// Number of entries in `pending_frames`
+pub(crate) const PENDING_FRAME_COUNT_KEY: &str = "pending_frame_count";
+
+/// Counted column families and the keys of their counts
+pub(crate) const COUNTED: [(ColumnFamily, &str); 3] = [
+    (ColumnFamily::Events, EVENT_COUNT_KEY),
+    (ColumnFamily::Anchors, ANCHOR_COUNT_KEY),
+    (ColumnFamily::PendingFrames, PENDING_FRAME_COUNT_KEY),
+];
+
+/// Committed value of the count under `key`
+pub(crate) fn get_count<B: KvBackend>(db: &B, key: &str) -> Result<u64> {
+    Ok(db.get(ColumnFamily::Metadata, &key)?.unwrap_or(0))
+}
+
+/// Add setting the count under `key` to its committed value plus `delta` to
+/// `batch`.
+///
+/// The caller must hold its store's write lock from reading the entries it
+/// counts until the batch is written.
+pub(crate) fn batch_add_count<B: KvBackend>(db: &B, batch: &mut KvBatch, key: &str, delta: i64) -> Result<()> {
+    let count = get_count(db, key)?.saturating_add_signed(delta);
+    db.batch_put(batch, ColumnFamily::Metadata, &key, &count)
+}
@@ -1,28 +1,36 @@
 use std::path::Path;
 use std::sync::Arc;
-use rocksdb::{DB, WriteBatch, IteratorMode};
-use serde::{Serialize, de::DeserializeOwned};
-use bincode::Encode;
+use rocksdb::{DB, WriteBatch, IteratorMode, Direction};
 
-use super::{StorageError, RocksDBConfig, ColumnFamily};
+use super::{StorageError, RocksDBConfig, ColumnFamily, migration};
 use super::error::Result;
+use crate::backend::{KvBackend, KvBatch, KvEntry, KvIter};
+use crate::metrics::StorageMetrics;
 
-/// Main database wrapper for Setu
+/// Main database wrapper for Setu, the RocksDB [`KvBackend`]
 pub struct SetuDB {
     db: Arc<DB>,
+    /// Options the database was opened with, which hold its statistics
+    options: Arc<rocksdb::Options>,
+    metrics: Option<Arc<StorageMetrics>>,
 }
 
 impl SetuDB {
-    /// Open or create a new database at the given path
+    /// Open or create a new database at the given path, migrating it to the
+    /// current schema version
     pub fn open(config: RocksDBConfig) -> Result<Self> {
         let opts = config.to_options();
         let cfs = ColumnFamily::descriptors();
         
         let db = DB::open_cf_descriptors(&opts, &config.path, cfs)?;
-        
-        Ok(Self {
+        let db = Self {
             db: Arc::new(db),
-        })
+            options: Arc::new(opts),
+            metrics: None,
+        };
+        
+        migration::run_migrations(&db)?;
+        Ok(db)
     }
     
     /// Open a database at the given path with default config
@@ -31,230 +39,112 @@ impl SetuDB {
         Self::open(config)
     }
     
+    /// Record operation latencies in `metrics`, including from clones made
+    /// after this call
+    pub fn with_metrics(mut self, metrics: Arc<StorageMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+    
+    /// Start timing an operation, observed when the timer is dropped
+    fn start_timer(&self, histogram: fn(&StorageMetrics) -> &prometheus::Histogram) -> Option<prometheus::HistogramTimer> {
+        self.metrics.as_ref().map(|metrics| histogram(metrics).start_timer())
+    }
+    
     /// Get a reference to the underlying RocksDB instance
     pub fn inner(&self) -> &DB {
         &self.db
     }
     
     /// Get a column family handle
-    fn cf_handle(&self, cf: ColumnFamily) -> Result<&rocksdb::ColumnFamily> {
+    pub(crate) fn cf_handle(&self, cf: ColumnFamily) -> Result<&rocksdb::ColumnFamily> {
         self.db
             .cf_handle(cf.name())
             .ok_or_else(|| StorageError::ColumnFamilyNotFound(cf.name().to_string()))
     }
     
-    /// Serialize a key using bincode
-    fn encode_key<K: Encode>(key: &K) -> Result<Vec<u8>> {
-        bincode::encode_to_vec(key, bincode::config::standard())
-            .map_err(|e| StorageError::Serialization(e.to_string()))
-    }
-    
-    /// Serialize a value using BCS (Binary Canonical Serialization)
-    fn encode_value<V: Serialize>(value: &V) -> Result<Vec<u8>> {
-        bcs::to_bytes(value)
-            .map_err(|e| StorageError::Serialization(e.to_string()))
-    }
-    
-    /// Deserialize a value using BCS
-    fn decode_value<V: DeserializeOwned>(bytes: &[u8]) -> Result<V> {
-        bcs::from_bytes(bytes)
-            .map_err(|e| StorageError::Deserialization(e.to_string()))
+    /// Flush the database to disk
+    pub fn flush(&self) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
     }
     
-    /// Put a key-value pair into a column family
-    pub fn put<K, V>(&self, cf: ColumnFamily, key: &K, value: &V) -> Result<()>
-    where
-        K: Encode,
-        V: Serialize,
-    {
+    /// Get property value for a column family
+    pub fn property_int_value(&self, cf: ColumnFamily, property: &str) -> Result<Option<u64>> {
         let cf_handle = self.cf_handle(cf)?;
-        let key_bytes = Self::encode_key(key)?;
-        let value_bytes = Self::encode_value(value)?;
-        
-        self.db.put_cf(cf_handle, key_bytes, value_bytes)?;
-        Ok(())
+        Ok(self.db.property_int_value_cf(cf_handle, property)?)
     }
     
-    /// Get a value by key from a column family
-    pub fn get<K, V>(&self, cf: ColumnFamily, key: &K) -> Result<Option<V>>
-    where
-        K: Encode,
-        V: DeserializeOwned,
-    {
-        let cf_handle = self.cf_handle(cf)?;
-        let key_bytes = Self::encode_key(key)?;
-        
-        match self.db.get_cf(cf_handle, key_bytes)? {
-            Some(bytes) => Ok(Some(Self::decode_value(&bytes)?)),
-            None => Ok(None),
-        }
+    /// Get a statistics ticker, 0 if statistics are disabled
+    pub fn ticker_count(&self, ticker: rocksdb::statistics::Ticker) -> u64 {
+        self.options.get_ticker_count(ticker)
     }
     
-    /// Delete a key from a column family
-    pub fn delete<K>(&self, cf: ColumnFamily, key: &K) -> Result<()>
-    where
-        K: Encode,
-    {
+    /// Compact a column family
+    pub fn compact(&self, cf: ColumnFamily) -> Result<()> {
         let cf_handle = self.cf_handle(cf)?;
-        let key_bytes = Self::encode_key(key)?;
-        
-        self.db.delete_cf(cf_handle, key_bytes)?;
+        self.db.compact_range_cf(cf_handle, None::<&[u8]>, None::<&[u8]>);
         Ok(())
     }
-    
-    /// Check if a key exists in a column family
-    pub fn exists<K>(&self, cf: ColumnFamily, key: &K) -> Result<bool>
-    where
-        K: Encode,
-    {
-        let cf_handle = self.cf_handle(cf)?;
-        let key_bytes = Self::encode_key(key)?;
-        
-        Ok(self.db.get_cf(cf_handle, key_bytes)?.is_some())
+}
+
+impl KvBackend for SetuDB {
+    fn get_raw(&self, cf: ColumnFamily, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let _timer = self.start_timer(|metrics| &metrics.get_latency);
+        Ok(self.db.get_cf(self.cf_handle(cf)?, key)?)
     }
     
-    /// Multi-get: retrieve multiple values at once
-    pub fn multi_get<K, V>(
-        &self,
-        cf: ColumnFamily,
-        keys: &[K],
-    ) -> Result<Vec<Option<V>>>
-    where
-        K: Encode,
-        V: DeserializeOwned,
-    {
+    fn multi_get_raw(&self, cf: ColumnFamily, keys: &[Vec<u8>]) -> Result<Vec<Option<Vec<u8>>>> {
+        let _timer = self.start_timer(|metrics| &metrics.multi_get_latency);
         let cf_handle = self.cf_handle(cf)?;
-        let key_bytes: Vec<_> = keys
-            .iter()
-            .map(Self::encode_key)
-            .collect::<Result<_>>()?;
-        
-        let results = self.db.multi_get_cf(
-            key_bytes.iter().map(|k| (cf_handle, k.as_slice()))
-        );
-        
-        results
+        self.db
+            .multi_get_cf(keys.iter().map(|key| (cf_handle, key.as_slice())))
             .into_iter()
-            .map(|result| match result? {
-                Some(bytes) => Ok(Some(Self::decode_value(&bytes)?)),
-                None => Ok(None),
-            })
+            .map(|result| Ok(result?))
             .collect()
     }
     
-    /// Create a new write batch
-    pub fn batch(&self) -> WriteBatch {
-        WriteBatch::default()
-    }
-    
-    /// Add a put operation to a write batch
-    pub fn batch_put<K, V>(
-        &self,
-        batch: &mut WriteBatch,
-        cf: ColumnFamily,
-        key: &K,
-        value: &V,
-    ) -> Result<()>
-    where
-        K: Encode,
-        V: Serialize,
-    {
-        let cf_handle = self.cf_handle(cf)?;
-        let key_bytes = Self::encode_key(key)?;
-        let value_bytes = Self::encode_value(value)?;
-        
-        batch.put_cf(cf_handle, key_bytes, value_bytes);
-        Ok(())
-    }
-    
-    /// Add a delete operation to a write batch
-    pub fn batch_delete<K>(
-        &self,
-        batch: &mut WriteBatch,
-        cf: ColumnFamily,
-        key: &K,
-    ) -> Result<()>
-    where
-        K: Encode,
-    {
-        let cf_handle = self.cf_handle(cf)?;
-        let key_bytes = Self::encode_key(key)?;
-        
-        batch.delete_cf(cf_handle, key_bytes);
+    fn put_raw(&self, cf: ColumnFamily, key: &[u8], value: &[u8]) -> Result<()> {
+        let _timer = self.start_timer(|metrics| &metrics.put_latency);
+        self.db.put_cf(self.cf_handle(cf)?, key, value)?;
         Ok(())
     }
     
-    /// Write a batch atomically
-    pub fn write_batch(&self, batch: WriteBatch) -> Result<()> {
-        self.db.write(batch)?;
+    fn delete_raw(&self, cf: ColumnFamily, key: &[u8]) -> Result<()> {
+        self.db.delete_cf(self.cf_handle(cf)?, key)?;
         Ok(())
     }
     
-    /// Iterate over all key-value pairs in a column family
-    pub fn iter<K, V>(&self, cf: ColumnFamily) -> Result<impl Iterator<Item = Result<(K, V)>> + '_>
-    where
-        K: DeserializeOwned + bincode::Decode<()>,
-        V: DeserializeOwned,
-    {
-        let cf_handle = self.cf_handle(cf)?;
-        
-        Ok(self
-            .db
-            .iterator_cf(cf_handle, IteratorMode::Start)
-            .map(|result| {
-                let (key_bytes, value_bytes) = result?;
-                let key = bincode::decode_from_slice(&key_bytes, bincode::config::standard())
-                    .map_err(|e| StorageError::Deserialization(e.to_string()))?
-                    .0;
-                let value = Self::decode_value(&value_bytes)?;
-                Ok((key, value))
-            }))
-    }
-    
-    /// Iterate with a prefix
-    pub fn prefix_iter<P, K, V>(
-        &self,
-        cf: ColumnFamily,
-        prefix: &P,
-    ) -> Result<impl Iterator<Item = Result<(K, V)>> + '_>
-    where
-        P: Encode,
-        K: DeserializeOwned + bincode::Decode<()>,
-        V: DeserializeOwned,
-    {
-        let cf_handle = self.cf_handle(cf)?;
-        let prefix_bytes = Self::encode_key(prefix)?;
-        
-        Ok(self
-            .db
-            .prefix_iterator_cf(cf_handle, &prefix_bytes)
-            .map(|result| {
-                let (key_bytes, value_bytes) = result?;
-                let key = bincode::decode_from_slice(&key_bytes, bincode::config::standard())
-                    .map_err(|e| StorageError::Deserialization(e.to_string()))?
-                    .0;
-                let value = Self::decode_value(&value_bytes)?;
-                Ok((key, value))
-            }))
-    }
-    
-    /// Flush the database to disk
-    pub fn flush(&self) -> Result<()> {
-        self.db.flush()?;
+    fn write_batch(&self, batch: KvBatch) -> Result<()> {
+        let _timer = self.start_timer(|metrics| &metrics.write_batch_latency);
+        let mut write_batch = WriteBatch::default();
+        for (cf, key, value) in batch.into_ops() {
+            let cf_handle = self.cf_handle(cf)?;
+            match value {
+                Some(value) => write_batch.put_cf(cf_handle, key, value),
+                None => write_batch.delete_cf(cf_handle, key),
+            }
+        }
+        self.db.write(write_batch)?;
         Ok(())
     }
     
-    /// Get property value for a column family
-    pub fn property_int_value(&self, cf: ColumnFamily, property: &str) -> Result<Option<u64>> {
-        let cf_handle = self.cf_handle(cf)?;
-        Ok(self.db.property_int_value_cf(cf_handle, property)?)
+    fn iter_raw(&self, cf: ColumnFamily, start: &[u8]) -> Result<KvIter<'_>> {
+        let iter = self.db.iterator_cf(self.cf_handle(cf)?, IteratorMode::From(start, Direction::Forward));
+        Ok(Box::new(iter.map(|entry| {
+            let (key, value) = entry?;
+            Ok((key.into_vec(), value.into_vec()))
+        })))
     }
     
-    /// Compact a column family
-    pub fn compact(&self, cf: ColumnFamily) -> Result<()> {
-        let cf_handle = self.cf_handle(cf)?;
-        self.db.compact_range_cf(cf_handle, None::<&[u8]>, None::<&[u8]>);
-        Ok(())
+    fn last_raw(&self, cf: ColumnFamily) -> Result<Option<KvEntry>> {
+        match self.db.iterator_cf(self.cf_handle(cf)?, IteratorMode::End).next() {
+            Some(entry) => {
+                let (key, value) = entry?;
+                Ok(Some((key.into_vec(), value.into_vec())))
+            }
+            None => Ok(None),
+        }
     }
 }
 
@@ -262,6 +152,8 @@ impl Clone for SetuDB {
     fn clone(&self) -> Self {
         Self {
             db: Arc::clone(&self.db),
+            options: Arc::clone(&self.options),
+            metrics: self.metrics.clone(),
         }
     }
 }
@@ -274,6 +166,7 @@ unsafe impl Sync for SetuDB {}
 mod tests {
     use super::*;
     use tempfile::TempDir;
+    use bincode::Encode;
     use serde::{Deserialize, Serialize};
     
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Encode)]
@@ -372,4 +265,25 @@ mod tests {
         assert_eq!(values.len(), 5);
         assert!(values.iter().all(|v| v.is_some()));
     }
+    
+    #[test]
+    fn test_prefix_iter_stops_at_prefix() {
+        let (db, _temp) = setup_test_db();
+        
+        for (owner, id) in [(1u64, 1u64), (1, 2), (2, 1), (3, 1)] {
+            db.put(ColumnFamily::Objects, &(owner, id), &id).unwrap();
+        }
+        
+        let entries: Vec<((u64, u64), u64)> = db
+            .prefix_iter(ColumnFamily::Objects, &1u64)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(entries, vec![((1, 1), 1), ((1, 2), 2)]);
+        assert_eq!(db.count_prefix(ColumnFamily::Objects, &2u64).unwrap(), 1);
+        assert_eq!(db.count(ColumnFamily::Objects).unwrap(), 4);
+        
+        let last: Option<((u64, u64), u64)> = db.last(ColumnFamily::Objects).unwrap();
+        assert_eq!(last, Some(((3, 1), 1)));
+    }
 }
@@ -20,6 +20,9 @@ pub enum StorageError {
     #[error("Invalid data: {0}")]
     InvalidData(String),
 
+    #[error("Database schema version {found} is newer than the supported version {supported}")]
+    UnsupportedSchemaVersion { found: u32, supported: u32 },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
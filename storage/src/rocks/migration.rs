@@ -0,0 +1,218 @@
+use rocksdb::{IteratorMode, WriteBatch};
+use setu_types::{Object, ObjectData, ObjectId, CoinData, ProfileData, CredentialData, RelationGraphData};
+
+use super::{SetuDB, ColumnFamily, KvBackend, StorageError, Transaction};
+use super::counter::COUNTED;
+use super::error::Result;
+use crate::object_store::{ObjectChange, StoredObject};
+use crate::rocks_object_store::{RocksObjectStore, WriteContext};
+
+/// Schema version written by this build
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Key of the schema version in `Metadata`
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// A step upgrading the database from `version - 1` to `version`
+struct Migration {
+    version: u32,
+    description: &'static str,
+    run: fn(&SetuDB) -> Result<()>,
+}
+
+/// Migrations in version order, ending at `SCHEMA_VERSION`.
+///
+/// Databases from before schema versioning have no version and start at 0.
+/// New steps are appended here together with a bump of `SCHEMA_VERSION`;
+/// released steps must not change.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "move per-type objects into the objects column family",
+        run: |db| migrate_typed_objects(db).map(|_| ()),
+    },
+    Migration {
+        version: 2,
+        description: "record entry counts in the metadata column family",
+        run: record_counts,
+    },
+];
+
+/// Schema version recorded in the database, `None` if it has none yet
+pub fn schema_version(db: &SetuDB) -> Result<Option<u32>> {
+    db.get(ColumnFamily::Metadata, &SCHEMA_VERSION_KEY)
+}
+
+/// Bring the database up to `SCHEMA_VERSION`, run by [`SetuDB::open`].
+///
+/// Each pending migration runs in order and the version is recorded after
+/// every step, so an interrupted upgrade resumes where it stopped. A database
+/// written by a newer schema is refused rather than read with the wrong
+/// layout. Returns the version the database was at.
+pub fn run_migrations(db: &SetuDB) -> Result<u32> {
+    let found = schema_version(db)?.unwrap_or(0);
+    if found > SCHEMA_VERSION {
+        return Err(StorageError::UnsupportedSchemaVersion { found, supported: SCHEMA_VERSION });
+    }
+
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > found) {
+        tracing::info!(
+            version = migration.version,
+            "Migrating database schema: {}",
+            migration.description
+        );
+        (migration.run)(db)?;
+        db.put(ColumnFamily::Metadata, &SCHEMA_VERSION_KEY, &migration.version)?;
+    }
+
+    Ok(found)
+}
+
+/// Indexes of the per-type object layout
+const LEGACY_INDEXES: [ColumnFamily; 5] = [
+    ColumnFamily::CoinsByOwner,
+    ColumnFamily::ProfileByAddress,
+    ColumnFamily::CredentialsByHolder,
+    ColumnFamily::CredentialsByIssuer,
+    ColumnFamily::GraphsByOwner,
+];
+
+/// Move objects from the per-type column families (`coins`, `profiles`, ...)
+/// into `objects`, indexing them as they go, and drop the per-type indexes.
+///
+/// Running this on an already migrated database does nothing. Returns the
+/// number of objects moved.
+pub fn migrate_typed_objects(db: &SetuDB) -> Result<usize> {
+    let mut txn = db.transaction();
+    let mut migrated = 0;
+
+    migrated += migrate_objects::<CoinData>(db, &mut txn, ColumnFamily::Coins)?;
+    migrated += migrate_objects::<ProfileData>(db, &mut txn, ColumnFamily::Profiles)?;
+    migrated += migrate_objects::<CredentialData>(db, &mut txn, ColumnFamily::Credentials)?;
+    migrated += migrate_objects::<RelationGraphData>(db, &mut txn, ColumnFamily::RelationGraphs)?;
+    if !txn.is_empty() {
+        txn.commit()?;
+    }
+
+    // The new indexes are built from the objects, so the old ones can go
+    for cf in LEGACY_INDEXES {
+        let cf_handle = db.cf_handle(cf)?;
+        let mut batch = WriteBatch::default();
+        for entry in db.inner().iterator_cf(cf_handle, IteratorMode::Start) {
+            let (key_bytes, _) = entry?;
+            batch.delete_cf(cf_handle, key_bytes);
+        }
+        if !batch.is_empty() {
+            db.inner().write(batch)?;
+        }
+    }
+
+    Ok(migrated)
+}
+
+/// Count the entries of the column families whose counts are kept in
+/// `metadata`, which stores update from then on.
+fn record_counts(db: &SetuDB) -> Result<()> {
+    for (cf, key) in COUNTED {
+        let count = db.count(cf)? as u64;
+        db.put(ColumnFamily::Metadata, &key, &count)?;
+    }
+    Ok(())
+}
+
+fn migrate_objects<T: ObjectData>(db: &SetuDB, txn: &mut Transaction, cf: ColumnFamily) -> Result<usize> {
+    let mut migrated = 0;
+    for entry in db.iter::<ObjectId, Object<T>>(cf)? {
+        let (id, object) = entry?;
+        let change = StoredObject::new(&object)
+            .map(ObjectChange::Put)
+            .map_err(|e| StorageError::Other(e.to_string()))?;
+        RocksObjectStore::stage(txn, &change, &WriteContext::default()).map_err(|e| StorageError::Other(e.to_string()))?;
+        txn.delete(cf, &id)?;
+        migrated += 1;
+    }
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rocks::counter::{get_count, ANCHOR_COUNT_KEY, EVENT_COUNT_KEY, PENDING_FRAME_COUNT_KEY};
+    use crate::ObjectStore;
+    use setu_types::{Address, Coin, create_kyc_credential};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_migrate_typed_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        let alice = Address::from_str_id("alice");
+        let issuer = Address::from_str_id("kyc_provider");
+        let coin = Coin::new(alice, 1000);
+        let credential = create_kyc_credential(alice, issuer, "level_2");
+
+        {
+            // Written in the per-type layout
+            let db = SetuDB::open_default(temp_dir.path()).unwrap();
+            db.put(ColumnFamily::Coins, &coin.metadata.id, &coin).unwrap();
+            db.put(ColumnFamily::CoinsByOwner, &(alice, coin.metadata.id), &()).unwrap();
+            db.put(ColumnFamily::Credentials, &credential.metadata.id, &credential).unwrap();
+            // by a build from before schema versions
+            db.delete(ColumnFamily::Metadata, &SCHEMA_VERSION_KEY).unwrap();
+        }
+
+        // Migrated on open
+        let store = RocksObjectStore::open(temp_dir.path()).unwrap();
+        assert_eq!(store.get_coins_by_owner(&alice, None, None).unwrap()[0].value(), 1000);
+        assert_eq!(store.get_credentials_by_issuer(&issuer).unwrap().len(), 1);
+
+        let db = SetuDB::open_default(tempfile::tempdir().unwrap().path()).unwrap();
+        assert_eq!(migrate_typed_objects(&db).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_record_counts() {
+        let temp_dir = TempDir::new().unwrap();
+        {
+            // Written before counts were kept
+            let db = SetuDB::open_default(temp_dir.path()).unwrap();
+            db.put(ColumnFamily::Anchors, &"anchor".to_string(), &()).unwrap();
+            db.put(ColumnFamily::PendingFrames, &"cf1".to_string(), &()).unwrap();
+            db.put(ColumnFamily::PendingFrames, &"cf2".to_string(), &()).unwrap();
+            for (_, key) in COUNTED {
+                db.delete(ColumnFamily::Metadata, &key).unwrap();
+            }
+            db.put(ColumnFamily::Metadata, &SCHEMA_VERSION_KEY, &1u32).unwrap();
+        }
+
+        let db = SetuDB::open_default(temp_dir.path()).unwrap();
+        assert_eq!(get_count(&db, EVENT_COUNT_KEY).unwrap(), 0);
+        assert_eq!(get_count(&db, ANCHOR_COUNT_KEY).unwrap(), 1);
+        assert_eq!(get_count(&db, PENDING_FRAME_COUNT_KEY).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_schema_version() {
+        let temp_dir = TempDir::new().unwrap();
+        {
+            let db = SetuDB::open_default(temp_dir.path()).unwrap();
+            assert_eq!(schema_version(&db).unwrap(), Some(SCHEMA_VERSION));
+            assert_eq!(run_migrations(&db).unwrap(), SCHEMA_VERSION);
+
+            // Written by a newer build
+            db.put(ColumnFamily::Metadata, &SCHEMA_VERSION_KEY, &(SCHEMA_VERSION + 1)).unwrap();
+        }
+
+        assert!(matches!(
+            SetuDB::open_default(temp_dir.path()),
+            Err(StorageError::UnsupportedSchemaVersion { found, supported })
+                if found == SCHEMA_VERSION + 1 && supported == SCHEMA_VERSION
+        ));
+    }
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        let versions: Vec<u32> = MIGRATIONS.iter().map(|migration| migration.version).collect();
+        let expected: Vec<u32> = (1..=SCHEMA_VERSION).collect();
+        assert_eq!(versions, expected);
+    }
+}
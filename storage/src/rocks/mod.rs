@@ -4,8 +4,13 @@ pub mod db;
 pub mod error;
 pub mod config;
 pub mod column_family;
+pub mod transaction;
+pub mod migration;
+pub(crate) mod counter;
 
 pub use db::SetuDB;
 pub use error::StorageError;
 pub use config::RocksDBConfig;
 pub use column_family::ColumnFamily;
+pub use transaction::Transaction;
+pub use crate::backend::KvBackend;
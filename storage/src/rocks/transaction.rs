@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use serde::{Serialize, de::DeserializeOwned};
+use bincode::Encode;
+
+use super::{SetuDB, ColumnFamily};
+use super::error::Result;
+use crate::backend::{KvBackend, KvBatch, encode_key, encode_value, decode_value};
+
+/// A group of writes committed atomically in a single batch.
+///
+/// Reads through the transaction see its own uncommitted writes, so the same
+/// key can be read, modified and written several times before commit. Nothing
+/// reaches the database until [`Transaction::commit`]; dropping an uncommitted
+/// transaction discards it.
+pub struct Transaction<'a, B: KvBackend = SetuDB> {
+    db: &'a B,
+    batch: KvBatch,
+    /// Encoded values written so far, `None` for deletes
+    staged: HashMap<(ColumnFamily, Vec<u8>), Option<Vec<u8>>>,
+}
+
+impl<'a, B: KvBackend> Transaction<'a, B> {
+    pub fn new(db: &'a B) -> Self {
+        Self {
+            db,
+            batch: db.batch(),
+            staged: HashMap::new(),
+        }
+    }
+    
+    /// Database the transaction writes to
+    pub fn db(&self) -> &'a B {
+        self.db
+    }
+    
+    /// Get a value, including writes staged in this transaction
+    pub fn get<K, V>(&self, cf: ColumnFamily, key: &K) -> Result<Option<V>>
+    where
+        K: Encode,
+        V: DeserializeOwned,
+    {
+        let key_bytes = encode_key(key)?;
+        match self.staged.get(&(cf, key_bytes)) {
+            Some(Some(value_bytes)) => decode_value(value_bytes).map(Some),
+            Some(None) => Ok(None),
+            None => self.db.get(cf, key),
+        }
+    }
+    
+    /// Stage a put
+    pub fn put<K, V>(&mut self, cf: ColumnFamily, key: &K, value: &V) -> Result<()>
+    where
+        K: Encode,
+        V: Serialize,
+    {
+        let key_bytes = encode_key(key)?;
+        let value_bytes = encode_value(value)?;
+        
+        self.batch.put_raw(cf, key_bytes.clone(), value_bytes.clone());
+        self.staged.insert((cf, key_bytes), Some(value_bytes));
+        Ok(())
+    }
+    
+    /// Stage a delete
+    pub fn delete<K: Encode>(&mut self, cf: ColumnFamily, key: &K) -> Result<()> {
+        let key_bytes = encode_key(key)?;
+        
+        self.batch.delete_raw(cf, key_bytes.clone());
+        self.staged.insert((cf, key_bytes), None);
+        Ok(())
+    }
+    
+    /// Number of staged operations
+    pub fn len(&self) -> usize {
+        self.batch.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.batch.is_empty()
+    }
+    
+    /// Write all staged operations atomically
+    pub fn commit(self) -> Result<()> {
+        self.db.write_batch(self.batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_read_your_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = SetuDB::open_default(temp_dir.path()).unwrap();
+        db.put(ColumnFamily::Objects, &1u64, &"old".to_string()).unwrap();
+        
+        let mut txn = db.transaction();
+        txn.put(ColumnFamily::Objects, &1u64, &"new".to_string()).unwrap();
+        txn.delete(ColumnFamily::Objects, &2u64).unwrap();
+        assert_eq!(txn.get::<_, String>(ColumnFamily::Objects, &1u64).unwrap(), Some("new".to_string()));
+        assert_eq!(txn.get::<_, String>(ColumnFamily::Objects, &2u64).unwrap(), None);
+        
+        // Nothing is visible until commit
+        assert_eq!(db.get::<_, String>(ColumnFamily::Objects, &1u64).unwrap(), Some("old".to_string()));
+        txn.commit().unwrap();
+        assert_eq!(db.get::<_, String>(ColumnFamily::Objects, &1u64).unwrap(), Some("new".to_string()));
+    }
+}
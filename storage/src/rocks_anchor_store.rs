@@ -0,0 +1,347 @@
+//! RocksDB implementations of AnchorStore and CFStore
+
+use crate::anchor_store::{AnchorStore, CFStore};
+use crate::rocks::{SetuDB, ColumnFamily, KvBackend};
+use crate::rocks::counter::{batch_add_count, get_count, ANCHOR_COUNT_KEY, PENDING_FRAME_COUNT_KEY};
+use async_trait::async_trait;
+use setu_types::{Anchor, AnchorId, ConsensusFrame, CFId, CFStatus, SetuResult, SetuError};
+use tokio::sync::Mutex;
+
+/// Next free key in an append-only column family keyed by big-endian sequence numbers
+fn next_seq(db: &SetuDB, cf: ColumnFamily) -> SetuResult<u64> {
+    let last: Option<([u8; 8], String)> = db.last(cf).map_err(|e| SetuError::StorageError(e.to_string()))?;
+    Ok(last.map(|(seq, _)| u64::from_be_bytes(seq) + 1).unwrap_or(0))
+}
+
+/// Anchor store persisted in the `anchors` column family.
+///
+/// The chain order is kept in `anchor_chain` under sequence numbers,
+/// `anchors_by_depth` maps each depth to its anchor and `event_anchors` maps
+/// each anchored event to the depth of its anchor. The number of anchors is
+/// kept in `metadata` and updated in the same batch as the anchors.
+pub struct RocksAnchorStore {
+    db: SetuDB,
+    /// Sequence number of the next anchor appended to the chain
+    next_seq: Mutex<u64>,
+}
+
+impl RocksAnchorStore {
+    pub fn new(db: SetuDB) -> SetuResult<Self> {
+        let next_seq = next_seq(&db, ColumnFamily::AnchorChain)?;
+        Ok(Self {
+            db,
+            next_seq: Mutex::new(next_seq),
+        })
+    }
+
+    pub fn open(path: impl AsRef<std::path::Path>) -> SetuResult<Self> {
+        let db = SetuDB::open_default(path)
+            .map_err(|e| SetuError::StorageError(format!("Failed to open database: {}", e)))?;
+        Self::new(db)
+    }
+}
+
+#[async_trait]
+impl AnchorStore for RocksAnchorStore {
+    async fn store(&self, anchor: Anchor) -> SetuResult<()> {
+        let mut next_seq = self.next_seq.lock().await;
+        let is_new = !self.db.exists(ColumnFamily::Anchors, &anchor.id)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        let mut batch = self.db.batch();
+
+        if is_new {
+            batch_add_count(&self.db, &mut batch, ANCHOR_COUNT_KEY, 1)
+                .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        }
+
+        self.db.batch_put(&mut batch, ColumnFamily::Anchors, &anchor.id, &anchor)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        self.db.batch_put(&mut batch, ColumnFamily::AnchorChain, &next_seq.to_be_bytes(), &anchor.id)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        self.db.batch_put(&mut batch, ColumnFamily::AnchorsByDepth, &anchor.depth.to_be_bytes(), &anchor.id)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        for event_id in &anchor.event_ids {
+            self.db.batch_put(&mut batch, ColumnFamily::EventAnchors, event_id, &anchor.depth)
+                .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        }
+        self.db.write_batch(batch).map_err(|e| SetuError::StorageError(e.to_string()))?;
+
+        *next_seq += 1;
+        Ok(())
+    }
+
+    async fn get(&self, anchor_id: &AnchorId) -> SetuResult<Option<Anchor>> {
+        self.db.get(ColumnFamily::Anchors, anchor_id).map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+
+    async fn get_latest(&self) -> SetuResult<Option<Anchor>> {
+        let last: Option<([u8; 8], AnchorId)> = self.db.last(ColumnFamily::AnchorChain)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        match last {
+            Some((_, id)) => self.get(&id).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn get_by_depth(&self, depth: u64) -> SetuResult<Option<Anchor>> {
+        let anchor_id: Option<AnchorId> = self.db.get(ColumnFamily::AnchorsByDepth, &depth.to_be_bytes())
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        match anchor_id {
+            Some(id) => self.get(&id).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn count(&self) -> SetuResult<usize> {
+        get_count(&self.db, ANCHOR_COUNT_KEY)
+            .map(|count| count as usize)
+            .map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+
+    async fn get_chain(&self) -> SetuResult<Vec<AnchorId>> {
+        self.db.iter::<[u8; 8], AnchorId>(ColumnFamily::AnchorChain)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?
+            .map(|entry| entry.map(|(_, id)| id))
+            .collect::<Result<_, _>>()
+            .map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+}
+
+/// Consensus frame store persisted in the `consensus_frames` column family.
+///
+/// Pending frames are listed in `pending_frames`; finalized frames are kept in
+/// finalization order in `finalized_frames`. The number of pending frames is
+/// kept in `metadata` and updated in the same batch as `pending_frames`.
+pub struct RocksCFStore {
+    db: SetuDB,
+    /// Sequence number of the next finalized frame
+    next_seq: Mutex<u64>,
+}
+
+impl RocksCFStore {
+    pub fn new(db: SetuDB) -> SetuResult<Self> {
+        let next_seq = next_seq(&db, ColumnFamily::FinalizedFrames)?;
+        Ok(Self {
+            db,
+            next_seq: Mutex::new(next_seq),
+        })
+    }
+
+    pub fn open(path: impl AsRef<std::path::Path>) -> SetuResult<Self> {
+        let db = SetuDB::open_default(path)
+            .map_err(|e| SetuError::StorageError(format!("Failed to open database: {}", e)))?;
+        Self::new(db)
+    }
+
+    fn get_frames(&self, cf_ids: &[CFId]) -> SetuResult<Vec<ConsensusFrame>> {
+        let frames: Vec<Option<ConsensusFrame>> = self.db.multi_get(ColumnFamily::ConsensusFrames, cf_ids)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        Ok(frames.into_iter().flatten().collect())
+    }
+
+    fn pending_ids(&self) -> SetuResult<Vec<CFId>> {
+        self.db.iter::<CFId, ()>(ColumnFamily::PendingFrames)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?
+            .map(|entry| entry.map(|(id, _)| id))
+            .collect::<Result<_, _>>()
+            .map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+
+    fn finalized_ids(&self) -> SetuResult<Vec<CFId>> {
+        self.db.iter::<[u8; 8], CFId>(ColumnFamily::FinalizedFrames)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?
+            .map(|entry| entry.map(|(_, id)| id))
+            .collect::<Result<_, _>>()
+            .map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+
+    fn is_pending(&self, cf_id: &CFId) -> SetuResult<bool> {
+        self.db.exists(ColumnFamily::PendingFrames, cf_id).map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl CFStore for RocksCFStore {
+    async fn store(&self, cf: ConsensusFrame) -> SetuResult<()> {
+        let mut next_seq = self.next_seq.lock().await;
+        let mut batch = self.db.batch();
+        let is_finalized = cf.status == CFStatus::Finalized;
+        let was_pending = self.is_pending(&cf.id)?;
+
+        self.db.batch_put(&mut batch, ColumnFamily::ConsensusFrames, &cf.id, &cf)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        if is_finalized {
+            self.db.batch_delete(&mut batch, ColumnFamily::PendingFrames, &cf.id)
+                .map_err(|e| SetuError::StorageError(e.to_string()))?;
+            self.db.batch_put(&mut batch, ColumnFamily::FinalizedFrames, &next_seq.to_be_bytes(), &cf.id)
+                .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        } else {
+            self.db.batch_put(&mut batch, ColumnFamily::PendingFrames, &cf.id, &())
+                .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        }
+        let pending_delta = match (was_pending, is_finalized) {
+            (true, true) => -1,
+            (false, false) => 1,
+            _ => 0,
+        };
+        if pending_delta != 0 {
+            batch_add_count(&self.db, &mut batch, PENDING_FRAME_COUNT_KEY, pending_delta)
+                .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        }
+        self.db.write_batch(batch).map_err(|e| SetuError::StorageError(e.to_string()))?;
+
+        if is_finalized {
+            *next_seq += 1;
+        }
+        Ok(())
+    }
+
+    async fn get(&self, cf_id: &CFId) -> SetuResult<Option<ConsensusFrame>> {
+        self.db.get(ColumnFamily::ConsensusFrames, cf_id).map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+
+    async fn mark_finalized(&self, cf_id: &CFId) -> SetuResult<()> {
+        let mut next_seq = self.next_seq.lock().await;
+        let mut cf = match self.get(cf_id).await? {
+            Some(cf) if cf.status != CFStatus::Finalized => cf,
+            // Unknown or already finalized
+            _ => return Ok(()),
+        };
+        cf.finalize();
+        let was_pending = self.is_pending(cf_id)?;
+
+        let mut batch = self.db.batch();
+        self.db.batch_put(&mut batch, ColumnFamily::ConsensusFrames, cf_id, &cf)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        self.db.batch_delete(&mut batch, ColumnFamily::PendingFrames, cf_id)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        self.db.batch_put(&mut batch, ColumnFamily::FinalizedFrames, &next_seq.to_be_bytes(), cf_id)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        if was_pending {
+            batch_add_count(&self.db, &mut batch, PENDING_FRAME_COUNT_KEY, -1)
+                .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        }
+        self.db.write_batch(batch).map_err(|e| SetuError::StorageError(e.to_string()))?;
+
+        *next_seq += 1;
+        Ok(())
+    }
+
+    async fn get_pending(&self) -> SetuResult<Vec<ConsensusFrame>> {
+        let ids = self.pending_ids()?;
+        self.get_frames(&ids)
+    }
+
+    async fn get_finalized(&self) -> SetuResult<Vec<ConsensusFrame>> {
+        let ids = self.finalized_ids()?;
+        self.get_frames(&ids)
+    }
+
+    async fn latest_finalized(&self) -> SetuResult<Option<ConsensusFrame>> {
+        let last: Option<([u8; 8], CFId)> = self.db.last(ColumnFamily::FinalizedFrames)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        match last {
+            Some((_, id)) => self.get(&id).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn finalized_count(&self) -> SetuResult<usize> {
+        // Finalized frames are numbered from 0 without gaps
+        next_seq(&self.db, ColumnFamily::FinalizedFrames).map(|seq| seq as usize)
+    }
+
+    async fn pending_count(&self) -> SetuResult<usize> {
+        get_count(&self.db, PENDING_FRAME_COUNT_KEY)
+            .map(|count| count as usize)
+            .map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use setu_types::{VectorClock, VLCSnapshot};
+
+    fn create_anchor(depth: u64) -> Anchor {
+        Anchor::new(
+            vec![format!("event{}", depth)],
+            VLCSnapshot {
+                vector_clock: VectorClock::new(),
+                logical_time: depth * 10,
+                physical_time: depth * 10000,
+            },
+            format!("state_root_{}", depth),
+            None,
+            String::new(),
+            depth,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_anchor_chain_survives_reopen() {
+        let temp_dir = TempDir::new().unwrap();
+        let anchors: Vec<Anchor> = (0..3).map(create_anchor).collect();
+
+        {
+            let store = RocksAnchorStore::open(temp_dir.path()).unwrap();
+            store.store(anchors[0].clone()).await.unwrap();
+            store.store(anchors[1].clone()).await.unwrap();
+        }
+
+        let store = RocksAnchorStore::open(temp_dir.path()).unwrap();
+        assert_eq!(store.get_latest().await.unwrap().unwrap().depth, 1);
+        assert_eq!(store.count().await.unwrap(), 2);
+
+        // Appending after a restart continues the chain
+        let other = RocksAnchorStore::new(store.db.clone()).unwrap();
+        store.store(anchors[2].clone()).await.unwrap();
+        assert_eq!(store.count().await.unwrap(), 3);
+        assert_eq!(other.count().await.unwrap(), 3);
+        assert_eq!(store.get_latest().await.unwrap().unwrap().depth, 2);
+        assert_eq!(store.get_by_depth(1).await.unwrap().unwrap().id, anchors[1].id);
+        assert!(store.get_by_depth(5).await.unwrap().is_none());
+
+        let ids: Vec<AnchorId> = anchors.iter().map(|a| a.id.clone()).collect();
+        assert_eq!(store.get_chain().await.unwrap(), ids);
+    }
+
+    #[tokio::test]
+    async fn test_cf_store_survives_reopen() {
+        let temp_dir = TempDir::new().unwrap();
+        let cf1 = ConsensusFrame::new(create_anchor(0), "validator1".to_string());
+        let cf2 = ConsensusFrame::new(create_anchor(1), "validator1".to_string());
+
+        {
+            let store = RocksCFStore::open(temp_dir.path()).unwrap();
+            store.store(cf1.clone()).await.unwrap();
+            store.store(cf2.clone()).await.unwrap();
+            // Storing a pending frame again does not count it twice
+            store.store(cf1.clone()).await.unwrap();
+            assert_eq!(store.pending_count().await.unwrap(), 2);
+
+            store.mark_finalized(&cf2.id).await.unwrap();
+            // Finalizing twice does not list the frame twice
+            store.mark_finalized(&cf2.id).await.unwrap();
+            assert_eq!(store.finalized_count().await.unwrap(), 1);
+        }
+
+        let store = RocksCFStore::open(temp_dir.path()).unwrap();
+        assert_eq!(store.pending_count().await.unwrap(), 1);
+        assert_eq!(store.get_pending().await.unwrap()[0].id, cf1.id);
+
+        let other = RocksCFStore::new(store.db.clone()).unwrap();
+        store.mark_finalized(&cf1.id).await.unwrap();
+        assert_eq!(store.pending_count().await.unwrap(), 0);
+        assert_eq!(store.finalized_count().await.unwrap(), 2);
+        assert_eq!(other.pending_count().await.unwrap(), 0);
+        assert_eq!(other.finalized_count().await.unwrap(), 2);
+        let finalized: Vec<CFId> = store.get_finalized().await.unwrap().into_iter().map(|cf| cf.id).collect();
+        assert_eq!(finalized, vec![cf2.id.clone(), cf1.id.clone()]);
+
+        let latest = store.latest_finalized().await.unwrap().unwrap();
+        assert_eq!(latest.id, cf1.id);
+        assert_eq!(latest.status, CFStatus::Finalized);
+    }
+}
@@ -0,0 +1,228 @@
+//! RocksDB implementation of EventStore
+
+use crate::event_store::EventStore;
+use crate::rocks::{SetuDB, ColumnFamily, KvBackend};
+use crate::rocks::counter::{batch_add_count, get_count, EVENT_COUNT_KEY};
+use async_trait::async_trait;
+use setu_types::{Event, EventId, EventStatus, SetuResult, SetuError};
+use tokio::sync::Mutex;
+
+/// Event store persisted in the `events` column family.
+///
+/// Secondary indexes are keyed by `(creator, event_id)` and
+/// `(status, event_id)` so lookups are prefix scans. The number of events is
+/// kept in `metadata` and updated in the same batch as the events.
+pub struct RocksEventStore {
+    db: SetuDB,
+    /// Serializes writers, which read the old event to fix up the indexes
+    /// and the count
+    write_lock: Mutex<()>,
+}
+
+/// Stable index key for a status; never reorder these values
+fn status_code(status: EventStatus) -> u8 {
+    match status {
+        EventStatus::Pending => 0,
+        EventStatus::InWorkQueue => 1,
+        EventStatus::Executed => 2,
+        EventStatus::Confirmed => 3,
+        EventStatus::Finalized => 4,
+        EventStatus::Failed => 5,
+    }
+}
+
+impl RocksEventStore {
+    pub fn new(db: SetuDB) -> Self {
+        Self {
+            db,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn open(path: impl AsRef<std::path::Path>) -> SetuResult<Self> {
+        let db = SetuDB::open_default(path)
+            .map_err(|e| SetuError::StorageError(format!("Failed to open database: {}", e)))?;
+        Ok(Self::new(db))
+    }
+
+    fn get_event(&self, event_id: &EventId) -> SetuResult<Option<Event>> {
+        self.db.get(ColumnFamily::Events, event_id).map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+
+    fn get_events(&self, event_ids: &[EventId]) -> SetuResult<Vec<Event>> {
+        let events: Vec<Option<Event>> = self.db.multi_get(ColumnFamily::Events, event_ids)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        Ok(events.into_iter().flatten().collect())
+    }
+
+    fn ids_by_creator(&self, creator: &str) -> SetuResult<Vec<EventId>> {
+        self.db.prefix_iter::<_, (String, EventId), ()>(ColumnFamily::EventsByCreator, &creator.to_string())
+            .map_err(|e| SetuError::StorageError(e.to_string()))?
+            .map(|entry| entry.map(|((_, id), _)| id))
+            .collect::<Result<_, _>>()
+            .map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+
+    fn ids_by_status(&self, status: EventStatus) -> SetuResult<Vec<EventId>> {
+        self.db.prefix_iter::<_, (u8, EventId), ()>(ColumnFamily::EventsByStatus, &status_code(status))
+            .map_err(|e| SetuError::StorageError(e.to_string()))?
+            .map(|entry| entry.map(|((_, id), _)| id))
+            .collect::<Result<_, _>>()
+            .map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl EventStore for RocksEventStore {
+    async fn store(&self, event: Event) -> SetuResult<()> {
+        let _guard = self.write_lock.lock().await;
+        let mut batch = self.db.batch();
+
+        match self.get_event(&event.id)? {
+            Some(old) => {
+                self.db.batch_delete(&mut batch, ColumnFamily::EventsByCreator, &(old.creator, old.id.clone()))
+                    .map_err(|e| SetuError::StorageError(e.to_string()))?;
+                self.db.batch_delete(&mut batch, ColumnFamily::EventsByStatus, &(status_code(old.status), old.id))
+                    .map_err(|e| SetuError::StorageError(e.to_string()))?;
+            }
+            None => batch_add_count(&self.db, &mut batch, EVENT_COUNT_KEY, 1)
+                .map_err(|e| SetuError::StorageError(e.to_string()))?,
+        }
+
+        self.db.batch_put(&mut batch, ColumnFamily::Events, &event.id, &event)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        self.db.batch_put(&mut batch, ColumnFamily::EventsByCreator, &(event.creator.clone(), event.id.clone()), &())
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        self.db.batch_put(&mut batch, ColumnFamily::EventsByStatus, &(status_code(event.status), event.id.clone()), &())
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+
+        self.db.write_batch(batch).map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+
+    async fn get(&self, event_id: &EventId) -> SetuResult<Option<Event>> {
+        self.get_event(event_id)
+    }
+
+    async fn get_many(&self, event_ids: &[EventId]) -> SetuResult<Vec<Event>> {
+        self.get_events(event_ids)
+    }
+
+    async fn update_status(&self, event_id: &EventId, new_status: EventStatus) -> SetuResult<()> {
+        let _guard = self.write_lock.lock().await;
+        let Some(mut event) = self.get_event(event_id)? else {
+            return Ok(());
+        };
+        let old_status = event.status;
+        event.status = new_status;
+
+        let mut batch = self.db.batch();
+        self.db.batch_put(&mut batch, ColumnFamily::Events, event_id, &event)
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        self.db.batch_delete(&mut batch, ColumnFamily::EventsByStatus, &(status_code(old_status), event_id.clone()))
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        self.db.batch_put(&mut batch, ColumnFamily::EventsByStatus, &(status_code(new_status), event_id.clone()), &())
+            .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        self.db.write_batch(batch).map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+
+    async fn get_by_creator(&self, creator: &str) -> SetuResult<Vec<Event>> {
+        let ids = self.ids_by_creator(creator)?;
+        self.get_events(&ids)
+    }
+
+    async fn get_by_status(&self, status: EventStatus) -> SetuResult<Vec<Event>> {
+        let ids = self.ids_by_status(status)?;
+        self.get_events(&ids)
+    }
+
+    async fn count(&self) -> SetuResult<usize> {
+        get_count(&self.db, EVENT_COUNT_KEY)
+            .map(|count| count as usize)
+            .map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+
+    async fn count_by_status(&self, status: EventStatus) -> SetuResult<usize> {
+        self.db.count_prefix(ColumnFamily::EventsByStatus, &status_code(status))
+            .map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+
+    async fn exists(&self, event_id: &EventId) -> SetuResult<bool> {
+        self.db.exists(ColumnFamily::Events, event_id).map_err(|e| SetuError::StorageError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use setu_types::{EventType, VectorClock, VLCSnapshot};
+
+    fn create_event(creator: &str, logical_time: u64) -> Event {
+        Event::new(
+            EventType::Transfer,
+            vec![],
+            VLCSnapshot {
+                vector_clock: VectorClock::new(),
+                logical_time,
+                physical_time: 1000,
+            },
+            creator.to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_indexes() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = RocksEventStore::open(temp_dir.path()).unwrap();
+
+        let event = create_event("node1", 1);
+        let event_id = event.id.clone();
+        store.store(event).await.unwrap();
+        store.store(create_event("node1", 2)).await.unwrap();
+        // A creator whose name extends another's must not show up under it
+        store.store(create_event("node10", 3)).await.unwrap();
+
+        assert_eq!(store.count().await.unwrap(), 3);
+        assert_eq!(store.get_by_creator("node1").await.unwrap().len(), 2);
+        assert_eq!(store.count_by_status(EventStatus::Pending).await.unwrap(), 3);
+
+        store.update_status(&event_id, EventStatus::Executed).await.unwrap();
+        assert_eq!(store.count_by_status(EventStatus::Pending).await.unwrap(), 2);
+        let executed = store.get_by_status(EventStatus::Executed).await.unwrap();
+        assert_eq!(executed.len(), 1);
+        assert_eq!(executed[0].id, event_id);
+
+        // Storing again replaces the index entries instead of duplicating them
+        let mut event = store.get(&event_id).await.unwrap().unwrap();
+        event.status = EventStatus::Finalized;
+        store.store(event).await.unwrap();
+        assert_eq!(store.count_by_status(EventStatus::Executed).await.unwrap(), 0);
+        assert_eq!(store.count_by_status(EventStatus::Finalized).await.unwrap(), 1);
+        assert_eq!(store.get_by_creator("node1").await.unwrap().len(), 2);
+        assert_eq!(store.count().await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_survives_reopen() {
+        let temp_dir = TempDir::new().unwrap();
+        let event = create_event("node1", 1);
+        let event_id = event.id.clone();
+
+        {
+            let store = RocksEventStore::open(temp_dir.path()).unwrap();
+            store.store(event).await.unwrap();
+            store.update_status(&event_id, EventStatus::Confirmed).await.unwrap();
+        }
+
+        let store = RocksEventStore::open(temp_dir.path()).unwrap();
+        assert!(store.exists(&event_id).await.unwrap());
+        assert_eq!(store.count().await.unwrap(), 1);
+        
+        // Stores sharing the database see each other's events in the count
+        let other = RocksEventStore::new(store.db.clone());
+        other.store(create_event("node2", 2)).await.unwrap();
+        assert_eq!(store.count().await.unwrap(), 2);
+        assert_eq!(store.get(&event_id).await.unwrap().unwrap().status, EventStatus::Confirmed);
+        assert_eq!(store.get_by_status(EventStatus::Confirmed).await.unwrap().len(), 1);
+    }
+}
@@ -1,242 +1,304 @@
 //! RocksDB implementation of ObjectStore
+//!
+//! The store is generic over its [`KvBackend`] and defaults to RocksDB
+//! ([`SetuDB`]); with a [`MemoryBackend`](crate::MemoryBackend) it runs fully
+//! in memory for tests and simulations.
+//!
+//! Objects of every type live in the `objects` column family as
+//! [`StoredObject`]s. `objects_by_owner` holds one
+//! `(owner, type_tag, object_id)` key per owned object and `objects_by_index`
+//! one `(type_tag, index, address, object_id)` key per secondary index entry,
+//! so lookups are prefix scans and no write rewrites other objects' entries.
+//!
+//! Each write also records the new version of the object in
+//! `object_versions`, keyed by `(object_id, version)`, together with the event
+//! that produced it. Old versions can be pruned by keeping only the most
+//! recent ones per object; the oldest remaining version is then flagged, so
+//! reads as of an earlier anchor fail instead of finding nothing.
+//!
+//! Every mutation is staged in a [`Transaction`] together with its index
+//! updates and committed as one `WriteBatch`, so a crash never leaves an
+//! object and its indexes out of step. Expected versions of
+//! [`ObjectChange::Update`]s are checked while staging under the write lock,
+//! so a conflicting change aborts the whole batch.
 
-use crate::object_store::ObjectStore;
-use crate::rocks::{SetuDB, ColumnFamily};
-use setu_types::{
-    ObjectId, Address,
-    Coin, Profile, Credential, RelationGraph, AccountView,
-    SetuResult, SetuError,
-};
+use crate::object_store::{ObjectStore, ObjectChange, ObjectVersionRecord, StoredObject};
+use crate::rocks::{SetuDB, ColumnFamily, KvBackend, StorageError, Transaction};
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use setu_types::{ObjectId, Address, Ownership, Anchor, EventId, SetuResult, SetuError};
+use std::sync::Mutex;
 
-pub struct RocksObjectStore {
-    db: SetuDB,
+/// Owner part of an `objects_by_owner` key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+enum OwnerKey {
+    Address(Address),
+    Object(ObjectId),
 }
 
-impl RocksObjectStore {
-    pub fn new(db: SetuDB) -> Self {
-        Self { db }
-    }
-    
-    pub fn open(path: impl AsRef<std::path::Path>) -> SetuResult<Self> {
-        let db = SetuDB::open_default(path)
-            .map_err(|e| SetuError::StorageError(format!("Failed to open database: {}", e)))?;
-        Ok(Self::new(db))
-    }
-    
-    fn add_to_index(&self, cf: ColumnFamily, key: &Address, object_id: &ObjectId) -> SetuResult<()> {
-        let mut ids: Vec<ObjectId> = self.db.get(cf, key)
-            .map_err(|e| SetuError::StorageError(e.to_string()))?
-            .unwrap_or_default();
-        if !ids.contains(object_id) {
-            ids.push(*object_id);
-            self.db.put(cf, key, &ids).map_err(|e| SetuError::StorageError(e.to_string()))?;
+impl OwnerKey {
+    fn of(ownership: &Ownership) -> Option<Self> {
+        match ownership {
+            Ownership::AddressOwner(address) => Some(Self::Address(*address)),
+            Ownership::ObjectOwner(parent) => Some(Self::Object(*parent)),
+            Ownership::Shared { .. } | Ownership::Immutable => None,
         }
-        Ok(())
-    }
-    
-    fn remove_from_index(&self, cf: ColumnFamily, key: &Address, object_id: &ObjectId) -> SetuResult<()> {
-        let mut ids: Vec<ObjectId> = self.db.get(cf, key)
-            .map_err(|e| SetuError::StorageError(e.to_string()))?
-            .unwrap_or_default();
-        let original_len = ids.len();
-        ids.retain(|id| id != object_id);
-        if ids.len() < original_len {
-            if ids.is_empty() {
-                self.db.delete(cf, key).map_err(|e| SetuError::StorageError(e.to_string()))?;
-            } else {
-                self.db.put(cf, key, &ids).map_err(|e| SetuError::StorageError(e.to_string()))?;
-            }
-        }
-        Ok(())
-    }
-    
-    fn get_index(&self, cf: ColumnFamily, key: &Address) -> SetuResult<Vec<ObjectId>> {
-        self.db.get(cf, key)
-            .map_err(|e| SetuError::StorageError(e.to_string()))
-            .map(|opt| opt.unwrap_or_default())
     }
 }
 
-impl ObjectStore for RocksObjectStore {
-    fn store_coin(&self, coin: &Coin) -> SetuResult<ObjectId> {
-        let id = coin.metadata.id;
-        self.db.put(ColumnFamily::Coins, &id, coin).map_err(|e| SetuError::StorageError(e.to_string()))?;
-        if let Some(owner) = &coin.metadata.owner {
-            self.add_to_index(ColumnFamily::CoinsByOwner, owner, &id)?;
+/// How a write is recorded in the version history
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct WriteContext<'a> {
+    /// Event producing the new versions
+    pub produced_by: Option<&'a EventId>,
+    /// Depth of an anchor the new versions are already finalized under
+    pub anchor_depth: Option<u64>,
+    /// Number of versions kept per object, `None` to keep all
+    pub retained_versions: Option<u64>,
+}
+
+pub struct RocksObjectStore<B: KvBackend = SetuDB> {
+    db: B,
+    /// Serializes writers, which read the old object to update its indexes
+    write_lock: Mutex<()>,
+    /// Number of versions kept per object, `None` to keep all
+    retained_versions: Option<u64>,
+}
+
+fn to_setu_error(e: StorageError) -> SetuError {
+    SetuError::StorageError(e.to_string())
+}
+
+impl<B: KvBackend> RocksObjectStore<B> {
+    pub fn new(db: B) -> Self {
+        Self {
+            db,
+            write_lock: Mutex::new(()),
+            retained_versions: None,
         }
-        Ok(id)
     }
     
-    fn get_coin(&self, id: &ObjectId) -> SetuResult<Option<Coin>> {
-        self.db.get(ColumnFamily::Coins, id).map_err(|e| SetuError::StorageError(e.to_string()))
+    /// Keep only the `versions` most recent versions of each object (at least one)
+    pub fn with_retained_versions(mut self, versions: u64) -> Self {
+        self.retained_versions = Some(versions.max(1));
+        self
     }
     
-    fn get_coins_by_owner(&self, owner: &Address) -> SetuResult<Vec<Coin>> {
-        let ids = self.get_index(ColumnFamily::CoinsByOwner, owner)?;
-        let coins: Vec<Option<Coin>> = self.db.multi_get(ColumnFamily::Coins, &ids)
-            .map_err(|e| SetuError::StorageError(e.to_string()))?;
-        Ok(coins.into_iter().flatten().collect())
+    /// Stage `changes` in a transaction and commit it
+    fn write(&self, produced_by: Option<&EventId>, changes: &[ObjectChange]) -> SetuResult<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let ctx = WriteContext {
+            produced_by,
+            retained_versions: self.retained_versions,
+            ..Default::default()
+        };
+        let mut txn = self.db.transaction();
+        for change in changes {
+            Self::stage(&mut txn, change, &ctx)?;
+        }
+        txn.commit().map_err(to_setu_error)
     }
     
-    fn update_coin(&self, coin: &Coin) -> SetuResult<()> {
-        if let Some(old_coin) = self.get_coin(&coin.metadata.id)? {
-            if old_coin.metadata.owner != coin.metadata.owner {
-                if let Some(old_owner) = &old_coin.metadata.owner {
-                    self.remove_from_index(ColumnFamily::CoinsByOwner, old_owner, &coin.metadata.id)?;
-                }
-                if let Some(new_owner) = &coin.metadata.owner {
-                    self.add_to_index(ColumnFamily::CoinsByOwner, new_owner, &coin.metadata.id)?;
+    /// Stage one change with its index updates and version record
+    pub(crate) fn stage(txn: &mut Transaction<'_, B>, change: &ObjectChange, ctx: &WriteContext) -> SetuResult<()> {
+        let id = change.object_id();
+        let old: Option<StoredObject> = txn.get(ColumnFamily::Objects, id).map_err(to_setu_error)?;
+        change.check_expected(old.as_ref())?;
+        if let Some(old) = &old {
+            Self::stage_index(txn, old, false)?;
+        }
+        
+        match change {
+            ObjectChange::Put(stored) | ObjectChange::Update { object: stored, .. } => {
+                Self::stage_index(txn, stored, true)?;
+                Self::stage_version(txn, stored.version, Some(stored.clone()), id, ctx)?;
+                txn.put(ColumnFamily::Objects, id, stored).map_err(to_setu_error)
+            }
+            ObjectChange::Delete(_) => {
+                if let Some(old) = &old {
+                    Self::stage_version(txn, old.version + 1, None, id, ctx)?;
                 }
+                txn.delete(ColumnFamily::Objects, id).map_err(to_setu_error)
             }
         }
-        self.db.put(ColumnFamily::Coins, &coin.metadata.id, coin).map_err(|e| SetuError::StorageError(e.to_string()))
     }
     
-    fn delete_coin(&self, id: &ObjectId) -> SetuResult<()> {
-        if let Some(coin) = self.get_coin(id)? {
-            if let Some(owner) = &coin.metadata.owner {
-                self.remove_from_index(ColumnFamily::CoinsByOwner, owner, id)?;
+    /// Record a version of an object and prune versions beyond the retention
+    fn stage_version(
+        txn: &mut Transaction<'_, B>,
+        version: u64,
+        object: Option<StoredObject>,
+        id: &ObjectId,
+        ctx: &WriteContext,
+    ) -> SetuResult<()> {
+        let mut record = ObjectVersionRecord {
+            object_id: *id,
+            version,
+            produced_by: ctx.produced_by.cloned(),
+            object,
+            anchor_depth: ctx.anchor_depth,
+            // Versions loaded at an anchor come without their history
+            pruned_before: ctx.anchor_depth.is_some(),
+        };
+        
+        if let Some(retained) = ctx.retained_versions {
+            // Only committed versions are seen here, so versions staged earlier
+            // in the same transaction are pruned by a later write
+            let history: Vec<ObjectVersionRecord> = txn.db()
+                .prefix_iter::<_, (ObjectId, [u8; 8]), ObjectVersionRecord>(ColumnFamily::ObjectVersions, id)
+                .map_err(to_setu_error)?
+                .map(|entry| entry.map(|(_, record)| record))
+                .collect::<Result<_, _>>()
+                .map_err(to_setu_error)?;
+            let (stale, kept): (Vec<_>, Vec<_>) = history
+                .into_iter()
+                .partition(|r| r.version + retained <= version);
+            for r in &stale {
+                txn.delete(ColumnFamily::ObjectVersions, &(*id, r.version.to_be_bytes()))
+                    .map_err(to_setu_error)?;
+            }
+            
+            // Flag the oldest remaining version, so that reads before it fail
+            // instead of finding no object
+            let pruned = !stale.is_empty() || kept.first().is_some_and(|r| r.pruned_before);
+            match kept.into_iter().next() {
+                Some(mut oldest) if oldest.version != version => {
+                    if pruned && !oldest.pruned_before {
+                        oldest.pruned_before = true;
+                        txn.put(ColumnFamily::ObjectVersions, &(*id, oldest.version.to_be_bytes()), &oldest)
+                            .map_err(to_setu_error)?;
+                    }
+                }
+                _ => record.pruned_before |= pruned,
             }
         }
-        self.db.delete(ColumnFamily::Coins, id).map_err(|e| SetuError::StorageError(e.to_string()))
-    }
-    
-    fn store_profile(&self, profile: &Profile) -> SetuResult<ObjectId> {
-        let id = profile.metadata.id;
-        self.db.put(ColumnFamily::Profiles, &id, profile).map_err(|e| SetuError::StorageError(e.to_string()))?;
-        self.db.put(ColumnFamily::ProfileByAddress, &profile.data.owner, &id).map_err(|e| SetuError::StorageError(e.to_string()))?;
-        Ok(id)
+        
+        txn.put(ColumnFamily::ObjectVersions, &(*id, version.to_be_bytes()), &record)
+            .map_err(to_setu_error)
     }
     
-    fn get_profile(&self, id: &ObjectId) -> SetuResult<Option<Profile>> {
-        self.db.get(ColumnFamily::Profiles, id).map_err(|e| SetuError::StorageError(e.to_string()))
+    /// Whether a version was finalized by the anchor at `depth`
+    fn is_anchored(&self, record: &ObjectVersionRecord, depth: u64) -> SetuResult<bool> {
+        let anchored_at = match (record.anchor_depth, &record.produced_by) {
+            (Some(anchor_depth), _) => Some(anchor_depth),
+            (None, Some(event_id)) => self.db.get(ColumnFamily::EventAnchors, event_id)
+                .map_err(to_setu_error)?,
+            // Written outside of any event, so never part of an anchor
+            (None, None) => None,
+        };
+        Ok(anchored_at.is_some_and(|anchored_at| anchored_at <= depth))
     }
     
-    fn get_profile_by_address(&self, address: &Address) -> SetuResult<Option<Profile>> {
-        let profile_id: Option<ObjectId> = self.db.get(ColumnFamily::ProfileByAddress, address)
-            .map_err(|e| SetuError::StorageError(e.to_string()))?;
-        match profile_id {
-            Some(id) => self.get_profile(&id),
-            None => Ok(None),
+    /// Add (or remove) the index entries of an object
+    fn stage_index(txn: &mut Transaction<'_, B>, stored: &StoredObject, add: bool) -> SetuResult<()> {
+        if let Some(owner) = OwnerKey::of(&stored.ownership) {
+            let key = (owner, stored.type_tag.clone(), stored.id);
+            if add {
+                txn.put(ColumnFamily::ObjectsByOwner, &key, &()).map_err(to_setu_error)?;
+            } else {
+                txn.delete(ColumnFamily::ObjectsByOwner, &key).map_err(to_setu_error)?;
+            }
         }
-    }
-    
-    fn update_profile(&self, profile: &Profile) -> SetuResult<()> {
-        if let Some(old_profile) = self.get_profile(&profile.metadata.id)? {
-            if old_profile.data.owner != profile.data.owner {
-                self.db.delete(ColumnFamily::ProfileByAddress, &old_profile.data.owner)
-                    .map_err(|e| SetuError::StorageError(e.to_string()))?;
+        for (index, address) in &stored.indexes {
+            let key = (stored.type_tag.clone(), index.clone(), *address, stored.id);
+            if add {
+                txn.put(ColumnFamily::ObjectsByIndex, &key, &()).map_err(to_setu_error)?;
+            } else {
+                txn.delete(ColumnFamily::ObjectsByIndex, &key).map_err(to_setu_error)?;
             }
         }
-        self.store_profile(profile)?;
         Ok(())
     }
     
-    fn delete_profile(&self, id: &ObjectId) -> SetuResult<()> {
-        if let Some(profile) = self.get_profile(id)? {
-            self.db.delete(ColumnFamily::ProfileByAddress, &profile.data.owner)
-                .map_err(|e| SetuError::StorageError(e.to_string()))?;
-        }
-        self.db.delete(ColumnFamily::Profiles, id).map_err(|e| SetuError::StorageError(e.to_string()))
-    }
-    
-    fn store_credential(&self, credential: &Credential) -> SetuResult<ObjectId> {
-        let id = credential.metadata.id;
-        self.db.put(ColumnFamily::Credentials, &id, credential).map_err(|e| SetuError::StorageError(e.to_string()))?;
-        self.add_to_index(ColumnFamily::CredentialsByHolder, &credential.data.holder, &id)?;
-        self.add_to_index(ColumnFamily::CredentialsByIssuer, &credential.data.issuer, &id)?;
-        Ok(id)
+    fn get_many(&self, ids: &[ObjectId]) -> SetuResult<Vec<StoredObject>> {
+        let objects: Vec<Option<StoredObject>> = self.db.multi_get(ColumnFamily::Objects, ids)
+            .map_err(to_setu_error)?;
+        Ok(objects.into_iter().flatten().collect())
     }
-    
-    fn get_credential(&self, id: &ObjectId) -> SetuResult<Option<Credential>> {
-        self.db.get(ColumnFamily::Credentials, id).map_err(|e| SetuError::StorageError(e.to_string()))
+}
+
+impl RocksObjectStore {
+    pub fn open(path: impl AsRef<std::path::Path>) -> SetuResult<Self> {
+        let db = SetuDB::open_default(path)
+            .map_err(|e| SetuError::StorageError(format!("Failed to open database: {}", e)))?;
+        Ok(Self::new(db))
     }
-    
-    fn get_credentials_by_holder(&self, holder: &Address) -> SetuResult<Vec<Credential>> {
-        let ids = self.get_index(ColumnFamily::CredentialsByHolder, holder)?;
-        let creds: Vec<Option<Credential>> = self.db.multi_get(ColumnFamily::Credentials, &ids)
-            .map_err(|e| SetuError::StorageError(e.to_string()))?;
-        Ok(creds.into_iter().flatten().collect())
+}
+
+impl<B: KvBackend> ObjectStore for RocksObjectStore<B> {
+    fn get_stored(&self, id: &ObjectId) -> SetuResult<Option<StoredObject>> {
+        self.db.get(ColumnFamily::Objects, id).map_err(to_setu_error)
     }
     
-    fn get_credentials_by_issuer(&self, issuer: &Address) -> SetuResult<Vec<Credential>> {
-        let ids = self.get_index(ColumnFamily::CredentialsByIssuer, issuer)?;
-        let creds: Vec<Option<Credential>> = self.db.multi_get(ColumnFamily::Credentials, &ids)
-            .map_err(|e| SetuError::StorageError(e.to_string()))?;
-        Ok(creds.into_iter().flatten().collect())
+    fn get_stored_by_owner(
+        &self,
+        owner: &Address,
+        type_tag: &str,
+        cursor: Option<ObjectId>,
+        limit: Option<usize>,
+    ) -> SetuResult<Vec<StoredObject>> {
+        let prefix = (OwnerKey::Address(*owner), type_tag.to_string());
+        let start = (prefix.0, prefix.1.clone(), cursor.unwrap_or_default());
+        let ids: Vec<ObjectId> = self.db
+            .prefix_iter_from::<_, _, (OwnerKey, String, ObjectId), ()>(ColumnFamily::ObjectsByOwner, &prefix, &start)
+            .map_err(to_setu_error)?
+            .map(|entry| entry.map(|((_, _, id), _)| id))
+            .filter(|entry| !matches!((entry, &cursor), (Ok(id), Some(cursor)) if id == cursor))
+            .take(limit.unwrap_or(usize::MAX))
+            .collect::<Result<_, _>>()
+            .map_err(to_setu_error)?;
+        self.get_many(&ids)
     }
     
-    fn update_credential(&self, credential: &Credential) -> SetuResult<()> {
-        if let Some(old_cred) = self.get_credential(&credential.metadata.id)? {
-            if old_cred.data.holder != credential.data.holder {
-                self.remove_from_index(ColumnFamily::CredentialsByHolder, &old_cred.data.holder, &credential.metadata.id)?;
-                self.add_to_index(ColumnFamily::CredentialsByHolder, &credential.data.holder, &credential.metadata.id)?;
-            }
-            if old_cred.data.issuer != credential.data.issuer {
-                self.remove_from_index(ColumnFamily::CredentialsByIssuer, &old_cred.data.issuer, &credential.metadata.id)?;
-                self.add_to_index(ColumnFamily::CredentialsByIssuer, &credential.data.issuer, &credential.metadata.id)?;
-            }
-        }
-        self.db.put(ColumnFamily::Credentials, &credential.metadata.id, credential).map_err(|e| SetuError::StorageError(e.to_string()))
+    fn get_stored_by_index(&self, type_tag: &str, index: &str, key: &Address) -> SetuResult<Vec<StoredObject>> {
+        let prefix = (type_tag.to_string(), index.to_string(), *key);
+        let ids: Vec<ObjectId> = self.db
+            .prefix_iter::<_, (String, String, Address, ObjectId), ()>(ColumnFamily::ObjectsByIndex, &prefix)
+            .map_err(to_setu_error)?
+            .map(|entry| entry.map(|((_, _, _, id), _)| id))
+            .collect::<Result<_, _>>()
+            .map_err(to_setu_error)?;
+        self.get_many(&ids)
     }
     
-    fn delete_credential(&self, id: &ObjectId) -> SetuResult<()> {
-        if let Some(cred) = self.get_credential(id)? {
-            self.remove_from_index(ColumnFamily::CredentialsByHolder, &cred.data.holder, id)?;
-            self.remove_from_index(ColumnFamily::CredentialsByIssuer, &cred.data.issuer, id)?;
-        }
-        self.db.delete(ColumnFamily::Credentials, id).map_err(|e| SetuError::StorageError(e.to_string()))
+    fn apply_changes(&self, changes: &[ObjectChange]) -> SetuResult<()> {
+        self.write(None, changes)
     }
     
-    fn store_graph(&self, graph: &RelationGraph) -> SetuResult<ObjectId> {
-        let id = graph.metadata.id;
-        self.db.put(ColumnFamily::RelationGraphs, &id, graph).map_err(|e| SetuError::StorageError(e.to_string()))?;
-        if let Some(owner) = &graph.metadata.owner {
-            self.add_to_index(ColumnFamily::GraphsByOwner, owner, &id)?;
-        }
-        Ok(id)
+    fn apply_event_changes(&self, event_id: &EventId, changes: &[ObjectChange]) -> SetuResult<()> {
+        self.write(Some(event_id), changes)
     }
     
-    fn get_graph(&self, id: &ObjectId) -> SetuResult<Option<RelationGraph>> {
-        self.db.get(ColumnFamily::RelationGraphs, id).map_err(|e| SetuError::StorageError(e.to_string()))
+    fn get_version_history(&self, id: &ObjectId) -> SetuResult<Vec<ObjectVersionRecord>> {
+        self.db.prefix_iter::<_, (ObjectId, [u8; 8]), ObjectVersionRecord>(ColumnFamily::ObjectVersions, id)
+            .map_err(to_setu_error)?
+            .map(|entry| entry.map(|(_, record)| record))
+            .collect::<Result<_, _>>()
+            .map_err(to_setu_error)
     }
     
-    fn get_graphs_by_owner(&self, owner: &Address) -> SetuResult<Vec<RelationGraph>> {
-        let ids = self.get_index(ColumnFamily::GraphsByOwner, owner)?;
-        let graphs: Vec<Option<RelationGraph>> = self.db.multi_get(ColumnFamily::RelationGraphs, &ids)
-            .map_err(|e| SetuError::StorageError(e.to_string()))?;
-        Ok(graphs.into_iter().flatten().collect())
+    fn get_stored_at_version(&self, id: &ObjectId, version: u64) -> SetuResult<Option<StoredObject>> {
+        let record: Option<ObjectVersionRecord> = self.db
+            .get(ColumnFamily::ObjectVersions, &(*id, version.to_be_bytes()))
+            .map_err(to_setu_error)?;
+        Ok(record.and_then(|record| record.object))
     }
     
-    fn update_graph(&self, graph: &RelationGraph) -> SetuResult<()> {
-        if let Some(old_graph) = self.get_graph(&graph.metadata.id)? {
-            if old_graph.metadata.owner != graph.metadata.owner {
-                if let Some(old_owner) = &old_graph.metadata.owner {
-                    self.remove_from_index(ColumnFamily::GraphsByOwner, old_owner, &graph.metadata.id)?;
-                }
-                if let Some(new_owner) = &graph.metadata.owner {
-                    self.add_to_index(ColumnFamily::GraphsByOwner, new_owner, &graph.metadata.id)?;
-                }
+    fn get_stored_as_of_anchor(&self, id: &ObjectId, anchor: &Anchor) -> SetuResult<Option<StoredObject>> {
+        let history = self.get_version_history(id)?;
+        let pruned = history.first().is_some_and(|record| record.pruned_before);
+        for record in history.into_iter().rev() {
+            if self.is_anchored(&record, anchor.depth)? {
+                return Ok(record.object);
             }
         }
-        self.db.put(ColumnFamily::RelationGraphs, &graph.metadata.id, graph).map_err(|e| SetuError::StorageError(e.to_string()))
-    }
-    
-    fn delete_graph(&self, id: &ObjectId) -> SetuResult<()> {
-        if let Some(graph) = self.get_graph(id)? {
-            if let Some(owner) = &graph.metadata.owner {
-                self.remove_from_index(ColumnFamily::GraphsByOwner, owner, id)?;
-            }
+        if pruned {
+            return Err(SetuError::StorageError(format!(
+                "Versions of object {} as of anchor {} were pruned",
+                id, anchor.depth
+            )));
         }
-        self.db.delete(ColumnFamily::RelationGraphs, id).map_err(|e| SetuError::StorageError(e.to_string()))
-    }
-    
-    fn load_account_view(&self, address: &Address) -> SetuResult<AccountView> {
-        let profile = self.get_profile_by_address(address)?;
-        let credentials = self.get_credentials_by_holder(address)?;
-        let coins = self.get_coins_by_owner(address)?;
-        let graphs = self.get_graphs_by_owner(address)?;
-        Ok(AccountView::new(address.clone(), profile, credentials, coins, graphs))
+        Ok(None)
     }
 }
 
@@ -244,7 +306,11 @@ impl ObjectStore for RocksObjectStore {
 mod tests {
     use super::*;
     use tempfile::TempDir;
-    use setu_types::{create_profile, create_kyc_credential, create_social_graph, generate_object_id};
+    use setu_types::{
+        create_profile, create_kyc_credential, create_social_graph, generate_object_id,
+        Coin, CoinData, ProfileData, VectorClock, VLCSnapshot,
+    };
+    use crate::{AnchorStore, MemoryBackend, RocksAnchorStore};
     
     fn setup_test_store() -> (RocksObjectStore, TempDir) {
         let temp_dir = TempDir::new().unwrap();
@@ -261,7 +327,7 @@ mod tests {
         store.store_coin(&coin).unwrap();
         let retrieved = store.get_coin(&coin_id).unwrap().unwrap();
         assert_eq!(retrieved.value(), 1000);
-        let coins = store.get_coins_by_owner(&alice).unwrap();
+        let coins = store.get_coins_by_owner(&alice, None, None).unwrap();
         assert_eq!(coins.len(), 1);
         store.delete_coin(&coin_id).unwrap();
         assert!(store.get_coin(&coin_id).unwrap().is_none());
@@ -326,4 +392,316 @@ mod tests {
         assert_eq!(view.graph_count, 1);
         assert!(view.has_kyc());
     }
+    
+    /// Check that the index column families hold exactly the entries of the stored objects
+    fn assert_indexes_consistent(store: &RocksObjectStore) {
+        let objects: Vec<StoredObject> = store.db.iter::<ObjectId, StoredObject>(ColumnFamily::Objects)
+            .unwrap()
+            .map(|entry| entry.unwrap().1)
+            .collect();
+        let mut expected_owned: Vec<_> = objects.iter()
+            .filter_map(|o| OwnerKey::of(&o.ownership).map(|owner| (owner, o.type_tag.clone(), o.id)))
+            .collect();
+        let mut expected_indexed: Vec<_> = objects.iter()
+            .flat_map(|o| o.indexes.iter().map(|(index, address)| (o.type_tag.clone(), index.clone(), *address, o.id)))
+            .collect();
+        expected_owned.sort_by_key(|key| bincode::encode_to_vec(key, bincode::config::standard()).unwrap());
+        expected_indexed.sort_by_key(|key| bincode::encode_to_vec(key, bincode::config::standard()).unwrap());
+        
+        let owned: Vec<(OwnerKey, String, ObjectId)> = store.db
+            .iter::<_, ()>(ColumnFamily::ObjectsByOwner)
+            .unwrap()
+            .map(|entry| entry.unwrap().0)
+            .collect();
+        let indexed: Vec<(String, String, Address, ObjectId)> = store.db
+            .iter::<_, ()>(ColumnFamily::ObjectsByIndex)
+            .unwrap()
+            .map(|entry| entry.unwrap().0)
+            .collect();
+        assert_eq!(owned, expected_owned);
+        assert_eq!(indexed, expected_indexed);
+    }
+    
+    #[test]
+    fn test_apply_changes_is_atomic() {
+        let temp_dir = TempDir::new().unwrap();
+        let alice = Address::from_str_id("alice");
+        let bob = Address::from_str_id("bob");
+        let coin = Coin::new(alice, 1000);
+        let coin_id = coin.metadata.id;
+        let stale = Coin::new(alice, 50);
+        let (stale_version, stale_digest) = (stale.version(), *stale.digest());
+        let burned = Coin::new(bob, 5);
+        let new_coin = Coin::new(alice, 10);
+        
+        {
+            let store = RocksObjectStore::open(temp_dir.path()).unwrap();
+            store.store_coin(&coin).unwrap();
+            let mut spent = stale.clone();
+            spent.transfer_to(bob);
+            store.store_coin(&spent).unwrap();
+            store.store_coin(&burned).unwrap();
+            
+            // Moves the coin between owners, then fails on an update whose
+            // expected version is no longer current
+            let mut transferred = coin.clone();
+            transferred.transfer_to(bob);
+            let mut conflicting = stale.clone();
+            conflicting.transfer_to(alice);
+            let result = store.apply_changes(&[
+                ObjectChange::put(&new_coin).unwrap(),
+                ObjectChange::put(&transferred).unwrap(),
+                ObjectChange::Delete(burned.metadata.id),
+                ObjectChange::update(&conflicting, stale_version, stale_digest).unwrap(),
+                ObjectChange::put(&create_profile(bob)).unwrap(),
+            ]);
+            assert!(matches!(result, Err(SetuError::VersionConflict { .. })));
+        }
+        
+        let store = RocksObjectStore::open(temp_dir.path()).unwrap();
+        assert!(store.get_coin(&new_coin.metadata.id).unwrap().is_none());
+        assert!(store.get_version_history(&new_coin.metadata.id).unwrap().is_empty());
+        assert!(store.get_coin(&coin_id).unwrap().unwrap().is_owned_by(&alice));
+        assert_eq!(store.get_version_history(&coin_id).unwrap().len(), 1);
+        assert!(store.get_coin(&stale.metadata.id).unwrap().unwrap().is_owned_by(&bob));
+        assert!(store.get_coin(&burned.metadata.id).unwrap().is_some());
+        assert!(store.get_profile_by_address(&bob).unwrap().is_none());
+        
+        let alice_coins = store.get_coins_by_owner(&alice, None, None).unwrap();
+        assert_eq!(alice_coins.len(), 1);
+        assert_eq!(alice_coins[0].metadata.id, coin_id);
+        let bob_coins: Vec<ObjectId> = store.get_coins_by_owner(&bob, None, None).unwrap()
+            .into_iter().map(|coin| coin.metadata.id).collect();
+        assert_eq!(bob_coins.len(), 2);
+        assert!(bob_coins.contains(&stale.metadata.id) && bob_coins.contains(&burned.metadata.id));
+        assert_indexes_consistent(&store);
+    }
+    
+    #[test]
+    fn test_coins_by_owner_pagination() {
+        let (store, _temp) = setup_test_store();
+        let alice = Address::from_str_id("alice");
+        for value in 0..5 {
+            store.store_coin(&Coin::new(alice, value)).unwrap();
+        }
+        store.store_coin(&Coin::new(Address::from_str_id("bob"), 1)).unwrap();
+        
+        let mut pages = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = store.get_coins_by_owner(&alice, cursor, Some(2)).unwrap();
+            if page.is_empty() {
+                break;
+            }
+            cursor = page.last().map(|coin| coin.metadata.id);
+            pages.push(page);
+        }
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 2, 1]);
+        
+        let paged: Vec<ObjectId> = pages.into_iter().flatten().map(|coin| coin.metadata.id).collect();
+        let all: Vec<ObjectId> = store.get_coins_by_owner(&alice, None, None).unwrap()
+            .into_iter().map(|coin| coin.metadata.id).collect();
+        assert_eq!(paged, all);
+    }
+    
+    #[test]
+    fn test_objects_are_typed() {
+        let (store, _temp) = setup_test_store();
+        let alice = Address::from_str_id("alice");
+        let coin = Coin::new(alice, 1000);
+        let coin_id = store.store_object(&coin).unwrap();
+        
+        // Stored once, readable only as the type it was stored as
+        assert!(store.get_object::<ProfileData>(&coin_id).unwrap().is_none());
+        assert!(store.get_profile_by_address(&alice).unwrap().is_none());
+        store.delete_profile(&coin_id).unwrap();
+        assert_eq!(store.get_coin(&coin_id).unwrap().unwrap().value(), 1000);
+        
+        store.delete_object(&coin_id).unwrap();
+        assert!(store.get_coin(&coin_id).unwrap().is_none());
+        assert!(store.get_coins_by_owner(&alice, None, None).unwrap().is_empty());
+    }
+    
+    fn create_anchor(event_ids: &[&str], depth: u64) -> Anchor {
+        Anchor::new(
+            event_ids.iter().map(|id| id.to_string()).collect(),
+            VLCSnapshot {
+                vector_clock: VectorClock::new(),
+                logical_time: depth,
+                physical_time: depth,
+            },
+            format!("state_root_{}", depth),
+            None,
+            String::new(),
+            depth,
+        )
+    }
+    
+    #[tokio::test]
+    async fn test_version_history() {
+        let (store, _temp) = setup_test_store();
+        let anchors = RocksAnchorStore::new(store.db.clone()).unwrap();
+        let alice = Address::from_str_id("alice");
+        let bob = Address::from_str_id("bob");
+        let mut coin = Coin::new(alice, 1000);
+        let coin_id = coin.metadata.id;
+        
+        store.apply_event_changes(&"e1".to_string(), &[ObjectChange::put(&coin).unwrap()]).unwrap();
+        coin.transfer_to(bob);
+        store.apply_event_changes(&"e2".to_string(), &[ObjectChange::put(&coin).unwrap()]).unwrap();
+        store.delete_coin(&coin_id).unwrap();
+        
+        let v1: Coin = store.get_object_at_version(&coin_id, 1).unwrap().unwrap();
+        let v2: Coin = store.get_object_at_version(&coin_id, 2).unwrap().unwrap();
+        assert!(v1.is_owned_by(&alice));
+        assert!(v2.is_owned_by(&bob));
+        assert!(store.get_coin(&coin_id).unwrap().is_none());
+        
+        let history = store.get_version_history(&coin_id).unwrap();
+        let produced_by: Vec<Option<&str>> = history.iter().map(|r| r.produced_by.as_deref()).collect();
+        assert_eq!(produced_by, vec![Some("e1"), Some("e2"), None]);
+        // The deletion is recorded as a version without an object
+        assert_eq!(history[2].version, 3);
+        assert!(history[2].object.is_none());
+        
+        // The deletion was not produced by an event, so no anchor includes it
+        let anchor = create_anchor(&["e1", "e2"], 0);
+        anchors.store(anchor.clone()).await.unwrap();
+        let at0: Coin = store.get_object_as_of_anchor(&coin_id, &anchor).unwrap().unwrap();
+        assert!(at0.is_owned_by(&bob));
+    }
+    
+    #[tokio::test]
+    async fn test_object_as_of_anchor() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = RocksObjectStore::open(temp_dir.path()).unwrap();
+        let anchors = RocksAnchorStore::new(store.db.clone()).unwrap();
+        let mut coin = Coin::new(Address::from_str_id("alice"), 1000);
+        let coin_id = coin.metadata.id;
+        
+        store.apply_event_changes(&"e1".to_string(), &[ObjectChange::put(&coin).unwrap()]).unwrap();
+        let anchor0 = create_anchor(&["e1"], 0);
+        anchors.store(anchor0.clone()).await.unwrap();
+        
+        for event_id in ["e2", "e3"] {
+            coin.transfer_to(Address::from_str_id(event_id));
+            store.apply_event_changes(&event_id.to_string(), &[ObjectChange::put(&coin).unwrap()]).unwrap();
+        }
+        // e3 is not anchored yet
+        let anchor1 = create_anchor(&["e2"], 1);
+        anchors.store(anchor1.clone()).await.unwrap();
+        
+        let at0: Coin = store.get_object_as_of_anchor(&coin_id, &anchor0).unwrap().unwrap();
+        let at1: Coin = store.get_object_as_of_anchor(&coin_id, &anchor1).unwrap().unwrap();
+        assert_eq!(at0.version(), 1);
+        assert_eq!(at1.version(), 2);
+        assert_eq!(store.get_coin(&coin_id).unwrap().unwrap().version(), 3);
+    }
+    
+    #[test]
+    fn test_version_pruning() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = RocksObjectStore::open(temp_dir.path()).unwrap().with_retained_versions(2);
+        let mut coin = Coin::new(Address::from_str_id("alice"), 1000);
+        let coin_id = coin.metadata.id;
+        
+        store.store_coin(&coin).unwrap();
+        for _ in 0..3 {
+            let (version, digest) = (coin.version(), *coin.digest());
+            coin.transfer_to(Address::from_str_id("bob"));
+            store.update_coin(&coin, version, digest).unwrap();
+        }
+        
+        let versions: Vec<u64> = store.get_version_history(&coin_id).unwrap().iter().map(|r| r.version).collect();
+        assert_eq!(versions, vec![3, 4]);
+        assert!(store.get_object_at_version::<CoinData>(&coin_id, 1).unwrap().is_none());
+        assert!(store.get_object_at_version::<CoinData>(&coin_id, 3).unwrap().is_some());
+        assert!(store.get_version_history(&coin_id).unwrap()[0].pruned_before);
+    }
+    
+    #[tokio::test]
+    async fn test_as_of_anchor_fails_on_pruned_versions() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = RocksObjectStore::open(temp_dir.path()).unwrap().with_retained_versions(1);
+        let anchors = RocksAnchorStore::new(store.db.clone()).unwrap();
+        let mut coin = Coin::new(Address::from_str_id("alice"), 1000);
+        let coin_id = coin.metadata.id;
+        let other = Coin::new(Address::from_str_id("carol"), 5);
+        
+        store.apply_event_changes(&"e1".to_string(), &[ObjectChange::put(&coin).unwrap()]).unwrap();
+        let anchor0 = create_anchor(&["e1"], 0);
+        anchors.store(anchor0.clone()).await.unwrap();
+        coin.transfer_to(Address::from_str_id("bob"));
+        store.apply_event_changes(&"e2".to_string(), &[ObjectChange::put(&coin).unwrap()]).unwrap();
+        store.apply_event_changes(&"e3".to_string(), &[ObjectChange::put(&other).unwrap()]).unwrap();
+        let anchor1 = create_anchor(&["e2", "e3"], 1);
+        anchors.store(anchor1.clone()).await.unwrap();
+        
+        let at1: Coin = store.get_object_as_of_anchor(&coin_id, &anchor1).unwrap().unwrap();
+        assert!(at1.is_owned_by(&Address::from_str_id("bob")));
+        // The version anchored at depth 0 is gone, which is not the same as
+        // the object not existing yet
+        assert!(store.get_object_as_of_anchor::<CoinData>(&coin_id, &anchor0).is_err());
+        // An object created after the anchor did not exist then
+        assert!(store.get_object_as_of_anchor::<CoinData>(&other.metadata.id, &anchor0).unwrap().is_none());
+    }
+    
+    #[test]
+    fn test_update_detects_version_conflict() {
+        let (store, _temp) = setup_test_store();
+        let alice = Address::from_str_id("alice");
+        let coin = Coin::new(alice, 1000);
+        let (version, digest) = (coin.version(), *coin.digest());
+        store.store_coin(&coin).unwrap();
+        
+        // Two writers read the same version and both try to transfer it
+        let mut first = coin.clone();
+        first.transfer_to(Address::from_str_id("bob"));
+        let mut second = coin.clone();
+        second.transfer_to(Address::from_str_id("carol"));
+        
+        store.update_coin(&first, version, digest).unwrap();
+        match store.update_coin(&second, version, digest) {
+            Err(SetuError::VersionConflict { object_id, actual, .. }) => {
+                assert_eq!(object_id, coin.metadata.id);
+                assert_eq!(actual, Some((first.version(), *first.digest())));
+            }
+            other => panic!("expected a version conflict, got {:?}", other),
+        }
+        assert!(store.get_coin(&coin.metadata.id).unwrap().unwrap().is_owned_by(&Address::from_str_id("bob")));
+        
+        // A conflict aborts the other changes of the batch too
+        let other = Coin::new(alice, 5);
+        let result = store.apply_changes(&[
+            ObjectChange::put(&other).unwrap(),
+            ObjectChange::update(&second, version, digest).unwrap(),
+        ]);
+        assert!(matches!(result, Err(SetuError::VersionConflict { .. })));
+        assert!(store.get_coin(&other.metadata.id).unwrap().is_none());
+        
+        // Updating a missing object is a conflict as well
+        let missing = Coin::new(alice, 1);
+        let result = store.update_coin(&missing, missing.version(), *missing.digest());
+        assert!(matches!(result, Err(SetuError::VersionConflict { actual: None, .. })));
+    }
+    
+    #[test]
+    fn test_in_memory_backend() {
+        let store = RocksObjectStore::new(MemoryBackend::new());
+        let alice = Address::from_str_id("alice");
+        let mut coin = Coin::new(alice, 1000);
+        let (version, digest) = (coin.version(), *coin.digest());
+        store.store_coin(&coin).unwrap();
+        store.store_coin(&Coin::new(alice, 5)).unwrap();
+        assert_eq!(store.get_coins_by_owner(&alice, None, None).unwrap().len(), 2);
+        
+        coin.transfer_to(Address::from_str_id("bob"));
+        store.update_coin(&coin, version, digest).unwrap();
+        assert_eq!(store.get_coins_by_owner(&alice, None, None).unwrap().len(), 1);
+        assert!(store.get_object_at_version::<CoinData>(&coin.metadata.id, version).unwrap().is_some());
+        assert!(matches!(
+            store.update_coin(&coin, version, digest),
+            Err(SetuError::VersionConflict { .. })
+        ));
+    }
 }
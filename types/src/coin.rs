@@ -6,7 +6,7 @@
 //! - Balance is a value type, not an object
 
 use serde::{Deserialize, Serialize};
-use crate::object::{Object, Address, generate_object_id};
+use crate::object::{Object, ObjectData, Address, generate_object_id};
 
 /// Coin type identifier (e.g., "SUI", "USDC", "SETU")
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -94,6 +94,10 @@ pub struct CoinData {
 /// Coin type alias
 pub type Coin = Object<CoinData>;
 
+impl ObjectData for CoinData {
+    const TYPE_TAG: &'static str = "coin";
+}
+
 impl Coin {
     /// Create a new Coin object with native coin type
     /// 
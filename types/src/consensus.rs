@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
+use setu_merkle::{BinaryMerkleProof, BinaryMerkleTree, HashValue, IncrementalMerkleTree};
 use sha2::{Sha256, Digest};
 use std::collections::HashMap;
 
 use crate::event::{EventId, VLCSnapshot};
+use crate::{SetuError, SetuResult};
 
 #[allow(unused_imports)]
 use crate::event::VectorClock;
@@ -14,9 +16,13 @@ pub type CFId = String;
 pub struct Anchor {
     pub id: AnchorId,
     pub event_ids: Vec<EventId>,
+    /// Hex root of the binary Merkle tree over `event_ids`, in order
+    pub events_root: String,
     pub vlc_snapshot: VLCSnapshot,
     pub state_root: String,
     pub previous_anchor: Option<AnchorId>,
+    /// Hex root of the Merkle Mountain Range over the IDs of all earlier anchors
+    pub anchor_chain_root: String,
     pub depth: u64,
     pub timestamp: u64,
 }
@@ -27,6 +33,7 @@ impl Anchor {
         vlc_snapshot: VLCSnapshot,
         state_root: String,
         previous_anchor: Option<AnchorId>,
+        anchor_chain_root: String,
         depth: u64,
     ) -> Self {
         let timestamp = std::time::SystemTime::now()
@@ -34,38 +41,123 @@ impl Anchor {
             .unwrap()
             .as_millis() as u64;
 
-        let id = Self::compute_id(&event_ids, &vlc_snapshot, &state_root, timestamp);
+        let events_root = Self::compute_events_root(&event_ids);
+        let id = Self::compute_id(
+            &events_root,
+            &vlc_snapshot,
+            &state_root,
+            &anchor_chain_root,
+            timestamp,
+        );
 
         Self {
             id,
             event_ids,
+            events_root,
             vlc_snapshot,
             state_root,
             previous_anchor,
+            anchor_chain_root,
             depth,
             timestamp,
         }
     }
 
     fn compute_id(
-        event_ids: &[EventId],
+        events_root: &str,
         vlc_snapshot: &VLCSnapshot,
         state_root: &str,
+        anchor_chain_root: &str,
         timestamp: u64,
     ) -> AnchorId {
         let mut hasher = Sha256::new();
-        for event_id in event_ids {
-            hasher.update(event_id.as_bytes());
-        }
+        hasher.update(events_root.as_bytes());
         hasher.update(vlc_snapshot.logical_time.to_le_bytes());
         hasher.update(state_root.as_bytes());
+        hasher.update(anchor_chain_root.as_bytes());
         hasher.update(timestamp.to_le_bytes());
         hex::encode(hasher.finalize())
     }
 
+    fn compute_events_root(event_ids: &[EventId]) -> String {
+        // Only the root is needed here, so skip materializing the tree
+        let mut tree = IncrementalMerkleTree::new();
+        for event_id in event_ids {
+            tree.append(event_id.as_bytes());
+        }
+        hex::encode(tree.root().as_bytes())
+    }
+
     pub fn event_count(&self) -> usize {
         self.event_ids.len()
     }
+
+    /// Check that the ID commits to this anchor's contents, including `events_root`
+    pub fn verify_id(&self) -> bool {
+        self.verify_header_id() && self.events_root == Self::compute_events_root(&self.event_ids)
+    }
+
+    /// Check that the ID commits to the header fields alone.
+    ///
+    /// Unlike [`verify_id`](Self::verify_id) this does not need `event_ids`,
+    /// so it works on an anchor header whose event list was left out.
+    pub fn verify_header_id(&self) -> bool {
+        let id = Self::compute_id(
+            &self.events_root,
+            &self.vlc_snapshot,
+            &self.state_root,
+            &self.anchor_chain_root,
+            self.timestamp,
+        );
+        self.id == id
+    }
+
+    /// Position of an event in this anchor and a proof against `events_root`
+    pub fn event_proof(&self, event_id: &EventId) -> Option<(usize, BinaryMerkleProof)> {
+        let index = self.event_ids.iter().position(|id| id == event_id)?;
+        let proof = BinaryMerkleTree::build(&self.event_ids).get_proof(index).ok()?;
+        Some((index, proof))
+    }
+}
+
+/// Compact proof that an event was folded into a finalized anchor.
+///
+/// A client holding the finalized anchor's header checks the receipt with
+/// [`FinalityReceipt::verify`] instead of downloading the anchor's event list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinalityReceipt {
+    pub event_id: EventId,
+    pub cf_id: CFId,
+    pub anchor_id: AnchorId,
+    pub event_index: usize,
+    pub proof: BinaryMerkleProof,
+    pub finalized_at: u64,
+}
+
+impl FinalityReceipt {
+    /// Verify the receipt against the anchor it names.
+    ///
+    /// Only the anchor header is used; `event_ids` may be empty.
+    pub fn verify(&self, anchor: &Anchor) -> SetuResult<()> {
+        if anchor.id != self.anchor_id {
+            return Err(SetuError::InvalidData(format!(
+                "Receipt is for anchor {}, got {}",
+                self.anchor_id, anchor.id
+            )));
+        }
+        if !anchor.verify_header_id() {
+            return Err(SetuError::InvalidData(format!(
+                "Anchor {} does not match its header",
+                anchor.id
+            )));
+        }
+
+        let root = HashValue::from_hex(&anchor.events_root)
+            .map_err(|e| SetuError::InvalidData(e.to_string()))?;
+        self.proof
+            .verify(&root, self.event_id.as_bytes(), self.event_index)
+            .map_err(|e| SetuError::InvalidData(e.to_string()))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -82,6 +174,11 @@ pub struct Vote {
     pub validator_id: String,
     pub cf_id: CFId,
     pub approve: bool,
+    /// Consensus round the vote was cast in, which its signature binds
+    #[serde(default)]
+    pub round: u64,
+    /// `flag || bytes` of the voter's signature over [`Vote::signing_bytes`]
+    /// in `round`, empty if unsigned
     pub signature: Vec<u8>,
     pub timestamp: u64,
 }
@@ -92,6 +189,7 @@ impl Vote {
             validator_id,
             cf_id,
             approve,
+            round: 0,
             signature: Vec::new(),
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
@@ -100,10 +198,30 @@ impl Vote {
         }
     }
 
+    pub fn with_round(mut self, round: u64) -> Self {
+        self.round = round;
+        self
+    }
+
     pub fn with_signature(mut self, signature: Vec<u8>) -> Self {
         self.signature = signature;
         self
     }
+
+    /// Bytes a validator signs for this vote; the signer binds the round.
+    ///
+    /// `len(validator_id) || validator_id || len(cf_id) || cf_id || approve`.
+    /// The timestamp is left out, so sending the same vote again signs the
+    /// same bytes.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend((self.validator_id.len() as u32).to_le_bytes());
+        bytes.extend(self.validator_id.as_bytes());
+        bytes.extend((self.cf_id.len() as u32).to_le_bytes());
+        bytes.extend(self.cf_id.as_bytes());
+        bytes.push(self.approve as u8);
+        bytes
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -175,6 +293,22 @@ impl ConsensusFrame {
     pub fn reject(&mut self) {
         self.status = CFStatus::Rejected;
     }
+
+    /// Receipt for an event folded into this frame, once it is finalized
+    pub fn finality_receipt(&self, event_id: &EventId) -> Option<FinalityReceipt> {
+        if self.status != CFStatus::Finalized {
+            return None;
+        }
+        let (event_index, proof) = self.anchor.event_proof(event_id)?;
+        Some(FinalityReceipt {
+            event_id: event_id.clone(),
+            cf_id: self.id.clone(),
+            anchor_id: self.anchor.id.clone(),
+            event_index,
+            proof,
+            finalized_at: self.finalized_at?,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -217,6 +351,7 @@ mod tests {
             create_vlc_snapshot(),
             "state_root_hash".to_string(),
             None,
+            String::new(),
             0,
         );
         assert_eq!(anchor.event_count(), 2);
@@ -229,6 +364,7 @@ mod tests {
             create_vlc_snapshot(),
             "state_root".to_string(),
             None,
+            String::new(),
             0,
         );
         let mut cf = ConsensusFrame::new(anchor, "validator1".to_string());
@@ -241,4 +377,50 @@ mod tests {
         assert_eq!(cf.reject_count(), 0);
         assert!(cf.check_quorum(3));
     }
+
+    #[test]
+    fn test_finality_receipt() {
+        let events: Vec<EventId> = (0..5).map(|i| format!("event{}", i)).collect();
+        let anchor = Anchor::new(
+            events.clone(),
+            create_vlc_snapshot(),
+            "state_root".to_string(),
+            None,
+            String::new(),
+            0,
+        );
+        assert!(anchor.verify_id());
+        let mut cf = ConsensusFrame::new(anchor.clone(), "validator1".to_string());
+
+        // No receipts before finalization
+        assert!(cf.finality_receipt(&events[3]).is_none());
+        cf.finalize();
+        assert!(cf.finality_receipt(&"missing".to_string()).is_none());
+
+        for event_id in &events {
+            let receipt = cf.finality_receipt(event_id).unwrap();
+            assert!(receipt.verify(&anchor).is_ok());
+        }
+
+        // The header is enough, without the event list
+        let mut header = anchor.clone();
+        header.event_ids.clear();
+        assert!(!header.verify_id());
+        assert!(header.verify_header_id());
+        assert!(cf.finality_receipt(&events[3]).unwrap().verify(&header).is_ok());
+
+        let mut receipt = cf.finality_receipt(&events[3]).unwrap();
+        receipt.event_id = "forged".to_string();
+        assert!(receipt.verify(&anchor).is_err());
+
+        // An anchor whose events root was swapped no longer matches its ID
+        let receipt = cf.finality_receipt(&events[3]).unwrap();
+        let mut tampered = anchor.clone();
+        tampered.event_ids[0] = "other".to_string();
+        let tampered_root = BinaryMerkleTree::build(&tampered.event_ids).root();
+        tampered.events_root = hex::encode(tampered_root.as_bytes());
+        assert!(!tampered.verify_id());
+        assert!(!tampered.verify_header_id());
+        assert!(receipt.verify(&tampered).is_err());
+    }
 }
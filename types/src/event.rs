@@ -4,6 +4,8 @@ use sha2::{Sha256, Digest};
 // Use independent VLC library
 pub use setu_vlc::{VectorClock, VLCSnapshot};
 
+pub use setu_keys::KeyRotation;
+
 // Placeholder types (to be replaced with actual implementations)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transfer {
@@ -29,6 +31,7 @@ pub enum EventType {
     Transfer,
     System,
     Genesis,
+    KeyRotation,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +40,9 @@ pub struct Event {
     pub event_type: EventType,
     pub parent_ids: Vec<EventId>,
     pub transfer: Option<Transfer>,
+    /// Present on `KeyRotation` events
+    #[serde(default)]
+    pub key_rotation: Option<Box<KeyRotation>>,
     pub vlc_snapshot: VLCSnapshot,
     pub creator: String,
     pub status: EventStatus,
@@ -70,13 +76,14 @@ impl Event {
             .unwrap()
             .as_millis() as u64;
         
-        let id = Self::compute_id(&parent_ids, &vlc_snapshot, &creator, timestamp);
+        let id = Self::compute_id(&parent_ids, &vlc_snapshot, &creator, timestamp, None);
         
         Self {
             id,
             event_type,
             parent_ids,
             transfer: None,
+            key_rotation: None,
             vlc_snapshot,
             creator,
             status: EventStatus::Pending,
@@ -94,6 +101,7 @@ impl Event {
         vlc_snapshot: &VLCSnapshot,
         creator: &str,
         timestamp: u64,
+        key_rotation: Option<&KeyRotation>,
     ) -> EventId {
         let mut hasher = Sha256::new();
         for parent_id in parent_ids {
@@ -102,6 +110,15 @@ impl Event {
         hasher.update(vlc_snapshot.logical_time.to_le_bytes());
         hasher.update(creator.as_bytes());
         hasher.update(timestamp.to_le_bytes());
+        // The ID commits to the rotation recorded by the event
+        if let Some(rotation) = key_rotation {
+            hasher.update(KeyRotation::signing_bytes(
+                &rotation.node_id,
+                &rotation.old_public_key,
+                &rotation.new_public_key,
+                rotation.effective_epoch,
+            ));
+        }
         hex::encode(hasher.finalize())
     }
 
@@ -110,6 +127,25 @@ impl Event {
         self
     }
 
+    /// Create an event recording a node's key rotation.
+    pub fn key_rotation(
+        rotation: KeyRotation,
+        parent_ids: Vec<EventId>,
+        vlc_snapshot: VLCSnapshot,
+        creator: String,
+    ) -> Self {
+        let mut event = Self::new(EventType::KeyRotation, parent_ids, vlc_snapshot, creator);
+        event.id = Self::compute_id(
+            &event.parent_ids,
+            &event.vlc_snapshot,
+            &event.creator,
+            event.timestamp,
+            Some(&rotation),
+        );
+        event.key_rotation = Some(Box::new(rotation));
+        event
+    }
+
     pub fn set_status(&mut self, status: EventStatus) {
         self.status = status;
     }
@@ -161,6 +197,30 @@ mod tests {
         assert_eq!(event.status, EventStatus::Pending);
     }
 
+    #[test]
+    fn test_key_rotation_event() {
+        use setu_keys::{SetuKeyPair, SignatureScheme};
+
+        let old = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let new = SetuKeyPair::generate(SignatureScheme::ED25519);
+        let rotation = KeyRotation::new("node1", &old, &new, 2);
+        let event = Event::key_rotation(rotation.clone(), vec![], create_vlc_snapshot(), "node1".to_string());
+        assert_eq!(event.event_type, EventType::KeyRotation);
+
+        // The ID changes with the rotation the event carries
+        let id_for = |rotation: Option<&KeyRotation>| {
+            Event::compute_id(&event.parent_ids, &event.vlc_snapshot, &event.creator, event.timestamp, rotation)
+        };
+        assert_eq!(event.id, id_for(Some(&rotation)));
+        assert_ne!(event.id, id_for(None));
+        let other = SetuKeyPair::generate(SignatureScheme::ED25519);
+        assert_ne!(event.id, id_for(Some(&KeyRotation::new("node1", &old, &other, 2))));
+
+        let bytes = bcs::to_bytes(&event).unwrap();
+        let decoded: Event = bcs::from_bytes(&bytes).unwrap();
+        assert!(decoded.key_rotation.unwrap().verify().is_ok());
+    }
+
     #[test]
     fn test_genesis_event() {
         let event = Event::genesis("node1".to_string(), create_vlc_snapshot());
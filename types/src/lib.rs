@@ -12,15 +12,15 @@ pub mod relation;       // RelationGraph object (social)
 pub mod account_view;   // Account aggregated view
 
 // Export commonly used types
-pub use event::{Event, EventId, EventStatus, EventType, Transfer};
-pub use consensus::{Anchor, AnchorId, ConsensusFrame, CFId, CFStatus, Vote, ConsensusConfig};
+pub use event::{Event, EventId, EventStatus, EventType, KeyRotation, Transfer};
+pub use consensus::{Anchor, AnchorId, ConsensusFrame, CFId, CFStatus, FinalityReceipt, Vote, ConsensusConfig};
 pub use node::*;
 
 // Re-export VLC types from setu-vlc
 pub use setu_vlc::{VectorClock, VLCSnapshot};
 
 // ========== Object Model Exports ==========
-pub use object::{Object, ObjectId, Address, ObjectDigest, ObjectType, ObjectMetadata, Ownership, generate_object_id};
+pub use object::{Object, ObjectData, ObjectId, Address, ObjectDigest, ObjectType, ObjectMetadata, Ownership, generate_object_id};
 
 // Coin related
 pub use coin::{Coin, CoinType, CoinData, Balance, create_coin, create_typed_coin};
@@ -61,6 +61,15 @@ pub enum SetuError {
     #[error("Invalid transfer: {0}")]
     InvalidTransfer(String),
     
+    #[error("Version conflict on object {object_id}: expected version {expected_version} ({expected_digest}), found {actual:?}")]
+    VersionConflict {
+        object_id: ObjectId,
+        expected_version: u64,
+        expected_digest: ObjectDigest,
+        /// Version and digest of the stored object, `None` if it does not exist
+        actual: Option<(u64, ObjectDigest)>,
+    },
+    
     #[error("Other error: {0}")]
     Other(String),
 }
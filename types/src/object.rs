@@ -418,6 +418,20 @@ impl<T: Serialize + Clone> Object<T> {
     }
 }
 
+/// Payload type of an object kept in the generic object store.
+///
+/// Objects of every type share one keyspace; the tag stored with each object
+/// makes sure it is only ever decoded as the type it was stored as.
+pub trait ObjectData: Serialize + serde::de::DeserializeOwned + Clone {
+    /// Tag identifying the type; must be unique and never change
+    const TYPE_TAG: &'static str;
+
+    /// Addresses the object is indexed under besides its owner, by index name
+    fn secondary_indexes(&self) -> Vec<(&'static str, Address)> {
+        Vec::new()
+    }
+}
+
 pub fn generate_object_id(seed: &[u8]) -> ObjectId {
     let mut hasher = Sha256::new();
     hasher.update(seed);
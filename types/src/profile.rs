@@ -9,7 +9,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::object::{Object, Address, generate_object_id};
+use crate::object::{Object, ObjectData, Address, generate_object_id};
 
 // ============================================================================
 // Profile - Optional Identity Display
@@ -46,6 +46,10 @@ pub struct ProfileData {
 /// Profile type alias
 pub type Profile = Object<ProfileData>;
 
+impl ObjectData for ProfileData {
+    const TYPE_TAG: &'static str = "profile";
+}
+
 impl ProfileData {
     /// Create new profile data
     pub fn new(owner: Address) -> Self {
@@ -173,6 +177,14 @@ pub struct CredentialData {
 /// Credential type alias
 pub type Credential = Object<CredentialData>;
 
+impl ObjectData for CredentialData {
+    const TYPE_TAG: &'static str = "credential";
+
+    fn secondary_indexes(&self) -> Vec<(&'static str, Address)> {
+        vec![("issuer", self.issuer)]
+    }
+}
+
 impl CredentialData {
     /// Create new credential data
     pub fn new(
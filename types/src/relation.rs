@@ -6,7 +6,7 @@
 //! - RelationGraph stores relationships to other SBTs
 
 use serde::{Deserialize, Serialize};
-use crate::object::{Object, ObjectId, Address, generate_object_id};
+use crate::object::{Object, ObjectData, ObjectId, Address, generate_object_id};
 
 /// Relationship edge
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -52,6 +52,10 @@ pub struct RelationGraphData {
 /// RelationGraph type alias
 pub type RelationGraph = Object<RelationGraphData>;
 
+impl ObjectData for RelationGraphData {
+    const TYPE_TAG: &'static str = "relation_graph";
+}
+
 impl RelationGraphData {
     /// Create a new relationship graph
     pub fn new(owner_sbt: ObjectId, owner_address: Address, graph_type: String) -> Self {